@@ -0,0 +1,30 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::backend::{
+  api::access_key::list::AccessKeyDto,
+  db::{DBConnection, access_key},
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAccessKeyDto {
+  #[serde(default)]
+  pub description: String,
+}
+
+pub async fn create_access_key(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Json(dto): Json<CreateAccessKeyDto>,
+) -> Result<Json<AccessKeyDto>, AppError> {
+  let conn = conn.lock().await;
+  let key = access_key::create_access_key(&conn, user.id, dto.description.trim())?;
+  let secret_key = key.secret_key.clone();
+  Ok(Json(AccessKeyDto {
+    secret_key: Some(secret_key),
+    ..AccessKeyDto::from(key)
+  }))
+}
@@ -0,0 +1,17 @@
+use axum::extract::{Path, State};
+
+use crate::backend::{
+  db::{DBConnection, access_key},
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+pub async fn delete_access_key(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  access_key::delete_access_key(&conn, id, user.id)?;
+  Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    access_key::{self, AccessKey},
+  },
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessKeyListResponse {
+  pub access_keys: Vec<AccessKeyDto>,
+}
+
+/// 列表中不返回密钥，只在创建时返回一次
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessKeyDto {
+  pub id: i64,
+  pub access_key_id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub secret_key: Option<String>,
+  pub description: String,
+  pub last_used_at: Option<String>,
+  pub created_at: String,
+}
+
+impl From<AccessKey> for AccessKeyDto {
+  fn from(key: AccessKey) -> Self {
+    Self {
+      id: key.id,
+      access_key_id: key.access_key_id,
+      secret_key: None,
+      description: key.description,
+      last_used_at: key.last_used_at,
+      created_at: key.created_at,
+    }
+  }
+}
+
+pub async fn list_access_keys(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+) -> Result<Json<AccessKeyListResponse>, AppError> {
+  let conn = conn.lock().await;
+  let keys = access_key::get_access_keys_by_user(&conn, user.id).context("获取访问密钥失败")?;
+  Ok(Json(AccessKeyListResponse {
+    access_keys: keys.into_iter().map(AccessKeyDto::from).collect(),
+  }))
+}
@@ -0,0 +1,17 @@
+mod create;
+mod delete;
+mod list;
+use axum::{
+  Router,
+  routing::{delete, get, post},
+};
+
+use crate::backend::db::DBConnection;
+
+/// 当前用户的 S3 访问密钥
+pub fn create_access_key_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(list::list_access_keys))
+    .route("/", post(create::create_access_key))
+    .route("/{id}", delete(delete::delete_access_key))
+}
@@ -4,7 +4,10 @@ use serde::Serialize;
 
 use crate::backend::{
   api::login::StorageDto,
-  db::{DBConnection, storage, user},
+  db::{
+    DBConnection, permission,
+    user::{self, Role},
+  },
   error::AppError,
   utils::auth,
 };
@@ -20,6 +23,7 @@ pub struct UserResponse {
   pub name: String,
   pub avatar: String,
   pub username: String,
+  pub role: Role,
 }
 
 #[derive(Serialize)]
@@ -44,19 +48,26 @@ pub async fn get_app_info(
 
   let logged_user = if let Some(user_id) = user_id {
     match user::get_user_by_id(&conn, user_id) {
-      Ok(user) => Some(UserResponse {
+      Ok(user) if !user.disabled => Some(UserResponse {
         id: user.id,
         name: user.name,
         avatar: user.avatar,
         username: user.username,
+        role: user.role,
       }),
-      Err(_) => None,
+      _ => None,
     }
   } else {
     None
   };
 
-  let storages = storage::get_all_enabled_storage(&conn).context("获取存储失败")?;
+  // 未登录时不返回任何存储
+  let storages = match &logged_user {
+    Some(user) => {
+      permission::get_accessible_storages(&conn, user.id, user.role).context("获取存储失败")?
+    }
+    None => Vec::new(),
+  };
 
   Ok(Json(AppInfoDto {
     version: env!("CARGO_PKG_VERSION").to_string(),
@@ -65,11 +76,12 @@ pub async fn get_app_info(
     user: logged_user,
     storages: storages
       .into_iter()
-      .map(|storage| StorageDto {
+      .map(|(storage, level)| StorageDto {
         id: storage.id,
         name: storage.name,
         path: storage.path,
         sort_index: storage.sort_index,
+        level,
       })
       .collect(),
   }))
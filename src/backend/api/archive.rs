@@ -0,0 +1,158 @@
+use std::io::{self, Write};
+
+use anyhow::Context;
+use axum::{
+  body::Body,
+  extract::RawQuery,
+  http::{
+    StatusCode,
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+  },
+  response::Response,
+};
+use tokio::{runtime::Handle, sync::mpsc};
+
+use crate::backend::{
+  api::download::content_disposition,
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::{
+    self,
+    archive::{self, ArchiveFormat, ChannelWriter},
+    file::is_system_file,
+  },
+};
+
+/// 把文件夹或文件夹下选中的多个条目打包下载，边打包边发送，不产生临时文件。
+/// 查询参数 `targets` 可以重复出现，省略时打包整个文件夹；`format` 为 `zip`（默认）或 `tarGz`
+pub async fn download_archive(
+  Storage {
+    path,
+    root,
+    backend,
+    ..
+  }: Storage,
+  RawQuery(query): RawQuery,
+) -> Result<Response, AppError> {
+  let (targets, format) = parse_query(query.as_deref().unwrap_or_default())?;
+  let folder = relative_path(&root, &path.get_path())?;
+  let folder_name = folder.rsplit('/').next().unwrap_or_default();
+
+  let (base, targets) = if !targets.is_empty() {
+    for target in &targets {
+      if !utils::validate::validate_name(target) || is_system_file(target) {
+        return Err(AppError::with_status(
+          StatusCode::BAD_REQUEST,
+          &format!("名称不合法: {}", target),
+        ));
+      }
+    }
+    (folder.clone(), targets)
+  } else if folder.is_empty() {
+    // 存储根目录没有名称，直接打包其中的所有条目
+    let children = backend
+      .list("")
+      .await?
+      .into_iter()
+      .filter(|entry| !is_system_file(&entry.name))
+      .map(|entry| entry.name)
+      .collect();
+    (folder.clone(), children)
+  } else {
+    let parent = folder
+      .rsplit_once('/')
+      .map(|(parent, _)| parent)
+      .unwrap_or_default();
+    (parent.to_string(), vec![folder_name.to_string()])
+  };
+
+  let entries = match archive::collect_entries(&*backend, &base, &targets).await {
+    Ok(entries) => entries,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      return Err(AppError::with_status(
+        StatusCode::NOT_FOUND,
+        &err.to_string(),
+      ));
+    }
+    Err(err) => return Err(err.into()),
+  };
+  let name = match targets.as_slice() {
+    [target] => target.as_str(),
+    _ if !folder_name.is_empty() => folder_name,
+    _ => "archive",
+  };
+  let file_name = format!("{}.{}", name, format.extension());
+
+  let (tx, rx) = mpsc::channel(8);
+  let handle = Handle::current();
+  tokio::task::spawn_blocking(move || {
+    let result = archive::write_archive(
+      ChannelWriter::new(tx.clone()),
+      format,
+      &entries,
+      backend,
+      handle,
+      |_| Ok(()),
+    )
+    .and_then(|mut writer| writer.flush());
+    if let Err(err) = result {
+      log::warn!("Failed to write archive: {err}");
+      // 已经开始发送，只能中断响应让客户端知道下载失败
+      tx.blocking_send(Err(err)).ok();
+    }
+  });
+  let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+    rx.recv().await.map(|item| (item, rx))
+  });
+
+  Ok(
+    Response::builder()
+      .header(CONTENT_TYPE, format.content_type())
+      .header(CONTENT_DISPOSITION, content_disposition(&file_name, false))
+      .body(Body::from_stream(stream))
+      .context("Failed to build response")?,
+  )
+}
+
+/// `targets` 可以重复，serde 的查询参数解析不支持，手动解析
+fn parse_query(query: &str) -> Result<(Vec<String>, ArchiveFormat), AppError> {
+  let mut targets = Vec::new();
+  let mut format = ArchiveFormat::default();
+  for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+    let value = urlencoding::decode(&value.replace('+', " "))
+      .map_err(|_| AppError::with_status(StatusCode::BAD_REQUEST, "查询参数不合法"))?
+      .into_owned();
+    match key {
+      "targets" => targets.push(value),
+      "format" => {
+        format = serde_json::from_value(serde_json::Value::String(value))
+          .map_err(|_| AppError::with_status(StatusCode::BAD_REQUEST, "不支持的压缩格式"))?;
+      }
+      _ => {}
+    }
+  }
+  Ok((targets, format))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_query() {
+    let Ok((targets, format)) =
+      parse_query("targets=a+b.txt&targets=%E6%96%87%E4%BB%B6&format=tarGz&token=x")
+    else {
+      panic!("failed to parse query");
+    };
+    assert_eq!(targets, ["a b.txt", "文件"]);
+    assert_eq!(format, ArchiveFormat::TarGz);
+    let Ok((targets, format)) = parse_query("") else {
+      panic!("failed to parse query");
+    };
+    assert!(targets.is_empty());
+    assert_eq!(format, ArchiveFormat::Zip);
+    assert!(parse_query("format=rar").is_err());
+  }
+}
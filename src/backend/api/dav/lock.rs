@@ -0,0 +1,301 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{LazyLock, Mutex},
+  time::{Duration, Instant},
+};
+
+use axum::{
+  body::{Body, to_bytes},
+  http::{HeaderMap, StatusCode},
+  response::Response,
+};
+use regex::Regex;
+use tokio::fs;
+
+use crate::backend::{error::AppError, utils::xml::escape_xml};
+
+use super::{DavContext, href, propfind::xml_response};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_LOCK_BODY: usize = 64 * 1024;
+
+/// 锁只保存在内存中，重启后客户端会重新加锁
+struct DavLock {
+  /// 加锁的用户，锁令牌会出现在 lockdiscovery 中，只有该用户提交令牌才有效
+  user_id: i64,
+  path: PathBuf,
+  /// 用于 lockdiscovery 中的 lockroot
+  href: String,
+  shared: bool,
+  depth_infinity: bool,
+  owner: Option<String>,
+  timeout: Duration,
+  expires: Instant,
+}
+
+static LOCKS: LazyLock<Mutex<HashMap<String, DavLock>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn locks() -> std::sync::MutexGuard<'static, HashMap<String, DavLock>> {
+  let mut locks = LOCKS.lock().unwrap_or_else(|err| err.into_inner());
+  let now = Instant::now();
+  locks.retain(|_, lock| lock.expires > now);
+  locks
+}
+
+impl DavLock {
+  /// 该锁是否覆盖 `path`，或者 `path` 下有被锁住的资源
+  fn conflicts_with(&self, path: &Path) -> bool {
+    self.path == path
+      || (self.depth_infinity && path.starts_with(&self.path))
+      || self.path.starts_with(path)
+  }
+}
+
+/// 写操作前检查资源是否被其它客户端锁住，客户端需要在 If 头中提交自己的锁令牌
+pub fn check_unlocked(ctx: &DavContext, path: &Path) -> Result<(), AppError> {
+  let submitted = submitted_tokens(&ctx.headers);
+  let locked = locks().iter().any(|(token, lock)| {
+    lock.conflicts_with(path)
+      && !(lock.user_id == ctx.user.id && submitted.contains(token.as_str()))
+  });
+  if locked {
+    return Err(AppError::with_status(StatusCode::LOCKED, "资源已被锁定"));
+  }
+  Ok(())
+}
+
+/// 资源被删除或移走后释放相关的锁
+pub fn remove_locks(path: &Path) {
+  locks().retain(|_, lock| !lock.path.starts_with(path));
+}
+
+/// `path` 上生效的锁，用于 PROPFIND 的 lockdiscovery
+pub fn lock_discovery(path: &Path) -> String {
+  locks()
+    .iter()
+    .filter(|(_, lock)| lock.path == path || (lock.depth_infinity && path.starts_with(&lock.path)))
+    .map(|(token, lock)| active_lock(token, lock))
+    .collect()
+}
+
+pub async fn lock(ctx: &DavContext, body: Body) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  let body = to_bytes(body, MAX_LOCK_BODY)
+    .await
+    .map_err(|_| AppError::with_status(StatusCode::BAD_REQUEST, "请求体过大"))?;
+  let timeout = parse_timeout(&ctx.headers);
+
+  // 没有请求体时为刷新已有的锁
+  if body.is_empty() {
+    let submitted = submitted_tokens(&ctx.headers);
+    let mut locks = locks();
+    let Some((token, lock)) = locks.iter_mut().find(|(token, lock)| {
+      lock.user_id == ctx.user.id
+        && submitted.contains(token.as_str())
+        && lock.conflicts_with(&path)
+    }) else {
+      return Err(AppError::with_status(
+        StatusCode::PRECONDITION_FAILED,
+        "锁不存在",
+      ));
+    };
+    lock.timeout = timeout;
+    lock.expires = Instant::now() + timeout;
+    let discovery = active_lock(token, lock);
+    drop(locks);
+    return lock_response(StatusCode::OK, None, discovery);
+  }
+
+  let body = String::from_utf8_lossy(&body);
+  let shared = lock_scope_regex().is_match(&body);
+  let owner = owner_regex()
+    .captures(&body)
+    .map(|captures| captures[1].trim().to_string());
+  let depth_infinity = ctx
+    .headers
+    .get("Depth")
+    .and_then(|value| value.to_str().ok())
+    .is_none_or(|depth| depth != "0");
+
+  let token = format!("opaquelocktoken:{}", new_token());
+  let lock = DavLock {
+    user_id: ctx.user.id,
+    path: path.clone(),
+    href: href(&ctx.raw_path, path.is_dir()),
+    shared,
+    depth_infinity,
+    owner,
+    timeout,
+    expires: Instant::now() + timeout,
+  };
+  let discovery = active_lock(&token, &lock);
+
+  // 锁定不存在的资源时创建空文件
+  let created = !path.exists();
+  if created {
+    if !path.parent().is_some_and(|parent| parent.is_dir()) {
+      return Ok(super::status(StatusCode::CONFLICT));
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    ctx.storage.policy.check_name(&name)?;
+  }
+
+  // 检查冲突和登记锁在同一次加锁中完成，避免两个请求同时拿到互斥的锁
+  {
+    let mut locks = locks();
+    let conflict = locks
+      .values()
+      .any(|existing| existing.conflicts_with(&path) && (!existing.shared || !shared));
+    if conflict {
+      return Err(AppError::with_status(StatusCode::LOCKED, "资源已被锁定"));
+    }
+    locks.insert(token.clone(), lock);
+  }
+  if created && let Err(err) = fs::File::create(&path).await {
+    locks().remove(&token);
+    return Err(err.into());
+  }
+
+  let status = if created {
+    StatusCode::CREATED
+  } else {
+    StatusCode::OK
+  };
+  lock_response(status, Some(&token), discovery)
+}
+
+/// 只能释放自己加的锁
+pub fn unlock(ctx: &DavContext) -> Result<Response, AppError> {
+  let token = ctx
+    .headers
+    .get("Lock-Token")
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.trim().trim_start_matches('<').trim_end_matches('>'))
+    .unwrap_or_default();
+  let path = ctx.local_path();
+  let mut locks = locks();
+  let Some(lock) = locks.get(token) else {
+    return Ok(super::status(StatusCode::CONFLICT));
+  };
+  if !lock.conflicts_with(&path) {
+    return Ok(super::status(StatusCode::CONFLICT));
+  }
+  if lock.user_id != ctx.user.id {
+    return Err(AppError::with_status(
+      StatusCode::FORBIDDEN,
+      "不能释放其他用户的锁",
+    ));
+  }
+  locks.remove(token);
+  Ok(super::status(StatusCode::NO_CONTENT))
+}
+
+fn submitted_tokens(headers: &HeaderMap) -> &str {
+  headers
+    .get("If")
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default()
+}
+
+fn lock_response(
+  status: StatusCode,
+  token: Option<&str>,
+  discovery: String,
+) -> Result<Response, AppError> {
+  let body = format!(
+    r#"<?xml version="1.0" encoding="utf-8"?><D:prop xmlns:D="DAV:"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>"#,
+    discovery
+  );
+  let mut response = xml_response(status, body)?;
+  if let Some(token) = token
+    && let Ok(value) = format!("<{}>", token).parse()
+  {
+    response.headers_mut().insert("Lock-Token", value);
+  }
+  Ok(response)
+}
+
+fn active_lock(token: &str, lock: &DavLock) -> String {
+  format!(
+    "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope>{}</D:lockscope><D:depth>{}</D:depth>{}<D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+    if lock.shared {
+      "<D:shared/>"
+    } else {
+      "<D:exclusive/>"
+    },
+    if lock.depth_infinity { "infinity" } else { "0" },
+    lock
+      .owner
+      .as_ref()
+      .map(|owner| format!("<D:owner>{}</D:owner>", owner))
+      .unwrap_or_default(),
+    lock.timeout.as_secs(),
+    escape_xml(token),
+    escape_xml(&lock.href)
+  )
+}
+
+/// `Timeout: Second-600, Infinite`，取第一个可以识别的值
+fn parse_timeout(headers: &HeaderMap) -> Duration {
+  headers
+    .get("Timeout")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| {
+      value.split(',').map(str::trim).find_map(|item| {
+        if item.eq_ignore_ascii_case("Infinite") {
+          Some(MAX_TIMEOUT)
+        } else {
+          item
+            .strip_prefix("Second-")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+        }
+      })
+    })
+    .unwrap_or(DEFAULT_TIMEOUT)
+    .min(MAX_TIMEOUT)
+}
+
+fn new_token() -> String {
+  let bytes = rand::random::<[u8; 16]>();
+  let hex = hex::encode(bytes);
+  format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  )
+}
+
+fn lock_scope_regex() -> &'static Regex {
+  static REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)lockscope>\s*<(?:\w+:)?shared").unwrap());
+  &REGEX
+}
+
+/// 客户端提交的 owner 原样返回
+fn owner_regex() -> &'static Regex {
+  static REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<(?:\w+:)?owner[^>]*>(.*?)</(?:\w+:)?owner>").unwrap());
+  &REGEX
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_timeout() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(parse_timeout(&headers), DEFAULT_TIMEOUT);
+    headers.insert("Timeout", "Second-600, Infinite".parse().unwrap());
+    assert_eq!(parse_timeout(&headers), Duration::from_secs(600));
+    headers.insert("Timeout", "Infinite".parse().unwrap());
+    assert_eq!(parse_timeout(&headers), MAX_TIMEOUT);
+  }
+}
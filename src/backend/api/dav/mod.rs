@@ -0,0 +1,170 @@
+mod lock;
+mod propfind;
+mod transfer;
+mod write;
+use std::path::PathBuf;
+
+use axum::{
+  Router,
+  body::Body,
+  extract::{Path, State},
+  http::{HeaderMap, Method, StatusCode, header::ALLOW},
+  response::{IntoResponse, Response},
+  routing::any,
+};
+
+use crate::backend::{
+  db::{DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::{auth::AuthUser, storage::Storage},
+};
+
+const ALLOWED_METHODS: &str =
+  "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, COPY, MOVE, PROPFIND, PROPPATCH, LOCK, UNLOCK";
+
+/// WebDAV 挂载入口：`/dav/` 列出可访问的存储，`/dav/{storage}/...` 对应存储内的路径
+pub fn create_dav_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/dav", any(dav_root))
+    .route("/dav/", any(dav_root))
+    .route("/dav/{*path}", any(dav))
+}
+
+/// 一次 WebDAV 请求解析后的上下文
+pub struct DavContext {
+  pub conn: DBConnection,
+  pub user: AuthUser,
+  pub storage: Storage,
+  /// 不带 `/dav/` 前缀和末尾 `/` 的请求路径，例如 `data/docs/a.txt`
+  pub raw_path: String,
+  pub headers: HeaderMap,
+}
+
+impl DavContext {
+  pub fn local_path(&self) -> PathBuf {
+    self.storage.path.get_path()
+  }
+
+  pub fn is_root(&self) -> bool {
+    self.local_path() == self.storage.root
+  }
+}
+
+async fn dav_root(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  method: Method,
+  headers: HeaderMap,
+) -> Response {
+  match method.as_str() {
+    "OPTIONS" => options(),
+    "PROPFIND" => propfind::propfind_root(&conn, &user, &headers)
+      .await
+      .unwrap_or_else(IntoResponse::into_response),
+    _ => status(StatusCode::METHOD_NOT_ALLOWED),
+  }
+}
+
+async fn dav(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  method: Method,
+  Path(path): Path<String>,
+  headers: HeaderMap,
+  body: Body,
+) -> Response {
+  if method == Method::OPTIONS {
+    return options();
+  }
+  handle(conn, user, method, path, headers, body)
+    .await
+    .unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn handle(
+  conn: DBConnection,
+  user: AuthUser,
+  method: Method,
+  path: String,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Response, AppError> {
+  let raw_path = path.trim_end_matches('/').to_string();
+  // 复制只需要读取源文件，目标存储的写权限在 COPY 中单独校验
+  let required = match method.as_str() {
+    "GET" | "HEAD" | "PROPFIND" | "COPY" => AccessLevel::Read,
+    _ => AccessLevel::Write,
+  };
+  let storage = {
+    let conn = conn.lock().await;
+    Storage::resolve(&conn, &user, &raw_path, required)?
+  };
+  storage.require_local()?;
+  let ctx = DavContext {
+    conn,
+    user,
+    storage,
+    raw_path,
+    headers,
+  };
+
+  match method.as_str() {
+    "GET" | "HEAD" => write::get(&ctx).await,
+    "PUT" => write::put(&ctx, body).await,
+    "MKCOL" => write::mkcol(&ctx, body).await,
+    "DELETE" => write::delete(&ctx).await,
+    "COPY" => transfer::copy_or_move(&ctx, false).await,
+    "MOVE" => transfer::copy_or_move(&ctx, true).await,
+    "PROPFIND" => propfind::propfind(&ctx).await,
+    "PROPPATCH" => propfind::proppatch(&ctx).await,
+    "LOCK" => lock::lock(&ctx, body).await,
+    "UNLOCK" => lock::unlock(&ctx),
+    _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+  }
+}
+
+fn options() -> Response {
+  Response::builder()
+    .status(StatusCode::OK)
+    .header("DAV", "1, 2")
+    .header("MS-Author-Via", "DAV")
+    .header(ALLOW, ALLOWED_METHODS)
+    .body(Body::empty())
+    .unwrap_or_default()
+}
+
+pub fn status(status: StatusCode) -> Response {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap_or_default()
+}
+
+/// `data/docs/a b.txt` -> `/dav/data/docs/a%20b.txt`，文件夹以 `/` 结尾
+pub fn href(raw_path: &str, is_dir: bool) -> String {
+  let mut href = String::from("/dav/");
+  href.push_str(
+    &raw_path
+      .split('/')
+      .filter(|segment| !segment.is_empty())
+      .map(|segment| urlencoding::encode(segment).into_owned())
+      .collect::<Vec<_>>()
+      .join("/"),
+  );
+  if is_dir && !href.ends_with('/') {
+    href.push('/');
+  }
+  href
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_href() {
+    assert_eq!(href("data/docs/a b.txt", false), "/dav/data/docs/a%20b.txt");
+    assert_eq!(href("data", true), "/dav/data/");
+    assert_eq!(href("data/文档", true), "/dav/data/%E6%96%87%E6%A1%A3/");
+  }
+}
@@ -0,0 +1,204 @@
+use std::fs::Metadata;
+
+use anyhow::Context;
+use axum::{
+  body::Body,
+  http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+  response::Response,
+};
+use tokio::fs;
+
+use crate::backend::{
+  api::download::make_etag,
+  db::{self, DBConnection},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::{file::is_system_file, xml::escape_xml},
+};
+
+use super::{DavContext, href, lock};
+
+struct PropEntry {
+  href: String,
+  name: String,
+  metadata: Option<Metadata>,
+  lock_discovery: String,
+}
+
+/// `/dav/` 下列出当前用户可以访问的存储
+pub async fn propfind_root(
+  conn: &DBConnection,
+  user: &AuthUser,
+  headers: &HeaderMap,
+) -> Result<Response, AppError> {
+  let storages = {
+    let conn = conn.lock().await;
+    db::permission::get_accessible_storages(&conn, user.id, user.role).context("获取存储失败")?
+  };
+  let mut entries = vec![PropEntry {
+    href: "/dav/".to_string(),
+    name: "dav".to_string(),
+    metadata: None,
+    lock_discovery: String::new(),
+  }];
+  if depth(headers) != Depth::Zero {
+    for (storage, _) in storages {
+      entries.push(PropEntry {
+        href: href(&storage.path, true),
+        name: storage.name,
+        metadata: fs::metadata(&storage.local_path).await.ok(),
+        lock_discovery: String::new(),
+      });
+    }
+  }
+  Ok(multistatus(&entries))
+}
+
+/// Depth: infinity 按 1 处理，避免一次遍历整个存储
+pub async fn propfind(ctx: &DavContext) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  let Ok(metadata) = fs::metadata(&path).await else {
+    return Ok(super::status(StatusCode::NOT_FOUND));
+  };
+  let name = ctx
+    .raw_path
+    .rsplit('/')
+    .next()
+    .unwrap_or_default()
+    .to_string();
+
+  let is_dir = metadata.is_dir();
+  let mut entries = vec![PropEntry {
+    href: href(&ctx.raw_path, is_dir),
+    name,
+    lock_discovery: lock::lock_discovery(&path),
+    metadata: Some(metadata),
+  }];
+
+  if is_dir && depth(&ctx.headers) != Depth::Zero {
+    let mut children = fs::read_dir(&path).await?;
+    while let Some(child) = children.next_entry().await? {
+      let Ok(name) = child.file_name().into_string() else {
+        continue;
+      };
+      if is_system_file(&name) {
+        continue;
+      }
+      let Ok(metadata) = child.metadata().await else {
+        continue;
+      };
+      entries.push(PropEntry {
+        href: href(&format!("{}/{}", ctx.raw_path, name), metadata.is_dir()),
+        lock_discovery: lock::lock_discovery(&child.path()),
+        name,
+        metadata: Some(metadata),
+      });
+    }
+  }
+  Ok(multistatus(&entries))
+}
+
+/// 不支持自定义属性，但 Windows 资源管理器会在上传后设置时间属性，这里直接返回成功
+pub async fn proppatch(ctx: &DavContext) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  if !path.exists() {
+    return Ok(super::status(StatusCode::NOT_FOUND));
+  }
+  lock::check_unlocked(ctx, &path)?;
+  let body = format!(
+    r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:"><D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response></D:multistatus>"#,
+    escape_xml(&href(&ctx.raw_path, path.is_dir()))
+  );
+  xml_response(StatusCode::MULTI_STATUS, body)
+}
+
+#[derive(PartialEq)]
+enum Depth {
+  Zero,
+  One,
+}
+
+fn depth(headers: &HeaderMap) -> Depth {
+  match headers.get("Depth").and_then(|value| value.to_str().ok()) {
+    Some("0") => Depth::Zero,
+    _ => Depth::One,
+  }
+}
+
+fn multistatus(entries: &[PropEntry]) -> Response {
+  let mut body =
+    String::from(r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#);
+  for entry in entries {
+    body.push_str(&format!(
+      "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+      escape_xml(&entry.href),
+      props(entry)
+    ));
+  }
+  body.push_str("</D:multistatus>");
+  xml_response(StatusCode::MULTI_STATUS, body).unwrap_or_default()
+}
+
+fn props(entry: &PropEntry) -> String {
+  let mut props = format!("<D:displayname>{}</D:displayname>", escape_xml(&entry.name));
+  let is_dir = entry
+    .metadata
+    .as_ref()
+    .is_none_or(|metadata| metadata.is_dir());
+  if is_dir {
+    props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+  } else {
+    props.push_str("<D:resourcetype/>");
+  }
+
+  if let Some(metadata) = &entry.metadata {
+    let modified = metadata.modified().ok();
+    if let Some(modified) = modified {
+      props.push_str(&format!(
+        "<D:getlastmodified>{}</D:getlastmodified>",
+        httpdate::fmt_http_date(modified)
+      ));
+    }
+    if let Some(created) = metadata.created().ok().or(modified) {
+      props.push_str(&format!(
+        "<D:creationdate>{}</D:creationdate>",
+        chrono::DateTime::<chrono::Utc>::from(created).to_rfc3339()
+      ));
+    }
+    if !is_dir {
+      props.push_str(&format!(
+        "<D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>{}</D:getcontenttype><D:getetag>{}</D:getetag>",
+        metadata.len(),
+        escape_xml(
+          mime_guess::from_path(&entry.name)
+            .first_or_octet_stream()
+            .as_ref()
+        ),
+        escape_xml(&make_etag(metadata.len(), modified))
+      ));
+    }
+  }
+
+  props.push_str(
+    "<D:supportedlock>\
+      <D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>\
+      <D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>\
+    </D:supportedlock>",
+  );
+  props.push_str(&format!(
+    "<D:lockdiscovery>{}</D:lockdiscovery>",
+    entry.lock_discovery
+  ));
+  props
+}
+
+pub fn xml_response(status: StatusCode, body: String) -> Result<Response, AppError> {
+  Ok(
+    Response::builder()
+      .status(status)
+      .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+      .body(Body::from(body))
+      .context("Failed to build response")?,
+  )
+}
@@ -0,0 +1,175 @@
+use axum::{http::StatusCode, response::Response};
+
+use crate::backend::{
+  db::permission::AccessLevel,
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::{
+    self,
+    events::{self, ChangeKind},
+    transfer, trash, version,
+  },
+};
+
+use super::{DavContext, lock, status};
+
+/// COPY / MOVE，目标由 Destination 头指定，可以是另一个存储
+pub async fn copy_or_move(ctx: &DavContext, is_move: bool) -> Result<Response, AppError> {
+  let source = ctx.local_path();
+  if !source.exists() {
+    return Ok(status(StatusCode::NOT_FOUND));
+  }
+  if is_move && ctx.is_root() {
+    return Ok(status(StatusCode::FORBIDDEN));
+  }
+  let Some(destination) = ctx
+    .headers
+    .get("Destination")
+    .and_then(|value| value.to_str().ok())
+    .and_then(parse_destination)
+  else {
+    return Ok(status(StatusCode::BAD_REQUEST));
+  };
+
+  let target_storage = {
+    let conn = ctx.conn.lock().await;
+    Storage::resolve(&conn, &ctx.user, &destination, AccessLevel::Write)?
+  };
+  target_storage.require_local()?;
+  let target = target_storage.path.get_path();
+  if target == target_storage.root {
+    return Ok(status(StatusCode::FORBIDDEN));
+  }
+  if target == source || (source.is_dir() && target.starts_with(&source)) {
+    return Ok(status(StatusCode::FORBIDDEN));
+  }
+  if !target.parent().is_some_and(|parent| parent.is_dir()) {
+    return Ok(status(StatusCode::CONFLICT));
+  }
+
+  let policy = &target_storage.policy;
+  if source.is_file() {
+    policy.check_name(&target.file_name().unwrap_or_default().to_string_lossy())?;
+  }
+  if target_storage.id != ctx.storage.id {
+    transfer::check_tree(&source, policy)?;
+  }
+
+  let existed = target.exists();
+  if existed {
+    let overwrite = ctx
+      .headers
+      .get("Overwrite")
+      .and_then(|value| value.to_str().ok())
+      .is_none_or(|value| !value.eq_ignore_ascii_case("F"));
+    if !overwrite {
+      return Ok(status(StatusCode::PRECONDITION_FAILED));
+    }
+    lock::check_unlocked(ctx, &target)?;
+    // 被覆盖的文件先保存为历史版本，与网页端覆盖一致，之后目标进入回收站，避免误操作丢失数据
+    if target.is_file() {
+      let relative = relative_path(&target_storage.root, &target)?;
+      version::preserve(&ctx.conn, target_storage.id, &relative, ctx.user.id).await?;
+    }
+    trash::move_to_trash(
+      &ctx.conn,
+      target_storage.id,
+      &target_storage.root,
+      &target,
+      ctx.user.id,
+    )
+    .await?;
+    lock::remove_locks(&target);
+  }
+
+  if is_move {
+    lock::check_unlocked(ctx, &source)?;
+  }
+  let shallow = !is_move
+    && source.is_dir()
+    && ctx
+      .headers
+      .get("Depth")
+      .and_then(|value| value.to_str().ok())
+      == Some("0");
+  {
+    let (source, target) = (source.clone(), target.clone());
+    tokio::task::spawn_blocking(move || {
+      if is_move {
+        transfer::move_path(&source, &target)
+      } else if shallow {
+        // Depth: 0 只复制文件夹本身
+        std::fs::create_dir(&target).map_err(AppError::from)
+      } else {
+        transfer::copy_path(&source, &target)
+      }
+    })
+    .await??;
+  }
+
+  let storage = &ctx.storage;
+  let user_id = ctx.user.id;
+  if is_move {
+    lock::remove_locks(&source);
+    utils::search::refresh(storage.id, &storage.root, &source);
+  }
+  utils::search::refresh(target_storage.id, &target_storage.root, &target);
+  if is_move && target_storage.id == storage.id {
+    events::publish_rename(storage.id, &storage.root, &source, &target, user_id);
+  } else {
+    if is_move {
+      events::publish(
+        storage.id,
+        &storage.root,
+        &source,
+        ChangeKind::Deleted,
+        user_id,
+      );
+    }
+    events::publish(
+      target_storage.id,
+      &target_storage.root,
+      &target,
+      ChangeKind::Created,
+      user_id,
+    );
+  }
+
+  Ok(status(if existed {
+    StatusCode::NO_CONTENT
+  } else {
+    StatusCode::CREATED
+  }))
+}
+
+/// `http://host/dav/data/a%20b.txt` 或 `/dav/data/a%20b.txt` -> `data/a b.txt`
+fn parse_destination(value: &str) -> Option<String> {
+  let path = match value.split_once("://") {
+    Some((_, rest)) => &rest[rest.find('/')?..],
+    None => value,
+  };
+  let path = path.split(['?', '#']).next()?;
+  let path = path.strip_prefix("/dav/")?;
+  let decoded = urlencoding::decode(path).ok()?;
+  let decoded = decoded.trim_end_matches('/');
+  (!decoded.is_empty()).then(|| decoded.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_destination() {
+    assert_eq!(
+      parse_destination("http://localhost:3330/dav/data/a%20b.txt").as_deref(),
+      Some("data/a b.txt")
+    );
+    assert_eq!(
+      parse_destination("/dav/data/folder/").as_deref(),
+      Some("data/folder")
+    );
+    assert_eq!(parse_destination("http://localhost/other/a.txt"), None);
+    assert_eq!(parse_destination("/dav/"), None);
+  }
+}
@@ -0,0 +1,148 @@
+use axum::{
+  body::Body,
+  http::{StatusCode, header::CONTENT_LENGTH},
+  response::Response,
+};
+use futures_util::TryStreamExt;
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::backend::{
+  api::download::stream_file,
+  error::AppError,
+  extractor::storage::relative_path,
+  utils::{
+    self,
+    events::{self, ChangeKind},
+    trash, version,
+  },
+};
+
+use super::{DavContext, lock, status};
+
+pub async fn get(ctx: &DavContext) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  if path.is_dir() {
+    return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+  }
+  if !path.is_file() {
+    return Ok(status(StatusCode::NOT_FOUND));
+  }
+  let relative = relative_path(&ctx.storage.root, &path)?;
+  stream_file(ctx.storage.backend.clone(), &relative, &ctx.headers, true).await
+}
+
+/// 先写入临时文件，完成后再替换目标文件，避免上传中断留下不完整的文件
+pub async fn put(ctx: &DavContext, body: Body) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  if ctx.is_root() || path.is_dir() {
+    return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+  }
+  if !path.parent().is_some_and(|parent| parent.is_dir()) {
+    return Ok(status(StatusCode::CONFLICT));
+  }
+  lock::check_unlocked(ctx, &path)?;
+
+  let policy = &ctx.storage.policy;
+  policy.check_name(&path.file_name().unwrap_or_default().to_string_lossy())?;
+  if let Some(length) = ctx
+    .headers
+    .get(CONTENT_LENGTH)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok())
+  {
+    policy.check_size(length)?;
+  }
+
+  let temp_path = utils::backend::temp_file(&ctx.storage.root).await?;
+
+  let result = async {
+    let mut file = fs::File::create(&temp_path).await?;
+    let mut stream = body.into_data_stream();
+    let mut size = 0;
+    while let Some(chunk) = stream.try_next().await? {
+      size += chunk.len() as u64;
+      policy.check_size(size)?;
+      file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    let relative = relative_path(&ctx.storage.root, &path)?;
+    version::preserve(&ctx.conn, ctx.storage.id, &relative, ctx.user.id).await?;
+    Ok::<_, AppError>(())
+  }
+  .await;
+  if let Err(err) = result {
+    let _ = fs::remove_file(&temp_path).await;
+    return Err(err);
+  }
+
+  let existed = path.exists();
+  fs::rename(&temp_path, &path).await?;
+
+  let storage = &ctx.storage;
+  utils::search::refresh(storage.id, &storage.root, &path);
+  let kind = if existed {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  events::publish(storage.id, &storage.root, &path, kind, ctx.user.id);
+
+  Ok(status(if existed {
+    StatusCode::NO_CONTENT
+  } else {
+    StatusCode::CREATED
+  }))
+}
+
+pub async fn mkcol(ctx: &DavContext, body: Body) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  let body = axum::body::to_bytes(body, 1024)
+    .await
+    .map_err(|_| AppError::with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE, "不支持请求体"))?;
+  if !body.is_empty() {
+    return Ok(status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+  }
+  if path.exists() {
+    return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+  }
+  if !path.parent().is_some_and(|parent| parent.is_dir()) {
+    return Ok(status(StatusCode::CONFLICT));
+  }
+  lock::check_unlocked(ctx, &path)?;
+  fs::create_dir(&path).await?;
+
+  let storage = &ctx.storage;
+  utils::search::refresh(storage.id, &storage.root, &path);
+  events::publish(
+    storage.id,
+    &storage.root,
+    &path,
+    ChangeKind::Created,
+    ctx.user.id,
+  );
+  Ok(status(StatusCode::CREATED))
+}
+
+/// 与网页端一致，删除的文件移动到回收站
+pub async fn delete(ctx: &DavContext) -> Result<Response, AppError> {
+  let path = ctx.local_path();
+  if ctx.is_root() {
+    return Ok(status(StatusCode::FORBIDDEN));
+  }
+  if !path.exists() {
+    return Ok(status(StatusCode::NOT_FOUND));
+  }
+  lock::check_unlocked(ctx, &path)?;
+
+  let storage = &ctx.storage;
+  trash::move_to_trash(&ctx.conn, storage.id, &storage.root, &path, ctx.user.id).await?;
+  lock::remove_locks(&path);
+  events::publish(
+    storage.id,
+    &storage.root,
+    &path,
+    ChangeKind::Deleted,
+    ctx.user.id,
+  );
+  Ok(status(StatusCode::NO_CONTENT))
+}
@@ -1,45 +1,264 @@
-use crate::backend::{error::AppError, extractor::storage::StoragePath};
+use std::{
+  sync::Arc,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::backend::{
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::{
+    backend::StorageBackend,
+    range::{RangeRequest, parse_range},
+  },
+};
 use anyhow::Context;
 use axum::{
-  body::Body,
-  http::{StatusCode, header::CONTENT_DISPOSITION},
+  body::{Body, Bytes},
+  extract::Query,
+  http::{
+    HeaderMap, StatusCode,
+    header::{
+      ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+      CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+  },
   response::Response,
 };
-use tokio_util::io::ReaderStream;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadQuery {
+  /// 存在时以 inline 方式返回，方便浏览器直接预览或播放
+  pub inline: Option<String>,
+}
 
 pub async fn download_file(
-  StoragePath(path): StoragePath,
+  Storage {
+    path,
+    root,
+    backend,
+    ..
+  }: Storage,
+  Query(query): Query<DownloadQuery>,
+  headers: HeaderMap,
 ) -> Result<axum::response::Response, AppError> {
-  let path = path.get_path();
-  if !path.exists() || !path.is_file() {
+  let path = relative_path(&root, &path.get_path())?;
+  stream_file(backend, &path, &headers, query.inline.is_some()).await
+}
+
+/// 流式返回存储中的文件，支持 Range 和条件请求
+pub async fn stream_file(
+  backend: Arc<dyn StorageBackend>,
+  path: &str,
+  headers: &HeaderMap,
+  inline: bool,
+) -> Result<Response, AppError> {
+  let metadata = backend
+    .try_stat(path)
+    .await
+    .context("Failed to get file metadata")?;
+  let Some(metadata) = metadata.filter(|metadata| !metadata.is_dir) else {
     log::error!("File not found: {:?}", path);
     return Err(AppError::new("File not found"));
-  }
-  let file = tokio::fs::File::open(&path)
-    .await
-    .context("Failed to open file")?;
+  };
+  let file_size = metadata.size;
+  let modified = metadata.modified;
+  let etag = make_etag(file_size, modified);
+  let last_modified = modified.map(httpdate::fmt_http_date);
 
-  let file_size = file
-    .metadata()
-    .await
-    .context("Failed to get file metadata")?
-    .len();
+  let builder = Response::builder()
+    .header(ETAG, &etag)
+    .header(ACCEPT_RANGES, "bytes")
+    .header(CACHE_CONTROL, "no-cache");
+  let builder = match &last_modified {
+    Some(last_modified) => builder.header(LAST_MODIFIED, last_modified),
+    None => builder,
+  };
+
+  if is_not_modified(headers, &etag, modified) {
+    return Ok(
+      builder
+        .status(StatusCode::NOT_MODIFIED)
+        .body(Body::empty())
+        .context("Failed to build response")?,
+    );
+  }
 
   let file_name = path
-    .file_name()
-    .and_then(|name| name.to_str())
+    .rsplit('/')
+    .next()
+    .filter(|name| !name.is_empty())
     .unwrap_or("download");
-  let reader_stream = ReaderStream::new(file);
-  let body = Body::from_stream(reader_stream);
-  let content_disposition = format!("attachment; filename=\"{}\"", file_name);
-  let response = Response::builder()
-    .status(StatusCode::OK)
-    .header("Content-Type", "application/octet-stream")
-    .header(CONTENT_DISPOSITION, content_disposition)
-    .header("Content-Length", file_size.to_string())
-    .header("Cache-Control", "no-cache")
-    .body(body)
-    .context("Failed to build response")?;
-
-  Ok(response)
+  let content_type = if inline {
+    mime_guess::from_path(file_name)
+      .first_or_octet_stream()
+      .to_string()
+  } else {
+    "application/octet-stream".to_string()
+  };
+  let builder = builder.header(CONTENT_DISPOSITION, content_disposition(file_name, inline));
+
+  let range = request_range(headers, &etag, modified, file_size);
+
+  let response = match range {
+    RangeRequest::Full => {
+      let stream = backend
+        .read(path, None)
+        .await
+        .context("Failed to open file")?;
+      builder
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type)
+        .header(CONTENT_LENGTH, file_size.to_string())
+        .body(Body::from_stream(stream))
+    }
+    RangeRequest::Unsatisfiable => builder
+      .status(StatusCode::RANGE_NOT_SATISFIABLE)
+      .header(CONTENT_RANGE, format!("bytes */{}", file_size))
+      .body(Body::empty()),
+    RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+      let (start, end) = ranges[0];
+      let stream = backend
+        .read(path, Some((start, end - start + 1)))
+        .await
+        .context("Failed to open file")?;
+      builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(CONTENT_TYPE, content_type)
+        .header(
+          CONTENT_RANGE,
+          format!("bytes {}-{}/{}", start, end, file_size),
+        )
+        .header(CONTENT_LENGTH, (end - start + 1).to_string())
+        .body(Body::from_stream(stream))
+    }
+    RangeRequest::Partial(ranges) => {
+      let boundary = hex::encode(rand::random::<[u8; 12]>());
+      let mut content_length = 0;
+      let mut parts = Vec::new();
+      for (start, end) in ranges {
+        let head = format!(
+          "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+          boundary, content_type, start, end, file_size
+        );
+        content_length += head.len() as u64 + end - start + 1;
+        parts.push(stream::once(async move { Ok(Bytes::from(head)) }).boxed());
+
+        let (backend, path) = (backend.clone(), path.to_string());
+        parts.push(
+          stream::once(async move { backend.read(&path, Some((start, end - start + 1))).await })
+            .try_flatten()
+            .boxed(),
+        );
+      }
+      let tail = format!("\r\n--{}--\r\n", boundary);
+      content_length += tail.len() as u64;
+      parts.push(stream::once(async move { Ok(Bytes::from(tail)) }).boxed());
+
+      builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(
+          CONTENT_TYPE,
+          format!("multipart/byteranges; boundary={}", boundary),
+        )
+        .header(CONTENT_LENGTH, content_length.to_string())
+        .body(Body::from_stream(stream::iter(parts).flatten()))
+    }
+  };
+
+  Ok(response.context("Failed to build response")?)
+}
+
+/// 同时提供 ASCII 的 filename 和 UTF-8 编码的 filename*
+pub fn content_disposition(file_name: &str, inline: bool) -> String {
+  format!(
+    "{}; filename=\"{}\"; filename*=UTF-8''{}",
+    if inline { "inline" } else { "attachment" },
+    file_name.replace('"', "\\\""),
+    urlencoding::encode(file_name)
+  )
+}
+
+pub fn make_etag(size: u64, modified: Option<SystemTime>) -> String {
+  let modified = modified
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|duration| duration.as_nanos())
+    .unwrap_or_default();
+  format!("\"{:x}-{:x}\"", size, modified)
+}
+
+/// If-None-Match 优先于 If-Modified-Since
+pub fn is_not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+  if let Some(if_none_match) = headers
+    .get(IF_NONE_MATCH)
+    .and_then(|value| value.to_str().ok())
+  {
+    return if_none_match
+      .split(',')
+      .map(|tag| tag.trim().trim_start_matches("W/"))
+      .any(|tag| tag == "*" || tag == etag);
+  }
+
+  match (
+    headers
+      .get(IF_MODIFIED_SINCE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| httpdate::parse_http_date(value).ok()),
+    modified,
+  ) {
+    (Some(since), Some(modified)) => unix_secs(modified) <= unix_secs(since),
+    _ => false,
+  }
+}
+
+/// 按请求头计算 `stream_file` 会返回的文件内容字节数，用于统计分享的下载量
+pub fn response_bytes(headers: &HeaderMap, file_size: u64, modified: Option<SystemTime>) -> u64 {
+  let etag = make_etag(file_size, modified);
+  if is_not_modified(headers, &etag, modified) {
+    return 0;
+  }
+  match request_range(headers, &etag, modified, file_size) {
+    RangeRequest::Full => file_size,
+    RangeRequest::Unsatisfiable => 0,
+    RangeRequest::Partial(ranges) => ranges.iter().map(|(start, end)| end - start + 1).sum(),
+  }
+}
+
+fn request_range(
+  headers: &HeaderMap,
+  etag: &str,
+  modified: Option<SystemTime>,
+  file_size: u64,
+) -> RangeRequest {
+  headers
+    .get(RANGE)
+    .and_then(|value| value.to_str().ok())
+    .filter(|_| is_range_fresh(headers, etag, modified))
+    .map(|value| parse_range(value, file_size))
+    .unwrap_or(RangeRequest::Full)
+}
+
+/// 没有 If-Range 或者 If-Range 与当前文件一致时才处理 Range
+fn is_range_fresh(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+  let Some(if_range) = headers.get(IF_RANGE).and_then(|value| value.to_str().ok()) else {
+    return true;
+  };
+  let if_range = if_range.trim();
+  if if_range.starts_with('"') || if_range.starts_with("W/") {
+    // If-Range 只能使用强校验
+    return if_range == etag;
+  }
+  match (httpdate::parse_http_date(if_range).ok(), modified) {
+    (Some(date), Some(modified)) => unix_secs(date) == unix_secs(modified),
+    _ => false,
+  }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or_default()
 }
@@ -0,0 +1,90 @@
+use std::{collections::HashSet, convert::Infallible};
+
+use anyhow::Context;
+use axum::{
+  extract::{Query, State},
+  response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{Stream, stream};
+use serde::Deserialize;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+use crate::backend::{
+  db::{self, DBConnection},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::events::{self, ChangeEvent},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsQuery {
+  /// 只接收该存储（存储的访问路径）的事件
+  storage: Option<String>,
+  /// 只接收该路径及其子路径的事件，需要同时指定 storage
+  path: Option<String>,
+}
+
+struct Subscription {
+  rx: Receiver<ChangeEvent>,
+  /// 连接建立时用户可以访问的存储，权限变化后需要重新连接
+  storages: HashSet<i64>,
+  scope: String,
+}
+
+impl Subscription {
+  fn accepts(&self, event: &ChangeEvent) -> bool {
+    self.storages.contains(&event.storage_id) && event.is_within(&self.scope)
+  }
+}
+
+/// 通过 SSE 推送文件变更
+pub async fn events(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+  let conn = conn.lock().await;
+  let mut storages = db::permission::get_accessible_storages(&conn, user.id, user.role)
+    .context("获取存储失败")?
+    .into_iter()
+    .map(|(storage, _)| storage.id)
+    .collect::<HashSet<_>>();
+  if let Some(path) = query.storage {
+    let storage = db::storage::get_storage_by_path(&conn, &path).context("存储不存在")?;
+    if !storages.contains(&storage.id) {
+      return Err(AppError::new("无权访问该存储"));
+    }
+    storages = HashSet::from([storage.id]);
+  } else if query.path.is_some() {
+    return Err(AppError::new("按路径过滤时需要指定存储"));
+  }
+  drop(conn);
+
+  let subscription = Subscription {
+    rx: events::subscribe(),
+    storages,
+    scope: query.path.unwrap_or_default(),
+  };
+  let stream = stream::unfold(subscription, |mut subscription| async move {
+    loop {
+      match subscription.rx.recv().await {
+        Ok(event) if subscription.accepts(&event) => {
+          let sse = Event::default()
+            .event(event.kind.name())
+            .json_data(&event)
+            .unwrap_or_default();
+          return Some((Ok(sse), subscription));
+        }
+        Ok(_) => continue,
+        // 客户端处理太慢丢失了事件，通知客户端重新加载列表
+        Err(RecvError::Lagged(skipped)) => {
+          let sse = Event::default().event("lagged").data(skipped.to_string());
+          return Some((Ok(sse), subscription));
+        }
+        Err(RecvError::Closed) => return None,
+      }
+    }
+  });
+  Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,290 @@
+use std::{
+  fs::File,
+  io::{self, BufWriter},
+  path::PathBuf,
+  sync::Arc,
+};
+
+use axum::{
+  Json,
+  body::Bytes,
+  extract::{Path, State},
+  http::StatusCode,
+  response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{runtime::Handle, sync::mpsc};
+
+use crate::backend::{
+  api::{
+    job::{self, JobPayload},
+    transfer::{TaskEvent, encode_event, ndjson_response},
+  },
+  db::{DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    archive::{self, ArchiveEntry, ArchiveFormat},
+    backend::{StorageBackend, join, temp_file},
+    events::{self, ChangeKind},
+    file::is_system_file,
+    job::{CancellableWriter, JobContext},
+    policy::StoragePolicy,
+    transfer::TransferProgress,
+    upload::{OnConflict, resolve_target},
+  },
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressDto {
+  /// 当前文件夹下要打包的条目
+  targets: Vec<String>,
+  /// 压缩包名称，没有对应的扩展名时自动补上
+  name: String,
+  #[serde(default)]
+  format: ArchiveFormat,
+  #[serde(default)]
+  conflict: OnConflict,
+  /// 作为后台任务执行，立即返回任务记录
+  #[serde(default, skip_serializing)]
+  background: bool,
+}
+
+#[derive(Serialize)]
+pub struct CompressResult {
+  /// 实际写入的路径，相对于存储根目录
+  path: String,
+}
+
+/// 把文件夹下选中的条目打包成压缩文件并保存在该文件夹中，
+/// 以 NDJSON 返回进度，客户端断开后继续打包
+pub async fn compress_files(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(path): Path<String>,
+  Json(dto): Json<CompressDto>,
+) -> Result<Response, AppError> {
+  let prepared = prepare(&conn, &user, &path, &dto).await?;
+  if dto.background {
+    return job::submit(&conn, &user, JobPayload::Compress { path, dto }).await;
+  }
+
+  let handle = Handle::current();
+  let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+  tokio::task::spawn_blocking(move || {
+    let result = execute(&conn, user.id, prepared, handle, None, |progress| {
+      // 客户端断开后继续完成打包，只是不再推送进度
+      let _ = tx.send(encode_event::<()>(&TaskEvent::Progress(progress)));
+      Ok(())
+    });
+    let event = match result {
+      Ok(result) => encode_event(&TaskEvent::Done { results: result }),
+      Err(err) => encode_event::<()>(&TaskEvent::Error {
+        message: err.message(),
+        code: err.code(),
+      }),
+    };
+    let _ = tx.send(event);
+  });
+
+  ndjson_response(rx)
+}
+
+/// 在后台任务中打包，取消时不保留压缩文件
+pub async fn run_job(
+  conn: DBConnection,
+  user: AuthUser,
+  path: String,
+  dto: CompressDto,
+  context: JobContext,
+) -> Result<Value, AppError> {
+  let prepared = prepare(&conn, &user, &path, &dto).await?;
+  let handle = Handle::current();
+  let result = tokio::task::spawn_blocking(move || {
+    let reporter = context.clone();
+    execute(
+      &conn,
+      user.id,
+      prepared,
+      handle,
+      Some(context),
+      |progress| reporter.report(progress),
+    )
+  })
+  .await??;
+  Ok(serde_json::to_value(result)?)
+}
+
+/// 检查过参数和目标文件的打包
+struct PreparedCompress {
+  storage_id: i64,
+  root: PathBuf,
+  policy: StoragePolicy,
+  backend: Arc<dyn StorageBackend>,
+  folder: String,
+  name: String,
+  format: ArchiveFormat,
+  conflict: OnConflict,
+  existed: bool,
+  entries: Vec<ArchiveEntry>,
+}
+
+async fn prepare(
+  conn: &DBConnection,
+  user: &AuthUser,
+  path: &str,
+  dto: &CompressDto,
+) -> Result<PreparedCompress, AppError> {
+  if dto.targets.is_empty() {
+    return Err(AppError::new("请选择要压缩的文件"));
+  }
+  for target in &dto.targets {
+    if !utils::validate::validate_name(target) || is_system_file(target) {
+      return Err(AppError::with_status(
+        StatusCode::BAD_REQUEST,
+        &format!("名称不合法: {}", target),
+      ));
+    }
+  }
+  let suffix = format!(".{}", dto.format.extension());
+  let name = if dto.name.to_ascii_lowercase().ends_with(&suffix) {
+    dto.name.clone()
+  } else {
+    format!("{}{}", dto.name, suffix)
+  };
+  if !utils::validate::validate_name(&name) {
+    return Err(AppError::new("文件名称不合法"));
+  }
+
+  let Storage {
+    id,
+    path: local_path,
+    root,
+    policy,
+    backend,
+    ..
+  } = Storage::resolve(&*conn.lock().await, user, path, AccessLevel::Write)?;
+  policy.check_name(&name)?;
+  let folder = relative_path(&root, &local_path.get_path())?;
+  if !backend
+    .try_stat(&folder)
+    .await?
+    .is_some_and(|entry| entry.is_dir)
+  {
+    return Err(AppError::new("目标文件夹不存在"));
+  }
+  let path = join(&folder, &name);
+  let existed = backend.exists(&path).await?;
+  if dto.conflict == OnConflict::Fail {
+    resolve_target(&*backend, &path, dto.conflict).await?;
+  }
+  let entries = match archive::collect_entries(&*backend, &folder, &dto.targets).await {
+    Ok(entries) => entries,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      return Err(AppError::with_status(
+        StatusCode::NOT_FOUND,
+        &err.to_string(),
+      ));
+    }
+    Err(err) => return Err(err.into()),
+  };
+
+  Ok(PreparedCompress {
+    storage_id: id,
+    root,
+    policy,
+    backend,
+    folder,
+    name,
+    format: dto.format,
+    conflict: dto.conflict,
+    existed,
+    entries,
+  })
+}
+
+/// 打包到临时文件后写入存储，然后更新索引并通知变更。需要在阻塞线程中调用
+fn execute(
+  conn: &DBConnection,
+  user_id: i64,
+  prepared: PreparedCompress,
+  handle: Handle,
+  context: Option<JobContext>,
+  mut on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Result<CompressResult, AppError> {
+  let PreparedCompress {
+    storage_id,
+    root,
+    policy,
+    backend,
+    folder,
+    name,
+    format,
+    conflict,
+    existed,
+    entries,
+  } = prepared;
+  let path = join(&folder, &name);
+
+  let mut progress = TransferProgress::default();
+  for entry in entries.iter().filter(|entry| !entry.is_dir) {
+    progress.total_files += 1;
+    progress.total_bytes += entry.size;
+  }
+  on_progress(&progress)?;
+
+  let temp = handle.block_on(temp_file(&root))?;
+  let result = (|| -> Result<String, AppError> {
+    let writer = CancellableWriter::new(BufWriter::new(File::create(&temp)?), context);
+    let writer = archive::write_archive(
+      writer,
+      format,
+      &entries,
+      backend.clone(),
+      handle.clone(),
+      |entry| {
+        if !entry.is_dir {
+          progress.done_files += 1;
+          progress.done_bytes += entry.size;
+        }
+        progress.current = entry.name.clone();
+        on_progress(&progress).map_err(|err| io::Error::other(err.message()))
+      },
+    )?;
+    writer
+      .into_inner()
+      .into_inner()
+      .map_err(|err| err.into_error())?
+      .sync_all()?;
+    policy.check_size(std::fs::metadata(&temp)?.len())?;
+    // 打包期间可能有同名文件被创建，写入前再按策略确定一次
+    let path = handle.block_on(resolve_target(&*backend, &path, conflict))?;
+    utils::version::preserve_blocking(conn, storage_id, &path, user_id)?;
+    handle.block_on(backend.put_file(&path, &temp))?;
+    Ok(path)
+  })();
+  let saved = match result {
+    Ok(saved) => saved,
+    Err(err) => {
+      std::fs::remove_file(&temp).ok();
+      log::warn!("Failed to compress into {}: {}", path, err.message());
+      return Err(err);
+    }
+  };
+
+  let target = root.join(&saved);
+  utils::search::refresh(storage_id, &root, &target);
+  let kind = if existed && saved == path {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  events::publish(storage_id, &root, &target, kind, user_id);
+  Ok(CompressResult { path: saved })
+}
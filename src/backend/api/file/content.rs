@@ -1,7 +1,19 @@
-use crate::backend::{error::AppError, extractor::storage::StoragePath};
-use axum::Json;
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    backend::{self, StorageBackend},
+    events::ChangeKind,
+  },
+};
+use anyhow::Context;
+use axum::{Json, extract::State};
 use serde::Deserialize;
-use tokio::fs;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,29 +21,48 @@ pub struct SaveFileContentDto {
   pub content: String,
 }
 
-pub async fn get_content(StoragePath(local_path): StoragePath) -> Result<String, AppError> {
-  let local_path = local_path.get_path();
-  if !local_path.exists() {
-    return Err(AppError::new("文件不存在"));
-  }
-  if local_path.is_dir() {
-    return Err(AppError::new("目标是文件夹"));
-  }
-  let content = fs::read_to_string(&local_path).await?;
-  Ok(content)
+pub async fn get_content(
+  Storage {
+    path: local_path,
+    root,
+    backend,
+    ..
+  }: Storage,
+) -> Result<String, AppError> {
+  let path = relative_path(&root, &local_path.get_path())?;
+  check_file(&*backend, &path).await?;
+  let content = backend::read_all(&*backend, &path).await?;
+  Ok(String::from_utf8(content).context("文件不是 UTF-8 文本")?)
 }
 
 pub async fn save_content(
-  StoragePath(local_path): StoragePath,
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    backend,
+    ..
+  }: Storage,
   Json(dto): Json<SaveFileContentDto>,
 ) -> Result<(), AppError> {
   let local_path = local_path.get_path();
-  if !local_path.exists() {
-    return Err(AppError::new("文件不存在"));
-  }
-  if local_path.is_dir() {
-    return Err(AppError::new("目标是文件夹"));
-  }
-  fs::write(&local_path, dto.content).await?;
+  let path = relative_path(&root, &local_path)?;
+  check_file(&*backend, &path).await?;
+  utils::version::preserve(&conn, id, &path, user.id).await?;
+  backend
+    .write(&path, backend::once(dto.content.into()))
+    .await?;
+  utils::search::refresh(id, &root, &local_path);
+  utils::events::publish(id, &root, &local_path, ChangeKind::Modified, user.id);
   Ok(())
 }
+
+async fn check_file(backend: &dyn StorageBackend, path: &str) -> Result<(), AppError> {
+  match backend.try_stat(path).await? {
+    None => Err(AppError::new("文件不存在")),
+    Some(entry) if entry.is_dir => Err(AppError::new("目标是文件夹")),
+    Some(_) => Ok(()),
+  }
+}
@@ -1,7 +1,13 @@
-use crate::backend::{error::AppError, extractor::storage::StoragePath, utils};
+use crate::backend::{
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{self, backend, events::ChangeKind},
+};
 use axum::Json;
 use serde::Deserialize;
-use tokio::fs;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,17 +16,31 @@ pub struct CreateFileDto {
 }
 
 pub async fn create_file(
-  StoragePath(local_path): StoragePath,
+  user: AuthUser,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    policy,
+    backend,
+    ..
+  }: Storage,
   Json(dto): Json<CreateFileDto>,
 ) -> Result<(), AppError> {
   let name = dto.name;
   if !utils::validate::validate_name(&name) {
     return Err(AppError::new("文件名称不合法"));
   }
+  policy.check_name(&name)?;
   let local_path = local_path.safe_join(&name)?;
-  if local_path.exists() {
+  let path = relative_path(&root, &local_path)?;
+  if backend.exists(&path).await? {
     return Err(AppError::new("文件已存在"));
   }
-  fs::File::create(&local_path).await?;
+  backend
+    .write(&path, backend::once(Default::default()))
+    .await?;
+  utils::search::refresh(id, &root, &local_path);
+  utils::events::publish(id, &root, &local_path, ChangeKind::Created, user.id);
   Ok(())
 }
@@ -1,8 +1,18 @@
-use axum::Json;
+use axum::{Json, extract::State};
 use serde::Deserialize;
-use tokio::fs;
 
-use crate::backend::{db::DBConnection, error::AppError, extractor::storage::StoragePath};
+use crate::backend::{
+  db::{DBConnection, storage::StorageKind},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    events::{self, ChangeKind},
+    trash,
+  },
+};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,22 +20,38 @@ pub struct DeleteFileDto {
   targets: Vec<String>,
 }
 
+/// 删除的文件会移动到回收站，远程存储没有回收站，直接删除
 #[axum::debug_handler(state = DBConnection)]
 pub async fn delete_file(
-  StoragePath(local_path): StoragePath,
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    kind,
+    backend,
+    ..
+  }: Storage,
   Json(dto): Json<DeleteFileDto>,
 ) -> Result<(), AppError> {
   for target in dto.targets {
     let local_path = local_path.safe_join(&target)?;
-    if !local_path.exists() {
+    let path = relative_path(&root, &local_path)?;
+    let Some(entry) = backend.try_stat(&path).await? else {
       log::error!("file not found: {}", local_path.display());
       continue;
-    }
-    if local_path.is_dir() {
+    };
+    if entry.is_dir {
       log::error!("file is a directory: {}", local_path.display());
       continue;
     }
-    fs::remove_file(&local_path).await?;
+    if kind == StorageKind::Local {
+      trash::move_to_trash(&conn, id, &root, &local_path, user.id).await?;
+    } else {
+      backend.delete(&path).await?;
+    }
+    events::publish(id, &root, &local_path, ChangeKind::Deleted, user.id);
   }
   Ok(())
 }
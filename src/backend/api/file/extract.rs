@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use axum::{
+  Json,
+  body::Bytes,
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{runtime::Handle, sync::mpsc};
+
+use crate::backend::{
+  api::{
+    job::{self, JobPayload},
+    transfer::{TaskEvent, encode_event, ndjson_response},
+  },
+  db::{DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    archive::{ArchiveKind, ExtractLimits, ExtractReport, ExtractTask},
+    events::{self, ChangeKind},
+    job::JobContext,
+    transfer::{ConflictPolicy, TransferProgress},
+    trash,
+  },
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractDto {
+  /// 带存储前缀的目标文件夹，省略时解压到压缩包旁边与其同名的文件夹
+  destination: Option<String>,
+  #[serde(default)]
+  conflict: ConflictPolicy,
+  /// 只列出压缩包的内容和每个条目的处理方式，不解压
+  #[serde(default, skip_serializing)]
+  dry_run: bool,
+  /// 作为后台任务执行，立即返回任务记录
+  #[serde(default, skip_serializing)]
+  background: bool,
+}
+
+/// 把存储中的压缩包（zip / tar / tar.gz / 7z）解压到文件夹，
+/// 以 NDJSON 返回进度，客户端断开后继续解压
+pub async fn extract_archive(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(path): Path<String>,
+  Json(dto): Json<ExtractDto>,
+) -> Result<Response, AppError> {
+  let prepared = prepare(&*conn.lock().await, &user, &path, &dto)?;
+
+  if dto.dry_run {
+    let entries = tokio::task::spawn_blocking(move || prepared.task.list()).await??;
+    return Ok(Json(entries).into_response());
+  }
+  if dto.background {
+    return job::submit(&conn, &user, JobPayload::Extract { path, dto }).await;
+  }
+
+  let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+  tokio::task::spawn_blocking(move || {
+    let result = execute(&conn, user.id, &prepared, |progress| {
+      // 客户端断开后继续完成解压，只是不再推送进度
+      let _ = tx.send(encode_event::<()>(&TaskEvent::Progress(progress)));
+      Ok(())
+    });
+    let event = match result {
+      Ok(report) => encode_event::<ExtractReport>(&TaskEvent::Done { results: report }),
+      Err(err) => encode_event::<()>(&TaskEvent::Error {
+        message: err.message(),
+        code: err.code(),
+      }),
+    };
+    let _ = tx.send(event);
+  });
+
+  ndjson_response(rx)
+}
+
+/// 在后台任务中解压，取消时已解压的文件会保留
+pub async fn run_job(
+  conn: DBConnection,
+  user: AuthUser,
+  path: String,
+  dto: ExtractDto,
+  context: JobContext,
+) -> Result<Value, AppError> {
+  let prepared = prepare(&*conn.lock().await, &user, &path, &dto)?;
+  let report = tokio::task::spawn_blocking(move || {
+    execute(&conn, user.id, &prepared, |progress| {
+      context.report(progress)
+    })
+  })
+  .await??;
+  Ok(serde_json::to_value(report)?)
+}
+
+/// 检查权限后的解压
+struct PreparedExtract {
+  task: ExtractTask,
+  destination_id: i64,
+  destination_root: PathBuf,
+  /// 目标文件夹由解压创建时，只需要通知文件夹本身
+  created: Option<PathBuf>,
+}
+
+fn prepare(
+  conn: &Connection,
+  user: &AuthUser,
+  path: &str,
+  dto: &ExtractDto,
+) -> Result<PreparedExtract, AppError> {
+  let source = Storage::resolve(conn, user, path, AccessLevel::Read)?;
+  source.require_local()?;
+  let archive = source.path.get_path();
+  if !archive.is_file() {
+    return Err(AppError::with_status(StatusCode::NOT_FOUND, "压缩包不存在"));
+  }
+  let name = archive
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let (kind, stem) = ArchiveKind::detect(&name)
+    .ok_or_else(|| AppError::with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE, "不支持的压缩格式"))?;
+
+  let destination = match &dto.destination {
+    Some(destination) => destination.clone(),
+    None => {
+      if !utils::validate::validate_name(stem) {
+        return Err(AppError::new("文件夹名称不合法"));
+      }
+      let parent = path.rsplit_once('/').map(|(parent, _)| parent);
+      format!("{}/{}", parent.unwrap_or_default(), stem)
+    }
+  };
+  let destination = Storage::resolve(conn, user, &destination, AccessLevel::Write)?;
+  destination.require_local()?;
+  let destination_path = destination.path.get_path();
+  if destination_path.exists() && !destination_path.is_dir() {
+    return Err(AppError::new("目标不是文件夹"));
+  }
+
+  let created = (!destination_path.exists()).then(|| destination_path.clone());
+  let task = ExtractTask {
+    archive,
+    kind,
+    destination: destination_path,
+    conflict: dto.conflict,
+    policy: destination.policy,
+    temp_dir: utils::backend::temp_dir(&destination.root),
+    limits: ExtractLimits::from_env(),
+  };
+  Ok(PreparedExtract {
+    task,
+    destination_id: destination.id,
+    destination_root: destination.root,
+    created,
+  })
+}
+
+/// 解压，然后更新索引并通知变更。需要在阻塞线程中调用
+fn execute(
+  conn: &DBConnection,
+  user_id: i64,
+  prepared: &PreparedExtract,
+  on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Result<ExtractReport, AppError> {
+  let PreparedExtract {
+    task,
+    destination_id,
+    destination_root,
+    created,
+  } = prepared;
+  let handle = Handle::current();
+  // 被覆盖的目标与删除一致，移动到回收站
+  let report = task
+    .run(on_progress, |target| {
+      let path = relative_path(destination_root, target)?;
+      utils::version::preserve_blocking(conn, *destination_id, &path, user_id)?;
+      handle.block_on(trash::move_to_trash(
+        conn,
+        *destination_id,
+        destination_root,
+        target,
+        user_id,
+      ))?;
+      Ok(())
+    })
+    .inspect_err(|err| {
+      log::warn!("Failed to extract {:?}: {}", task.archive, err.message());
+    })?;
+
+  let targets = match created {
+    Some(destination) => vec![destination.clone()],
+    None => report
+      .written
+      .iter()
+      .map(|name| task.destination.join(name))
+      .collect(),
+  };
+  for target in targets {
+    utils::search::refresh(*destination_id, destination_root, &target);
+    events::publish(
+      *destination_id,
+      destination_root,
+      &target,
+      ChangeKind::Created,
+      user_id,
+    );
+  }
+  Ok(report)
+}
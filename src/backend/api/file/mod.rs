@@ -1,8 +1,11 @@
+pub mod compress;
 mod content;
 mod create;
 mod delete;
-mod list;
+pub mod extract;
+pub mod list;
 mod rename;
+mod sign;
 mod upload;
 use axum::{
   Router,
@@ -18,7 +21,9 @@ pub fn create_file_router() -> Router<DBConnection> {
     .route("/{*path}", put(content::save_content))
     .route("/{*path}", patch(rename::rename))
     .route("/{*path}", post(create::create_file))
-    .route("/upload/{*path}", post(upload::upload_file))
-    .route("/abort/{*path}", post(upload::abort_file))
+    .route("/upload/{*path}", put(upload::upload_stream))
     .route("/list/{*path}", get(list::list_files))
+    .route("/sign/{*path}", get(sign::sign_download))
+    .route("/extract/{*path}", post(extract::extract_archive))
+    .route("/compress/{*path}", post(compress::compress_files))
 }
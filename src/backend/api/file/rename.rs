@@ -1,8 +1,14 @@
 use axum::Json;
 use serde::Deserialize;
-use tokio::fs;
 
-use crate::backend::{error::AppError, extractor::storage::StoragePath};
+use crate::backend::{
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils,
+};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,26 +18,41 @@ pub struct RenameFileDto {
 }
 
 pub async fn rename(
-  StoragePath(local_path): StoragePath,
+  user: AuthUser,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    policy,
+    backend,
+    ..
+  }: Storage,
   Json(dto): Json<RenameFileDto>,
 ) -> Result<(), AppError> {
   let old_file_path = local_path.safe_join(&dto.from)?;
+  let old_path = relative_path(&root, &old_file_path)?;
   // 判断文件是否存在
-  if !old_file_path.exists() {
+  let Some(old_entry) = backend.try_stat(&old_path).await? else {
     return Err(AppError::new("文件不存在"));
-  }
+  };
   // 判断文件是否是文件
-  if !old_file_path.is_file() {
+  if old_entry.is_dir {
     return Err(AppError::new("目标不是文件"));
   }
 
+  policy.check_name(&dto.to)?;
   let new_file_path = local_path.safe_join(&dto.to)?;
+  let new_path = relative_path(&root, &new_file_path)?;
 
-  if new_file_path.exists() {
+  if backend.exists(&new_path).await? {
     return Err(AppError::new("文件已存在"));
   }
 
-  fs::rename(&old_file_path, &new_file_path).await?;
+  backend.rename(&old_path, &new_path).await?;
+
+  utils::search::refresh(id, &root, &old_file_path);
+  utils::search::refresh(id, &root, &new_file_path);
+  utils::events::publish_rename(id, &root, &old_file_path, &new_file_path, user.id);
 
   Ok(())
 }
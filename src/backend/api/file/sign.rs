@@ -0,0 +1,72 @@
+use axum::{
+  Json,
+  extract::{Path, Query},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::auth,
+};
+
+/// 默认有效期 1 小时，最长 7 天
+const DEFAULT_EXPIRES_IN: i64 = 60 * 60;
+const MAX_EXPIRES_IN: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignQuery {
+  expires_in: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignResponse {
+  pub url: String,
+  pub expires: i64,
+}
+
+pub async fn sign_download(
+  user: AuthUser,
+  Path(path): Path<String>,
+  Storage {
+    path: local_path,
+    root,
+    backend,
+    ..
+  }: Storage,
+  Query(query): Query<SignQuery>,
+) -> Result<Json<SignResponse>, AppError> {
+  let entry = backend
+    .try_stat(&relative_path(&root, &local_path.get_path())?)
+    .await?;
+  if entry.is_none_or(|entry| entry.is_dir) {
+    return Err(AppError::new("文件不存在"));
+  }
+  let expires_in = query
+    .expires_in
+    .unwrap_or(DEFAULT_EXPIRES_IN)
+    .clamp(1, MAX_EXPIRES_IN);
+  let expires = Utc::now().timestamp() + expires_in;
+  let path = path.trim_start_matches('/');
+  let sign = auth::sign_download(path, user.id, expires)?;
+
+  let encoded_path = path
+    .split('/')
+    .map(|segment| urlencoding::encode(segment).into_owned())
+    .collect::<Vec<_>>()
+    .join("/");
+
+  Ok(Json(SignResponse {
+    url: format!(
+      "/download/{}?uid={}&expires={}&sign={}",
+      encoded_path, user.id, expires, sign
+    ),
+    expires,
+  }))
+}
@@ -1,162 +1,134 @@
-use anyhow::Context;
+use std::io;
+
 use axum::{
   Json,
-  extract::Multipart,
-  http::StatusCode,
-  response::{IntoResponse, Response},
+  body::Body,
+  extract::{Query, State},
+  http::{HeaderMap, header::CONTENT_LENGTH},
 };
-use serde::Deserialize;
-use tokio::{
-  fs::{self, OpenOptions},
-  io::AsyncWriteExt,
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    backend::temp_file,
+    events::ChangeKind,
+    upload::{OnConflict, check_sha256, resolve_target},
+  },
 };
 
-use crate::backend::{db::DBConnection, error::AppError, extractor::storage::Storage};
+#[derive(Deserialize)]
+pub struct UploadStreamQuery {
+  #[serde(default)]
+  conflict: OnConflict,
+  /// 整个文件的 SHA-256 (hex)，不一致时不写入
+  sha256: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UploadStreamResponse {
+  path: String,
+}
 
-#[axum::debug_handler(state = DBConnection)]
-pub async fn upload_file(
+/// 单次请求上传整个文件，请求体原样写入路径指向的文件。
+/// 数据边接收边写入临时文件，校验通过后再放到目标位置，内存占用与文件大小无关
+pub async fn upload_stream(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
   Storage {
+    id,
     path: local_path,
     root,
+    policy,
+    backend,
+    ..
   }: Storage,
-  mut multipart: Multipart,
-) -> Result<impl IntoResponse, AppError> {
-  if !local_path.0.exists() {
-    if let Some(parent) = local_path.0.parent() {
-      if !parent.exists() {
-        return Err(AppError::new("Target directory does not exist"));
-      }
-    }
+  Query(query): Query<UploadStreamQuery>,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Json<UploadStreamResponse>, AppError> {
+  let local_path = local_path.get_path();
+  let name = local_path
+    .file_name()
+    .and_then(|name| name.to_str())
+    .unwrap_or_default();
+  if local_path == root || !utils::validate::validate_name(name) {
+    return Err(AppError::new("文件名称不合法"));
   }
-
-  let mut chunk_index: Option<usize> = None;
-  let mut total_chunks: Option<usize> = None;
-  let mut file_bytes: Option<Vec<u8>> = None;
-  let mut filename: Option<String> = None;
-
-  while let Some(field) = multipart.next_field().await? {
-    let name = field.name().unwrap_or_default().to_string();
-
-    if name == "file" {
-      let data = field.bytes().await.context("Failed to read file bytes")?;
-      file_bytes = Some(data.to_vec());
-    } else if name == "chunk" {
-      let val = field.text().await.context("Failed to read chunk index")?;
-      chunk_index = Some(val.parse().unwrap_or(0));
-    } else if name == "total" {
-      let val = field.text().await.context("Failed to read total chunks")?;
-      total_chunks = Some(val.parse().unwrap_or(1));
-    } else if name == "filename" {
-      let val = field.text().await.context("Failed to read filename")?;
-      filename = Some(val);
-    }
+  policy.check_name(name)?;
+  if let Some(size) = headers
+    .get(CONTENT_LENGTH)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok())
+  {
+    policy.check_size(size)?;
   }
 
-  let (chunk_index, total_chunks, bytes, filename_encoded) =
-    match (chunk_index, total_chunks, file_bytes, filename) {
-      (Some(i), Some(t), Some(b), Some(f)) => (i, t, b, f),
-      _ => {
-        return Err(AppError::new(
-          "Missing required fields: chunk, total, file, or filename",
-        ));
-      }
-    };
-
-  // Decode filename
-  let filename = urlencoding::decode(&filename_encoded)
-    .map_err(|_| AppError::new("Failed to decode filename"))?
-    .to_string();
-
-  // Calculate chunk hash
-  use sha2::{Digest, Sha256};
-  let mut hasher = Sha256::new();
-  hasher.update(&bytes);
-  let chunk_hash = hex::encode(hasher.finalize());
-
-  // 1. Prepare temp directory: root/.storkitty/chunks/{filename}
-  let storkitty_dir = root.join(".storkitty");
-  let chunks_root = storkitty_dir.join("chunks");
-  let file_chunks_dir = chunks_root.join(&filename);
-
-  if !file_chunks_dir.exists() {
-    fs::create_dir_all(&file_chunks_dir).await?;
+  let path = relative_path(&root, &local_path)?;
+  let parent = path
+    .rsplit_once('/')
+    .map(|(dir, _)| dir)
+    .unwrap_or_default();
+  if !backend
+    .try_stat(parent)
+    .await?
+    .is_some_and(|entry| entry.is_dir)
+  {
+    return Err(AppError::new("目标文件夹不存在"));
   }
-
-  // 2. Save chunk: {index}_{chunk_hash}
-  let chunk_filename = format!("{}_{}", chunk_index, chunk_hash);
-  let chunk_path = file_chunks_dir.join(&chunk_filename);
-
-  if !chunk_path.exists() {
-    fs::write(&chunk_path, bytes).await?;
-    log::info!("Saved chunk: {}", chunk_filename);
+  let existed = backend.exists(&path).await?;
+  if query.conflict == OnConflict::Fail {
+    resolve_target(&*backend, &path, query.conflict).await?;
   }
 
-  // 3. Check completion
-  // We need to find if we have files for all indices 0..total_chunks
-  let mut found_chunks = vec![None; total_chunks];
-  let mut entries = fs::read_dir(&file_chunks_dir).await?;
-
-  while let Some(entry) = entries.next_entry().await? {
-    let name = entry.file_name().to_string_lossy().to_string();
-    // name format: {index}_{hash}
-    if let Some((idx_str, _)) = name.split_once('_') {
-      if let Ok(idx) = idx_str.parse::<usize>() {
-        if idx < total_chunks {
-          found_chunks[idx] = Some(entry.path());
-        }
-      }
+  let temp = temp_file(&root).await?;
+  let result = async {
+    let mut file = fs::File::create(&temp).await?;
+    let mut hasher = Sha256::new();
+    let mut received = 0;
+    let mut stream = body.into_data_stream().map_err(io::Error::other);
+    while let Some(data) = stream.try_next().await? {
+      // 没有 Content-Length 或客户端声明不实时，按实际收到的大小中止写入
+      received += data.len() as u64;
+      policy.check_size(received)?;
+      hasher.update(&data);
+      file.write_all(&data).await?;
     }
-  }
-
-  if found_chunks.iter().all(|c| c.is_some()) {
-    let save_file_path = local_path.0.join(&filename);
-    log::info!(
-      "All chunks received, merging to {}",
-      save_file_path.display()
-    );
-
-    let mut final_file = OpenOptions::new()
-      .create(true)
-      .write(true)
-      .truncate(true)
-      .open(&save_file_path)
-      .await?;
-
-    for path in found_chunks.iter().flatten() {
-      let chunk_data = fs::read(path).await?;
-      final_file.write_all(&chunk_data).await?;
+    file.sync_all().await?;
+    if let Some(expected) = &query.sha256 {
+      check_sha256(&hex::encode(hasher.finalize()), expected)?;
     }
-
-    // Cleanup
-    fs::remove_dir_all(&file_chunks_dir).await?;
-    log::info!("Merge complete");
+    // 接收期间可能有同名文件被创建，写入前再按策略确定一次
+    let path = resolve_target(&*backend, &path, query.conflict).await?;
+    utils::version::preserve(&conn, id, &path, user.id).await?;
+    backend.put_file(&path, &temp).await?;
+    Ok::<_, AppError>(path)
   }
-
-  Ok(
-    Response::builder()
-      .status(StatusCode::OK)
-      .body(axum::body::Body::from(chunk_hash))
-      .unwrap_or_default(),
-  )
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AbortFileDto {
-  file: String,
-}
-
-#[axum::debug_handler(state = DBConnection)]
-pub async fn abort_file(
-  Storage { path: _, root }: Storage,
-  Json(dto): Json<AbortFileDto>,
-) -> Result<impl IntoResponse, AppError> {
-  let storkitty_dir = root.join(".storkitty");
-  let chunks_root = storkitty_dir.join("chunks");
-  let file_chunks_dir = chunks_root.join(&dto.file);
-  log::info!("Abort file: {}", file_chunks_dir.display());
-  // 等待 3 秒钟
-  tokio::time::sleep(tokio::time::Duration::from_millis(3000)).await;
-  fs::remove_dir_all(&file_chunks_dir).await?;
-  Ok(())
+  .await;
+  let path = match result {
+    Ok(path) => path,
+    Err(err) => {
+      fs::remove_file(&temp).await.ok();
+      return Err(err);
+    }
+  };
+
+  let local_path = root.join(&path);
+  utils::search::refresh(id, &root, &local_path);
+  let kind = if existed && query.conflict == OnConflict::Overwrite {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  utils::events::publish(id, &root, &local_path, kind, user.id);
+  Ok(Json(UploadStreamResponse { path }))
 }
@@ -1,8 +1,15 @@
 use axum::Json;
 use serde::Deserialize;
-use tokio::fs;
 
-use crate::backend::{db::DBConnection, error::AppError, extractor::storage::StoragePath, utils};
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{self, events::ChangeKind},
+};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,7 +19,14 @@ pub struct CreateFolderDto {
 
 #[axum::debug_handler(state = DBConnection)]
 pub async fn create_folder(
-  StoragePath(local_path): StoragePath,
+  user: AuthUser,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    backend,
+    ..
+  }: Storage,
   Json(dto): Json<CreateFolderDto>,
 ) -> Result<(), AppError> {
   let name = dto.name;
@@ -21,13 +35,16 @@ pub async fn create_folder(
     return Err(AppError::new("文件夹名称不合法"));
   }
   let local_path = local_path.safe_join(&name)?;
-  if local_path.exists() {
+  let path = relative_path(&root, &local_path)?;
+  if backend.exists(&path).await? {
     return Err(AppError::new("目录已存在"));
   }
 
   log::info!("create_folder: {}", local_path.display());
 
-  fs::create_dir_all(&local_path).await?;
+  backend.mkdir(&path).await?;
+  utils::search::refresh(id, &root, &local_path);
+  utils::events::publish(id, &root, &local_path, ChangeKind::Created, user.id);
 
   Ok(())
 }
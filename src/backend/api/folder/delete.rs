@@ -1,31 +1,112 @@
-use axum::Json;
-use serde::Deserialize;
-use tokio::fs;
+use axum::{
+  Json,
+  extract::{Path, State},
+  response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::backend::{db::DBConnection, error::AppError, extractor::storage::StoragePath};
+use crate::backend::{
+  api::job::{self, JobPayload},
+  db::{DBConnection, permission::AccessLevel, storage::StorageKind},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    events::{self, ChangeKind},
+    job::JobContext,
+    transfer::TransferProgress,
+    trash,
+  },
+};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteFolderDto {
   targets: Vec<String>,
+  /// 作为后台任务执行，立即返回任务记录。远程存储删除大文件夹时比较耗时
+  #[serde(default, skip_serializing)]
+  background: bool,
 }
 
+/// 删除的文件夹会移动到回收站，远程存储没有回收站，直接删除
 #[axum::debug_handler(state = DBConnection)]
 pub async fn delete_folder(
-  StoragePath(local_path): StoragePath,
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(path): Path<String>,
+  storage: Storage,
   Json(dto): Json<DeleteFolderDto>,
-) -> Result<(), AppError> {
-  for target in dto.targets {
-    let local_path = local_path.safe_join(&target)?;
-    if !local_path.exists() {
+) -> Result<Response, AppError> {
+  if dto.background {
+    return job::submit(&conn, &user, JobPayload::DeleteFolder { path, dto }).await;
+  }
+  delete_targets(&conn, user.id, storage, &dto.targets, |_| Ok(())).await?;
+  Ok(().into_response())
+}
+
+/// 在后台任务中删除，取消时已删除的文件夹不会还原
+pub async fn run_job(
+  conn: DBConnection,
+  user: AuthUser,
+  path: String,
+  dto: DeleteFolderDto,
+  context: JobContext,
+) -> Result<Value, AppError> {
+  let storage = Storage::resolve(&*conn.lock().await, &user, &path, AccessLevel::Write)?;
+  let deleted = delete_targets(&conn, user.id, storage, &dto.targets, |progress| {
+    context.report(progress)
+  })
+  .await?;
+  Ok(serde_json::json!({ "deleted": deleted }))
+}
+
+/// 依次删除文件夹，不存在或不是文件夹的条目跳过，返回删除的数量
+async fn delete_targets(
+  conn: &DBConnection,
+  user_id: i64,
+  Storage {
+    id,
+    path: local_path,
+    root,
+    kind,
+    backend,
+    ..
+  }: Storage,
+  targets: &[String],
+  mut on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Result<usize, AppError> {
+  let mut progress = TransferProgress {
+    total_files: targets.len() as u64,
+    ..Default::default()
+  };
+  let mut deleted = 0;
+  for target in targets {
+    progress.current = target.clone();
+    on_progress(&progress)?;
+    progress.done_files += 1;
+
+    let local_path = local_path.safe_join(target)?;
+    let path = relative_path(&root, &local_path)?;
+    let Some(entry) = backend.try_stat(&path).await? else {
       log::error!("folder not found: {}", local_path.display());
       continue;
-    }
-    if local_path.is_file() {
+    };
+    if !entry.is_dir {
       log::error!("folder is a file: {}", local_path.display());
       continue;
     }
-    fs::remove_dir_all(&local_path).await?;
+    if kind == StorageKind::Local {
+      trash::move_to_trash(conn, id, &root, &local_path, user_id).await?;
+    } else {
+      backend.delete(&path).await?;
+    }
+    events::publish(id, &root, &local_path, ChangeKind::Deleted, user_id);
+    deleted += 1;
   }
-  Ok(())
+  // 已经全部删除，这时取消不影响结果
+  on_progress(&progress).ok();
+  Ok(deleted)
 }
@@ -1,5 +1,5 @@
 mod create;
-mod delete;
+pub mod delete;
 mod rename;
 use axum::{
   Router,
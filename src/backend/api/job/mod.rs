@@ -0,0 +1,257 @@
+use axum::{
+  Json, Router,
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::backend::{
+  api::{
+    file::{compress, extract},
+    folder::delete,
+    transfer,
+  },
+  db::{
+    self, DBConnection,
+    job::{Job, JobStatus},
+  },
+  error::AppError,
+  extractor::auth::{AuthUser, load_auth_user},
+  utils::{
+    self,
+    job::{JobContext, cancelled},
+    transfer::TransferMode,
+  },
+};
+
+/// 列表最多返回的任务数
+const LIST_LIMIT: u32 = 100;
+
+pub fn create_job_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(list_jobs))
+    .route("/{id}", get(get_job))
+    .route("/{id}/cancel", post(cancel_job))
+    .route("/{id}/retry", post(retry_job))
+}
+
+/// 后台任务的参数，保存在任务记录中，重启或重试时按它重新执行
+#[derive(Serialize, Deserialize)]
+#[serde(
+  tag = "kind",
+  rename_all = "camelCase",
+  rename_all_fields = "camelCase"
+)]
+pub enum JobPayload {
+  Transfer {
+    mode: TransferMode,
+    #[serde(flatten)]
+    dto: transfer::TransferDto,
+  },
+  Extract {
+    path: String,
+    #[serde(flatten)]
+    dto: extract::ExtractDto,
+  },
+  Compress {
+    path: String,
+    #[serde(flatten)]
+    dto: compress::CompressDto,
+  },
+  DeleteFolder {
+    path: String,
+    #[serde(flatten)]
+    dto: delete::DeleteFolderDto,
+  },
+  /// 重建存储的搜索索引，只有管理员可以提交
+  Reindex { storage_id: i64 },
+}
+
+impl JobPayload {
+  fn kind(&self) -> &'static str {
+    match self {
+      JobPayload::Transfer { .. } => "transfer",
+      JobPayload::Extract { .. } => "extract",
+      JobPayload::Compress { .. } => "compress",
+      JobPayload::DeleteFolder { .. } => "deleteFolder",
+      JobPayload::Reindex { .. } => "reindex",
+    }
+  }
+}
+
+/// 创建任务并放到后台执行，返回 202 和任务记录
+pub async fn submit(
+  conn: &DBConnection,
+  user: &AuthUser,
+  payload: JobPayload,
+) -> Result<Response, AppError> {
+  let job = {
+    let conn = conn.lock().await;
+    let id = db::job::create_job(
+      &conn,
+      user.id,
+      payload.kind(),
+      &serde_json::to_string(&payload)?,
+    )?;
+    db::job::get_job(&conn, id)?.ok_or_else(|| AppError::new("任务不存在"))?
+  };
+  log::info!("Submit job {} ({})", job.id, job.kind);
+  start(conn.clone(), job.id, job.user_id, payload);
+  Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+fn start(conn: DBConnection, id: i64, user_id: i64, payload: JobPayload) {
+  let runner = conn.clone();
+  utils::job::start(conn, id, move |context| {
+    Box::pin(async move {
+      // 按所有者当前的权限重新解析路径，权限被收回后重试同样会失败
+      let user = load_auth_user(&runner, user_id)
+        .await
+        .map_err(|_| AppError::new("任务所有者不存在或已被禁用"))?;
+      match payload {
+        JobPayload::Transfer { mode, dto } => {
+          transfer::run_job(runner, user, mode, dto, context).await
+        }
+        JobPayload::Extract { path, dto } => {
+          extract::run_job(runner, user, path, dto, context).await
+        }
+        JobPayload::Compress { path, dto } => {
+          compress::run_job(runner, user, path, dto, context).await
+        }
+        JobPayload::DeleteFolder { path, dto } => {
+          delete::run_job(runner, user, path, dto, context).await
+        }
+        JobPayload::Reindex { storage_id } => reindex(runner, user, storage_id, context).await,
+      }
+    })
+  });
+}
+
+async fn reindex(
+  conn: DBConnection,
+  user: AuthUser,
+  storage_id: i64,
+  context: JobContext,
+) -> Result<Value, AppError> {
+  if !user.is_admin() {
+    return Err(AppError::with_status(StatusCode::FORBIDDEN, "没有权限"));
+  }
+  let storage = db::storage::get_storage_by_id(&*conn.lock().await, storage_id)?;
+  if storage.kind != db::storage::StorageKind::Local {
+    return Err(AppError::new("远程存储不支持搜索索引"));
+  }
+  context.check()?;
+  let root = std::path::PathBuf::from(&storage.local_path);
+  let entries = utils::search::rebuild(&conn, storage_id, &root).await;
+  Ok(serde_json::json!({ "entries": entries }))
+}
+
+/// 继续执行重启前还在排队的任务，执行被中断的任务标记为失败
+pub fn spawn_job_runner(conn: DBConnection) {
+  tokio::spawn(async move {
+    let jobs = match db::job::requeue_unfinished_jobs(&*conn.lock().await, "任务因服务重启而中断")
+    {
+      Ok(jobs) => jobs,
+      Err(err) => {
+        log::error!("Failed to load unfinished jobs: {err}");
+        return;
+      }
+    };
+    for job in jobs {
+      match serde_json::from_value::<JobPayload>(job.payload) {
+        Ok(payload) => {
+          log::info!("Resume job {} ({})", job.id, job.kind);
+          start(conn.clone(), job.id, job.user_id, payload);
+        }
+        Err(err) => {
+          log::warn!("Failed to parse payload of job {}: {err}", job.id);
+          let conn = conn.lock().await;
+          if let Err(err) = db::job::finish_job(
+            &conn,
+            job.id,
+            JobStatus::Failed,
+            None,
+            Some("任务参数不合法"),
+          ) {
+            log::warn!("Failed to save job {}: {err}", job.id);
+          }
+        }
+      }
+    }
+  });
+}
+
+async fn list_jobs(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+) -> Result<Json<Vec<Job>>, AppError> {
+  let jobs = db::job::get_jobs_by_user(&*conn.lock().await, user.id, LIST_LIMIT)?;
+  Ok(Json(jobs))
+}
+
+async fn get_job(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<Json<Job>, AppError> {
+  Ok(Json(find_job(&conn, &user, id).await?))
+}
+
+/// 通知任务停止，任务在下一次上报进度时结束，状态变为 `cancelled`
+async fn cancel_job(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<Json<Job>, AppError> {
+  let job = find_job(&conn, &user, id).await?;
+  if job.status.is_finished() {
+    return Err(AppError::with_status(StatusCode::CONFLICT, "任务已结束"));
+  }
+  if !utils::job::cancel(id) {
+    // 没有在执行的任务直接标记为已取消
+    db::job::finish_job(
+      &*conn.lock().await,
+      id,
+      JobStatus::Cancelled,
+      None,
+      Some(&cancelled().message()),
+    )?;
+  }
+  Ok(Json(find_job(&conn, &user, id).await?))
+}
+
+/// 按原来的参数重新执行失败或已取消的任务
+async fn retry_job(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+  let job = find_job(&conn, &user, id).await?;
+  let payload = serde_json::from_value::<JobPayload>(job.payload)
+    .map_err(|_| AppError::new("任务参数不合法"))?;
+  // 取消后还没有退出的任务不能重试，状态在同一条更新语句中检查，并发的重试只有一次成功
+  let job = {
+    let conn = conn.lock().await;
+    if utils::job::is_active(id) || !db::job::reset_job(&conn, id)? {
+      return Err(AppError::with_status(
+        StatusCode::CONFLICT,
+        "只能重试失败或已取消的任务",
+      ));
+    }
+    db::job::get_job(&conn, id)?.ok_or_else(|| AppError::new("任务不存在"))?
+  };
+  log::info!("Retry job {} ({})", job.id, job.kind);
+  start(conn.clone(), job.id, job.user_id, payload);
+  Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+/// 用户只能访问自己的任务，管理员可以访问所有任务
+async fn find_job(conn: &DBConnection, user: &AuthUser, id: i64) -> Result<Job, AppError> {
+  match db::job::get_job(&*conn.lock().await, id)? {
+    Some(job) if job.user_id == user.id || user.is_admin() => Ok(job),
+    _ => Err(AppError::with_status(StatusCode::NOT_FOUND, "任务不存在")),
+  }
+}
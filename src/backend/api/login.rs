@@ -3,7 +3,7 @@ use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
 
 use crate::backend::{
-  db::{self, DBConnection},
+  db::{self, DBConnection, permission::AccessLevel, user::Role},
   error::AppError,
   utils::auth,
 };
@@ -23,6 +23,7 @@ pub struct UserDto {
   pub name: String,
   pub avatar: String,
   pub username: String,
+  pub role: Role,
 }
 
 #[derive(Deserialize)]
@@ -39,6 +40,7 @@ pub struct StorageDto {
   pub name: String,
   pub path: String,
   pub sort_index: i64,
+  pub level: AccessLevel,
 }
 
 pub async fn login(
@@ -54,8 +56,13 @@ pub async fn login(
     return Err(AppError::new("用户名或密码错误"));
   }
 
+  if user_info.disabled {
+    return Err(AppError::new("用户已禁用"));
+  }
+
   let token = auth::generate_token(user_info.id)?;
-  let storages = db::storage::get_all_enabled_storage(&conn).context("获取存储失败")?;
+  let storages = db::permission::get_accessible_storages(&conn, user_info.id, user_info.role)
+    .context("获取存储失败")?;
 
   Ok(Json(LoginResponseDto {
     user: UserDto {
@@ -63,15 +70,17 @@ pub async fn login(
       name: user_info.name,
       avatar: user_info.avatar,
       username: user_info.username,
+      role: user_info.role,
     },
     token,
     storages: storages
       .into_iter()
-      .map(|storage| StorageDto {
+      .map(|(storage, level)| StorageDto {
         id: storage.id,
         name: storage.name,
         path: storage.path,
         sort_index: storage.sort_index,
+        level,
       })
       .collect(),
   }))
@@ -1,9 +1,26 @@
+mod access_key;
 mod app;
+mod archive;
+mod dav;
 mod download;
+mod events;
 mod file;
 mod folder;
+mod job;
 mod login;
+mod s3;
+mod search;
 mod setup;
+mod share;
+mod storage;
+mod thumb;
+mod token;
+mod transfer;
+mod trash;
+mod tus;
+mod upload;
+mod user;
+mod version;
 use axum::{
   Router, middleware,
   routing::{self, get_service},
@@ -13,20 +30,45 @@ use tower_http::services::{ServeDir, ServeFile};
 
 use crate::backend::{
   db::{DBConnection, init_db},
-  extractor::auth::auth_middleware,
+  extractor::auth::{
+    admin_middleware, auth_middleware, basic_auth_middleware, download_auth_middleware,
+    token_auth_middleware,
+  },
+  utils,
 };
 
 pub async fn start_server() -> anyhow::Result<()> {
   // 构建静态文件服务（用于 serve ./web 目录）
   let serve_dir = ServeDir::new("./web").not_found_service(ServeFile::new("./web/index.html"));
   let conn = init_db()?;
+  utils::trash::spawn_trash_cleaner(conn.clone());
+  utils::thumb::spawn_thumb_cleaner(conn.clone());
+  utils::upload::spawn_upload_cleaner(conn.clone());
+  utils::search::spawn_indexer(conn.clone());
+  utils::job::spawn_job_cleaner(conn.clone());
+  job::spawn_job_runner(conn.clone());
+  utils::version::spawn_version_cleaner(conn.clone());
+  utils::watcher::spawn_watcher(conn.clone());
 
   let app = Router::<DBConnection>::new()
-    .nest("/api", create_api_router())
-    .route("/download/{*path}", routing::get(download::download_file))
+    .nest("/api", create_api_router(conn.clone()))
+    .route(
+      "/download/{*path}",
+      routing::get(download::download_file).layer(middleware::from_fn_with_state(
+        conn.clone(),
+        download_auth_middleware,
+      )),
+    )
+    .merge(
+      dav::create_dav_router().layer(middleware::from_fn_with_state(
+        conn.clone(),
+        basic_auth_middleware,
+      )),
+    )
+    .nest("/s", share::create_public_share_router())
     .fallback_service(get_service(serve_dir))
     .layer(axum::extract::DefaultBodyLimit::disable())
-    .with_state(conn);
+    .with_state(conn.clone());
 
   let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
   let port = std::env::var("PORT")
@@ -36,6 +78,8 @@ pub async fn start_server() -> anyhow::Result<()> {
   let addr = format!("{}:{}", host, port)
     .parse::<SocketAddr>()
     .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], port)));
+  s3::spawn_s3_server(conn, &host);
+
   let listener = tokio::net::TcpListener::bind(addr).await?;
   log::info!("Server starting on port {}", port);
   axum::serve(listener, app).await?;
@@ -43,18 +87,66 @@ pub async fn start_server() -> anyhow::Result<()> {
   Ok(())
 }
 
-fn create_api_router() -> Router<DBConnection> {
+fn create_api_router(conn: DBConnection) -> Router<DBConnection> {
+  let auth = middleware::from_fn_with_state(conn.clone(), auth_middleware);
+  let token_auth = middleware::from_fn_with_state(conn, token_auth_middleware);
+  let admin = middleware::from_fn(admin_middleware);
+
   Router::<DBConnection>::new()
     .nest("/app", app::create_app_router())
     .route("/setup", routing::post(setup::setup))
     .route("/login", routing::post(login::login))
     .route("/test", routing::get(|| async { "Hello, World!" }))
-    .nest(
-      "/file",
-      file::create_file_router().layer(middleware::from_fn(auth_middleware)),
+    .route(
+      "/token",
+      routing::post(token::create_query_token).layer(auth.clone()),
     )
+    .route(
+      "/events",
+      routing::get(events::events).layer(token_auth.clone()),
+    )
+    .route(
+      "/thumb/{*path}",
+      routing::get(thumb::get_thumb).layer(token_auth.clone()),
+    )
+    .route(
+      "/archive/{*path}",
+      routing::get(archive::download_archive).layer(token_auth),
+    )
+    .nest("/file", file::create_file_router().layer(auth.clone()))
     .nest(
       "/folder",
-      folder::create_folder_router().layer(middleware::from_fn(auth_middleware)),
+      folder::create_folder_router().layer(auth.clone()),
+    )
+    .nest("/trash", trash::create_trash_router().layer(auth.clone()))
+    .nest(
+      "/version",
+      version::create_version_router().layer(auth.clone()),
+    )
+    .nest("/job", job::create_job_router().layer(auth.clone()))
+    .nest("/tus", tus::create_tus_router().layer(auth.clone()))
+    .nest(
+      "/upload",
+      upload::create_upload_router().layer(auth.clone()),
+    )
+    .nest(
+      "/transfer",
+      transfer::create_transfer_router().layer(auth.clone()),
+    )
+    .nest(
+      "/search",
+      search::create_search_router().layer(auth.clone()),
+    )
+    .nest("/share", share::create_share_router().layer(auth.clone()))
+    .nest(
+      "/access-key",
+      access_key::create_access_key_router().layer(auth.clone()),
+    )
+    .nest(
+      "/storage",
+      storage::create_storage_router()
+        .layer(admin.clone())
+        .layer(auth.clone()),
     )
+    .nest("/user", user::create_user_router().layer(admin).layer(auth))
 }
@@ -0,0 +1,296 @@
+use std::path::Path;
+
+use axum::{
+  body::{Body, Bytes},
+  http::{HeaderMap, StatusCode, header::CONTENT_LENGTH},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::TryStreamExt;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::backend::utils::policy::StoragePolicy;
+
+use super::{
+  S3Error,
+  auth::{ChunkSigner, STREAMING_SIGNED_PAYLOAD},
+};
+
+const MAX_XML_BODY: usize = 2 * 1024 * 1024;
+
+/// 将请求体写入 `dest`，同时校验 x-amz-content-sha256 / Content-MD5、数据块签名和存储的大小限制，
+/// 返回请求体的 MD5。失败时由调用方删除 `dest`
+pub async fn write_body(
+  body: Body,
+  headers: &HeaderMap,
+  signer: Option<ChunkSigner>,
+  dest: &Path,
+  policy: &StoragePolicy,
+) -> Result<[u8; 16], S3Error> {
+  let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+  let content_sha256 = header("x-amz-content-sha256").unwrap_or_default();
+  let chunked = content_sha256.starts_with("STREAMING-")
+    || header("content-encoding").is_some_and(|value| value.contains("aws-chunked"));
+  let expected_sha256 = (content_sha256.len() == 64)
+    .then(|| hex::decode(content_sha256).ok())
+    .flatten();
+  if content_sha256 == STREAMING_SIGNED_PAYLOAD && signer.is_none() {
+    return Err(S3Error::access_denied("缺少数据块签名信息"));
+  }
+  let expected_md5 = header("content-md5")
+    .map(|value| {
+      STANDARD.decode(value).map_err(|_| {
+        S3Error::new(
+          StatusCode::BAD_REQUEST,
+          "InvalidDigest",
+          "Content-MD5 不合法",
+        )
+      })
+    })
+    .transpose()?;
+
+  let declared = if chunked {
+    header("x-amz-decoded-content-length")
+  } else {
+    header(CONTENT_LENGTH.as_str())
+  };
+  if let Some(size) = declared.and_then(|value| value.parse::<u64>().ok()) {
+    policy.check_size(size)?;
+  }
+
+  let mut file = fs::File::create(dest).await?;
+  let mut decoder = chunked.then(|| ChunkDecoder {
+    signer,
+    ..Default::default()
+  });
+  let mut sha256 = Sha256::new();
+  let mut md5 = Md5::new();
+  let mut size = 0;
+  let mut stream = body.into_data_stream();
+  while let Some(data) = stream
+    .try_next()
+    .await
+    .map_err(|err| S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", &err.to_string()))?
+  {
+    let chunks = match &mut decoder {
+      Some(decoder) => decoder.feed(&data)?,
+      None => vec![data],
+    };
+    for chunk in chunks {
+      size += chunk.len() as u64;
+      policy.check_size(size)?;
+      if expected_sha256.is_some() {
+        sha256.update(&chunk);
+      }
+      md5.update(&chunk);
+      file.write_all(&chunk).await?;
+    }
+  }
+  file.flush().await?;
+  if decoder.is_some_and(|decoder| !decoder.is_done()) {
+    return Err(S3Error::new(
+      StatusCode::BAD_REQUEST,
+      "IncompleteBody",
+      "请求体不完整",
+    ));
+  }
+
+  if let Some(expected) = expected_sha256
+    && sha256.finalize().as_slice() != expected.as_slice()
+  {
+    return Err(S3Error::new(
+      StatusCode::BAD_REQUEST,
+      "XAmzContentSHA256Mismatch",
+      "请求体的 SHA256 与 x-amz-content-sha256 不一致",
+    ));
+  }
+  let md5: [u8; 16] = md5.finalize().into();
+  if let Some(expected) = expected_md5
+    && md5.as_slice() != expected.as_slice()
+  {
+    return Err(S3Error::new(
+      StatusCode::BAD_REQUEST,
+      "BadDigest",
+      "请求体的 MD5 与 Content-MD5 不一致",
+    ));
+  }
+  Ok(md5)
+}
+
+/// 读取 DeleteObjects、CompleteMultipartUpload 等较小的 XML 请求体
+pub async fn read_xml_body(body: Body, headers: &HeaderMap) -> Result<String, S3Error> {
+  let bytes = axum::body::to_bytes(body, MAX_XML_BODY)
+    .await
+    .map_err(|_| S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "请求体过大"))?;
+  let expected = headers
+    .get("x-amz-content-sha256")
+    .and_then(|value| value.to_str().ok())
+    .filter(|value| value.len() == 64);
+  if let Some(expected) = expected
+    && hex::encode(Sha256::digest(&bytes)) != expected.to_ascii_lowercase()
+  {
+    return Err(S3Error::new(
+      StatusCode::BAD_REQUEST,
+      "XAmzContentSHA256Mismatch",
+      "请求体的 SHA256 与 x-amz-content-sha256 不一致",
+    ));
+  }
+  String::from_utf8(bytes.to_vec())
+    .map_err(|_| S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "请求体不是 UTF-8"))
+}
+
+#[derive(Default, PartialEq)]
+enum ChunkState {
+  /// 读取 `长度[;chunk-signature=...]\r\n`
+  #[default]
+  Size,
+  Data(usize),
+  /// 数据块后的 `\r\n`
+  DataEnd,
+  /// 长度为 0 的块之后是可选的 trailer，以空行结束
+  Trailer,
+  Done,
+}
+
+/// 解码 aws-chunked 编码的请求体，有 `signer` 时逐块校验 chunk-signature
+#[derive(Default)]
+struct ChunkDecoder {
+  buffer: Vec<u8>,
+  state: ChunkState,
+  signer: Option<ChunkSigner>,
+  /// 当前数据块的签名和已读取部分的 SHA256
+  signature: String,
+  sha256: Sha256,
+}
+
+impl ChunkDecoder {
+  fn feed(&mut self, data: &[u8]) -> Result<Vec<Bytes>, S3Error> {
+    self.buffer.extend_from_slice(data);
+    let mut chunks = Vec::new();
+    let mut pos = 0;
+    loop {
+      match self.state {
+        ChunkState::Size => {
+          let Some(end) = find_line(&self.buffer[pos..]) else {
+            break;
+          };
+          let line = String::from_utf8_lossy(&self.buffer[pos..pos + end]);
+          let (size, extension) = line.split_once(';').unwrap_or((&line, ""));
+          let size = usize::from_str_radix(size.trim(), 16).map_err(|_| invalid_chunk())?;
+          self.signature = extension
+            .trim()
+            .strip_prefix("chunk-signature=")
+            .unwrap_or_default()
+            .to_string();
+          pos += end + 2;
+          self.state = if size == 0 {
+            self.verify_chunk()?;
+            ChunkState::Trailer
+          } else {
+            ChunkState::Data(size)
+          };
+        }
+        ChunkState::Data(remaining) => {
+          let available = (self.buffer.len() - pos).min(remaining);
+          if available == 0 {
+            break;
+          }
+          let data = &self.buffer[pos..pos + available];
+          if self.signer.is_some() {
+            self.sha256.update(data);
+          }
+          chunks.push(Bytes::copy_from_slice(data));
+          pos += available;
+          self.state = if available == remaining {
+            self.verify_chunk()?;
+            ChunkState::DataEnd
+          } else {
+            ChunkState::Data(remaining - available)
+          };
+        }
+        ChunkState::DataEnd => {
+          if self.buffer.len() - pos < 2 {
+            break;
+          }
+          if &self.buffer[pos..pos + 2] != b"\r\n" {
+            return Err(invalid_chunk());
+          }
+          pos += 2;
+          self.state = ChunkState::Size;
+        }
+        ChunkState::Trailer => {
+          let Some(end) = find_line(&self.buffer[pos..]) else {
+            break;
+          };
+          pos += end + 2;
+          if end == 0 {
+            self.state = ChunkState::Done;
+          }
+        }
+        ChunkState::Done => {
+          pos = self.buffer.len();
+          break;
+        }
+      }
+    }
+    self.buffer.drain(..pos);
+    Ok(chunks)
+  }
+
+  fn verify_chunk(&mut self) -> Result<(), S3Error> {
+    let sha256 = self.sha256.finalize_reset();
+    if let Some(signer) = &mut self.signer
+      && !signer.verify(&sha256, &self.signature)
+    {
+      return Err(S3Error::new(
+        StatusCode::FORBIDDEN,
+        "SignatureDoesNotMatch",
+        "数据块签名不匹配",
+      ));
+    }
+    Ok(())
+  }
+
+  /// 有的客户端在最后一个块后不发送结尾的空行
+  fn is_done(&self) -> bool {
+    matches!(self.state, ChunkState::Done | ChunkState::Trailer)
+  }
+}
+
+fn find_line(data: &[u8]) -> Option<usize> {
+  data.windows(2).position(|window| window == b"\r\n")
+}
+
+fn invalid_chunk() -> S3Error {
+  S3Error::new(
+    StatusCode::BAD_REQUEST,
+    "IncompleteBody",
+    "aws-chunked 编码格式错误",
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_chunk_decoder() {
+    let body = b"5;chunk-signature=abc\r\nhello\r\n6;chunk-signature=def\r\n world\r\n0;chunk-signature=0\r\nx-amz-checksum-crc32:AAAA\r\n\r\n";
+    // 按任意位置切分输入，结果应该一致
+    for split in 1..body.len() {
+      let mut decoder = ChunkDecoder::default();
+      let mut output = Vec::new();
+      for part in [&body[..split], &body[split..]] {
+        for chunk in decoder.feed(part).unwrap() {
+          output.extend_from_slice(&chunk);
+        }
+      }
+      assert_eq!(output, b"hello world");
+      assert!(decoder.is_done());
+    }
+
+    let mut decoder = ChunkDecoder::default();
+    assert!(decoder.feed(b"zz\r\n").is_err());
+  }
+}
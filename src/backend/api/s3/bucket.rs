@@ -0,0 +1,55 @@
+use axum::{extract::State, http::StatusCode, response::Response};
+use chrono::NaiveDateTime;
+
+use crate::backend::{
+  db::{self, DBConnection},
+  extractor::auth::AuthUser,
+  utils::xml::escape_xml,
+};
+
+use super::{S3Error, XMLNS, xml_response};
+
+/// ListBuckets：当前用户可以访问的存储
+pub async fn list_buckets(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+) -> Result<Response, S3Error> {
+  let conn = conn.lock().await;
+  let storages = db::permission::get_accessible_storages(&conn, user.id, user.role)?;
+  let username = db::user::get_user_by_id(&conn, user.id)
+    .map(|user| user.username)
+    .unwrap_or_default();
+
+  let buckets: String = storages
+    .into_iter()
+    .map(|(storage, _)| {
+      // created_at 为 SQLite 的 CURRENT_TIMESTAMP 格式
+      let created = NaiveDateTime::parse_from_str(&storage.created_at, "%Y-%m-%d %H:%M:%S")
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S.000Z").to_string())
+        .unwrap_or_else(|_| super::format_time(None));
+      format!(
+        "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+        escape_xml(&storage.path),
+        created
+      )
+    })
+    .collect();
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<ListAllMyBucketsResult xmlns="{}"><Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner><Buckets>{}</Buckets></ListAllMyBucketsResult>"#,
+      XMLNS,
+      user.id,
+      escape_xml(&username),
+      buckets
+    ),
+  ))
+}
+
+/// 不区分区域，返回空的 LocationConstraint（即 us-east-1）
+pub fn get_location() -> Result<Response, S3Error> {
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(r#"<LocationConstraint xmlns="{}"/>"#, XMLNS),
+  ))
+}
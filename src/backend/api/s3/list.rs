@@ -0,0 +1,298 @@
+use std::{path::Path, time::SystemTime};
+
+use axum::{http::StatusCode, response::Response};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+use crate::backend::{
+  api::download::make_etag,
+  utils::{file::is_system_file, validate::validate_path, xml::escape_xml},
+};
+
+use super::{S3Context, S3Error, XMLNS, format_time, xml_response};
+
+const MAX_KEYS: usize = 1000;
+
+pub struct ObjectEntry {
+  /// 文件夹以 `/` 结尾
+  pub key: String,
+  pub size: u64,
+  pub modified: Option<SystemTime>,
+}
+
+#[derive(Default)]
+struct Listing {
+  contents: Vec<ObjectEntry>,
+  common_prefixes: Vec<String>,
+  truncated: bool,
+  /// 最后返回的键或公共前缀，用于下一页
+  last: Option<String>,
+}
+
+/// ListObjects (V1) / ListObjectsV2
+pub async fn list_objects(ctx: &S3Context) -> Result<Response, S3Error> {
+  let param = |name: &str| ctx.query.get(name).cloned().unwrap_or_default();
+  let v2 = param("list-type") == "2";
+  let prefix = param("prefix");
+  let delimiter = param("delimiter");
+  let url_encoding = param("encoding-type") == "url";
+  let max_keys = ctx
+    .query
+    .get("max-keys")
+    .map(|value| value.parse::<usize>())
+    .transpose()
+    .map_err(|_| {
+      S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidArgument",
+        "max-keys 不合法",
+      )
+    })?
+    .unwrap_or(MAX_KEYS)
+    .min(MAX_KEYS);
+
+  let continuation = ctx.query.get("continuation-token").cloned();
+  let after = if v2 {
+    match &continuation {
+      Some(token) => URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| {
+          S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidArgument",
+            "continuation-token 不合法",
+          )
+        })?,
+      None => param("start-after"),
+    }
+  } else {
+    param("marker")
+  };
+
+  let root = ctx.storage.root.clone();
+  let listing = {
+    let (prefix, delimiter) = (prefix.clone(), delimiter.clone());
+    tokio::task::spawn_blocking(move || {
+      let entries = collect_entries(&root, &prefix, &delimiter);
+      paginate(entries, &prefix, &delimiter, &after, max_keys)
+    })
+    .await?
+  };
+
+  let encode = |value: &str| {
+    if url_encoding {
+      urlencoding::encode(value).into_owned()
+    } else {
+      escape_xml(value)
+    }
+  };
+  let mut body = format!(
+    r#"<ListBucketResult xmlns="{}"><Name>{}</Name><Prefix>{}</Prefix><MaxKeys>{}</MaxKeys><IsTruncated>{}</IsTruncated>"#,
+    XMLNS,
+    escape_xml(&ctx.bucket),
+    encode(&prefix),
+    max_keys,
+    listing.truncated
+  );
+  if !delimiter.is_empty() {
+    body.push_str(&format!("<Delimiter>{}</Delimiter>", encode(&delimiter)));
+  }
+  if url_encoding {
+    body.push_str("<EncodingType>url</EncodingType>");
+  }
+  let next = listing.last.as_deref().filter(|_| listing.truncated);
+  if v2 {
+    body.push_str(&format!(
+      "<KeyCount>{}</KeyCount>",
+      listing.contents.len() + listing.common_prefixes.len()
+    ));
+    if let Some(token) = &continuation {
+      body.push_str(&format!(
+        "<ContinuationToken>{}</ContinuationToken>",
+        escape_xml(token)
+      ));
+    }
+    if let Some(start_after) = ctx.query.get("start-after") {
+      body.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+    }
+    if let Some(next) = next {
+      body.push_str(&format!(
+        "<NextContinuationToken>{}</NextContinuationToken>",
+        URL_SAFE_NO_PAD.encode(next)
+      ));
+    }
+  } else {
+    body.push_str(&format!("<Marker>{}</Marker>", encode(&param("marker"))));
+    if let Some(next) = next {
+      body.push_str(&format!("<NextMarker>{}</NextMarker>", encode(next)));
+    }
+  }
+
+  for entry in &listing.contents {
+    body.push_str(&format!(
+      "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+      encode(&entry.key),
+      format_time(entry.modified),
+      escape_xml(&make_etag(entry.size, entry.modified)),
+      entry.size
+    ));
+  }
+  for prefix in &listing.common_prefixes {
+    body.push_str(&format!(
+      "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+      encode(prefix)
+    ));
+  }
+  body.push_str("</ListBucketResult>");
+  Ok(xml_response(StatusCode::OK, body))
+}
+
+/// 分隔符为 `/` 时只需要读取前缀所在的文件夹，否则递归遍历
+fn collect_entries(root: &Path, prefix: &str, delimiter: &str) -> Vec<ObjectEntry> {
+  let base_key = prefix.rfind('/').map(|i| &prefix[..=i]).unwrap_or_default();
+  if !base_key.is_empty() && !validate_path(base_key) {
+    return Vec::new();
+  }
+  let mut entries = Vec::new();
+  walk(
+    &root.join(base_key),
+    base_key,
+    delimiter != "/",
+    &mut entries,
+  );
+  entries.retain(|entry| entry.key.starts_with(prefix));
+  entries.sort_by(|a, b| a.key.cmp(&b.key));
+  entries
+}
+
+fn walk(dir: &Path, key_prefix: &str, recursive: bool, entries: &mut Vec<ObjectEntry>) {
+  let Ok(children) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for child in children.flatten() {
+    let Ok(name) = child.file_name().into_string() else {
+      continue;
+    };
+    if is_system_file(&name) {
+      continue;
+    }
+    let path = child.path();
+    let Ok(metadata) = std::fs::metadata(&path) else {
+      continue;
+    };
+    if metadata.is_dir() {
+      let key = format!("{}{}/", key_prefix, name);
+      let count = entries.len();
+      if recursive {
+        walk(&path, &key, true, entries);
+      }
+      // 空文件夹以 `dir/` 对象的形式出现，与 S3 客户端创建的文件夹标记一致
+      if !recursive || entries.len() == count {
+        entries.push(ObjectEntry {
+          key,
+          size: 0,
+          modified: metadata.modified().ok(),
+        });
+      }
+    } else {
+      entries.push(ObjectEntry {
+        key: format!("{}{}", key_prefix, name),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+      });
+    }
+  }
+}
+
+/// 按分隔符折叠公共前缀并分页，`after` 为上一页最后返回的键
+fn paginate(
+  entries: Vec<ObjectEntry>,
+  prefix: &str,
+  delimiter: &str,
+  after: &str,
+  max_keys: usize,
+) -> Listing {
+  let mut listing = Listing::default();
+  let mut count = 0;
+  for entry in entries {
+    if entry.key.as_str() <= after {
+      continue;
+    }
+    let rest = &entry.key[prefix.len()..];
+    let common_prefix = (!delimiter.is_empty())
+      .then(|| rest.find(delimiter))
+      .flatten()
+      .map(|i| entry.key[..prefix.len() + i + delimiter.len()].to_string());
+
+    if let Some(common_prefix) = &common_prefix
+      && (common_prefix.as_str() <= after || listing.last.as_ref() == Some(common_prefix))
+    {
+      continue;
+    }
+    if count == max_keys {
+      listing.truncated = true;
+      break;
+    }
+    count += 1;
+    match common_prefix {
+      Some(common_prefix) => {
+        listing.last = Some(common_prefix.clone());
+        listing.common_prefixes.push(common_prefix);
+      }
+      None => {
+        listing.last = Some(entry.key.clone());
+        listing.contents.push(entry);
+      }
+    }
+  }
+  listing
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entries(keys: &[&str]) -> Vec<ObjectEntry> {
+    keys
+      .iter()
+      .map(|key| ObjectEntry {
+        key: key.to_string(),
+        size: 0,
+        modified: None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_paginate() {
+    let keys = [
+      "a.txt",
+      "docs/a.txt",
+      "docs/b/c.txt",
+      "photos/x.jpg",
+      "z.txt",
+    ];
+
+    let listing = paginate(entries(&keys), "", "/", "", 1000);
+    let contents: Vec<_> = listing.contents.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(contents, ["a.txt", "z.txt"]);
+    assert_eq!(listing.common_prefixes, ["docs/", "photos/"]);
+    assert!(!listing.truncated);
+
+    let listing = paginate(entries(&keys[1..3]), "docs/", "/", "", 1000);
+    assert_eq!(listing.contents.len(), 1);
+    assert_eq!(listing.common_prefixes, ["docs/b/"]);
+
+    let listing = paginate(entries(&keys), "", "/", "", 2);
+    assert!(listing.truncated);
+    assert_eq!(listing.last.as_deref(), Some("docs/"));
+    let listing = paginate(entries(&keys), "", "/", "docs/", 2);
+    assert_eq!(listing.common_prefixes, ["photos/"]);
+    assert_eq!(listing.contents[0].key, "z.txt");
+    assert!(!listing.truncated);
+
+    let listing = paginate(entries(&keys), "", "", "docs/a.txt", 1000);
+    assert_eq!(listing.contents.len(), 3);
+  }
+}
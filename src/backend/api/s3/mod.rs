@@ -0,0 +1,362 @@
+mod auth;
+mod body;
+mod bucket;
+mod list;
+mod multipart;
+mod object;
+use std::{collections::HashMap, net::SocketAddr, time::SystemTime};
+
+use axum::{
+  Extension, Router,
+  body::Body,
+  extract::{Path, Query, State},
+  http::{HeaderMap, Method, StatusCode, header::CONTENT_TYPE},
+  middleware,
+  response::{IntoResponse, Response},
+  routing::{any, get},
+};
+
+use crate::backend::{
+  db::{DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::{auth::AuthUser, storage::Storage},
+  utils::{
+    policy::{EXTENSION_BLOCKED, EXTENSION_NOT_ALLOWED, FILE_TOO_LARGE},
+    xml::escape_xml,
+  },
+};
+
+const XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// S3 兼容接口使用独立端口，以 path-style 访问：`/{bucket}/{key}`，bucket 对应存储路径。
+/// 默认关闭，设置 `S3_ENABLED=true` 开启，端口由 `S3_PORT` 指定
+pub fn spawn_s3_server(conn: DBConnection, host: &str) {
+  let enabled = std::env::var("S3_ENABLED").is_ok_and(|value| value == "true");
+  if !enabled {
+    return;
+  }
+  let port = std::env::var("S3_PORT")
+    .ok()
+    .and_then(|value| value.parse::<u16>().ok())
+    .unwrap_or(3331);
+  let addr = format!("{}:{}", host, port)
+    .parse::<SocketAddr>()
+    .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], port)));
+
+  let app = create_s3_router()
+    .layer(middleware::from_fn_with_state(
+      conn.clone(),
+      auth::s3_auth_middleware,
+    ))
+    .layer(axum::extract::DefaultBodyLimit::disable())
+    .with_state(conn);
+  tokio::spawn(async move {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+      Ok(listener) => listener,
+      Err(err) => {
+        log::error!("S3 server failed to bind {}: {}", addr, err);
+        return;
+      }
+    };
+    log::info!("S3 server starting on port {}", port);
+    if let Err(err) = axum::serve(listener, app).await {
+      log::error!("S3 server stopped: {}", err);
+    }
+  });
+}
+
+fn create_s3_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(bucket::list_buckets))
+    .route("/{bucket}", any(bucket_handler))
+    .route("/{bucket}/", any(bucket_handler))
+    .route("/{bucket}/{*key}", any(object_handler))
+}
+
+/// 一次 S3 请求解析后的上下文
+pub struct S3Context {
+  pub conn: DBConnection,
+  pub user: AuthUser,
+  pub storage: Storage,
+  pub bucket: String,
+  /// 对象键，文件夹以 `/` 结尾
+  pub key: String,
+  pub headers: HeaderMap,
+  pub query: HashMap<String, String>,
+  /// 数据块带签名的上传才有
+  pub chunk_signer: Option<auth::ChunkSigner>,
+}
+
+impl S3Context {
+  #[allow(clippy::too_many_arguments)]
+  async fn new(
+    conn: DBConnection,
+    user: AuthUser,
+    bucket: String,
+    key: String,
+    headers: HeaderMap,
+    query: HashMap<String, String>,
+    chunk_signer: Option<auth::ChunkSigner>,
+    required: AccessLevel,
+  ) -> Result<Self, S3Error> {
+    let storage = resolve(&conn, &user, &bucket, &key, required).await?;
+    Ok(Self {
+      conn,
+      user,
+      storage,
+      bucket,
+      key,
+      headers,
+      query,
+      chunk_signer,
+    })
+  }
+}
+
+/// 对象键映射为存储内的路径，沿用 `utils::validate` 的路径校验
+pub async fn resolve(
+  conn: &DBConnection,
+  user: &AuthUser,
+  bucket: &str,
+  key: &str,
+  required: AccessLevel,
+) -> Result<Storage, S3Error> {
+  if bucket.contains('/') {
+    return Err(S3Error::no_such_bucket());
+  }
+  let raw_path = match key.trim_end_matches('/') {
+    "" => bucket.to_string(),
+    key => format!("{}/{}", bucket, key),
+  };
+  let conn = conn.lock().await;
+  let storage = Storage::resolve(&conn, user, &raw_path, required).map_err(|err| {
+    if err.status() == StatusCode::NOT_FOUND {
+      S3Error::no_such_bucket()
+    } else if err.status() == StatusCode::BAD_REQUEST {
+      S3Error::new(StatusCode::BAD_REQUEST, "InvalidObjectName", &err.message())
+    } else {
+      S3Error::from(err)
+    }
+  })?;
+  storage.require_local()?;
+  Ok(storage)
+}
+
+async fn bucket_handler(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  method: Method,
+  Path(bucket): Path<String>,
+  Query(query): Query<HashMap<String, String>>,
+  headers: HeaderMap,
+  body: Body,
+) -> Response {
+  let required = match method {
+    Method::GET | Method::HEAD => AccessLevel::Read,
+    _ => AccessLevel::Write,
+  };
+  let result = async {
+    let ctx = S3Context::new(
+      conn,
+      user,
+      bucket,
+      String::new(),
+      headers,
+      query,
+      None,
+      required,
+    )
+    .await?;
+    match method {
+      Method::HEAD => Ok(empty(StatusCode::OK)),
+      Method::GET if ctx.query.contains_key("location") => bucket::get_location(),
+      Method::GET if ctx.query.contains_key("uploads") => multipart::list_uploads(&ctx).await,
+      Method::GET => list::list_objects(&ctx).await,
+      Method::POST if ctx.query.contains_key("delete") => object::delete_objects(&ctx, body).await,
+      // 存储只能由管理员在网页端创建 / 删除
+      Method::PUT => Err(S3Error::new(
+        StatusCode::CONFLICT,
+        "BucketAlreadyOwnedByYou",
+        "存储已存在",
+      )),
+      _ => Err(S3Error::not_implemented()),
+    }
+  }
+  .await;
+  result.unwrap_or_else(IntoResponse::into_response)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn object_handler(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  method: Method,
+  Path((bucket, key)): Path<(String, String)>,
+  Query(query): Query<HashMap<String, String>>,
+  chunk_signer: Option<Extension<auth::ChunkSigner>>,
+  headers: HeaderMap,
+  body: Body,
+) -> Response {
+  let required = match method {
+    Method::GET | Method::HEAD => AccessLevel::Read,
+    _ => AccessLevel::Write,
+  };
+  let result = async {
+    let chunk_signer = chunk_signer.map(|Extension(signer)| signer);
+    let ctx = S3Context::new(
+      conn,
+      user,
+      bucket,
+      key,
+      headers,
+      query,
+      chunk_signer,
+      required,
+    )
+    .await?;
+    let upload_id = ctx.query.get("uploadId").cloned();
+    match (method, upload_id) {
+      (Method::GET | Method::HEAD, None) => object::get_object(&ctx).await,
+      (Method::PUT, None) if ctx.headers.contains_key("x-amz-copy-source") => {
+        object::copy_object(&ctx).await
+      }
+      (Method::PUT, None) => object::put_object(&ctx, body).await,
+      (Method::DELETE, None) => object::delete_object(&ctx).await,
+      (Method::POST, None) if ctx.query.contains_key("uploads") => {
+        multipart::create_upload(&ctx).await
+      }
+      (Method::PUT, Some(upload_id)) => multipart::upload_part(&ctx, &upload_id, body).await,
+      (Method::GET, Some(upload_id)) => multipart::list_parts(&ctx, &upload_id).await,
+      (Method::POST, Some(upload_id)) => multipart::complete_upload(&ctx, &upload_id, body).await,
+      (Method::DELETE, Some(upload_id)) => multipart::abort_upload(&ctx, &upload_id).await,
+      _ => Err(S3Error::not_implemented()),
+    }
+  }
+  .await;
+  result.unwrap_or_else(IntoResponse::into_response)
+}
+
+// -------------------------------------------
+// S3Error
+// -------------------------------------------
+
+/// S3 客户端根据 XML 中的 Code 判断错误类型
+#[derive(Debug)]
+pub struct S3Error {
+  status: StatusCode,
+  code: &'static str,
+  message: String,
+}
+
+impl S3Error {
+  pub fn new(status: StatusCode, code: &'static str, message: &str) -> Self {
+    Self {
+      status,
+      code,
+      message: message.to_string(),
+    }
+  }
+
+  pub fn access_denied(message: &str) -> Self {
+    Self::new(StatusCode::FORBIDDEN, "AccessDenied", message)
+  }
+
+  pub fn invalid_request(message: &str) -> Self {
+    Self::new(StatusCode::BAD_REQUEST, "InvalidRequest", message)
+  }
+
+  pub fn no_such_bucket() -> Self {
+    Self::new(StatusCode::NOT_FOUND, "NoSuchBucket", "存储不存在")
+  }
+
+  pub fn no_such_key() -> Self {
+    Self::new(StatusCode::NOT_FOUND, "NoSuchKey", "文件不存在")
+  }
+
+  pub fn not_implemented() -> Self {
+    Self::new(
+      StatusCode::NOT_IMPLEMENTED,
+      "NotImplemented",
+      "不支持该操作",
+    )
+  }
+}
+
+impl From<AppError> for S3Error {
+  fn from(err: AppError) -> Self {
+    let code = match (err.code(), err.status()) {
+      (Some(FILE_TOO_LARGE), _) => "EntityTooLarge",
+      (Some(EXTENSION_BLOCKED | EXTENSION_NOT_ALLOWED), _) => "AccessDenied",
+      (_, StatusCode::NOT_FOUND) => "NoSuchKey",
+      (_, StatusCode::FORBIDDEN) => "AccessDenied",
+      (_, StatusCode::BAD_REQUEST) => "InvalidArgument",
+      _ => "InternalError",
+    };
+    let status = match code {
+      "EntityTooLarge" => StatusCode::BAD_REQUEST,
+      _ => err.status(),
+    };
+    Self::new(status, code, &err.message())
+  }
+}
+
+impl From<std::io::Error> for S3Error {
+  fn from(err: std::io::Error) -> Self {
+    Self::from(AppError::from(err))
+  }
+}
+
+impl From<anyhow::Error> for S3Error {
+  fn from(err: anyhow::Error) -> Self {
+    Self::from(AppError::from(err))
+  }
+}
+
+impl From<tokio::task::JoinError> for S3Error {
+  fn from(err: tokio::task::JoinError) -> Self {
+    Self::from(AppError::from(err))
+  }
+}
+
+impl IntoResponse for S3Error {
+  fn into_response(self) -> Response {
+    if self.status.is_server_error() {
+      log::error!("S3 error: {}", self.message);
+    }
+    let body = format!(
+      "<Error><Code>{}</Code><Message>{}</Message></Error>",
+      self.code,
+      escape_xml(&self.message)
+    );
+    xml_response(self.status, body)
+  }
+}
+
+// -------------------------------------------
+// 响应工具
+// -------------------------------------------
+
+pub fn xml_response(status: StatusCode, body: String) -> Response {
+  Response::builder()
+    .status(status)
+    .header(CONTENT_TYPE, "application/xml")
+    .body(Body::from(format!(
+      r#"<?xml version="1.0" encoding="UTF-8"?>{}"#,
+      body
+    )))
+    .unwrap_or_default()
+}
+
+pub fn empty(status: StatusCode) -> Response {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap_or_default()
+}
+
+/// S3 使用带毫秒的 ISO 8601 时间
+pub fn format_time(time: Option<SystemTime>) -> String {
+  chrono::DateTime::<chrono::Utc>::from(time.unwrap_or(SystemTime::UNIX_EPOCH))
+    .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+    .to_string()
+}
@@ -0,0 +1,372 @@
+use std::{
+  io::Write,
+  path::{Path, PathBuf},
+  sync::LazyLock,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use axum::{
+  body::Body,
+  http::{StatusCode, header::ETAG},
+  response::Response,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::backend::utils::{
+  self,
+  xml::{escape_xml, unescape_xml},
+};
+
+use super::{
+  S3Context, S3Error, XMLNS, body, format_time,
+  object::{finish_write, prepare_parent},
+  xml_response,
+};
+
+const MAX_PART_NUMBER: u32 = 10000;
+
+/// 分片上传的信息保存在 `upload.json`，分片保存为 `{n}.part`，其 MD5 保存在 `{n}.etag`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadMeta {
+  key: String,
+  user_id: i64,
+  /// unix 时间戳，秒
+  initiated: u64,
+}
+
+struct Part {
+  number: u32,
+  etag: String,
+  size: u64,
+  modified: Option<SystemTime>,
+}
+
+/// 未完成的分片上传放在存储内，服务重启后仍然可以继续
+fn uploads_dir(root: &Path) -> PathBuf {
+  root.join(".storkitty").join("s3-uploads")
+}
+
+/// CreateMultipartUpload
+pub async fn create_upload(ctx: &S3Context) -> Result<Response, S3Error> {
+  let path = ctx.storage.path.get_path();
+  if ctx.key.ends_with('/') {
+    return Err(S3Error::invalid_request("不能分片上传文件夹"));
+  }
+  ctx
+    .storage
+    .policy
+    .check_name(&path.file_name().unwrap_or_default().to_string_lossy())?;
+
+  let upload_id = hex::encode(rand::random::<[u8; 16]>());
+  let dir = uploads_dir(&ctx.storage.root).join(&upload_id);
+  fs::create_dir_all(&dir).await?;
+  let meta = UploadMeta {
+    key: ctx.key.clone(),
+    user_id: ctx.user.id,
+    initiated: SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_secs(),
+  };
+  let meta = serde_json::to_vec(&meta).context("Failed to serialize upload")?;
+  fs::write(dir.join("upload.json"), meta).await?;
+
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<InitiateMultipartUploadResult xmlns="{}"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>"#,
+      XMLNS,
+      escape_xml(&ctx.bucket),
+      escape_xml(&ctx.key),
+      upload_id
+    ),
+  ))
+}
+
+/// UploadPart
+pub async fn upload_part(
+  ctx: &S3Context,
+  upload_id: &str,
+  body: Body,
+) -> Result<Response, S3Error> {
+  let dir = load_upload(ctx, upload_id).await?;
+  let number = ctx
+    .query
+    .get("partNumber")
+    .and_then(|value| value.parse::<u32>().ok())
+    .filter(|number| (1..=MAX_PART_NUMBER).contains(number))
+    .ok_or_else(|| {
+      S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidArgument",
+        "partNumber 必须在 1 到 10000 之间",
+      )
+    })?;
+
+  let temp = dir.join(format!("{}.tmp", number));
+  let md5 = match body::write_body(
+    body,
+    &ctx.headers,
+    ctx.chunk_signer.clone(),
+    &temp,
+    &ctx.storage.policy,
+  )
+  .await
+  {
+    Ok(md5) => md5,
+    Err(err) => {
+      let _ = fs::remove_file(&temp).await;
+      return Err(err);
+    }
+  };
+  let etag = hex::encode(md5);
+  fs::rename(&temp, dir.join(format!("{}.part", number))).await?;
+  fs::write(dir.join(format!("{}.etag", number)), &etag).await?;
+
+  Ok(
+    Response::builder()
+      .status(StatusCode::OK)
+      .header(ETAG, format!("\"{}\"", etag))
+      .body(Body::empty())
+      .unwrap_or_default(),
+  )
+}
+
+/// ListParts
+pub async fn list_parts(ctx: &S3Context, upload_id: &str) -> Result<Response, S3Error> {
+  let dir = load_upload(ctx, upload_id).await?;
+  let parts: String = read_parts(&dir)
+    .await?
+    .into_iter()
+    .map(|part| {
+      format!(
+        "<Part><PartNumber>{}</PartNumber><LastModified>{}</LastModified><ETag>\"{}\"</ETag><Size>{}</Size></Part>",
+        part.number,
+        format_time(part.modified),
+        part.etag,
+        part.size
+      )
+    })
+    .collect();
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<ListPartsResult xmlns="{}"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId><PartNumberMarker>0</PartNumberMarker><MaxParts>{}</MaxParts><IsTruncated>false</IsTruncated><StorageClass>STANDARD</StorageClass>{}</ListPartsResult>"#,
+      XMLNS,
+      escape_xml(&ctx.bucket),
+      escape_xml(&ctx.key),
+      upload_id,
+      MAX_PART_NUMBER,
+      parts
+    ),
+  ))
+}
+
+/// CompleteMultipartUpload：按请求中的顺序合并分片
+pub async fn complete_upload(
+  ctx: &S3Context,
+  upload_id: &str,
+  body: Body,
+) -> Result<Response, S3Error> {
+  let dir = load_upload(ctx, upload_id).await?;
+  let body = body::read_xml_body(body, &ctx.headers).await?;
+  let requested = parse_complete_parts(&body)?;
+
+  let parts = read_parts(&dir).await?;
+  let mut files = Vec::with_capacity(requested.len());
+  let mut total = 0;
+  for (number, etag) in &requested {
+    let part = parts
+      .iter()
+      .find(|part| part.number == *number && part.etag == *etag)
+      .ok_or_else(|| {
+        S3Error::new(
+          StatusCode::BAD_REQUEST,
+          "InvalidPart",
+          &format!("分片 {} 不存在或 ETag 不匹配", number),
+        )
+      })?;
+    total += part.size;
+    files.push(dir.join(format!("{}.part", number)));
+  }
+  ctx.storage.policy.check_size(total)?;
+
+  let path = ctx.storage.path.get_path();
+  let created = prepare_parent(&ctx.storage.root, &path)?;
+  let temp = utils::backend::temp_file(&ctx.storage.root).await?;
+  let merged = {
+    let temp = temp.clone();
+    tokio::task::spawn_blocking(move || merge_parts(&files, &temp)).await?
+  };
+  if let Err(err) = merged {
+    let _ = fs::remove_file(&temp).await;
+    return Err(err.into());
+  }
+  let etag = finish_write(ctx, &temp, &path, created).await?;
+  let _ = fs::remove_dir_all(&dir).await;
+
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<CompleteMultipartUploadResult xmlns="{}"><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>"#,
+      XMLNS,
+      escape_xml(&ctx.bucket),
+      escape_xml(&ctx.key),
+      escape_xml(&etag)
+    ),
+  ))
+}
+
+/// AbortMultipartUpload
+pub async fn abort_upload(ctx: &S3Context, upload_id: &str) -> Result<Response, S3Error> {
+  let dir = load_upload(ctx, upload_id).await?;
+  fs::remove_dir_all(&dir).await?;
+  Ok(super::empty(StatusCode::NO_CONTENT))
+}
+
+/// ListMultipartUploads：只列出当前用户发起的上传
+pub async fn list_uploads(ctx: &S3Context) -> Result<Response, S3Error> {
+  let prefix = ctx.query.get("prefix").cloned().unwrap_or_default();
+  let mut uploads = Vec::new();
+  if let Ok(mut children) = fs::read_dir(uploads_dir(&ctx.storage.root)).await {
+    while let Some(child) = children.next_entry().await? {
+      let Ok(data) = fs::read(child.path().join("upload.json")).await else {
+        continue;
+      };
+      let Ok(meta) = serde_json::from_slice::<UploadMeta>(&data) else {
+        continue;
+      };
+      if meta.user_id == ctx.user.id && meta.key.starts_with(&prefix) {
+        uploads.push((meta, child.file_name().to_string_lossy().to_string()));
+      }
+    }
+  }
+  uploads.sort_by(|(a, a_id), (b, b_id)| (&a.key, a_id).cmp(&(&b.key, b_id)));
+
+  let uploads: String = uploads
+    .into_iter()
+    .map(|(meta, upload_id)| {
+      format!(
+        "<Upload><Key>{}</Key><UploadId>{}</UploadId><StorageClass>STANDARD</StorageClass><Initiated>{}</Initiated></Upload>",
+        escape_xml(&meta.key),
+        upload_id,
+        format_time(Some(UNIX_EPOCH + Duration::from_secs(meta.initiated)))
+      )
+    })
+    .collect();
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<ListMultipartUploadsResult xmlns="{}"><Bucket>{}</Bucket><KeyMarker></KeyMarker><UploadIdMarker></UploadIdMarker><Prefix>{}</Prefix><MaxUploads>1000</MaxUploads><IsTruncated>false</IsTruncated>{}</ListMultipartUploadsResult>"#,
+      XMLNS,
+      escape_xml(&ctx.bucket),
+      escape_xml(&prefix),
+      uploads
+    ),
+  ))
+}
+
+/// 校验上传 ID 并确认属于当前用户和对象键
+async fn load_upload(ctx: &S3Context, upload_id: &str) -> Result<PathBuf, S3Error> {
+  let no_such_upload = || S3Error::new(StatusCode::NOT_FOUND, "NoSuchUpload", "分片上传不存在");
+  if upload_id.len() != 32 || !upload_id.chars().all(|c| c.is_ascii_hexdigit()) {
+    return Err(no_such_upload());
+  }
+  let dir = uploads_dir(&ctx.storage.root).join(upload_id);
+  let data = fs::read(dir.join("upload.json"))
+    .await
+    .map_err(|_| no_such_upload())?;
+  let meta: UploadMeta = serde_json::from_slice(&data).map_err(|_| no_such_upload())?;
+  if meta.key != ctx.key || meta.user_id != ctx.user.id {
+    return Err(no_such_upload());
+  }
+  Ok(dir)
+}
+
+async fn read_parts(dir: &Path) -> Result<Vec<Part>, S3Error> {
+  let mut parts = Vec::new();
+  let mut children = fs::read_dir(dir).await?;
+  while let Some(child) = children.next_entry().await? {
+    let name = child.file_name().to_string_lossy().to_string();
+    let Some(number) = name
+      .strip_suffix(".part")
+      .and_then(|number| number.parse::<u32>().ok())
+    else {
+      continue;
+    };
+    let Ok(etag) = fs::read_to_string(dir.join(format!("{}.etag", number))).await else {
+      continue;
+    };
+    let metadata = child.metadata().await?;
+    parts.push(Part {
+      number,
+      etag,
+      size: metadata.len(),
+      modified: metadata.modified().ok(),
+    });
+  }
+  parts.sort_by_key(|part| part.number);
+  Ok(parts)
+}
+
+/// 解析 `<Part><PartNumber>1</PartNumber><ETag>"..."</ETag></Part>`，分片需要按升序排列
+fn parse_complete_parts(body: &str) -> Result<Vec<(u32, String)>, S3Error> {
+  static PART: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<Part>(.*?)</Part>").unwrap());
+  static NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<PartNumber>\s*(\d+)\s*</PartNumber>").unwrap());
+  static ETAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<ETag>(.*?)</ETag>").unwrap());
+  let malformed = || S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "分片列表格式错误");
+
+  let mut parts: Vec<(u32, String)> = Vec::new();
+  for captures in PART.captures_iter(body) {
+    let part = &captures[1];
+    let number = NUMBER
+      .captures(part)
+      .and_then(|captures| captures[1].parse().ok())
+      .ok_or_else(malformed)?;
+    let etag = ETAG.captures(part).ok_or_else(malformed)?;
+    let etag = unescape_xml(&etag[1]).trim().trim_matches('"').to_string();
+    if parts.last().is_some_and(|(last, _)| *last >= number) {
+      return Err(S3Error::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidPartOrder",
+        "分片需要按升序排列",
+      ));
+    }
+    parts.push((number, etag));
+  }
+  if parts.is_empty() {
+    return Err(malformed());
+  }
+  Ok(parts)
+}
+
+fn merge_parts(files: &[PathBuf], target: &Path) -> anyhow::Result<()> {
+  let mut output = std::fs::File::create(target).context("Failed to create merged file")?;
+  for file in files {
+    let mut input = std::fs::File::open(file).context("Failed to open part")?;
+    std::io::copy(&mut input, &mut output).context("Failed to merge part")?;
+  }
+  output.flush()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_complete_parts() {
+    let body = r#"<CompleteMultipartUpload><Part><ETag>&quot;aa&quot;</ETag><PartNumber>1</PartNumber></Part><Part><PartNumber>3</PartNumber><ETag>"bb"</ETag></Part></CompleteMultipartUpload>"#;
+    let parts = parse_complete_parts(body).unwrap();
+    assert_eq!(parts, [(1, "aa".to_string()), (3, "bb".to_string())]);
+
+    let body = "<Part><PartNumber>2</PartNumber><ETag>a</ETag></Part><Part><PartNumber>1</PartNumber><ETag>b</ETag></Part>";
+    assert!(parse_complete_parts(body).is_err());
+    assert!(parse_complete_parts("<CompleteMultipartUpload/>").is_err());
+  }
+}
@@ -0,0 +1,317 @@
+use std::path::{Path, PathBuf};
+
+use axum::{
+  body::Body,
+  http::{
+    StatusCode,
+    header::{CONTENT_LENGTH, ETAG, LAST_MODIFIED},
+  },
+  response::Response,
+};
+use regex::Regex;
+use tokio::fs;
+
+use crate::backend::{
+  api::download::{make_etag, stream_file},
+  db::permission::AccessLevel,
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::{
+    self,
+    events::{self, ChangeKind},
+    transfer, trash,
+    xml::{escape_xml, unescape_xml},
+  },
+};
+
+use super::{S3Context, S3Error, XMLNS, body, format_time, resolve, xml_response};
+
+/// DeleteObjects 一次最多删除的数量
+const MAX_DELETE_KEYS: usize = 1000;
+
+/// GetObject / HeadObject，Range 和条件请求由 `stream_file` 处理
+pub async fn get_object(ctx: &S3Context) -> Result<Response, S3Error> {
+  let path = ctx.storage.path.get_path();
+  if ctx.key.ends_with('/') {
+    // 文件夹标记对象
+    let metadata = fs::metadata(&path)
+      .await
+      .ok()
+      .filter(|metadata| metadata.is_dir())
+      .ok_or_else(S3Error::no_such_key)?;
+    let modified = metadata.modified().ok();
+    let mut builder = Response::builder()
+      .header(CONTENT_LENGTH, 0)
+      .header(ETAG, make_etag(0, modified));
+    if let Some(modified) = modified {
+      builder = builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+    return Ok(builder.body(Body::empty()).unwrap_or_default());
+  }
+  if !path.is_file() {
+    return Err(S3Error::no_such_key());
+  }
+  let relative = relative_path(&ctx.storage.root, &path)?;
+  Ok(stream_file(ctx.storage.backend.clone(), &relative, &ctx.headers, true).await?)
+}
+
+/// PutObject，键以 `/` 结尾时创建文件夹
+pub async fn put_object(ctx: &S3Context, body: Body) -> Result<Response, S3Error> {
+  let storage = &ctx.storage;
+  let path = storage.path.get_path();
+  if ctx.key.ends_with('/') {
+    let created = prepare_dir(&storage.root, &path)?;
+    if let Some(created) = &created {
+      utils::search::refresh(storage.id, &storage.root, created);
+      events::publish(
+        storage.id,
+        &storage.root,
+        created,
+        ChangeKind::Created,
+        ctx.user.id,
+      );
+    }
+    let modified = fs::metadata(&path).await?.modified().ok();
+    return Ok(etag_response(&make_etag(0, modified)));
+  }
+
+  storage
+    .policy
+    .check_name(&path.file_name().unwrap_or_default().to_string_lossy())?;
+  let created = prepare_parent(&storage.root, &path)?;
+  let temp = utils::backend::temp_file(&storage.root).await?;
+  if let Err(err) = body::write_body(
+    body,
+    &ctx.headers,
+    ctx.chunk_signer.clone(),
+    &temp,
+    &storage.policy,
+  )
+  .await
+  {
+    let _ = fs::remove_file(&temp).await;
+    return Err(err);
+  }
+  let etag = finish_write(ctx, &temp, &path, created).await?;
+  Ok(etag_response(&etag))
+}
+
+/// CopyObject，来源可以是另一个存储
+pub async fn copy_object(ctx: &S3Context) -> Result<Response, S3Error> {
+  let source = ctx
+    .headers
+    .get("x-amz-copy-source")
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default();
+  let source = source.split('?').next().unwrap_or_default();
+  let source = urlencoding::decode(source)
+    .map_err(|_| S3Error::invalid_request("x-amz-copy-source 不合法"))?;
+  let Some((bucket, key)) = source.trim_start_matches('/').split_once('/') else {
+    return Err(S3Error::invalid_request("x-amz-copy-source 不合法"));
+  };
+  let source_storage = resolve(&ctx.conn, &ctx.user, bucket, key, AccessLevel::Read).await?;
+  let source_path = source_storage.path.get_path();
+  if key.ends_with('/') || !source_path.is_file() {
+    return Err(S3Error::no_such_key());
+  }
+
+  let storage = &ctx.storage;
+  let path = storage.path.get_path();
+  if ctx.key.ends_with('/') {
+    return Err(S3Error::invalid_request("目标不能是文件夹"));
+  }
+  if source_path != path {
+    storage
+      .policy
+      .check_name(&path.file_name().unwrap_or_default().to_string_lossy())?;
+    storage
+      .policy
+      .check_size(fs::metadata(&source_path).await?.len())?;
+    let created = prepare_parent(&storage.root, &path)?;
+    let temp = utils::backend::temp_file(&storage.root).await?;
+    let result = {
+      let (source_path, temp) = (source_path.clone(), temp.clone());
+      tokio::task::spawn_blocking(move || transfer::copy_path(&source_path, &temp)).await?
+    };
+    if let Err(err) = result {
+      let _ = fs::remove_file(&temp).await;
+      return Err(err.into());
+    }
+    finish_write(ctx, &temp, &path, created).await?;
+  }
+
+  // 复制到自身只用于更新元数据，这里不保存元数据，直接返回成功
+  let metadata = fs::metadata(&path).await?;
+  let modified = metadata.modified().ok();
+  Ok(xml_response(
+    StatusCode::OK,
+    format!(
+      r#"<CopyObjectResult xmlns="{}"><ETag>{}</ETag><LastModified>{}</LastModified></CopyObjectResult>"#,
+      XMLNS,
+      escape_xml(&make_etag(metadata.len(), modified)),
+      format_time(modified)
+    ),
+  ))
+}
+
+/// DeleteObject，不存在时同样返回成功
+pub async fn delete_object(ctx: &S3Context) -> Result<Response, S3Error> {
+  delete_key(ctx, &ctx.storage, &ctx.key).await?;
+  Ok(super::empty(StatusCode::NO_CONTENT))
+}
+
+/// DeleteObjects：`<Delete><Object><Key>..</Key></Object>...</Delete>`
+pub async fn delete_objects(ctx: &S3Context, body: Body) -> Result<Response, S3Error> {
+  let body = body::read_xml_body(body, &ctx.headers).await?;
+  let quiet = quiet_regex().is_match(&body);
+  let keys: Vec<String> = key_regex()
+    .captures_iter(&body)
+    .map(|captures| unescape_xml(&captures[1]))
+    .collect();
+  if keys.len() > MAX_DELETE_KEYS {
+    return Err(S3Error::new(
+      StatusCode::BAD_REQUEST,
+      "MalformedXML",
+      "一次最多删除 1000 个对象",
+    ));
+  }
+
+  let mut result = format!(r#"<DeleteResult xmlns="{}">"#, XMLNS);
+  for key in keys {
+    let deleted = async {
+      let storage = resolve(&ctx.conn, &ctx.user, &ctx.bucket, &key, AccessLevel::Write).await?;
+      delete_key(ctx, &storage, &key).await
+    }
+    .await;
+    match deleted {
+      Ok(()) if quiet => {}
+      Ok(()) => result.push_str(&format!(
+        "<Deleted><Key>{}</Key></Deleted>",
+        escape_xml(&key)
+      )),
+      Err(err) => result.push_str(&format!(
+        "<Error><Key>{}</Key><Code>{}</Code><Message>{}</Message></Error>",
+        escape_xml(&key),
+        err.code,
+        escape_xml(&err.message)
+      )),
+    }
+  }
+  result.push_str("</DeleteResult>");
+  Ok(xml_response(StatusCode::OK, result))
+}
+
+/// 文件移动到回收站；文件夹标记只在文件夹为空时删除，与 S3 中删除标记不影响其它对象一致
+async fn delete_key(ctx: &S3Context, storage: &Storage, key: &str) -> Result<(), S3Error> {
+  let path = storage.path.get_path();
+  if path == storage.root {
+    return Ok(());
+  }
+  if key.ends_with('/') {
+    let is_empty = path.is_dir() && std::fs::read_dir(&path)?.next().is_none();
+    if !is_empty {
+      return Ok(());
+    }
+    std::fs::remove_dir(&path)?;
+    utils::search::refresh(storage.id, &storage.root, &path);
+  } else if path.is_file() {
+    trash::move_to_trash(&ctx.conn, storage.id, &storage.root, &path, ctx.user.id).await?;
+  } else {
+    return Ok(());
+  }
+  events::publish(
+    storage.id,
+    &storage.root,
+    &path,
+    ChangeKind::Deleted,
+    ctx.user.id,
+  );
+  Ok(())
+}
+
+/// S3 中没有文件夹，写入对象前自动创建上级文件夹，返回新建的最上层文件夹
+pub fn prepare_dir(root: &Path, dir: &Path) -> Result<Option<PathBuf>, S3Error> {
+  let mut created = None;
+  for ancestor in dir.ancestors().take_while(|ancestor| *ancestor != root) {
+    match std::fs::metadata(ancestor) {
+      Ok(metadata) if !metadata.is_dir() => return Err(path_conflict()),
+      Ok(_) => break,
+      Err(_) => created = Some(ancestor.to_path_buf()),
+    }
+  }
+  std::fs::create_dir_all(dir)?;
+  Ok(created)
+}
+
+/// 写入文件前检查目标不是文件夹，并准备上级文件夹
+pub fn prepare_parent(root: &Path, path: &Path) -> Result<Option<PathBuf>, S3Error> {
+  if path.is_dir() {
+    return Err(path_conflict());
+  }
+  match path.parent() {
+    Some(parent) => prepare_dir(root, parent),
+    None => Ok(None),
+  }
+}
+
+fn path_conflict() -> S3Error {
+  S3Error::new(
+    StatusCode::CONFLICT,
+    "InvalidRequest",
+    "路径中存在同名的文件或文件夹",
+  )
+}
+
+/// 临时文件替换目标文件，更新索引并发布事件，返回新的 ETag
+pub async fn finish_write(
+  ctx: &S3Context,
+  temp: &Path,
+  path: &Path,
+  created: Option<PathBuf>,
+) -> Result<String, S3Error> {
+  let storage = &ctx.storage;
+  let existed = path.exists();
+  let result = async {
+    let relative = relative_path(&storage.root, path)?;
+    utils::version::preserve(&ctx.conn, storage.id, &relative, ctx.user.id).await?;
+    fs::rename(temp, path).await?;
+    Ok::<_, AppError>(())
+  }
+  .await;
+  if let Err(err) = result {
+    let _ = fs::remove_file(temp).await;
+    return Err(err.into());
+  }
+
+  let target = created.as_deref().unwrap_or(path);
+  utils::search::refresh(storage.id, &storage.root, target);
+  let kind = if existed {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  events::publish(storage.id, &storage.root, target, kind, ctx.user.id);
+
+  let metadata = fs::metadata(path).await?;
+  Ok(make_etag(metadata.len(), metadata.modified().ok()))
+}
+
+fn etag_response(etag: &str) -> Response {
+  Response::builder()
+    .status(StatusCode::OK)
+    .header(ETAG, etag)
+    .body(Body::empty())
+    .unwrap_or_default()
+}
+
+fn key_regex() -> &'static Regex {
+  static REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"(?s)<Key>(.*?)</Key>").unwrap());
+  &REGEX
+}
+
+fn quiet_regex() -> &'static Regex {
+  static REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"(?i)<Quiet>\s*true\s*</Quiet>").unwrap());
+  &REGEX
+}
@@ -0,0 +1,115 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::Context;
+use axum::{
+  Json, Router,
+  extract::{Query, State},
+  routing::get,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{
+  api::file::list::{FileInfo, FileType},
+  db::{self, DBConnection, search::SearchQuery},
+  error::AppError,
+  extractor::storage::Storage,
+  utils,
+};
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1000;
+
+pub fn create_search_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new().route("/{*path}", get(search))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchParams {
+  /// 全文关键字，匹配文件名以及已索引的文本内容
+  q: Option<String>,
+  /// 文件名子串或通配符，例如 `*.pdf`
+  name: Option<String>,
+  /// 逗号分隔的扩展名
+  ext: Option<String>,
+  min_size: Option<u64>,
+  max_size: Option<u64>,
+  modified_after: Option<String>,
+  modified_before: Option<String>,
+  limit: Option<u32>,
+  offset: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+  /// 路径相对于存储根目录
+  files: Vec<FileInfo>,
+  total: u64,
+}
+
+/// 在路径所在的文件夹（包含子文件夹）中搜索
+async fn search(
+  State(conn): State<DBConnection>,
+  storage: Storage,
+  Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResponse>, AppError> {
+  // 只有本地存储会建立索引
+  storage.require_local()?;
+  let Storage { id, path, root, .. } = storage;
+  let path = path.get_path();
+  if !path.is_dir() {
+    return Err(AppError::new("搜索范围不是文件夹"));
+  }
+  let parse_time = |value: Option<String>| match value.filter(|value| !value.is_empty()) {
+    Some(value) => utils::time::parse_time(&value)
+      .map(Some)
+      .ok_or_else(|| AppError::new(&format!("时间格式不正确: {}", value))),
+    None => Ok(None),
+  };
+
+  let query = SearchQuery {
+    scope: path
+      .strip_prefix(&root)
+      .context("路径不合法")?
+      .to_string_lossy()
+      .to_string(),
+    keyword: params.q,
+    name: params.name,
+    extensions: params
+      .ext
+      .unwrap_or_default()
+      .split(',')
+      .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+      .filter(|ext| !ext.is_empty())
+      .collect(),
+    min_size: params.min_size,
+    max_size: params.max_size,
+    modified_after: parse_time(params.modified_after)?,
+    modified_before: parse_time(params.modified_before)?,
+    limit: params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+    offset: params.offset.unwrap_or_default(),
+  };
+
+  let conn = conn.lock().await;
+  let (entries, total) = db::search::search(&conn, id, &query).context("搜索失败")?;
+  let files = entries
+    .into_iter()
+    .map(|entry| FileInfo {
+      thumbnail: !entry.is_dir && utils::thumb::supports(&entry.name),
+      name: entry.name,
+      path: entry.path,
+      file_type: if entry.is_dir {
+        FileType::Folder
+      } else {
+        FileType::File
+      },
+      size: (!entry.is_dir).then_some(entry.size),
+      modified: utils::time::format_modified_time(
+        UNIX_EPOCH + Duration::from_secs(entry.modified.max(0) as u64),
+      ),
+      items: None,
+    })
+    .collect();
+  Ok(Json(SearchResponse { files, total }))
+}
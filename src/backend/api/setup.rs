@@ -15,19 +15,30 @@ pub struct SetupDto {
 
 pub async fn setup(
   State(conn): State<DBConnection>,
-  Json(setup): Json<SetupDto>,
+  Json(mut setup): Json<SetupDto>,
 ) -> Result<(), AppError> {
+  let password_hash = utils::auth::hash_password(setup.user.password.clone()).await?;
   let mut conn = conn.lock().await;
   let no_user = db::user::is_no_user(&conn).unwrap_or(true);
   if !no_user {
     return Err(AppError::from(anyhow::anyhow!("用户已存在")));
   }
-  let tx = conn.transaction()?;
+  let mut user = setup.user;
+  user.role = db::user::Role::Admin;
+
   utils::file::create_dir(&setup.storage.local_path)?;
+  utils::backend::verify(
+    setup.storage.kind,
+    &mut setup.storage.config,
+    &setup.storage.local_path,
+  )
+  .await?;
+  let tx = conn.transaction()?;
 
-  db::user::create_user(&tx, setup.user)?;
+  db::user::create_user(&tx, user, &password_hash)?;
   db::storage::create_storage(&tx, setup.storage)?;
 
   tx.commit()?;
+  utils::watcher::reload();
   Ok(())
 }
@@ -0,0 +1,45 @@
+use axum::{Json, extract::State};
+
+use crate::backend::{
+  api::share::list::ShareDto,
+  db::{
+    DBConnection,
+    share::{self, CreateShareDto},
+  },
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::auth,
+};
+
+pub async fn create_share(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Storage {
+    id,
+    path,
+    root,
+    backend,
+    ..
+  }: Storage,
+  Json(dto): Json<CreateShareDto>,
+) -> Result<Json<ShareDto>, AppError> {
+  let relative_path = relative_path(&root, &path.get_path())?;
+  if !backend.exists(&relative_path).await? {
+    return Err(AppError::new("文件不存在"));
+  }
+  if dto.max_downloads.is_some_and(|max| max <= 0) {
+    return Err(AppError::new("下载次数限制必须大于 0"));
+  }
+
+  // 哈希很慢，不要在持有数据库锁时计算
+  let password_hash = match dto.password.clone().filter(|p| !p.is_empty()) {
+    Some(password) => Some(auth::hash_password(password).await?),
+    None => None,
+  };
+  let conn = conn.lock().await;
+  let share = share::create_share(&conn, id, &relative_path, user.id, dto, password_hash)?;
+  Ok(Json(ShareDto::from(share)))
+}
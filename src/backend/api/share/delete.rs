@@ -0,0 +1,30 @@
+use anyhow::Context;
+use axum::extract::{Path, State};
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    permission::{self, AccessLevel},
+    share,
+  },
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+/// 分享的创建者或者拥有存储管理权限的用户可以取消分享
+pub async fn delete_share(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  let target = share::get_share_by_id(&conn, id).context("分享不存在")?;
+  if target.created_by != user.id {
+    let level = permission::get_access_level(&conn, user.id, user.role, target.storage_id)?;
+    if level != Some(AccessLevel::Manage) {
+      return Err(AppError::new("无权取消该分享"));
+    }
+  }
+  share::delete_share(&conn, id)?;
+  Ok(())
+}
@@ -0,0 +1,69 @@
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    share::{self, Share},
+  },
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareListResponse {
+  pub shares: Vec<ShareDto>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareDto {
+  pub id: i64,
+  pub token: String,
+  pub url: String,
+  pub storage_id: i64,
+  pub path: String,
+  pub has_password: bool,
+  pub expires_at: Option<i64>,
+  pub max_downloads: Option<i64>,
+  pub download_count: i64,
+  pub created_by: i64,
+  pub created_at: String,
+}
+
+impl From<Share> for ShareDto {
+  fn from(share: Share) -> Self {
+    Self {
+      id: share.id,
+      url: format!("/s/{}", share.token),
+      token: share.token,
+      storage_id: share.storage_id,
+      path: share.path,
+      has_password: share.password.is_some(),
+      expires_at: share.expires_at,
+      max_downloads: share.max_downloads,
+      download_count: share.download_count,
+      created_by: share.created_by,
+      created_at: share.created_at,
+    }
+  }
+}
+
+/// 管理员可以看到全部分享，普通用户只能看到自己创建的
+pub async fn list_shares(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+) -> Result<Json<ShareListResponse>, AppError> {
+  let conn = conn.lock().await;
+  let shares = if user.is_admin() {
+    share::get_all_shares(&conn)
+  } else {
+    share::get_shares_by_user(&conn, user.id)
+  }
+  .context("获取分享失败")?;
+  Ok(Json(ShareListResponse {
+    shares: shares.into_iter().map(ShareDto::from).collect(),
+  }))
+}
@@ -0,0 +1,27 @@
+mod create;
+mod delete;
+mod list;
+mod public;
+use axum::{
+  Router,
+  routing::{delete, get, post},
+};
+
+use crate::backend::db::DBConnection;
+
+pub fn create_share_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(list::list_shares))
+    .route("/{id}", delete(delete::delete_share))
+    .route("/path/{*path}", post(create::create_share))
+}
+
+/// 无需登录的分享访问路由
+pub fn create_public_share_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/{token}", get(public::get_share).post(public::post_share))
+    .route(
+      "/{token}/{*path}",
+      get(public::get_share_path).post(public::post_share_path),
+    )
+}
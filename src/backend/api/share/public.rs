@@ -0,0 +1,324 @@
+use std::{
+  collections::HashMap,
+  sync::{LazyLock, Mutex},
+  time::{Duration, Instant},
+};
+
+use axum::{
+  Form, Json,
+  extract::{Path, Query, State},
+  http::{HeaderMap, StatusCode},
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::backend::{
+  api::{
+    download::{response_bytes, stream_file},
+    file::list::{FileListResponse, read_dir_files},
+  },
+  db::{self, DBConnection, permission::AccessLevel, share},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::auth,
+};
+
+pub const SHARE_NOT_FOUND: &str = "SHARE_NOT_FOUND";
+pub const SHARE_EXPIRED: &str = "SHARE_EXPIRED";
+pub const SHARE_PASSWORD_REQUIRED: &str = "SHARE_PASSWORD_REQUIRED";
+pub const SHARE_PASSWORD_INVALID: &str = "SHARE_PASSWORD_INVALID";
+pub const SHARE_DOWNLOAD_LIMIT: &str = "SHARE_DOWNLOAD_LIMIT";
+pub const SHARE_TOO_MANY_ATTEMPTS: &str = "SHARE_TOO_MANY_ATTEMPTS";
+
+/// 同一分享在时间窗口内允许的密码错误次数，超过后暂时拒绝验证
+const MAX_FAILED_ATTEMPTS: u32 = 10;
+const FAILED_ATTEMPTS_WINDOW: Duration = Duration::from_secs(10 * 60);
+/// 未下载完的记录超过这么久没有新的请求就丢弃，之后的请求计为新的下载
+const SERVED_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// 分享中未下载完的文件，按 (分享 id, 文件路径) 记录
+static SERVED: LazyLock<Mutex<HashMap<(i64, String), Served>>> = LazyLock::new(Default::default);
+
+struct Served {
+  /// 已返回的字节数
+  bytes: u64,
+  /// 最后一次请求的时间
+  updated: Instant,
+}
+
+/// 按分享 token 记录密码错误的次数和第一次错误的时间
+static FAILED_ATTEMPTS: LazyLock<Mutex<HashMap<String, (u32, Instant)>>> =
+  LazyLock::new(Default::default);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareQuery {
+  inline: Option<String>,
+}
+
+/// 用表单提交密码，方便不能设置请求头的场景直接下载。
+/// 密码不接受查询参数，避免出现在访问日志、浏览器历史和 Referer 中
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharePasswordForm {
+  password: Option<String>,
+}
+
+pub async fn get_share(
+  State(conn): State<DBConnection>,
+  Path(token): Path<String>,
+  Query(query): Query<ShareQuery>,
+  headers: HeaderMap,
+) -> Result<Response, AppError> {
+  let password = header_password(&headers);
+  access_share(conn, &token, None, password, query, &headers).await
+}
+
+pub async fn get_share_path(
+  State(conn): State<DBConnection>,
+  Path((token, path)): Path<(String, String)>,
+  Query(query): Query<ShareQuery>,
+  headers: HeaderMap,
+) -> Result<Response, AppError> {
+  let password = header_password(&headers);
+  access_share(conn, &token, Some(&path), password, query, &headers).await
+}
+
+pub async fn post_share(
+  State(conn): State<DBConnection>,
+  Path(token): Path<String>,
+  Query(query): Query<ShareQuery>,
+  headers: HeaderMap,
+  Form(form): Form<SharePasswordForm>,
+) -> Result<Response, AppError> {
+  let password = form.password.or_else(|| header_password(&headers));
+  access_share(conn, &token, None, password, query, &headers).await
+}
+
+pub async fn post_share_path(
+  State(conn): State<DBConnection>,
+  Path((token, path)): Path<(String, String)>,
+  Query(query): Query<ShareQuery>,
+  headers: HeaderMap,
+  Form(form): Form<SharePasswordForm>,
+) -> Result<Response, AppError> {
+  let password = form.password.or_else(|| header_password(&headers));
+  access_share(conn, &token, Some(&path), password, query, &headers).await
+}
+
+fn header_password(headers: &HeaderMap) -> Option<String> {
+  headers
+    .get("x-share-password")
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string())
+}
+
+fn share_invalid() -> AppError {
+  AppError::with_code(StatusCode::NOT_FOUND, SHARE_NOT_FOUND, "分享已失效")
+}
+
+/// 分享的是文件时直接下载，分享的是文件夹时返回只读的文件列表
+async fn access_share(
+  conn: DBConnection,
+  token: &str,
+  sub_path: Option<&str>,
+  password: Option<String>,
+  query: ShareQuery,
+  headers: &HeaderMap,
+) -> Result<Response, AppError> {
+  let share = share::get_share_by_token(&*conn.lock().await, token)
+    .map_err(|_| AppError::with_code(StatusCode::NOT_FOUND, SHARE_NOT_FOUND, "分享不存在"))?;
+
+  if share
+    .expires_at
+    .is_some_and(|expires_at| expires_at < Utc::now().timestamp())
+  {
+    return Err(AppError::with_code(
+      StatusCode::GONE,
+      SHARE_EXPIRED,
+      "分享已过期",
+    ));
+  }
+
+  // 不需要登录就能访问，bcrypt 校验很慢，放到阻塞线程中执行且不持有数据库锁
+  if let Some(hash) = share.password.clone() {
+    let Some(password) = password else {
+      return Err(AppError::with_code(
+        StatusCode::UNAUTHORIZED,
+        SHARE_PASSWORD_REQUIRED,
+        "需要提供分享密码",
+      ));
+    };
+    if too_many_attempts(token) {
+      return Err(AppError::with_code(
+        StatusCode::TOO_MANY_REQUESTS,
+        SHARE_TOO_MANY_ATTEMPTS,
+        "密码错误次数过多，请稍后再试",
+      ));
+    }
+    let valid =
+      tokio::task::spawn_blocking(move || auth::verify_password_cached(&password, &hash)).await?;
+    if !valid {
+      record_failed_attempt(token);
+      return Err(AppError::with_code(
+        StatusCode::UNAUTHORIZED,
+        SHARE_PASSWORD_INVALID,
+        "分享密码错误",
+      ));
+    }
+  }
+
+  let (share_id, backend, target) = {
+    let conn = conn.lock().await;
+    // 分享按创建者当前的权限访问，创建者被禁用或失去存储权限后分享随之失效
+    let creator = db::user::get_user_by_id(&conn, share.created_by)
+      .ok()
+      .filter(|user| !user.disabled)
+      .ok_or_else(share_invalid)?;
+    let creator = AuthUser {
+      id: creator.id,
+      role: creator.role,
+    };
+    let storage = Storage::resolve_by_id(
+      &conn,
+      &creator,
+      share.storage_id,
+      &share.path,
+      AccessLevel::Read,
+    )
+    .map_err(|_| share_invalid())?;
+
+    let target = match sub_path.filter(|p| !p.is_empty()) {
+      Some(sub_path) => storage.path.safe_join(sub_path)?,
+      None => storage.path.get_path(),
+    };
+    (
+      share.id,
+      storage.backend,
+      relative_path(&storage.root, &target)?,
+    )
+  };
+
+  // 远程存储的请求可能较慢，查询文件信息时不占用数据库连接
+  let Some(entry) = backend.try_stat(&target).await? else {
+    return Err(AppError::with_code(
+      StatusCode::NOT_FOUND,
+      SHARE_NOT_FOUND,
+      "文件不存在",
+    ));
+  };
+
+  if !entry.is_dir {
+    let bytes = response_bytes(headers, entry.size, entry.modified);
+    if !continue_download(share_id, &target, bytes, entry.size) {
+      if !share::increase_download_count(&*conn.lock().await, share_id)? {
+        return Err(AppError::with_code(
+          StatusCode::GONE,
+          SHARE_DOWNLOAD_LIMIT,
+          "分享下载次数已用完",
+        ));
+      }
+      start_download(share_id, &target, bytes, entry.size);
+    }
+  }
+
+  if entry.is_dir {
+    return Ok(
+      Json(FileListResponse {
+        files: read_dir_files(&*backend, &target).await?,
+      })
+      .into_response(),
+    );
+  }
+  stream_file(backend, &target, headers, query.inline.is_some()).await
+}
+
+fn too_many_attempts(token: &str) -> bool {
+  let mut attempts = FAILED_ATTEMPTS
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  attempts.retain(|_, (_, first)| first.elapsed() < FAILED_ATTEMPTS_WINDOW);
+  attempts
+    .get(token)
+    .is_some_and(|(count, _)| *count >= MAX_FAILED_ATTEMPTS)
+}
+
+fn record_failed_attempt(token: &str) {
+  let mut attempts = FAILED_ATTEMPTS
+    .lock()
+    .unwrap_or_else(|err| err.into_inner());
+  attempts
+    .entry(token.to_string())
+    .or_insert((0, Instant::now()))
+    .0 += 1;
+}
+
+/// 当前下载已返回的字节数。断点续传和拖动进度产生的 Range 请求在同一次下载内累计，
+/// 一次下载最多返回文件大小的字节数，超出时计为新的下载，所以 Range 无法绕过下载次数限制
+fn continue_download(share_id: i64, path: &str, bytes: u64, size: u64) -> bool {
+  if bytes == 0 {
+    return true;
+  }
+  let mut served = SERVED.lock().unwrap_or_else(|err| err.into_inner());
+  served.retain(|_, served| served.updated.elapsed() < SERVED_TTL);
+  let key = (share_id, path.to_string());
+  match served.get_mut(&key) {
+    Some(done) if done.bytes + bytes <= size => {
+      done.bytes += bytes;
+      done.updated = Instant::now();
+      if done.bytes >= size {
+        served.remove(&key);
+      }
+      true
+    }
+    _ => false,
+  }
+}
+
+fn start_download(share_id: i64, path: &str, bytes: u64, size: u64) {
+  let mut served = SERVED.lock().unwrap_or_else(|err| err.into_inner());
+  let key = (share_id, path.to_string());
+  if bytes >= size {
+    served.remove(&key);
+  } else {
+    served.insert(
+      key,
+      Served {
+        bytes,
+        updated: Instant::now(),
+      },
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_range_requests_share_one_download() {
+    // 第一次请求计为新的下载，后续请求在文件大小内累计
+    assert!(!continue_download(1, "a.txt", 40, 100));
+    start_download(1, "a.txt", 40, 100);
+    assert!(continue_download(1, "a.txt", 60, 100));
+    // 已经返回完整文件，再请求任何内容都是新的下载
+    assert!(!continue_download(1, "a.txt", 1, 100));
+    start_download(1, "a.txt", 100, 100);
+    assert!(!continue_download(1, "a.txt", 100, 100));
+    assert!(continue_download(1, "a.txt", 0, 100));
+  }
+
+  #[test]
+  fn test_failed_attempts_limit() {
+    for _ in 0..MAX_FAILED_ATTEMPTS {
+      assert!(!too_many_attempts("token-a"));
+      record_failed_attempt("token-a");
+    }
+    assert!(too_many_attempts("token-a"));
+    assert!(!too_many_attempts("token-b"));
+  }
+}
@@ -0,0 +1,14 @@
+use axum::{Json, extract::State};
+
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  utils::upload::{self, CleanupReport},
+};
+
+/// 立即清理所有存储中过期的上传和遗留的暂存文件，返回回收的空间
+pub async fn cleanup_uploads(
+  State(conn): State<DBConnection>,
+) -> Result<Json<CleanupReport>, AppError> {
+  Ok(Json(upload::cleanup(&conn).await?))
+}
@@ -0,0 +1,25 @@
+use axum::{Json, extract::State};
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    storage::{self, CreateStorageDto},
+  },
+  error::AppError,
+  utils,
+};
+
+pub async fn create_storage(
+  State(conn): State<DBConnection>,
+  Json(mut dto): Json<CreateStorageDto>,
+) -> Result<(), AppError> {
+  utils::file::create_dir(&dto.local_path)?;
+  utils::backend::verify(dto.kind, &mut dto.config, &dto.local_path).await?;
+  let conn = conn.lock().await;
+  if storage::get_storage_by_path(&conn, &dto.path).is_ok() {
+    return Err(AppError::new("应用路径已存在"));
+  }
+  storage::create_storage(&conn, dto)?;
+  utils::watcher::reload();
+  Ok(())
+}
@@ -0,0 +1,17 @@
+use axum::extract::{Path, State};
+
+use crate::backend::{
+  db::{DBConnection, storage},
+  error::AppError,
+  utils,
+};
+
+pub async fn delete_storage(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  storage::delete_storage(&conn, id)?;
+  utils::watcher::reload();
+  Ok(())
+}
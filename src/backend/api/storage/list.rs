@@ -0,0 +1,42 @@
+use anyhow::Context;
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    storage::{self, StorageDatabase},
+  },
+  error::AppError,
+  utils,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageListResponse {
+  pub storages: Vec<StorageDatabase>,
+}
+
+pub async fn list_storages(
+  State(conn): State<DBConnection>,
+) -> Result<Json<StorageListResponse>, AppError> {
+  let conn = conn.lock().await;
+  let mut storages = storage::get_all_storage(&conn).context("获取存储失败")?;
+  for storage in &mut storages {
+    utils::backend::redact_config(&mut storage.config);
+  }
+  Ok(Json(StorageListResponse { storages }))
+}
+
+pub async fn get_storage(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+) -> Result<Json<StorageDatabase>, AppError> {
+  let conn = conn.lock().await;
+  let mut storage = storage::get_storage_by_id(&conn, id).context("存储不存在")?;
+  utils::backend::redact_config(&mut storage.config);
+  Ok(Json(storage))
+}
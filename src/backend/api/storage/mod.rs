@@ -0,0 +1,31 @@
+mod cleanup;
+mod create;
+mod delete;
+mod list;
+mod permission;
+mod reindex;
+mod update;
+use axum::{
+  Router,
+  routing::{delete, get, patch, post, put},
+};
+
+use crate::backend::db::DBConnection;
+
+pub fn create_storage_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(list::list_storages))
+    .route("/", post(create::create_storage))
+    .route("/upload-cleanup", post(cleanup::cleanup_uploads))
+    .route("/{id}", get(list::get_storage))
+    .route("/{id}", put(update::update_storage))
+    .route("/{id}", delete(delete::delete_storage))
+    .route("/{id}/disabled", patch(update::set_disabled))
+    .route("/{id}/reindex", post(reindex::reindex_storage))
+    .route("/{id}/permission", get(permission::list_permissions))
+    .route("/{id}/permission", put(permission::set_permission))
+    .route(
+      "/{id}/permission/{user_id}",
+      delete(permission::delete_permission),
+    )
+}
@@ -0,0 +1,58 @@
+use anyhow::Context;
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    permission::{self, AccessLevel, StoragePermission},
+    storage, user,
+  },
+  error::AppError,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionListResponse {
+  pub permissions: Vec<StoragePermission>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPermissionDto {
+  user_id: i64,
+  level: AccessLevel,
+}
+
+pub async fn list_permissions(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+) -> Result<Json<PermissionListResponse>, AppError> {
+  let conn = conn.lock().await;
+  let permissions = permission::get_storage_permissions(&conn, id).context("获取权限失败")?;
+  Ok(Json(PermissionListResponse { permissions }))
+}
+
+pub async fn set_permission(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+  Json(dto): Json<SetPermissionDto>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  storage::get_storage_by_id(&conn, id).context("存储不存在")?;
+  user::get_user_by_id(&conn, dto.user_id).context("用户不存在")?;
+  permission::set_permission(&conn, id, dto.user_id, dto.level)?;
+  Ok(())
+}
+
+pub async fn delete_permission(
+  State(conn): State<DBConnection>,
+  Path((id, user_id)): Path<(i64, i64)>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  permission::delete_permission(&conn, id, user_id)?;
+  Ok(())
+}
@@ -0,0 +1,24 @@
+use axum::{
+  extract::{Path, State},
+  response::Response,
+};
+
+use crate::backend::{
+  api::job::{self, JobPayload},
+  db::{self, DBConnection},
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+/// 在后台重建存储的搜索索引，返回任务记录
+pub async fn reindex_storage(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+  let storage = db::storage::get_storage_by_id(&*conn.lock().await, id)?;
+  if storage.kind != db::storage::StorageKind::Local {
+    return Err(AppError::new("远程存储不支持搜索索引"));
+  }
+  job::submit(&conn, &user, JobPayload::Reindex { storage_id: id }).await
+}
@@ -0,0 +1,50 @@
+use anyhow::Context;
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use serde::Deserialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    storage::{self, UpdateStorageDto},
+  },
+  error::AppError,
+  utils,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDisabledDto {
+  disabled: bool,
+}
+
+pub async fn update_storage(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+  Json(mut dto): Json<UpdateStorageDto>,
+) -> Result<(), AppError> {
+  // 返回给前端的配置不含密钥，没有重新填写时沿用原来的
+  let existing = storage::get_storage_by_id(&*conn.lock().await, id).context("存储不存在")?;
+  if existing.kind == dto.kind {
+    utils::backend::keep_secrets(&mut dto.config, &existing.config);
+  }
+  utils::file::create_dir(&dto.local_path)?;
+  utils::backend::verify(dto.kind, &mut dto.config, &dto.local_path).await?;
+  let conn = conn.lock().await;
+  storage::update_storage(&conn, id, dto)?;
+  utils::watcher::reload();
+  Ok(())
+}
+
+pub async fn set_disabled(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+  Json(dto): Json<SetDisabledDto>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  storage::set_storage_disabled(&conn, id, dto.disabled)?;
+  utils::watcher::reload();
+  Ok(())
+}
@@ -0,0 +1,81 @@
+use anyhow::Context;
+use axum::{
+  body::Body,
+  extract::Query,
+  http::{
+    HeaderMap, StatusCode,
+    header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+  },
+  response::Response,
+};
+use serde::Deserialize;
+
+use crate::backend::{
+  api::download::{is_not_modified, make_etag},
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::thumb::{self, ThumbSize},
+};
+
+#[derive(Deserialize)]
+pub struct ThumbQuery {
+  #[serde(default)]
+  size: ThumbSize,
+}
+
+/// 返回图片的缩略图，首次请求时生成并缓存。
+/// ETag 由原文件和尺寸决定，客户端可以用条件请求避免重复下载
+pub async fn get_thumb(
+  Storage {
+    path,
+    root,
+    backend,
+    ..
+  }: Storage,
+  Query(query): Query<ThumbQuery>,
+  headers: HeaderMap,
+) -> Result<Response, AppError> {
+  let path = relative_path(&root, &path.get_path())?;
+  let entry = backend
+    .try_stat(&path)
+    .await?
+    .filter(|entry| !entry.is_dir)
+    .ok_or_else(|| AppError::with_status(StatusCode::NOT_FOUND, "文件不存在"))?;
+  if !thumb::supports(&path) {
+    return Err(AppError::with_status(
+      StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      "不支持生成缩略图的文件类型",
+    ));
+  }
+
+  let etag = format!(
+    "\"{}-{}\"",
+    query.size.as_str(),
+    make_etag(entry.size, entry.modified).trim_matches('"')
+  );
+  let builder = Response::builder()
+    .header(ETAG, &etag)
+    .header(CACHE_CONTROL, "private, no-cache");
+  let builder = match entry.modified {
+    Some(modified) => builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified)),
+    None => builder,
+  };
+  if is_not_modified(&headers, &etag, entry.modified) {
+    return Ok(
+      builder
+        .status(StatusCode::NOT_MODIFIED)
+        .body(Body::empty())
+        .context("Failed to build response")?,
+    );
+  }
+
+  let (file, content_type) =
+    thumb::get_or_create(&*backend, &root, &path, &entry, query.size).await?;
+  let data = tokio::fs::read(file).await?;
+  Ok(
+    builder
+      .header(CONTENT_TYPE, content_type)
+      .body(Body::from(data))
+      .context("Failed to build response")?,
+  )
+}
@@ -0,0 +1,17 @@
+use axum::Json;
+use serde::Serialize;
+
+use crate::backend::{error::AppError, extractor::auth::AuthUser, utils::auth};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTokenResponse {
+  pub token: String,
+  pub expires: i64,
+}
+
+/// 生成放在 `?token=` 中使用的短期 token，用于不能设置请求头的事件订阅、缩略图和打包下载
+pub async fn create_query_token(user: AuthUser) -> Result<Json<QueryTokenResponse>, AppError> {
+  let (token, expires) = auth::generate_query_token(user.id)?;
+  Ok(Json(QueryTokenResponse { token, expires }))
+}
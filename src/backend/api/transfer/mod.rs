@@ -0,0 +1,279 @@
+use std::{convert::Infallible, path::PathBuf};
+
+use anyhow::Context;
+use axum::{
+  Json, Router,
+  body::{Body, Bytes},
+  extract::State,
+  http::header::CONTENT_TYPE,
+  response::Response,
+  routing::post,
+};
+use futures_util::stream;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{runtime::Handle, sync::mpsc};
+
+use crate::backend::{
+  api::job::{self, JobPayload},
+  db::{DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    events::{self, ChangeKind},
+    job::JobContext,
+    transfer::{
+      ConflictPolicy, TransferMode, TransferProgress, TransferResult, TransferSource, TransferTask,
+    },
+    trash,
+  },
+};
+
+pub fn create_transfer_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/move", post(move_files))
+    .route("/copy", post(copy_files))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferDto {
+  /// 带存储前缀的源路径，例如 `data/folder/a.txt`
+  sources: Vec<String>,
+  /// 目标文件夹，可以是另一个存储
+  destination: String,
+  #[serde(default)]
+  conflict: ConflictPolicy,
+  /// 作为后台任务执行，立即返回任务记录
+  #[serde(default, skip_serializing)]
+  background: bool,
+}
+
+/// 耗时操作以 NDJSON 逐行返回的事件
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TaskEvent<'a, T> {
+  Progress(&'a TransferProgress),
+  Done {
+    results: T,
+  },
+  /// 操作整体失败，例如压缩包损坏
+  Error {
+    message: String,
+    code: Option<&'static str>,
+  },
+}
+
+type TransferEvent<'a> = TaskEvent<'a, Vec<TransferResult>>;
+
+async fn move_files(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Json(dto): Json<TransferDto>,
+) -> Result<Response, AppError> {
+  transfer(conn, user, dto, TransferMode::Move).await
+}
+
+async fn copy_files(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Json(dto): Json<TransferDto>,
+) -> Result<Response, AppError> {
+  transfer(conn, user, dto, TransferMode::Copy).await
+}
+
+async fn transfer(
+  conn: DBConnection,
+  user: AuthUser,
+  dto: TransferDto,
+  mode: TransferMode,
+) -> Result<Response, AppError> {
+  let prepared = prepare(&*conn.lock().await, &user, &dto, mode)?;
+  if dto.background {
+    return job::submit(&conn, &user, JobPayload::Transfer { mode, dto }).await;
+  }
+
+  let (tx, rx) = mpsc::unbounded_channel::<Bytes>();
+  tokio::task::spawn_blocking(move || {
+    let results = execute(&conn, user.id, &prepared, |progress| {
+      // 客户端断开后继续完成操作，只是不再推送进度
+      let _ = tx.send(encode_event(&TransferEvent::Progress(progress)));
+      Ok(())
+    });
+    let _ = tx.send(encode_event(&TransferEvent::Done { results }));
+  });
+
+  ndjson_response(rx)
+}
+
+/// 在后台任务中执行移动或复制，取消时已处理的源不会还原
+pub async fn run_job(
+  conn: DBConnection,
+  user: AuthUser,
+  mode: TransferMode,
+  dto: TransferDto,
+  context: JobContext,
+) -> Result<Value, AppError> {
+  let prepared = prepare(&*conn.lock().await, &user, &dto, mode)?;
+  let reporter = context.clone();
+  let results = tokio::task::spawn_blocking(move || {
+    execute(&conn, user.id, &prepared, |progress| {
+      reporter.report(progress)
+    })
+  })
+  .await?;
+  context.check()?;
+  Ok(serde_json::to_value(results)?)
+}
+
+/// 检查权限后的移动或复制
+struct PreparedTransfer {
+  task: TransferTask,
+  source_storages: Vec<(i64, PathBuf)>,
+  destination_id: i64,
+  destination_root: PathBuf,
+}
+
+fn prepare(
+  conn: &Connection,
+  user: &AuthUser,
+  dto: &TransferDto,
+  mode: TransferMode,
+) -> Result<PreparedTransfer, AppError> {
+  if dto.sources.is_empty() {
+    return Err(AppError::new("请选择要处理的文件"));
+  }
+
+  let destination = Storage::resolve(conn, user, &dto.destination, AccessLevel::Write)?;
+  destination.require_local()?;
+  let destination_path = destination.path.get_path();
+  if !destination_path.is_dir() {
+    return Err(AppError::new("目标文件夹不存在"));
+  }
+
+  // 移动会删除源文件，需要写权限；复制只需要读权限
+  let required = match mode {
+    TransferMode::Move => AccessLevel::Write,
+    TransferMode::Copy => AccessLevel::Read,
+  };
+  let mut sources = Vec::new();
+  let mut source_storages = Vec::new();
+  let mut cross_storage = false;
+  for name in &dto.sources {
+    let source = Storage::resolve(conn, user, name, required)?;
+    source.require_local()?;
+    let path = source.path.get_path();
+    if path == source.root {
+      return Err(AppError::new("不能移动或复制存储根目录"));
+    }
+    cross_storage |= source.id != destination.id;
+    sources.push(TransferSource {
+      name: name.clone(),
+      path,
+    });
+    source_storages.push((source.id, source.root));
+  }
+
+  let task = TransferTask {
+    mode,
+    conflict: dto.conflict,
+    sources,
+    destination: destination_path,
+    policy: cross_storage.then_some(destination.policy),
+  };
+  Ok(PreparedTransfer {
+    task,
+    source_storages,
+    destination_id: destination.id,
+    destination_root: destination.root,
+  })
+}
+
+/// 执行移动或复制，然后更新索引并通知变更。需要在阻塞线程中调用
+fn execute(
+  conn: &DBConnection,
+  user_id: i64,
+  prepared: &PreparedTransfer,
+  on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Vec<TransferResult> {
+  let PreparedTransfer {
+    task,
+    source_storages,
+    destination_id,
+    destination_root,
+  } = prepared;
+  let mode = task.mode;
+  let handle = Handle::current();
+  // 被覆盖的目标与删除一致，移动到目标存储的回收站
+  let results = task.run(on_progress, |target| {
+    let path = relative_path(destination_root, target)?;
+    utils::version::preserve_blocking(conn, *destination_id, &path, user_id)?;
+    handle.block_on(trash::move_to_trash(
+      conn,
+      *destination_id,
+      destination_root,
+      target,
+      user_id,
+    ))?;
+    Ok(())
+  });
+
+  for ((result, source), (storage_id, root)) in
+    results.iter().zip(&task.sources).zip(source_storages)
+  {
+    let Some(target) = &result.target else {
+      continue;
+    };
+    let target = task.destination.join(target);
+    if mode == TransferMode::Move {
+      utils::search::refresh(*storage_id, root, &source.path);
+    }
+    utils::search::refresh(*destination_id, destination_root, &target);
+
+    if mode == TransferMode::Move && storage_id == destination_id {
+      events::publish_rename(*destination_id, root, &source.path, &target, user_id);
+      continue;
+    }
+    if mode == TransferMode::Move {
+      events::publish(
+        *storage_id,
+        root,
+        &source.path,
+        ChangeKind::Deleted,
+        user_id,
+      );
+    }
+    events::publish(
+      *destination_id,
+      destination_root,
+      &target,
+      ChangeKind::Created,
+      user_id,
+    );
+  }
+  results
+}
+
+/// 把通道中的事件作为 NDJSON 响应返回，发送端在客户端断开后仍可继续执行
+pub fn ndjson_response(rx: mpsc::UnboundedReceiver<Bytes>) -> Result<Response, AppError> {
+  let stream = stream::unfold(rx, |mut rx| async move {
+    rx.recv().await.map(|line| (Ok::<_, Infallible>(line), rx))
+  });
+  Ok(
+    Response::builder()
+      .header(CONTENT_TYPE, "application/x-ndjson")
+      .body(Body::from_stream(stream))
+      .context("Failed to build response")?,
+  )
+}
+
+pub fn encode_event<T: Serialize>(event: &TaskEvent<T>) -> Bytes {
+  let mut line = serde_json::to_vec(event).unwrap_or_default();
+  line.push(b'\n');
+  Bytes::from(line)
+}
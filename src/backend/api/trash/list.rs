@@ -0,0 +1,32 @@
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    permission::AccessLevel,
+    trash::{self, TrashItem},
+  },
+  error::AppError,
+  extractor::storage::Storage,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashListResponse {
+  pub items: Vec<TrashItem>,
+}
+
+/// 回收站里的内容可以被还原，所以需要写权限才能查看
+pub async fn list_trash(
+  State(conn): State<DBConnection>,
+  Storage { id, level, .. }: Storage,
+) -> Result<Json<TrashListResponse>, AppError> {
+  if level < AccessLevel::Write {
+    return Err(AppError::new("没有该存储的写入权限"));
+  }
+  let conn = conn.lock().await;
+  let items = trash::get_trash_by_storage(&conn, id).context("获取回收站失败")?;
+  Ok(Json(TrashListResponse { items }))
+}
@@ -0,0 +1,16 @@
+mod list;
+mod purge;
+mod restore;
+use axum::{
+  Router,
+  routing::{delete, get, post},
+};
+
+use crate::backend::db::DBConnection;
+
+pub fn create_trash_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/{*path}", get(list::list_trash))
+    .route("/{*path}", delete(purge::purge_trash))
+    .route("/restore/{*path}", post(restore::restore_trash))
+}
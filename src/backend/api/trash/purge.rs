@@ -0,0 +1,47 @@
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::backend::{
+  db::{self, DBConnection, permission::AccessLevel},
+  error::AppError,
+  extractor::storage::Storage,
+  utils::trash,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeTrashDto {
+  #[serde(default)]
+  ids: Vec<i64>,
+  /// 为 true 时清空整个回收站
+  #[serde(default)]
+  all: bool,
+}
+
+/// 彻底删除需要存储的管理权限
+pub async fn purge_trash(
+  State(conn): State<DBConnection>,
+  Storage {
+    id, root, level, ..
+  }: Storage,
+  Json(dto): Json<PurgeTrashDto>,
+) -> Result<(), AppError> {
+  if level < AccessLevel::Manage {
+    return Err(AppError::new("没有该存储的管理权限"));
+  }
+  let ids = if dto.all {
+    db::trash::get_trash_by_storage(&*conn.lock().await, id)
+      .context("获取回收站失败")?
+      .into_iter()
+      .map(|item| item.id)
+      .collect()
+  } else {
+    dto.ids
+  };
+  for trash_id in ids {
+    db::trash::get_trash_item(&*conn.lock().await, id, trash_id).context("回收站条目不存在")?;
+    trash::purge(&conn, &root, trash_id).await?;
+  }
+  Ok(())
+}
@@ -0,0 +1,31 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::{auth::AuthUser, storage::Storage},
+  utils::{
+    events::{self, ChangeKind},
+    trash,
+  },
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreTrashDto {
+  ids: Vec<i64>,
+}
+
+pub async fn restore_trash(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Storage { id, root, .. }: Storage,
+  Json(dto): Json<RestoreTrashDto>,
+) -> Result<(), AppError> {
+  for trash_id in dto.ids {
+    let target = trash::restore(&conn, id, &root, trash_id).await?;
+    events::publish(id, &root, &target, ChangeKind::Created, user.id);
+  }
+  Ok(())
+}
@@ -0,0 +1,144 @@
+use axum::{
+  body::Body,
+  extract::State,
+  http::{
+    HeaderMap, StatusCode,
+    header::{CONTENT_TYPE, LOCATION},
+  },
+  response::Response,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tokio::fs;
+
+use crate::backend::{
+  api::tus::{OFFSET_OCTET_STREAM, append_body, finish, tus_path, upload_options},
+  db::{
+    DBConnection,
+    tus::{self, CreateTusUpload},
+  },
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    upload::{OnConflict, resolve_target},
+  },
+};
+
+/// 在路径指向的文件夹下创建上传，文件名取自 Upload-Metadata 的 `filename` 或 `name`。
+/// 请求体不为空时按 creation-with-upload 扩展写入第一段数据
+pub async fn create_upload(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  storage: Storage,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Response, AppError> {
+  if headers.contains_key("upload-defer-length") {
+    return Err(AppError::with_status(
+      StatusCode::BAD_REQUEST,
+      "不支持延迟声明上传长度",
+    ));
+  }
+  let length = headers
+    .get("upload-length")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok())
+    .ok_or_else(|| AppError::with_status(StatusCode::BAD_REQUEST, "缺少 Upload-Length"))?;
+  let metadata = headers
+    .get("upload-metadata")
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default();
+  let filename = parse_metadata(metadata, "filename")
+    .or_else(|| parse_metadata(metadata, "name"))
+    .ok_or_else(|| AppError::with_status(StatusCode::BAD_REQUEST, "缺少文件名"))?;
+  if !utils::validate::validate_name(&filename) {
+    return Err(AppError::with_status(
+      StatusCode::BAD_REQUEST,
+      "文件名不合法",
+    ));
+  }
+  storage.policy.check_name(&filename)?;
+  storage.policy.check_size(length)?;
+
+  let root = &storage.root;
+  let target_dir = storage
+    .backend
+    .try_stat(&relative_path(root, &storage.path.get_path())?)
+    .await?;
+  if !target_dir.is_some_and(|entry| entry.is_dir) {
+    return Err(AppError::with_status(
+      StatusCode::NOT_FOUND,
+      "目标文件夹不存在",
+    ));
+  }
+  let target = relative_path(root, &storage.path.safe_join(&filename)?)?;
+  let (conflict, _) = upload_options(metadata)?;
+  if conflict == OnConflict::Fail {
+    resolve_target(&*storage.backend, &target, conflict).await?;
+  }
+
+  let upload = tus::create_tus_upload(
+    &*conn.lock().await,
+    CreateTusUpload {
+      storage_id: storage.id,
+      user_id: user.id,
+      path: &target,
+      length,
+      metadata,
+    },
+  )?;
+  let file = tus_path(root, &upload.id);
+  if let Some(parent) = file.parent() {
+    fs::create_dir_all(parent).await?;
+  }
+  fs::File::create(&file).await?;
+
+  let with_upload = headers
+    .get(CONTENT_TYPE)
+    .is_some_and(|value| value == OFFSET_OCTET_STREAM);
+  let offset = if with_upload {
+    append_body(&upload, &file, 0, &headers, body).await?
+  } else {
+    0
+  };
+  if offset == length {
+    finish(&conn, &user, &upload, &storage).await?;
+  }
+
+  let mut response = Response::builder()
+    .status(StatusCode::CREATED)
+    .header(LOCATION, format!("/api/tus/files/{}", upload.id));
+  if with_upload {
+    response = response.header("upload-offset", offset);
+  }
+  Ok(response.body(Body::empty()).unwrap_or_default())
+}
+
+/// Upload-Metadata 为逗号分隔的 `key base64(value)`，值可以省略
+pub(super) fn parse_metadata(metadata: &str, key: &str) -> Option<String> {
+  metadata.split(',').find_map(|pair| {
+    let mut parts = pair.trim().splitn(2, ' ');
+    if parts.next()? != key {
+      return None;
+    }
+    let value = STANDARD.decode(parts.next()?.trim()).ok()?;
+    String::from_utf8(value).ok()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_metadata() {
+    let metadata = "relativePath,name bXkudHh0,filename 5paH5Lu2LnR4dA==";
+    assert_eq!(parse_metadata(metadata, "name").unwrap(), "my.txt");
+    assert_eq!(parse_metadata(metadata, "filename").unwrap(), "文件.txt");
+    assert!(parse_metadata(metadata, "relativePath").is_none());
+    assert!(parse_metadata(metadata, "type").is_none());
+  }
+}
@@ -0,0 +1,37 @@
+use axum::{
+  body::Body,
+  extract::{Path, State},
+  http::{StatusCode, header::CACHE_CONTROL},
+  response::Response,
+};
+use tokio::fs;
+
+use crate::backend::{
+  api::tus::{load_upload, tus_path},
+  db::DBConnection,
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+/// 返回当前偏移量，客户端从这里继续上传
+pub async fn get_offset(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+) -> Result<Response, AppError> {
+  let (upload, storage) = load_upload(&conn, &user, &id).await?;
+  let offset = fs::metadata(tus_path(&storage.root, &upload.id))
+    .await
+    .map_err(|_| AppError::with_status(StatusCode::NOT_FOUND, "上传不存在"))?
+    .len();
+
+  let mut response = Response::builder()
+    .status(StatusCode::OK)
+    .header("upload-offset", offset)
+    .header("upload-length", upload.length)
+    .header(CACHE_CONTROL, "no-store");
+  if !upload.metadata.is_empty() {
+    response = response.header("upload-metadata", &upload.metadata);
+  }
+  Ok(response.body(Body::empty()).unwrap_or_default())
+}
@@ -0,0 +1,284 @@
+mod create;
+mod head;
+mod patch;
+mod terminate;
+use std::{
+  collections::HashSet,
+  io::{self, SeekFrom},
+  path::Path,
+  sync::{LazyLock, Mutex},
+};
+
+use axum::{
+  Router,
+  body::Body,
+  extract::Request,
+  http::{HeaderMap, HeaderValue, Method, StatusCode},
+  middleware::{self, Next},
+  response::{IntoResponse, Response},
+  routing::{head, options, post},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{StreamExt, TryStreamExt};
+use sha2::{Digest, digest::DynDigest};
+use tokio::{
+  fs,
+  io::{AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    permission::AccessLevel,
+    tus::{self, TusUpload},
+  },
+  error::AppError,
+  extractor::{auth::AuthUser, storage::Storage},
+  utils::{
+    self,
+    events::ChangeKind,
+    upload::{self, OnConflict, tus_path},
+  },
+};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,creation-with-upload,termination,checksum";
+const TUS_CHECKSUM_ALGORITHMS: &str = "sha1,sha256,md5";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// tus 1.0 断点续传：`POST /api/tus/{storage}/{folder}` 创建上传，
+/// 之后通过返回的 `/api/tus/files/{id}` 查询偏移量、追加数据或取消
+pub fn create_tus_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/{*path}", post(create::create_upload).options(tus_options))
+    .route(
+      "/files/{id}",
+      head(head::get_offset)
+        .patch(patch::append)
+        .delete(terminate::terminate)
+        .options(tus_options),
+    )
+    .route("/", options(tus_options))
+    .layer(middleware::from_fn(tus_resumable))
+}
+
+/// 除 OPTIONS 外的请求必须带有支持的 Tus-Resumable，所有响应都带上该头
+async fn tus_resumable(req: Request, next: Next) -> Response {
+  let supported = req.method() == Method::OPTIONS
+    || req
+      .headers()
+      .get("tus-resumable")
+      .is_some_and(|value| value == TUS_VERSION);
+  let mut response = if supported {
+    next.run(req).await
+  } else {
+    let mut response = StatusCode::PRECONDITION_FAILED.into_response();
+    response
+      .headers_mut()
+      .insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+    response
+  };
+  response
+    .headers_mut()
+    .insert("tus-resumable", HeaderValue::from_static(TUS_VERSION));
+  response
+}
+
+async fn tus_options() -> Response {
+  Response::builder()
+    .status(StatusCode::NO_CONTENT)
+    .header("tus-version", TUS_VERSION)
+    .header("tus-extension", TUS_EXTENSIONS)
+    .header("tus-checksum-algorithm", TUS_CHECKSUM_ALGORITHMS)
+    .body(Body::empty())
+    .unwrap_or_default()
+}
+
+/// 读取当前用户的上传，并按目标路径重新校验存储的写入权限
+async fn load_upload(
+  conn: &DBConnection,
+  user: &AuthUser,
+  id: &str,
+) -> Result<(TusUpload, Storage), AppError> {
+  let conn = conn.lock().await;
+  let upload = tus::get_tus_upload(&conn, id)?
+    .filter(|upload| upload.user_id == user.id)
+    .ok_or_else(|| AppError::with_status(StatusCode::NOT_FOUND, "上传不存在"))?;
+  let storage = Storage::resolve_by_id(
+    &conn,
+    user,
+    upload.storage_id,
+    &upload.path,
+    AccessLevel::Write,
+  )?;
+  Ok((upload, storage))
+}
+
+/// 正在写入的上传，同一上传不允许并发 PATCH
+static WRITING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+struct WriteGuard(String);
+
+impl WriteGuard {
+  fn acquire(id: &str) -> Result<Self, AppError> {
+    let mut writing = WRITING.lock().unwrap_or_else(|err| err.into_inner());
+    if !writing.insert(id.to_string()) {
+      return Err(AppError::with_status(StatusCode::LOCKED, "该上传正在写入"));
+    }
+    Ok(Self(id.to_string()))
+  }
+}
+
+impl Drop for WriteGuard {
+  fn drop(&mut self) {
+    WRITING
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .remove(&self.0);
+  }
+}
+
+/// 校验算法和期望的摘要
+type Checksum = (Box<dyn DynDigest + Send>, Vec<u8>);
+
+/// 解析 `Upload-Checksum: <算法> <base64>`
+fn parse_checksum(headers: &HeaderMap) -> Result<Option<Checksum>, AppError> {
+  let Some(value) = headers.get("upload-checksum") else {
+    return Ok(None);
+  };
+  let (algorithm, expected) = value
+    .to_str()
+    .ok()
+    .and_then(|value| value.split_once(' '))
+    .ok_or_else(|| AppError::with_status(StatusCode::BAD_REQUEST, "Upload-Checksum 不合法"))?;
+  let hasher: Box<dyn DynDigest + Send> = match algorithm {
+    "sha1" => Box::new(sha1::Sha1::new()),
+    "sha256" => Box::new(sha2::Sha256::new()),
+    "md5" => Box::new(md5::Md5::new()),
+    _ => {
+      return Err(AppError::with_status(
+        StatusCode::BAD_REQUEST,
+        "不支持的校验算法",
+      ));
+    }
+  };
+  let expected = STANDARD
+    .decode(expected.trim())
+    .map_err(|_| AppError::with_status(StatusCode::BAD_REQUEST, "Upload-Checksum 不合法"))?;
+  Ok(Some((hasher, expected)))
+}
+
+/// 把请求体追加到数据文件的 `offset` 处，返回新的偏移量。
+/// 没有校验和时保留连接中断前收到的数据，有校验和时不匹配或不完整都回退到 `offset`
+async fn append_body(
+  upload: &TusUpload,
+  file: &Path,
+  offset: u64,
+  headers: &HeaderMap,
+  body: Body,
+) -> Result<u64, AppError> {
+  let mut checksum = parse_checksum(headers)?;
+  let mut data = fs::OpenOptions::new().write(true).open(file).await?;
+  data.seek(SeekFrom::Start(offset)).await?;
+
+  let mut written = offset;
+  let mut stream = body.into_data_stream().map_err(io::Error::other).boxed();
+  let result = async {
+    while let Some(chunk) = stream.try_next().await? {
+      if written + chunk.len() as u64 > upload.length {
+        return Err(AppError::with_status(
+          StatusCode::PAYLOAD_TOO_LARGE,
+          "数据超出了上传的长度",
+        ));
+      }
+      if let Some((hasher, _)) = &mut checksum {
+        hasher.update(&chunk);
+      }
+      data.write_all(&chunk).await?;
+      written += chunk.len() as u64;
+    }
+    Ok(())
+  }
+  .await;
+
+  let result = match (result, checksum) {
+    (Ok(()), Some((hasher, expected))) => {
+      if *hasher.finalize() == *expected {
+        Ok(())
+      } else {
+        Err(AppError::with_status(checksum_mismatch(), "数据校验失败"))
+      }
+    }
+    (Err(err), Some(_)) => Err(err),
+    // 没有校验和时，连接中断前收到的数据仍然有效
+    (Err(err), None) if err.status() != StatusCode::PAYLOAD_TOO_LARGE => {
+      data.flush().await?;
+      return Err(err);
+    }
+    (result, _) => result,
+  };
+  if let Err(err) = result {
+    data.set_len(offset).await?;
+    return Err(err);
+  }
+  data.flush().await?;
+  Ok(written)
+}
+
+/// tus 校验和扩展定义的 460 Checksum Mismatch
+fn checksum_mismatch() -> StatusCode {
+  StatusCode::from_u16(460).unwrap_or(StatusCode::BAD_REQUEST)
+}
+
+/// 从 Upload-Metadata 读取冲突处理方式 `conflict` 和整个文件的 `sha256`
+fn upload_options(metadata: &str) -> Result<(OnConflict, Option<String>), AppError> {
+  let conflict = match create::parse_metadata(metadata, "conflict") {
+    Some(value) => value.parse().map_err(|err: anyhow::Error| {
+      AppError::with_status(StatusCode::BAD_REQUEST, &err.to_string())
+    })?,
+    None => OnConflict::default(),
+  };
+  Ok((conflict, create::parse_metadata(metadata, "sha256")))
+}
+
+/// 数据全部收到后校验并写入目标路径，删除上传记录。`storage` 只用到存储本身，不要求指向目标文件。
+/// 整个文件的校验失败时上传作废，需要重新创建
+async fn finish(
+  conn: &DBConnection,
+  user: &AuthUser,
+  upload: &TusUpload,
+  storage: &Storage,
+) -> Result<(), AppError> {
+  // 上传创建后存储的限制可能被修改，写入前再校验一次
+  let filename = upload.path.rsplit('/').next().unwrap_or_default();
+  storage.policy.check_name(filename)?;
+  storage.policy.check_size(upload.length)?;
+
+  let file = tus_path(&storage.root, &upload.id);
+  let (conflict, sha256) = upload_options(&upload.metadata)?;
+  if let Some(expected) = sha256 {
+    let actual = upload::sha256_files(vec![file.clone()]).await?;
+    if let Err(err) = upload::check_sha256(&actual, &expected) {
+      tus::delete_tus_upload(&*conn.lock().await, &upload.id)?;
+      fs::remove_file(&file).await?;
+      return Err(err);
+    }
+  }
+
+  let backend = &storage.backend;
+  let existed = backend.exists(&upload.path).await?;
+  let path = upload::resolve_target(&**backend, &upload.path, conflict).await?;
+  utils::version::preserve(conn, storage.id, &path, user.id).await?;
+  backend.put_file(&path, &file).await?;
+  tus::delete_tus_upload(&*conn.lock().await, &upload.id)?;
+
+  let target = storage.root.join(&path);
+  utils::search::refresh(storage.id, &storage.root, &target);
+  let kind = if existed && path == upload.path {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  utils::events::publish(storage.id, &storage.root, &target, kind, user.id);
+  Ok(())
+}
@@ -0,0 +1,77 @@
+use axum::{
+  body::Body,
+  extract::{Path, State},
+  http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+  response::Response,
+};
+use tokio::fs;
+
+use crate::backend::{
+  api::tus::{OFFSET_OCTET_STREAM, WriteGuard, append_body, finish, load_upload, tus_path},
+  db::{DBConnection, tus},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::upload::ActiveUpload,
+};
+
+/// 从 Upload-Offset 处追加数据，偏移量必须与服务端一致
+pub async fn append(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+  headers: HeaderMap,
+  body: Body,
+) -> Result<Response, AppError> {
+  if headers
+    .get(CONTENT_TYPE)
+    .is_none_or(|value| value != OFFSET_OCTET_STREAM)
+  {
+    return Err(AppError::with_status(
+      StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      "Content-Type 必须为 application/offset+octet-stream",
+    ));
+  }
+  let offset = headers
+    .get("upload-offset")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok())
+    .ok_or_else(|| AppError::with_status(StatusCode::BAD_REQUEST, "缺少 Upload-Offset"))?;
+
+  let (upload, storage) = load_upload(&conn, &user, &id).await?;
+  let _guard = WriteGuard::acquire(&upload.id)?;
+  let file = tus_path(&storage.root, &upload.id);
+  let current = fs::metadata(&file)
+    .await
+    .map_err(|_| AppError::with_status(StatusCode::NOT_FOUND, "上传不存在"))?
+    .len();
+  if offset != current {
+    return Err(AppError::with_status(
+      StatusCode::CONFLICT,
+      &format!("偏移量不一致，当前为 {}", current),
+    ));
+  }
+
+  let active = ActiveUpload::track(&upload.id);
+  let offset = tokio::select! {
+    result = append_body(&upload, &file, offset, &headers, body) => result?,
+    _ = active.cancelled() => {
+      return Err(AppError::with_status(StatusCode::NOT_FOUND, "上传不存在"));
+    }
+  };
+  if !tus::touch_tus_upload(&*conn.lock().await, &upload.id)? {
+    // 写入过程中上传被取消
+    fs::remove_file(&file).await.ok();
+    return Err(AppError::with_status(StatusCode::NOT_FOUND, "上传不存在"));
+  }
+  if offset == upload.length {
+    finish(&conn, &user, &upload, &storage).await?;
+  }
+
+  Ok(
+    Response::builder()
+      .status(StatusCode::NO_CONTENT)
+      .header("upload-offset", offset)
+      .body(Body::empty())
+      .unwrap_or_default(),
+  )
+}
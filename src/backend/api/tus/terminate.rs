@@ -0,0 +1,30 @@
+use std::io;
+
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+};
+use tokio::fs;
+
+use crate::backend::{
+  api::tus::{load_upload, tus_path},
+  db::{DBConnection, tus},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils,
+};
+
+/// termination 扩展：取消上传并删除已收到的数据
+pub async fn terminate(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+  let (upload, storage) = load_upload(&conn, &user, &id).await?;
+  tus::delete_tus_upload(&*conn.lock().await, &upload.id)?;
+  utils::upload::cancel(&upload.id);
+  match fs::remove_file(tus_path(&storage.root, &upload.id)).await {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+    _ => Ok(StatusCode::NO_CONTENT),
+  }
+}
@@ -0,0 +1,28 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+};
+
+use crate::backend::{
+  api::upload::{SessionGuard, load_session, remove_chunks},
+  db::{DBConnection, upload},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils,
+};
+
+pub async fn abort_upload(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+) -> Result<(), AppError> {
+  let (session, storage) = load_session(&conn, &user, &id).await?;
+  if SessionGuard::is_completing(&session.id) {
+    return Err(AppError::with_status(StatusCode::LOCKED, "上传正在合并"));
+  }
+  // 先删除会话，之后到达的分片会因为会话不存在而被拒绝，正在接收的分片立即停止
+  upload::delete_upload_session(&*conn.lock().await, &session.id)?;
+  utils::upload::cancel(&session.id);
+  remove_chunks(&storage.root, &session.id).await?;
+  Ok(())
+}
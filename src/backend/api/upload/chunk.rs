@@ -0,0 +1,120 @@
+use anyhow::Context;
+use axum::{
+  Json,
+  extract::{Multipart, Path, State, multipart::Field},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::backend::{
+  api::upload::{SessionGuard, chunks_dir, load_session, missing_chunks, remove_chunks},
+  db::{DBConnection, upload},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::{self, events::ChangeKind, upload::ActiveUpload},
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadChunkResponse {
+  index: u64,
+  /// 分片的 SHA-256
+  hash: String,
+  missing_chunks: usize,
+}
+
+/// 上传一个分片，分片放在 multipart 的 `file` 字段中。重复上传同一分片会覆盖之前的内容
+pub async fn upload_chunk(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path((id, index)): Path<(String, u64)>,
+  mut multipart: Multipart,
+) -> Result<Json<UploadChunkResponse>, AppError> {
+  // 先登记再读取会话，合并完成后到达的分片会因为会话已删除而被拒绝
+  let _guard = SessionGuard::chunk(&id)?;
+  let (session, storage) = load_session(&conn, &user, &id).await?;
+  if index >= session.total_chunks() {
+    return Err(AppError::new("分片序号超出范围"));
+  }
+
+  // 先写入临时文件再重命名，中断的分片不会被当作已收到。
+  // 同一分片可能被并发重传，临时文件名带上随机后缀
+  let dir = chunks_dir(&storage.root, &session.id);
+  fs::create_dir_all(&dir).await?;
+  let part = dir.join(format!(
+    "{}.{}.part",
+    index,
+    hex::encode(rand::random::<[u8; 4]>())
+  ));
+  let active = ActiveUpload::track(&session.id);
+  let mut hash = None;
+  while let Some(field) = multipart.next_field().await? {
+    if field.name() != Some("file") {
+      continue;
+    }
+    let result = tokio::select! {
+      result = write_field(field, &part, session.chunk_len(index)) => result,
+      _ = active.cancelled() => Err(AppError::new("上传会话已取消")),
+    };
+    match result {
+      Ok(digest) => hash = Some(digest),
+      Err(err) => {
+        fs::remove_file(&part).await.ok();
+        return Err(err);
+      }
+    }
+  }
+  let hash = hash.ok_or_else(|| AppError::new("缺少分片内容"))?;
+  fs::rename(&part, dir.join(index.to_string())).await?;
+
+  if !upload::touch_upload_session(&*conn.lock().await, &session.id)? {
+    // 上传过程中会话被取消
+    remove_chunks(&storage.root, &session.id).await.ok();
+    return Err(AppError::new("上传会话已取消"));
+  }
+
+  let missing = missing_chunks(&storage.root, &session).await?.len();
+  let total_chunks = session.total_chunks() as usize;
+  utils::events::publish(
+    storage.id,
+    &storage.root,
+    &storage.path.get_path(),
+    ChangeKind::UploadProgress {
+      uploaded_chunks: total_chunks - missing,
+      total_chunks,
+    },
+    user.id,
+  );
+
+  Ok(Json(UploadChunkResponse {
+    index,
+    hash,
+    missing_chunks: missing,
+  }))
+}
+
+/// 边接收边写入磁盘并计算 SHA-256，内存中只保留当前的数据块。
+/// 超过 `expected` 时立即停止接收
+async fn write_field(
+  mut field: Field<'_>,
+  path: &std::path::Path,
+  expected: u64,
+) -> Result<String, AppError> {
+  let mut file = fs::File::create(path).await?;
+  let mut hasher = Sha256::new();
+  let mut size = 0;
+  while let Some(data) = field.chunk().await.context("Failed to read chunk")? {
+    size += data.len() as u64;
+    if size > expected {
+      return Err(AppError::new("分片大小不正确"));
+    }
+    hasher.update(&data);
+    file.write_all(&data).await?;
+  }
+  if size != expected {
+    return Err(AppError::new("分片大小不正确"));
+  }
+  file.flush().await?;
+  Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,119 @@
+use std::{io, path::PathBuf};
+
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+
+use crate::backend::{
+  api::upload::{SessionGuard, chunks_dir, load_session, missing_chunks, remove_chunks},
+  db::{DBConnection, storage::StorageKind, upload},
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::{
+    self,
+    events::ChangeKind,
+    upload::{ActiveUpload, check_sha256, resolve_target, sha256_files},
+  },
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompleteUploadResponse {
+  /// 实际写入的路径，冲突时自动重命名可能与会话的路径不同
+  path: String,
+}
+
+/// 所有分片都已收到后按顺序合并到目标文件，并删除会话。
+/// 合并结果先写入临时文件，校验通过后再放到目标位置，中途失败不会影响已有文件。
+/// 合并期间独占会话，并登记为正在写入，避免被清理任务删除分片
+pub async fn complete_upload(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+) -> Result<Json<CompleteUploadResponse>, AppError> {
+  let _guard = SessionGuard::complete(&id)?;
+  let _active = ActiveUpload::track(&id);
+  let (session, storage) = load_session(&conn, &user, &id).await?;
+  let missing = missing_chunks(&storage.root, &session).await?;
+  if !missing.is_empty() {
+    return Err(AppError::new(&format!(
+      "还有 {} 个分片未上传",
+      missing.len()
+    )));
+  }
+
+  // 会话创建后存储的限制可能被修改，合并前再校验一次
+  let filename = session.path.rsplit('/').next().unwrap_or_default();
+  storage.policy.check_name(filename)?;
+  storage.policy.check_size(session.size)?;
+
+  let dir = chunks_dir(&storage.root, &session.id);
+  let chunks: Vec<PathBuf> = (0..session.total_chunks())
+    .map(|index| dir.join(index.to_string()))
+    .collect();
+  if let Some(expected) = &session.hash {
+    let actual = sha256_files(chunks.clone()).await?;
+    if let Err(err) = check_sha256(&actual, expected) {
+      // 无法确定是哪个分片出错，清空分片让客户端重新上传
+      remove_chunks(&storage.root, &session.id).await?;
+      return Err(err);
+    }
+  }
+
+  let backend = &storage.backend;
+  let existed = backend.exists(&session.path).await?;
+  let path = resolve_target(&**backend, &session.path, session.conflict).await?;
+  utils::version::preserve(&conn, storage.id, &path, user.id).await?;
+  let target = storage.root.join(&path);
+  log::info!("All chunks received, merging to {}", target.display());
+  if storage.kind == StorageKind::Local {
+    let merged = utils::backend::temp_file(&storage.root).await?;
+    let result = async {
+      merge_local(chunks, merged.clone()).await?;
+      backend.put_file(&path, &merged).await
+    }
+    .await;
+    if let Err(err) = result {
+      fs::remove_file(&merged).await.ok();
+      return Err(err.into());
+    }
+  } else {
+    let stream = futures_util::stream::iter(chunks)
+      .then(fs::File::open)
+      .map_ok(ReaderStream::new)
+      .try_flatten()
+      .boxed();
+    backend.write(&path, stream).await?;
+  }
+
+  upload::delete_upload_session(&*conn.lock().await, &session.id)?;
+  remove_chunks(&storage.root, &session.id).await?;
+  log::info!("Merge complete");
+
+  utils::search::refresh(storage.id, &storage.root, &target);
+  let kind = if existed && path == session.path {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  utils::events::publish(storage.id, &storage.root, &target, kind, user.id);
+  Ok(Json(CompleteUploadResponse { path }))
+}
+
+/// 本地存储直接在文件之间复制，Linux 上 `std::io::copy` 会使用 copy_file_range，
+/// 数据不经过用户态
+async fn merge_local(chunks: Vec<PathBuf>, merged: PathBuf) -> io::Result<()> {
+  tokio::task::spawn_blocking(move || {
+    let mut file = std::fs::File::create(&merged)?;
+    for chunk in chunks {
+      io::copy(&mut std::fs::File::open(chunk)?, &mut file)?;
+    }
+    file.sync_all()
+  })
+  .await?
+}
@@ -0,0 +1,104 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::backend::{
+  api::upload::UploadSessionDto,
+  db::{
+    DBConnection,
+    permission::AccessLevel,
+    upload::{self, CreateUploadSession},
+  },
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    self,
+    upload::{OnConflict, resolve_target},
+  },
+};
+
+const DEFAULT_CHUNK_SIZE: u64 = 5 * 1024 * 1024;
+const MAX_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+const MAX_CHUNKS: u64 = 10000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadDto {
+  /// 带存储前缀的目标文件夹，例如 `data/folder`
+  path: String,
+  filename: String,
+  size: u64,
+  chunk_size: Option<u64>,
+  /// 整个文件的 SHA-256 (hex)，提供时相同文件可以续传之前的会话
+  hash: Option<String>,
+  /// 目标文件已存在时的处理方式，在创建会话时和合并前各检查一次
+  #[serde(default)]
+  conflict: OnConflict,
+}
+
+pub async fn create_upload(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Json(dto): Json<CreateUploadDto>,
+) -> Result<Json<UploadSessionDto>, AppError> {
+  if !utils::validate::validate_name(&dto.filename) {
+    return Err(AppError::new("文件名不合法"));
+  }
+  let chunk_size = dto.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+  if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+    return Err(AppError::new("分片大小不合法"));
+  }
+  if dto.size.div_ceil(chunk_size) > MAX_CHUNKS {
+    return Err(AppError::new("分片数量过多，请增大分片大小"));
+  }
+  let hash = match dto.hash {
+    Some(hash) if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+      Some(hash.to_lowercase())
+    }
+    Some(_) => return Err(AppError::new("文件哈希不合法")),
+    None => None,
+  };
+
+  let Storage {
+    id,
+    path: local_path,
+    root,
+    policy,
+    backend,
+    ..
+  } = Storage::resolve(&*conn.lock().await, &user, &dto.path, AccessLevel::Write)?;
+  policy.check_name(&dto.filename)?;
+  policy.check_size(dto.size)?;
+
+  let target_dir = backend
+    .try_stat(&relative_path(&root, &local_path.get_path())?)
+    .await?;
+  if !target_dir.is_some_and(|entry| entry.is_dir) {
+    return Err(AppError::new("目标文件夹不存在"));
+  }
+  let target = relative_path(&root, &local_path.safe_join(&dto.filename)?)?;
+  if dto.conflict == OnConflict::Fail {
+    // 尽早失败，避免上传完成后才发现冲突
+    resolve_target(&*backend, &target, dto.conflict).await?;
+  }
+
+  let session = {
+    let conn = conn.lock().await;
+    let new_session = CreateUploadSession {
+      storage_id: id,
+      user_id: user.id,
+      path: &target,
+      size: dto.size,
+      chunk_size,
+      hash: hash.as_deref(),
+      conflict: dto.conflict,
+    };
+    match upload::find_upload_session(&conn, &new_session)? {
+      Some(session) => session,
+      None => upload::create_upload_session(&conn, new_session)?,
+    }
+  };
+  Ok(Json(UploadSessionDto::new(&root, session).await?))
+}
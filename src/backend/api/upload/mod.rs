@@ -0,0 +1,219 @@
+mod abort;
+mod chunk;
+mod complete;
+mod create;
+mod status;
+use std::{
+  collections::HashMap,
+  io,
+  path::Path,
+  sync::{LazyLock, Mutex},
+};
+
+use axum::{
+  Router,
+  http::StatusCode,
+  routing::{delete, get, post, put},
+};
+use serde::Serialize;
+use tokio::fs;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    permission::AccessLevel,
+    upload::{self, UploadSession},
+  },
+  error::AppError,
+  extractor::{auth::AuthUser, storage::Storage},
+  utils::upload::{OnConflict, chunks_dir},
+};
+
+/// 分片上传会话：创建 -> 上传分片（可查询缺失的分片后续传） -> 完成或取消
+pub fn create_upload_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", post(create::create_upload))
+    .route("/{id}", get(status::get_upload))
+    .route("/{id}", delete(abort::abort_upload))
+    .route("/{id}/{index}", put(chunk::upload_chunk))
+    .route("/{id}/complete", post(complete::complete_upload))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSessionDto {
+  id: String,
+  /// 目标文件相对于存储根目录的路径
+  path: String,
+  size: u64,
+  chunk_size: u64,
+  total_chunks: u64,
+  hash: Option<String>,
+  conflict: OnConflict,
+  /// 尚未收到的分片序号，为空时可以完成上传
+  missing_chunks: Vec<u64>,
+  created_at: String,
+  updated_at: String,
+}
+
+impl UploadSessionDto {
+  async fn new(root: &Path, session: UploadSession) -> Result<Self, AppError> {
+    let missing_chunks = missing_chunks(root, &session).await?;
+    Ok(Self {
+      total_chunks: session.total_chunks(),
+      id: session.id,
+      path: session.path,
+      size: session.size,
+      chunk_size: session.chunk_size,
+      hash: session.hash,
+      conflict: session.conflict,
+      missing_chunks,
+      created_at: session.created_at,
+      updated_at: session.updated_at,
+    })
+  }
+}
+
+/// 会话正在进行的写入：并发写入分片的请求数，或者正在合并
+enum Writing {
+  Chunks(usize),
+  Completing,
+}
+
+static WRITING: LazyLock<Mutex<HashMap<String, Writing>>> = LazyLock::new(Default::default);
+
+/// 同一会话的分片可以并发上传，合并时独占会话，
+/// 校验和合并过程中不会有分片被改写，也不会有两个请求同时合并
+struct SessionGuard(String);
+
+impl SessionGuard {
+  fn chunk(id: &str) -> Result<Self, AppError> {
+    let mut writing = WRITING.lock().unwrap_or_else(|err| err.into_inner());
+    match writing.entry(id.to_string()).or_insert(Writing::Chunks(0)) {
+      Writing::Chunks(count) => *count += 1,
+      Writing::Completing => return Err(completing()),
+    }
+    Ok(Self(id.to_string()))
+  }
+
+  fn complete(id: &str) -> Result<Self, AppError> {
+    let mut writing = WRITING.lock().unwrap_or_else(|err| err.into_inner());
+    match writing.get(id) {
+      None => {
+        writing.insert(id.to_string(), Writing::Completing);
+        Ok(Self(id.to_string()))
+      }
+      Some(Writing::Chunks(_)) => Err(AppError::with_status(
+        StatusCode::LOCKED,
+        "还有分片正在上传",
+      )),
+      Some(Writing::Completing) => Err(completing()),
+    }
+  }
+
+  fn is_completing(id: &str) -> bool {
+    matches!(
+      WRITING
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(id),
+      Some(Writing::Completing)
+    )
+  }
+}
+
+impl Drop for SessionGuard {
+  fn drop(&mut self) {
+    let mut writing = WRITING.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(Writing::Chunks(count)) = writing.get_mut(&self.0)
+      && *count > 1
+    {
+      *count -= 1;
+    } else {
+      writing.remove(&self.0);
+    }
+  }
+}
+
+fn completing() -> AppError {
+  AppError::with_status(StatusCode::LOCKED, "上传正在合并")
+}
+
+/// 删除会话的分片目录，空文件的会话没有分片目录
+async fn remove_chunks(root: &Path, id: &str) -> io::Result<()> {
+  match fs::remove_dir_all(chunks_dir(root, id)).await {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+    _ => Ok(()),
+  }
+}
+
+/// 已完整写入的分片以序号命名，写入中的分片带有 `.part` 后缀
+async fn missing_chunks(root: &Path, session: &UploadSession) -> Result<Vec<u64>, AppError> {
+  let total = session.total_chunks();
+  let mut received = vec![false; total as usize];
+  let mut entries = match fs::read_dir(chunks_dir(root, &session.id)).await {
+    Ok(entries) => entries,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0..total).collect()),
+    Err(err) => return Err(err.into()),
+  };
+  while let Some(entry) = entries.next_entry().await? {
+    if let Some(index) = entry
+      .file_name()
+      .to_str()
+      .and_then(|name| name.parse::<u64>().ok())
+      .filter(|index| *index < total)
+    {
+      received[index as usize] = true;
+    }
+  }
+  Ok(
+    (0..total)
+      .filter(|index| !received[*index as usize])
+      .collect(),
+  )
+}
+
+/// 读取当前用户的会话，并按会话的目标路径重新校验存储的写入权限
+async fn load_session(
+  conn: &DBConnection,
+  user: &AuthUser,
+  id: &str,
+) -> Result<(UploadSession, Storage), AppError> {
+  let conn = conn.lock().await;
+  let session = upload::get_upload_session(&conn, id)?
+    .filter(|session| session.user_id == user.id)
+    .ok_or_else(|| AppError::with_status(StatusCode::NOT_FOUND, "上传会话不存在"))?;
+  let storage = Storage::resolve_by_id(
+    &conn,
+    user,
+    session.storage_id,
+    &session.path,
+    AccessLevel::Write,
+  )?;
+  Ok((session, storage))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_session_guard() {
+    let first = SessionGuard::chunk("guard-test").ok();
+    let second = SessionGuard::chunk("guard-test").ok();
+    assert!(first.is_some() && second.is_some());
+    assert!(SessionGuard::complete("guard-test").is_err());
+    drop(first);
+    assert!(SessionGuard::complete("guard-test").is_err());
+    drop(second);
+
+    let complete = SessionGuard::complete("guard-test").ok();
+    assert!(complete.is_some());
+    assert!(SessionGuard::is_completing("guard-test"));
+    assert!(SessionGuard::chunk("guard-test").is_err());
+    assert!(SessionGuard::complete("guard-test").is_err());
+    drop(complete);
+    assert!(SessionGuard::chunk("guard-test").is_ok());
+    assert!(!SessionGuard::is_completing("guard-test"));
+  }
+}
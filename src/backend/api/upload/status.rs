@@ -0,0 +1,21 @@
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+
+use crate::backend::{
+  api::upload::{UploadSessionDto, load_session},
+  db::DBConnection,
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+/// 查询会话状态，客户端据此只补传缺失的分片
+pub async fn get_upload(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  Path(id): Path<String>,
+) -> Result<Json<UploadSessionDto>, AppError> {
+  let (session, storage) = load_session(&conn, &user, &id).await?;
+  Ok(Json(UploadSessionDto::new(&storage.root, session).await?))
+}
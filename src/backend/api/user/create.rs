@@ -0,0 +1,23 @@
+use axum::{Json, extract::State};
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    user::{self, CreateUserDto},
+  },
+  error::AppError,
+  utils::auth,
+};
+
+pub async fn create_user(
+  State(conn): State<DBConnection>,
+  Json(dto): Json<CreateUserDto>,
+) -> Result<(), AppError> {
+  if dto.username.trim().is_empty() || dto.password.is_empty() {
+    return Err(AppError::new("用户名和密码不能为空"));
+  }
+  let password_hash = auth::hash_password(dto.password.clone()).await?;
+  let conn = conn.lock().await;
+  user::create_user(&conn, dto, &password_hash)?;
+  Ok(())
+}
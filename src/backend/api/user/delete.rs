@@ -0,0 +1,22 @@
+use axum::extract::{Path, State};
+
+use crate::backend::{
+  api::user::update::ensure_not_last_admin,
+  db::{DBConnection, user},
+  error::AppError,
+  extractor::auth::AuthUser,
+};
+
+pub async fn delete_user(
+  State(conn): State<DBConnection>,
+  current: AuthUser,
+  Path(id): Path<i64>,
+) -> Result<(), AppError> {
+  if current.id == id {
+    return Err(AppError::new("不能删除当前登录的用户"));
+  }
+  let conn = conn.lock().await;
+  ensure_not_last_admin(&conn, id)?;
+  user::delete_user(&conn, id)?;
+  Ok(())
+}
@@ -0,0 +1,52 @@
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    user::{self, Role},
+  },
+  error::AppError,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserListResponse {
+  pub users: Vec<UserItem>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserItem {
+  pub id: i64,
+  pub name: String,
+  pub username: String,
+  pub avatar: String,
+  pub role: Role,
+  pub disabled: bool,
+  pub created_at: String,
+  pub updated_at: String,
+}
+
+pub async fn list_users(
+  State(conn): State<DBConnection>,
+) -> Result<Json<UserListResponse>, AppError> {
+  let conn = conn.lock().await;
+  let users = user::get_all_users(&conn).context("获取用户失败")?;
+  Ok(Json(UserListResponse {
+    users: users
+      .into_iter()
+      .map(|user| UserItem {
+        id: user.id,
+        name: user.name,
+        username: user.username,
+        avatar: user.avatar,
+        role: user.role,
+        disabled: user.disabled,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+      })
+      .collect(),
+  }))
+}
@@ -0,0 +1,20 @@
+mod create;
+mod delete;
+mod list;
+mod update;
+use axum::{
+  Router,
+  routing::{delete, get, patch, post, put},
+};
+
+use crate::backend::db::DBConnection;
+
+pub fn create_user_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/", get(list::list_users))
+    .route("/", post(create::create_user))
+    .route("/{id}", put(update::update_user))
+    .route("/{id}", delete(delete::delete_user))
+    .route("/{id}/disabled", patch(update::set_disabled))
+    .route("/{id}/password", put(update::reset_password))
+}
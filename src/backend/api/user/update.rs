@@ -0,0 +1,81 @@
+use axum::{
+  Json,
+  extract::{Path, State},
+};
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::backend::{
+  db::{
+    DBConnection,
+    user::{self, Role, UpdateUserDto},
+  },
+  error::AppError,
+  extractor::auth::AuthUser,
+  utils::auth,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDisabledDto {
+  disabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordDto {
+  password: String,
+}
+
+pub async fn update_user(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+  Json(dto): Json<UpdateUserDto>,
+) -> Result<(), AppError> {
+  let conn = conn.lock().await;
+  if dto.role != Role::Admin {
+    ensure_not_last_admin(&conn, id)?;
+  }
+  user::update_user(&conn, id, dto)?;
+  Ok(())
+}
+
+pub async fn set_disabled(
+  State(conn): State<DBConnection>,
+  current: AuthUser,
+  Path(id): Path<i64>,
+  Json(dto): Json<SetDisabledDto>,
+) -> Result<(), AppError> {
+  if dto.disabled && current.id == id {
+    return Err(AppError::new("不能禁用当前登录的用户"));
+  }
+  let conn = conn.lock().await;
+  if dto.disabled {
+    ensure_not_last_admin(&conn, id)?;
+  }
+  user::set_user_disabled(&conn, id, dto.disabled)?;
+  Ok(())
+}
+
+pub async fn reset_password(
+  State(conn): State<DBConnection>,
+  Path(id): Path<i64>,
+  Json(dto): Json<ResetPasswordDto>,
+) -> Result<(), AppError> {
+  if dto.password.is_empty() {
+    return Err(AppError::new("密码不能为空"));
+  }
+  let password_hash = auth::hash_password(dto.password).await?;
+  let conn = conn.lock().await;
+  user::update_password(&conn, id, &password_hash)?;
+  Ok(())
+}
+
+/// 目标用户是最后一个可用的管理员时，不允许降级、禁用或删除
+pub fn ensure_not_last_admin(conn: &Connection, user_id: i64) -> Result<(), AppError> {
+  let target = user::get_user_by_id(conn, user_id).map_err(|_| AppError::new("用户不存在"))?;
+  if target.role == Role::Admin && !target.disabled && user::count_active_admins(conn)? <= 1 {
+    return Err(AppError::new("至少需要保留一个管理员"));
+  }
+  Ok(())
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use anyhow::Context;
+use axum::extract::{Query, State};
+use serde::Deserialize;
+
+use super::find_version;
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::version::{diff_text, version_path},
+};
+
+/// 参与比较的文件大小上限
+const MAX_DIFF_SIZE: u64 = 2 * 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffQuery {
+  pub from: i64,
+  /// 为空时和当前文件比较
+  pub to: Option<i64>,
+}
+
+/// 两个文本版本之间的统一格式差异
+pub async fn diff_version(
+  State(conn): State<DBConnection>,
+  storage: Storage,
+  Query(query): Query<DiffQuery>,
+) -> Result<String, AppError> {
+  storage.require_local()?;
+  let local_path = storage.path.get_path();
+  let path = relative_path(&storage.root, &local_path)?;
+  let from = find_version(&conn, storage.id, &path, query.from).await?;
+  let old = read_text(&version_path(&storage.root, from.id)).await?;
+  let (new, new_name) = match query.to {
+    Some(id) => {
+      let to = find_version(&conn, storage.id, &path, id).await?;
+      let new = read_text(&version_path(&storage.root, to.id)).await?;
+      (new, format!("{} (#{})", path, to.id))
+    }
+    None => (read_text(&local_path).await?, path.clone()),
+  };
+  Ok(diff_text(
+    &old,
+    &new,
+    &format!("{} (#{})", path, from.id),
+    &new_name,
+  ))
+}
+
+async fn read_text(path: &Path) -> Result<String, AppError> {
+  let metadata = tokio::fs::metadata(path).await.context("文件不存在")?;
+  if !metadata.is_file() {
+    return Err(AppError::new("目标是文件夹"));
+  }
+  if metadata.len() > MAX_DIFF_SIZE {
+    return Err(AppError::new("文件过大，无法比较"));
+  }
+  let content = tokio::fs::read(path).await?;
+  Ok(String::from_utf8(content).context("文件不是 UTF-8 文本")?)
+}
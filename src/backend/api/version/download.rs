@@ -0,0 +1,46 @@
+use anyhow::Context;
+use axum::{
+  body::Body,
+  extract::{Query, State},
+  http::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+  response::Response,
+};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
+
+use super::find_version;
+use crate::backend::{
+  api::download::content_disposition,
+  db::DBConnection,
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+  utils::version::version_path,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionQuery {
+  pub id: i64,
+}
+
+pub async fn download_version(
+  State(conn): State<DBConnection>,
+  storage: Storage,
+  Query(query): Query<VersionQuery>,
+) -> Result<Response, AppError> {
+  storage.require_local()?;
+  let path = relative_path(&storage.root, &storage.path.get_path())?;
+  let version = find_version(&conn, storage.id, &path, query.id).await?;
+  let file = tokio::fs::File::open(version_path(&storage.root, version.id))
+    .await
+    .context("版本文件已丢失")?;
+  let size = file.metadata().await?.len();
+  let file_name = path.rsplit('/').next().unwrap_or_default();
+  Ok(
+    Response::builder()
+      .header(CONTENT_TYPE, "application/octet-stream")
+      .header(CONTENT_LENGTH, size)
+      .header(CONTENT_DISPOSITION, content_disposition(file_name, false))
+      .body(Body::from_stream(ReaderStream::new(file)))?,
+  )
+}
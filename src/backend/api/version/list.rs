@@ -0,0 +1,25 @@
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::backend::{
+  db::{self, DBConnection, version::FileVersion},
+  error::AppError,
+  extractor::storage::{Storage, relative_path},
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionListResponse {
+  pub versions: Vec<FileVersion>,
+}
+
+/// 文件的历史版本，最新的在前。文件已被删除时仍然可以查看
+pub async fn list_versions(
+  State(conn): State<DBConnection>,
+  storage: Storage,
+) -> Result<Json<VersionListResponse>, AppError> {
+  storage.require_local()?;
+  let path = relative_path(&storage.root, &storage.path.get_path())?;
+  let versions = db::version::get_versions(&*conn.lock().await, storage.id, &path)?;
+  Ok(Json(VersionListResponse { versions }))
+}
@@ -0,0 +1,35 @@
+mod diff;
+mod download;
+mod list;
+mod restore;
+use axum::{
+  Router,
+  http::StatusCode,
+  routing::{get, post},
+};
+
+use crate::backend::{
+  db::{self, DBConnection, version::FileVersion},
+  error::AppError,
+};
+
+pub fn create_version_router() -> Router<DBConnection> {
+  Router::<DBConnection>::new()
+    .route("/{*path}", get(list::list_versions))
+    .route("/download/{*path}", get(download::download_version))
+    .route("/restore/{*path}", post(restore::restore_version))
+    .route("/diff/{*path}", get(diff::diff_version))
+}
+
+/// 查找属于 `path` 的版本，防止通过其它文件的路径访问没有权限的版本
+async fn find_version(
+  conn: &DBConnection,
+  storage_id: i64,
+  path: &str,
+  id: i64,
+) -> Result<FileVersion, AppError> {
+  match db::version::get_version(&*conn.lock().await, storage_id, id) {
+    Ok(version) if version.path == path => Ok(version),
+    _ => Err(AppError::with_status(StatusCode::NOT_FOUND, "版本不存在")),
+  }
+}
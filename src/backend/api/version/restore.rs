@@ -0,0 +1,44 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use super::find_version;
+use crate::backend::{
+  db::DBConnection,
+  error::AppError,
+  extractor::{
+    auth::AuthUser,
+    storage::{Storage, relative_path},
+  },
+  utils::{
+    events::{self, ChangeKind},
+    version,
+  },
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreVersionDto {
+  id: i64,
+}
+
+/// 用历史版本替换当前内容，被替换的内容会保存为新的版本
+pub async fn restore_version(
+  State(conn): State<DBConnection>,
+  user: AuthUser,
+  storage: Storage,
+  Json(dto): Json<RestoreVersionDto>,
+) -> Result<(), AppError> {
+  storage.require_local()?;
+  let local_path = storage.path.get_path();
+  let path = relative_path(&storage.root, &local_path)?;
+  find_version(&conn, storage.id, &path, dto.id).await?;
+  let existed = local_path.is_file();
+  let target = version::restore(&conn, storage.id, &storage.root, dto.id, user.id).await?;
+  let kind = if existed {
+    ChangeKind::Modified
+  } else {
+    ChangeKind::Created
+  };
+  events::publish(storage.id, &storage.root, &target, kind, user.id);
+  Ok(())
+}
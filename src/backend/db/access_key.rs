@@ -0,0 +1,129 @@
+use rand::{Rng, distributions::Alphanumeric};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+/// S3 兼容接口使用的访问密钥
+pub struct AccessKey {
+  pub id: i64,
+  pub user_id: i64,
+  pub access_key_id: String,
+  /// SigV4 签名需要原始密钥参与计算，因此只能明文保存
+  pub secret_key: String,
+  pub description: String,
+  pub last_used_at: Option<String>,
+  pub created_at: String,
+}
+
+pub fn create_access_key_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS access_key (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      user_id INTEGER NOT NULL,
+      access_key_id TEXT NOT NULL UNIQUE,
+      secret_key TEXT NOT NULL,
+      description TEXT NOT NULL DEFAULT '',
+      last_used_at TEXT,
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_access_key(
+  conn: &Connection,
+  user_id: i64,
+  description: &str,
+) -> anyhow::Result<AccessKey> {
+  // 与 AWS 的格式保持一致：20 位大写 ID + 40 位密钥
+  let access_key_id = format!("SK{}", random_string(18).to_uppercase());
+  let secret_key = random_string(40);
+  conn.execute(
+    "INSERT INTO access_key (user_id, access_key_id, secret_key, description) VALUES (?, ?, ?, ?)",
+    (user_id, &access_key_id, &secret_key, description),
+  )?;
+  get_access_key(conn, &access_key_id)?.ok_or_else(|| anyhow::anyhow!("创建访问密钥失败"))
+}
+
+pub fn get_access_key(conn: &Connection, access_key_id: &str) -> anyhow::Result<Option<AccessKey>> {
+  let key = conn
+    .query_row(
+      "SELECT * FROM access_key WHERE access_key_id = ?",
+      (access_key_id,),
+      map_access_key_row,
+    )
+    .optional()?;
+  Ok(key)
+}
+
+pub fn get_access_keys_by_user(conn: &Connection, user_id: i64) -> anyhow::Result<Vec<AccessKey>> {
+  let mut stmt = conn.prepare("SELECT * FROM access_key WHERE user_id = ? ORDER BY id DESC")?;
+  let keys = stmt
+    .query_map((user_id,), map_access_key_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(keys)
+}
+
+pub fn touch_access_key(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "UPDATE access_key SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (id,),
+  )?;
+  Ok(())
+}
+
+/// 只能删除自己的密钥
+pub fn delete_access_key(conn: &Connection, id: i64, user_id: i64) -> anyhow::Result<()> {
+  let deleted = conn.execute(
+    "DELETE FROM access_key WHERE id = ? AND user_id = ?",
+    (id, user_id),
+  )?;
+  if deleted == 0 {
+    return Err(anyhow::anyhow!("访问密钥不存在"));
+  }
+  Ok(())
+}
+
+pub fn delete_access_keys_by_user(conn: &Connection, user_id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM access_key WHERE user_id = ?", (user_id,))?;
+  Ok(())
+}
+
+fn random_string(len: usize) -> String {
+  rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(len)
+    .map(char::from)
+    .collect()
+}
+
+fn map_access_key_row(row: &Row) -> rusqlite::Result<AccessKey> {
+  Ok(AccessKey {
+    id: row.get("id")?,
+    user_id: row.get("user_id")?,
+    access_key_id: row.get("access_key_id")?,
+    secret_key: row.get("secret_key")?,
+    description: row.get("description")?,
+    last_used_at: row.get("last_used_at")?,
+    created_at: row.get("created_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_access_key() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_access_key_database(&conn).unwrap();
+    let key = create_access_key(&conn, 1, "backup").unwrap();
+    assert_eq!(key.access_key_id.len(), 20);
+    assert_eq!(key.secret_key.len(), 40);
+
+    let found = get_access_key(&conn, &key.access_key_id).unwrap().unwrap();
+    assert_eq!(found.user_id, 1);
+    assert!(delete_access_key(&conn, key.id, 2).is_err());
+    delete_access_key(&conn, key.id, 1).unwrap();
+    assert!(get_access_key(&conn, &key.access_key_id).unwrap().is_none());
+  }
+}
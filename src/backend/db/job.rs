@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+  Queued,
+  Running,
+  Completed,
+  Failed,
+  Cancelled,
+}
+
+impl JobStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      JobStatus::Queued => "queued",
+      JobStatus::Running => "running",
+      JobStatus::Completed => "completed",
+      JobStatus::Failed => "failed",
+      JobStatus::Cancelled => "cancelled",
+    }
+  }
+
+  /// 已经结束，不会再有进度更新
+  pub fn is_finished(&self) -> bool {
+    matches!(
+      self,
+      JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+    )
+  }
+}
+
+impl FromStr for JobStatus {
+  type Err = anyhow::Error;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "queued" => Ok(JobStatus::Queued),
+      "running" => Ok(JobStatus::Running),
+      "completed" => Ok(JobStatus::Completed),
+      "failed" => Ok(JobStatus::Failed),
+      "cancelled" => Ok(JobStatus::Cancelled),
+      _ => Err(anyhow::anyhow!("未知的任务状态: {}", value)),
+    }
+  }
+}
+
+/// 后台任务，参数保存在 `payload` 中，重启或重试时按它重新执行
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+  pub id: i64,
+  pub user_id: i64,
+  /// 任务类型，例如 `transfer`、`extract`
+  pub kind: String,
+  pub status: JobStatus,
+  pub payload: Value,
+  /// 最近一次保存的进度
+  pub progress: Option<Value>,
+  pub result: Option<Value>,
+  pub error: Option<String>,
+  pub created_at: String,
+  pub updated_at: String,
+}
+
+pub fn create_job_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS job (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      user_id INTEGER NOT NULL,
+      kind TEXT NOT NULL,
+      status TEXT NOT NULL DEFAULT 'queued',
+      payload TEXT NOT NULL,
+      progress TEXT,
+      result TEXT,
+      error TEXT,
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+      updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_job(
+  conn: &Connection,
+  user_id: i64,
+  kind: &str,
+  payload: &str,
+) -> anyhow::Result<i64> {
+  conn.execute(
+    "INSERT INTO job (user_id, kind, payload) VALUES (?, ?, ?)",
+    (user_id, kind, payload),
+  )?;
+  Ok(conn.last_insert_rowid())
+}
+
+pub fn get_job(conn: &Connection, id: i64) -> anyhow::Result<Option<Job>> {
+  let job = conn
+    .query_row("SELECT * FROM job WHERE id = ?", (id,), map_job_row)
+    .optional()?;
+  Ok(job)
+}
+
+/// 用户最近的任务，按创建时间倒序
+pub fn get_jobs_by_user(conn: &Connection, user_id: i64, limit: u32) -> anyhow::Result<Vec<Job>> {
+  let mut stmt = conn.prepare("SELECT * FROM job WHERE user_id = ? ORDER BY id DESC LIMIT ?")?;
+  let jobs = stmt
+    .query_map((user_id, limit), map_job_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(jobs)
+}
+
+/// 重启前没有开始执行的任务，按创建顺序返回。执行被中断的任务可能已经写入了部分文件，
+/// 从头执行会重复写入，所以标记为失败，由用户确认后重试
+pub fn requeue_unfinished_jobs(conn: &Connection, interrupted: &str) -> anyhow::Result<Vec<Job>> {
+  conn.execute(
+    "UPDATE job SET status = 'failed', error = ?, updated_at = CURRENT_TIMESTAMP
+      WHERE status = 'running'",
+    (interrupted,),
+  )?;
+  let mut stmt = conn.prepare("SELECT * FROM job WHERE status = 'queued' ORDER BY id")?;
+  let jobs = stmt
+    .query_map((), map_job_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(jobs)
+}
+
+pub fn set_job_running(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "UPDATE job SET status = 'running', updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (id,),
+  )?;
+  Ok(())
+}
+
+pub fn update_job_progress(conn: &Connection, id: i64, progress: &str) -> anyhow::Result<()> {
+  conn.execute(
+    "UPDATE job SET progress = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (progress, id),
+  )?;
+  Ok(())
+}
+
+pub fn finish_job(
+  conn: &Connection,
+  id: i64,
+  status: JobStatus,
+  result: Option<&str>,
+  error: Option<&str>,
+) -> anyhow::Result<()> {
+  conn.execute(
+    "UPDATE job SET status = ?, result = ?, error = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (status.as_str(), result, error, id),
+  )?;
+  Ok(())
+}
+
+/// 重试时清空上次的进度和结果，重新排队。只有失败或已取消的任务可以重试，
+/// 返回是否成功重置，同时发起的多次重试只有一次成功
+pub fn reset_job(conn: &Connection, id: i64) -> anyhow::Result<bool> {
+  let updated = conn.execute(
+    "UPDATE job SET status = 'queued', progress = NULL, result = NULL, error = NULL,
+      updated_at = CURRENT_TIMESTAMP WHERE id = ? AND status IN ('failed', 'cancelled')",
+    (id,),
+  )?;
+  Ok(updated > 0)
+}
+
+/// 删除结束时间早于 `days` 天前的任务
+pub fn delete_expired_jobs(conn: &Connection, days: i64) -> anyhow::Result<usize> {
+  let count = conn.execute(
+    "DELETE FROM job WHERE status IN ('completed', 'failed', 'cancelled')
+      AND updated_at < datetime('now', ?)",
+    (format!("-{} days", days),),
+  )?;
+  Ok(count)
+}
+
+fn map_job_row(row: &Row) -> rusqlite::Result<Job> {
+  let json = |column: &str| -> rusqlite::Result<Option<Value>> {
+    Ok(
+      row
+        .get::<_, Option<String>>(column)?
+        .and_then(|text| serde_json::from_str(&text).ok()),
+    )
+  };
+  Ok(Job {
+    id: row.get("id")?,
+    user_id: row.get("user_id")?,
+    kind: row.get("kind")?,
+    status: row
+      .get::<_, String>("status")?
+      .parse()
+      .unwrap_or(JobStatus::Failed),
+    payload: json("payload")?.unwrap_or_default(),
+    progress: json("progress")?,
+    result: json("result")?,
+    error: row.get("error")?,
+    created_at: row.get("created_at")?,
+    updated_at: row.get("updated_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_requeue_unfinished_jobs() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_job_database(&conn).unwrap();
+    let running = create_job(&conn, 1, "reindex", r#"{"kind":"reindex"}"#).unwrap();
+    let queued = create_job(&conn, 1, "reindex", r#"{"kind":"reindex"}"#).unwrap();
+    let done = create_job(&conn, 1, "reindex", r#"{"kind":"reindex"}"#).unwrap();
+    set_job_running(&conn, running).unwrap();
+    update_job_progress(&conn, running, r#"{"doneFiles":1}"#).unwrap();
+    finish_job(&conn, done, JobStatus::Failed, None, Some("失败")).unwrap();
+
+    let jobs = requeue_unfinished_jobs(&conn, "中断").unwrap();
+    let ids = jobs.iter().map(|job| job.id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![queued]);
+    let job = get_job(&conn, running).unwrap().unwrap();
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.error.as_deref(), Some("中断"));
+    assert_eq!(job.progress.unwrap()["doneFiles"], 1);
+
+    assert!(reset_job(&conn, done).unwrap());
+    assert!(!reset_job(&conn, done).unwrap());
+    assert!(!reset_job(&conn, queued).unwrap());
+    let job = get_job(&conn, done).unwrap().unwrap();
+    assert_eq!(job.status, JobStatus::Queued);
+    assert!(job.error.is_none());
+  }
+}
@@ -1,5 +1,14 @@
+pub mod access_key;
+pub mod job;
+pub mod permission;
+pub mod search;
+pub mod share;
 pub mod storage;
+pub mod trash;
+pub mod tus;
+pub mod upload;
 pub mod user;
+pub mod version;
 use std::sync::Arc;
 
 use rusqlite::Connection;
@@ -12,5 +21,14 @@ pub fn init_db() -> anyhow::Result<DBConnection> {
 
   user::create_user_database(&conn)?;
   storage::create_storage_database(&conn)?;
+  permission::create_permission_database(&conn)?;
+  share::create_share_database(&conn)?;
+  trash::create_trash_database(&conn)?;
+  search::create_search_database(&conn)?;
+  access_key::create_access_key_database(&conn)?;
+  upload::create_upload_database(&conn)?;
+  tus::create_tus_database(&conn)?;
+  job::create_job_database(&conn)?;
+  version::create_version_database(&conn)?;
   Ok(Arc::new(Mutex::new(conn)))
 }
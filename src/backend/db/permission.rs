@@ -0,0 +1,205 @@
+use std::str::FromStr;
+
+use rusqlite::{
+  Connection, Row,
+  types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::db::{
+  storage::{self, StorageDatabase},
+  user::Role,
+};
+
+/// 存储访问级别，按权限从低到高排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessLevel {
+  Read,
+  Write,
+  /// 读写之外还可以管理该存储的分享、回收站等
+  Manage,
+}
+
+impl AccessLevel {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      AccessLevel::Read => "read",
+      AccessLevel::Write => "write",
+      AccessLevel::Manage => "manage",
+    }
+  }
+}
+
+impl FromStr for AccessLevel {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "read" => Ok(AccessLevel::Read),
+      "write" => Ok(AccessLevel::Write),
+      "manage" => Ok(AccessLevel::Manage),
+      _ => Err(anyhow::anyhow!("未知的权限: {}", s)),
+    }
+  }
+}
+
+impl ToSql for AccessLevel {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
+  }
+}
+
+impl FromSql for AccessLevel {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    value
+      .as_str()?
+      .parse()
+      .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePermission {
+  pub user_id: i64,
+  pub username: String,
+  pub name: String,
+  pub level: AccessLevel,
+}
+
+pub fn create_permission_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS storage_permission (
+      user_id INTEGER NOT NULL,
+      storage_id INTEGER NOT NULL,
+      level TEXT NOT NULL,
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+      PRIMARY KEY (user_id, storage_id)
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn set_permission(
+  conn: &Connection,
+  storage_id: i64,
+  user_id: i64,
+  level: AccessLevel,
+) -> anyhow::Result<()> {
+  conn.execute(
+    "INSERT INTO storage_permission (user_id, storage_id, level) VALUES (?, ?, ?)
+      ON CONFLICT (user_id, storage_id) DO UPDATE SET level = excluded.level",
+    (user_id, storage_id, level),
+  )?;
+  Ok(())
+}
+
+pub fn delete_permission(conn: &Connection, storage_id: i64, user_id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "DELETE FROM storage_permission WHERE storage_id = ? AND user_id = ?",
+    (storage_id, user_id),
+  )?;
+  Ok(())
+}
+
+pub fn delete_permissions_by_user(conn: &Connection, user_id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "DELETE FROM storage_permission WHERE user_id = ?",
+    (user_id,),
+  )?;
+  Ok(())
+}
+
+pub fn delete_permissions_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "DELETE FROM storage_permission WHERE storage_id = ?",
+    (storage_id,),
+  )?;
+  Ok(())
+}
+
+pub fn get_storage_permissions(
+  conn: &Connection,
+  storage_id: i64,
+) -> anyhow::Result<Vec<StoragePermission>> {
+  let mut stmt = conn.prepare(
+    "SELECT p.user_id, p.level, u.username, u.name FROM storage_permission p
+      JOIN user u ON u.id = p.user_id
+      WHERE p.storage_id = ? ORDER BY u.id",
+  )?;
+  let permissions = stmt
+    .query_map((storage_id,), |row: &Row| {
+      Ok(StoragePermission {
+        user_id: row.get("user_id")?,
+        username: row.get("username")?,
+        name: row.get("name")?,
+        level: row.get("level")?,
+      })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(permissions)
+}
+
+/// 用户对存储的实际权限：管理员拥有全部权限，访客最多只读
+pub fn get_access_level(
+  conn: &Connection,
+  user_id: i64,
+  role: Role,
+  storage_id: i64,
+) -> anyhow::Result<Option<AccessLevel>> {
+  if role == Role::Admin {
+    return Ok(Some(AccessLevel::Manage));
+  }
+  let level: Option<AccessLevel> = conn
+    .query_row(
+      "SELECT level FROM storage_permission WHERE user_id = ? AND storage_id = ?",
+      (user_id, storage_id),
+      |row| row.get(0),
+    )
+    .ok();
+  Ok(level.map(|level| clamp_by_role(level, role)))
+}
+
+/// 用户可以访问的已启用存储及对应权限
+pub fn get_accessible_storages(
+  conn: &Connection,
+  user_id: i64,
+  role: Role,
+) -> anyhow::Result<Vec<(StorageDatabase, AccessLevel)>> {
+  let storages = storage::get_all_enabled_storage(conn)?;
+  let mut result = Vec::new();
+  for storage in storages {
+    if let Some(level) = get_access_level(conn, user_id, role, storage.id)? {
+      result.push((storage, level));
+    }
+  }
+  Ok(result)
+}
+
+fn clamp_by_role(level: AccessLevel, role: Role) -> AccessLevel {
+  match role {
+    Role::Guest => level.min(AccessLevel::Read),
+    _ => level,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_clamp_by_role() {
+    assert_eq!(
+      clamp_by_role(AccessLevel::Manage, Role::Guest),
+      AccessLevel::Read
+    );
+    assert_eq!(
+      clamp_by_role(AccessLevel::Write, Role::User),
+      AccessLevel::Write
+    );
+    assert!(AccessLevel::Read < AccessLevel::Write);
+    assert!(AccessLevel::Write < AccessLevel::Manage);
+  }
+}
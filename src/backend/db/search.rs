@@ -0,0 +1,401 @@
+use rusqlite::{Connection, Row, params_from_iter, types::Value};
+
+/// 文件索引中的一条记录，路径相对于存储根目录
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+  pub path: String,
+  pub name: String,
+  /// 小写扩展名，不带 `.`
+  pub extension: String,
+  pub is_dir: bool,
+  pub size: u64,
+  /// 修改时间，Unix 秒
+  pub modified: i64,
+  /// 文本文件内容，仅在开启内容索引时写入
+  pub content: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+  /// 搜索范围，相对于存储根目录，为空表示整个存储
+  pub scope: String,
+  /// 全文关键字，匹配文件名和内容
+  pub keyword: Option<String>,
+  /// 文件名，包含 `*` / `?` 时按通配符匹配，否则按子串匹配
+  pub name: Option<String>,
+  pub extensions: Vec<String>,
+  pub min_size: Option<u64>,
+  pub max_size: Option<u64>,
+  pub modified_after: Option<i64>,
+  pub modified_before: Option<i64>,
+  pub limit: u32,
+  pub offset: u32,
+}
+
+pub fn create_search_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS file_index (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      storage_id INTEGER NOT NULL,
+      path TEXT NOT NULL,
+      name TEXT NOT NULL,
+      extension TEXT NOT NULL,
+      is_dir BOOLEAN NOT NULL DEFAULT FALSE,
+      size INTEGER NOT NULL DEFAULT 0,
+      modified INTEGER NOT NULL DEFAULT 0,
+      UNIQUE (storage_id, path)
+    )",
+    (),
+  )?;
+  // rowid 与 file_index.id 一致
+  conn.execute(
+    "CREATE VIRTUAL TABLE IF NOT EXISTS file_index_fts USING fts5(name, content)",
+    (),
+  )?;
+  Ok(())
+}
+
+/// 写入一批索引，路径已有索引时原地更新，用于边扫描边分批写入
+pub fn upsert_entries(
+  conn: &Connection,
+  storage_id: i64,
+  entries: &[IndexEntry],
+) -> anyhow::Result<()> {
+  let tx = conn.unchecked_transaction()?;
+  {
+    let mut upsert = tx.prepare(
+      "INSERT INTO file_index (storage_id, path, name, extension, is_dir, size, modified)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (storage_id, path) DO UPDATE SET
+          name = excluded.name,
+          extension = excluded.extension,
+          is_dir = excluded.is_dir,
+          size = excluded.size,
+          modified = excluded.modified
+        RETURNING id",
+    )?;
+    let mut delete_fts = tx.prepare("DELETE FROM file_index_fts WHERE rowid = ?")?;
+    let mut insert_fts =
+      tx.prepare("INSERT INTO file_index_fts (rowid, name, content) VALUES (?, ?, ?)")?;
+    for entry in entries {
+      let id: i64 = upsert.query_row(
+        (
+          storage_id,
+          &entry.path,
+          &entry.name,
+          &entry.extension,
+          entry.is_dir,
+          entry.size,
+          entry.modified,
+        ),
+        |row| row.get(0),
+      )?;
+      delete_fts.execute((id,))?;
+      insert_fts.execute((id, &entry.name, &entry.content))?;
+    }
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+/// `prefix` 自身及其子路径的索引 id 和路径
+pub fn list_paths(
+  conn: &Connection,
+  storage_id: i64,
+  prefix: &str,
+) -> anyhow::Result<Vec<(i64, String)>> {
+  let (condition, params) = prefix_condition(storage_id, prefix, true);
+  let mut stmt = conn.prepare(&format!(
+    "SELECT id, path FROM file_index WHERE {}",
+    condition
+  ))?;
+  let paths = stmt
+    .query_map(params_from_iter(params.iter()), |row| {
+      Ok((row.get(0)?, row.get(1)?))
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(paths)
+}
+
+pub fn delete_by_ids(conn: &Connection, ids: &[i64]) -> anyhow::Result<()> {
+  let tx = conn.unchecked_transaction()?;
+  {
+    let mut delete = tx.prepare("DELETE FROM file_index WHERE id = ?")?;
+    let mut delete_fts = tx.prepare("DELETE FROM file_index_fts WHERE rowid = ?")?;
+    for id in ids {
+      delete.execute((id,))?;
+      delete_fts.execute((id,))?;
+    }
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+/// 删除 `prefix` 自身及其子路径的索引，`prefix` 为空时删除整个存储的索引
+pub fn delete_entries(conn: &Connection, storage_id: i64, prefix: &str) -> anyhow::Result<()> {
+  let (condition, params) = prefix_condition(storage_id, prefix, true);
+  conn.execute(
+    &format!(
+      "DELETE FROM file_index_fts WHERE rowid IN (SELECT id FROM file_index WHERE {})",
+      condition
+    ),
+    params_from_iter(params.iter()),
+  )?;
+  conn.execute(
+    &format!("DELETE FROM file_index WHERE {}", condition),
+    params_from_iter(params.iter()),
+  )?;
+  Ok(())
+}
+
+pub fn delete_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<()> {
+  delete_entries(conn, storage_id, "")
+}
+
+/// 返回当前页的结果和总数
+pub fn search(
+  conn: &Connection,
+  storage_id: i64,
+  query: &SearchQuery,
+) -> anyhow::Result<(Vec<IndexEntry>, u64)> {
+  let (condition, mut params) = prefix_condition(storage_id, &query.scope, false);
+  let mut conditions = vec![condition];
+
+  if let Some(keyword) = query.keyword.as_deref().and_then(fts_query) {
+    conditions
+      .push("id IN (SELECT rowid FROM file_index_fts WHERE file_index_fts MATCH ?)".to_string());
+    params.push(Value::Text(keyword));
+  }
+  if let Some(name) = query.name.as_deref().filter(|name| !name.is_empty()) {
+    conditions.push("name LIKE ? ESCAPE '\\'".to_string());
+    params.push(Value::Text(name_pattern(name)));
+  }
+  if !query.extensions.is_empty() {
+    conditions.push(format!(
+      "extension IN ({})",
+      vec!["?"; query.extensions.len()].join(", ")
+    ));
+    params.extend(query.extensions.iter().cloned().map(Value::Text));
+  }
+  if let Some(min_size) = query.min_size {
+    conditions.push("is_dir = FALSE AND size >= ?".to_string());
+    params.push(Value::Integer(min_size as i64));
+  }
+  if let Some(max_size) = query.max_size {
+    conditions.push("is_dir = FALSE AND size <= ?".to_string());
+    params.push(Value::Integer(max_size as i64));
+  }
+  if let Some(after) = query.modified_after {
+    conditions.push("modified >= ?".to_string());
+    params.push(Value::Integer(after));
+  }
+  if let Some(before) = query.modified_before {
+    conditions.push("modified < ?".to_string());
+    params.push(Value::Integer(before));
+  }
+  let condition = conditions.join(" AND ");
+
+  let total: u64 = conn.query_row(
+    &format!("SELECT COUNT(*) FROM file_index WHERE {}", condition),
+    params_from_iter(params.iter()),
+    |row| row.get(0),
+  )?;
+
+  params.push(Value::Integer(query.limit as i64));
+  params.push(Value::Integer(query.offset as i64));
+  let mut stmt = conn.prepare(&format!(
+    "SELECT * FROM file_index WHERE {}
+      ORDER BY is_dir DESC, name COLLATE NOCASE, path LIMIT ? OFFSET ?",
+    condition
+  ))?;
+  let entries = stmt
+    .query_map(params_from_iter(params.iter()), map_index_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok((entries, total))
+}
+
+fn prefix_condition(storage_id: i64, prefix: &str, include_self: bool) -> (String, Vec<Value>) {
+  let prefix = prefix.trim_matches('/');
+  if prefix.is_empty() {
+    return (
+      "storage_id = ?".to_string(),
+      vec![Value::Integer(storage_id)],
+    );
+  }
+  let children = format!("{}/%", escape_like(prefix));
+  if include_self {
+    (
+      "storage_id = ? AND (path = ? OR path LIKE ? ESCAPE '\\')".to_string(),
+      vec![
+        Value::Integer(storage_id),
+        Value::Text(prefix.to_string()),
+        Value::Text(children),
+      ],
+    )
+  } else {
+    (
+      "storage_id = ? AND path LIKE ? ESCAPE '\\'".to_string(),
+      vec![Value::Integer(storage_id), Value::Text(children)],
+    )
+  }
+}
+
+fn escape_like(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// `*.txt` -> `%.txt`，没有通配符时按子串匹配
+fn name_pattern(name: &str) -> String {
+  let pattern = escape_like(name).replace('*', "%").replace('?', "_");
+  if name.contains(['*', '?']) {
+    pattern
+  } else {
+    format!("%{}%", pattern)
+  }
+}
+
+/// 把用户输入转换为 FTS5 查询：每个词按前缀匹配，词之间为 AND
+fn fts_query(keyword: &str) -> Option<String> {
+  let terms = keyword
+    .split_whitespace()
+    .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+    .collect::<Vec<_>>();
+  (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn map_index_row(row: &Row) -> rusqlite::Result<IndexEntry> {
+  Ok(IndexEntry {
+    path: row.get("path")?,
+    name: row.get("name")?,
+    extension: row.get("extension")?,
+    is_dir: row.get("is_dir")?,
+    size: row.get("size")?,
+    modified: row.get("modified")?,
+    content: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(path: &str, size: u64, content: Option<&str>) -> IndexEntry {
+    let name = path.rsplit('/').next().unwrap().to_string();
+    IndexEntry {
+      extension: name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .unwrap_or_default()
+        .to_string(),
+      path: path.to_string(),
+      name,
+      is_dir: false,
+      size,
+      modified: 100,
+      content: content.map(|content| content.to_string()),
+    }
+  }
+
+  #[test]
+  fn test_search() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_search_database(&conn).unwrap();
+    upsert_entries(
+      &conn,
+      1,
+      &[
+        entry("docs/report_2024.md", 10, Some("quarterly revenue")),
+        entry("docs/notes.txt", 500, None),
+        entry("photos/report.jpg", 2000, None),
+      ],
+    )
+    .unwrap();
+
+    let search_names = |query: SearchQuery| {
+      search(&conn, 1, &query)
+        .unwrap()
+        .0
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect::<Vec<_>>()
+    };
+    let limit = 50;
+
+    assert_eq!(
+      search_names(SearchQuery {
+        name: Some("report".to_string()),
+        limit,
+        ..Default::default()
+      }),
+      ["photos/report.jpg", "docs/report_2024.md"]
+    );
+    assert_eq!(
+      search_names(SearchQuery {
+        name: Some("*.txt".to_string()),
+        limit,
+        ..Default::default()
+      }),
+      ["docs/notes.txt"]
+    );
+    assert_eq!(
+      search_names(SearchQuery {
+        keyword: Some("revenue".to_string()),
+        limit,
+        ..Default::default()
+      }),
+      ["docs/report_2024.md"]
+    );
+    assert_eq!(
+      search_names(SearchQuery {
+        scope: "docs".to_string(),
+        min_size: Some(100),
+        limit,
+        ..Default::default()
+      }),
+      ["docs/notes.txt"]
+    );
+
+    // 已有的路径原地更新，内容索引随之替换
+    upsert_entries(
+      &conn,
+      1,
+      &[entry("docs/report_2024.md", 10, Some("annual summary"))],
+    )
+    .unwrap();
+    assert!(
+      search_names(SearchQuery {
+        keyword: Some("revenue".to_string()),
+        limit,
+        ..Default::default()
+      })
+      .is_empty()
+    );
+    let stale: Vec<_> = list_paths(&conn, 1, "docs")
+      .unwrap()
+      .into_iter()
+      .filter(|(_, path)| path == "docs/notes.txt")
+      .map(|(id, _)| id)
+      .collect();
+    delete_by_ids(&conn, &stale).unwrap();
+    assert_eq!(
+      search_names(SearchQuery {
+        scope: "docs".to_string(),
+        limit,
+        ..Default::default()
+      }),
+      ["docs/report_2024.md"]
+    );
+
+    delete_entries(&conn, 1, "docs").unwrap();
+    assert_eq!(
+      search_names(SearchQuery {
+        limit,
+        ..Default::default()
+      }),
+      ["photos/report.jpg"]
+    );
+  }
+}
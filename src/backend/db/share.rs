@@ -0,0 +1,167 @@
+use rand::{Rng, distributions::Alphanumeric};
+use rusqlite::{Connection, Row};
+use serde::Deserialize;
+
+pub struct Share {
+  pub id: i64,
+  pub token: String,
+  pub storage_id: i64,
+  /// 存储内的相对路径，为空表示整个存储
+  pub path: String,
+  pub password: Option<String>,
+  /// 过期时间 (unix 时间戳，秒)
+  pub expires_at: Option<i64>,
+  pub max_downloads: Option<i64>,
+  pub download_count: i64,
+  pub created_by: i64,
+  pub created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShareDto {
+  pub password: Option<String>,
+  pub expires_at: Option<i64>,
+  pub max_downloads: Option<i64>,
+}
+
+pub fn create_share_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS share (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      token TEXT NOT NULL UNIQUE,
+      storage_id INTEGER NOT NULL,
+      path TEXT NOT NULL DEFAULT '',
+      password TEXT,
+      expires_at INTEGER,
+      max_downloads INTEGER,
+      download_count INTEGER NOT NULL DEFAULT 0,
+      created_by INTEGER NOT NULL,
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_share(
+  conn: &Connection,
+  storage_id: i64,
+  path: &str,
+  user_id: i64,
+  share: CreateShareDto,
+  password_hash: Option<String>,
+) -> anyhow::Result<Share> {
+  let token: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(16)
+    .map(char::from)
+    .collect();
+  conn.execute(
+    "INSERT INTO share (token, storage_id, path, password, expires_at, max_downloads, created_by) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    (&token, storage_id, path, password_hash, share.expires_at, share.max_downloads, user_id),
+  )?;
+  get_share_by_token(conn, &token)
+}
+
+pub fn get_share_by_token(conn: &Connection, token: &str) -> anyhow::Result<Share> {
+  let share = conn.query_row(
+    "SELECT * FROM share WHERE token = ?",
+    (token,),
+    map_share_row,
+  )?;
+  Ok(share)
+}
+
+pub fn get_share_by_id(conn: &Connection, id: i64) -> anyhow::Result<Share> {
+  let share = conn.query_row("SELECT * FROM share WHERE id = ?", (id,), map_share_row)?;
+  Ok(share)
+}
+
+pub fn get_all_shares(conn: &Connection) -> anyhow::Result<Vec<Share>> {
+  let mut stmt = conn.prepare("SELECT * FROM share ORDER BY id DESC")?;
+  let shares = stmt
+    .query_map([], map_share_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(shares)
+}
+
+pub fn get_shares_by_user(conn: &Connection, user_id: i64) -> anyhow::Result<Vec<Share>> {
+  let mut stmt = conn.prepare("SELECT * FROM share WHERE created_by = ? ORDER BY id DESC")?;
+  let shares = stmt
+    .query_map((user_id,), map_share_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(shares)
+}
+
+/// 下载次数 +1，超过限制时返回 false
+pub fn increase_download_count(conn: &Connection, id: i64) -> anyhow::Result<bool> {
+  let updated = conn.execute(
+    "UPDATE share SET download_count = download_count + 1
+      WHERE id = ? AND (max_downloads IS NULL OR download_count < max_downloads)",
+    (id,),
+  )?;
+  Ok(updated > 0)
+}
+
+pub fn delete_share(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  let deleted = conn.execute("DELETE FROM share WHERE id = ?", (id,))?;
+  if deleted == 0 {
+    return Err(anyhow::anyhow!("分享不存在"));
+  }
+  Ok(())
+}
+
+pub fn delete_shares_by_user(conn: &Connection, user_id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM share WHERE created_by = ?", (user_id,))?;
+  Ok(())
+}
+
+pub fn delete_shares_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM share WHERE storage_id = ?", (storage_id,))?;
+  Ok(())
+}
+
+fn map_share_row(row: &Row) -> rusqlite::Result<Share> {
+  Ok(Share {
+    id: row.get("id")?,
+    token: row.get("token")?,
+    storage_id: row.get("storage_id")?,
+    path: row.get("path")?,
+    password: row.get("password")?,
+    expires_at: row.get("expires_at")?,
+    max_downloads: row.get("max_downloads")?,
+    download_count: row.get("download_count")?,
+    created_by: row.get("created_by")?,
+    created_at: row.get("created_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_download_limit() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_share_database(&conn).unwrap();
+    let share = create_share(
+      &conn,
+      1,
+      "a.txt",
+      1,
+      CreateShareDto {
+        password: None,
+        expires_at: None,
+        max_downloads: Some(2),
+      },
+      None,
+    )
+    .unwrap();
+
+    assert!(increase_download_count(&conn, share.id).unwrap());
+    assert!(increase_download_count(&conn, share.id).unwrap());
+    assert!(!increase_download_count(&conn, share.id).unwrap());
+    assert_eq!(get_share_by_id(&conn, share.id).unwrap().download_count, 2);
+  }
+}
@@ -1,17 +1,80 @@
+use std::str::FromStr;
+
 use anyhow::Context;
-use rusqlite::Connection;
+use rusqlite::{
+  Connection, Row,
+  types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+};
 use serde::{Deserialize, Serialize};
 
+/// 存储后端类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageKind {
+  /// 本地目录
+  #[default]
+  Local,
+  /// S3 兼容的对象存储
+  S3,
+  Sftp,
+}
+
+impl StorageKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      StorageKind::Local => "local",
+      StorageKind::S3 => "s3",
+      StorageKind::Sftp => "sftp",
+    }
+  }
+}
+
+impl FromStr for StorageKind {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "local" => Ok(StorageKind::Local),
+      "s3" => Ok(StorageKind::S3),
+      "sftp" => Ok(StorageKind::Sftp),
+      _ => Err(anyhow::anyhow!("未知的存储类型: {}", s)),
+    }
+  }
+}
+
+impl ToSql for StorageKind {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
+  }
+}
+
+impl FromSql for StorageKind {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    value
+      .as_str()?
+      .parse()
+      .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+  }
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StorageDatabase {
   pub id: i64,
   pub name: String,
   pub path: String,
+  /// 本地存储的根目录；远程存储用来存放上传分片等临时文件
   pub local_path: String,
+  pub kind: StorageKind,
+  /// 远程存储的连接配置，见 `utils::backend`
+  pub config: serde_json::Value,
   pub max_file_size: u64,
   pub allow_extensions: String,
   pub block_extensions: String,
+  /// 每个文件最多保留的历史版本数，0 表示不保留
+  pub max_versions: u32,
+  /// 历史版本保留的天数，0 表示不按时间清理
+  pub version_days: u32,
   pub disabled: bool,
   pub sort_index: i64,
   pub created_at: String,
@@ -24,12 +87,47 @@ pub struct CreateStorageDto {
   pub name: String,
   pub path: String,
   pub local_path: String,
+  #[serde(default)]
+  pub kind: StorageKind,
+  #[serde(default)]
+  pub config: serde_json::Value,
   pub max_file_size: u64,
   pub allow_extensions: String,
   pub block_extensions: String,
+  #[serde(default = "default_max_versions")]
+  pub max_versions: u32,
+  #[serde(default = "default_version_days")]
+  pub version_days: u32,
   pub sort_index: i64,
 }
 
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateStorageDto {
+  pub name: String,
+  pub local_path: String,
+  #[serde(default)]
+  pub kind: StorageKind,
+  #[serde(default)]
+  pub config: serde_json::Value,
+  pub max_file_size: u64,
+  pub allow_extensions: String,
+  pub block_extensions: String,
+  #[serde(default = "default_max_versions")]
+  pub max_versions: u32,
+  #[serde(default = "default_version_days")]
+  pub version_days: u32,
+  pub sort_index: i64,
+}
+
+fn default_max_versions() -> u32 {
+  10
+}
+
+fn default_version_days() -> u32 {
+  30
+}
+
 pub fn create_storage_database(conn: &Connection) -> anyhow::Result<()> {
   // path 唯一
   conn.execute(
@@ -38,9 +136,13 @@ pub fn create_storage_database(conn: &Connection) -> anyhow::Result<()> {
       name TEXT NOT NULL,
       path TEXT NOT NULL UNIQUE,
       local_path TEXT NOT NULL,
+      kind TEXT NOT NULL DEFAULT 'local',
+      config TEXT NOT NULL DEFAULT '{}',
       max_file_size INTEGER DEFAULT 0,
       allow_extensions TEXT DEFAULT '',
       block_extensions TEXT DEFAULT '',
+      max_versions INTEGER NOT NULL DEFAULT 10,
+      version_days INTEGER NOT NULL DEFAULT 30,
       disabled BOOLEAN NOT NULL DEFAULT FALSE,
       sort_index INTEGER DEFAULT 0,
       created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
@@ -48,6 +150,46 @@ pub fn create_storage_database(conn: &Connection) -> anyhow::Result<()> {
     )",
     (),
   )?;
+  migrate_kind_column(conn)?;
+  migrate_version_columns(conn)?;
+  Ok(())
+}
+
+/// 旧版本数据库只有本地存储，补上 kind 和 config 字段
+fn migrate_kind_column(conn: &Connection) -> anyhow::Result<()> {
+  let has_kind = conn
+    .prepare("SELECT 1 FROM pragma_table_info('storage') WHERE name = 'kind'")?
+    .exists(())?;
+  if has_kind {
+    return Ok(());
+  }
+  conn.execute(
+    "ALTER TABLE storage ADD COLUMN kind TEXT NOT NULL DEFAULT 'local'",
+    (),
+  )?;
+  conn.execute(
+    "ALTER TABLE storage ADD COLUMN config TEXT NOT NULL DEFAULT '{}'",
+    (),
+  )?;
+  Ok(())
+}
+
+/// 旧版本数据库没有历史版本设置，补上 max_versions 和 version_days 字段
+fn migrate_version_columns(conn: &Connection) -> anyhow::Result<()> {
+  let has_versions = conn
+    .prepare("SELECT 1 FROM pragma_table_info('storage') WHERE name = 'max_versions'")?
+    .exists(())?;
+  if has_versions {
+    return Ok(());
+  }
+  conn.execute(
+    "ALTER TABLE storage ADD COLUMN max_versions INTEGER NOT NULL DEFAULT 10",
+    (),
+  )?;
+  conn.execute(
+    "ALTER TABLE storage ADD COLUMN version_days INTEGER NOT NULL DEFAULT 30",
+    (),
+  )?;
   Ok(())
 }
 
@@ -62,33 +204,31 @@ pub fn create_storage(conn: &Connection, storage: CreateStorageDto) -> anyhow::R
   }
 
   conn.execute(
-    "INSERT INTO storage (name, path, local_path, max_file_size, allow_extensions, block_extensions, sort_index) VALUES (?, ?, ?, ?, ?, ?, ?)",
-    (storage.name, storage.path, storage.local_path, storage.max_file_size, storage.allow_extensions, storage.block_extensions, storage.sort_index),
+    "INSERT INTO storage (name, path, local_path, kind, config, max_file_size, allow_extensions, block_extensions, max_versions, version_days, sort_index) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    (storage.name, storage.path, storage.local_path, storage.kind, config_text(&storage.config), storage.max_file_size, storage.allow_extensions, storage.block_extensions, storage.max_versions, storage.version_days, storage.sort_index),
   )?;
   Ok(())
 }
 
 pub fn get_all_enabled_storage(conn: &Connection) -> anyhow::Result<Vec<StorageDatabase>> {
   let mut stmt = conn
-    .prepare("SELECT * FROM storage WHERE disabled = FALSE")
+    .prepare("SELECT * FROM storage WHERE disabled = FALSE ORDER BY sort_index, id")
     .context("获取存储失败")?;
 
   let storages = stmt
-    .query_map([], |row| {
-      Ok(StorageDatabase {
-        id: row.get("id")?,
-        name: row.get("name")?,
-        path: row.get("path")?,
-        local_path: row.get("local_path")?,
-        max_file_size: row.get("max_file_size")?,
-        allow_extensions: row.get("allow_extensions")?,
-        block_extensions: row.get("block_extensions")?,
-        disabled: row.get("disabled")?,
-        sort_index: row.get("sort_index")?,
-        created_at: row.get("created_at")?,
-        updated_at: row.get("updated_at")?,
-      })
-    })?
+    .query_map([], map_storage_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+
+  Ok(storages)
+}
+
+pub fn get_all_storage(conn: &Connection) -> anyhow::Result<Vec<StorageDatabase>> {
+  let mut stmt = conn
+    .prepare("SELECT * FROM storage ORDER BY sort_index, id")
+    .context("获取存储失败")?;
+
+  let storages = stmt
+    .query_map([], map_storage_row)?
     .collect::<Result<Vec<_>, _>>()?;
 
   Ok(storages)
@@ -98,20 +238,120 @@ pub fn get_storage_by_path(conn: &Connection, path: &str) -> anyhow::Result<Stor
   let mut stmt = conn
     .prepare("SELECT * FROM storage WHERE path = ?")
     .context("获取存储失败")?;
-  let storage = stmt.query_one((path,), |row| {
-    Ok(StorageDatabase {
-      id: row.get("id")?,
-      name: row.get("name")?,
-      path: row.get("path")?,
-      local_path: row.get("local_path")?,
-      max_file_size: row.get("max_file_size")?,
-      allow_extensions: row.get("allow_extensions")?,
-      block_extensions: row.get("block_extensions")?,
-      disabled: row.get("disabled")?,
-      sort_index: row.get("sort_index")?,
-      created_at: row.get("created_at")?,
-      updated_at: row.get("updated_at")?,
-    })
-  })?;
+  let storage = stmt.query_one((path,), map_storage_row)?;
   Ok(storage)
 }
+
+pub fn get_storage_by_id(conn: &Connection, id: i64) -> anyhow::Result<StorageDatabase> {
+  let mut stmt = conn
+    .prepare("SELECT * FROM storage WHERE id = ?")
+    .context("获取存储失败")?;
+  let storage = stmt.query_one((id,), map_storage_row)?;
+  Ok(storage)
+}
+
+pub fn update_storage(conn: &Connection, id: i64, storage: UpdateStorageDto) -> anyhow::Result<()> {
+  let updated = conn.execute(
+    "UPDATE storage SET name = ?, local_path = ?, kind = ?, config = ?, max_file_size = ?, allow_extensions = ?, block_extensions = ?, max_versions = ?, version_days = ?, sort_index = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (storage.name, storage.local_path, storage.kind, config_text(&storage.config), storage.max_file_size, storage.allow_extensions, storage.block_extensions, storage.max_versions, storage.version_days, storage.sort_index, id),
+  )?;
+  if updated == 0 {
+    return Err(anyhow::anyhow!("存储不存在"));
+  }
+  Ok(())
+}
+
+pub fn set_storage_disabled(conn: &Connection, id: i64, disabled: bool) -> anyhow::Result<()> {
+  let updated = conn.execute(
+    "UPDATE storage SET disabled = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (disabled, id),
+  )?;
+  if updated == 0 {
+    return Err(anyhow::anyhow!("存储不存在"));
+  }
+  Ok(())
+}
+
+/// 只删除存储记录，不会删除磁盘上的文件
+pub fn delete_storage(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  let deleted = conn.execute("DELETE FROM storage WHERE id = ?", (id,))?;
+  if deleted == 0 {
+    return Err(anyhow::anyhow!("存储不存在"));
+  }
+  super::permission::delete_permissions_by_storage(conn, id)?;
+  super::share::delete_shares_by_storage(conn, id)?;
+  super::trash::delete_trash_by_storage(conn, id)?;
+  super::search::delete_by_storage(conn, id)?;
+  super::version::delete_versions_by_storage(conn, id)?;
+  Ok(())
+}
+
+/// 没有配置时保存为空对象
+fn config_text(config: &serde_json::Value) -> String {
+  match config {
+    serde_json::Value::Null => "{}".to_string(),
+    config => config.to_string(),
+  }
+}
+
+fn map_storage_row(row: &Row) -> rusqlite::Result<StorageDatabase> {
+  Ok(StorageDatabase {
+    id: row.get("id")?,
+    name: row.get("name")?,
+    path: row.get("path")?,
+    local_path: row.get("local_path")?,
+    kind: row.get("kind")?,
+    config: serde_json::from_str(&row.get::<_, String>("config")?).unwrap_or_default(),
+    max_file_size: row.get("max_file_size")?,
+    allow_extensions: row.get("allow_extensions")?,
+    block_extensions: row.get("block_extensions")?,
+    max_versions: row.get("max_versions")?,
+    version_days: row.get("version_days")?,
+    disabled: row.get("disabled")?,
+    sort_index: row.get("sort_index")?,
+    created_at: row.get("created_at")?,
+    updated_at: row.get("updated_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_migrate_kind_column() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn
+      .execute(
+        "CREATE TABLE storage (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          name TEXT NOT NULL,
+          path TEXT NOT NULL UNIQUE,
+          local_path TEXT NOT NULL,
+          max_file_size INTEGER DEFAULT 0,
+          allow_extensions TEXT DEFAULT '',
+          block_extensions TEXT DEFAULT '',
+          disabled BOOLEAN NOT NULL DEFAULT FALSE,
+          sort_index INTEGER DEFAULT 0,
+          created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+          updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        (),
+      )
+      .unwrap();
+    conn
+      .execute(
+        "INSERT INTO storage (name, path, local_path) VALUES ('a', 'a', '/tmp/a')",
+        (),
+      )
+      .unwrap();
+
+    create_storage_database(&conn).unwrap();
+
+    let storage = get_storage_by_path(&conn, "a").unwrap();
+    assert_eq!(storage.kind, StorageKind::Local);
+    assert_eq!(storage.config, serde_json::json!({}));
+    assert_eq!(storage.max_versions, 10);
+    assert_eq!(storage.version_days, 30);
+  }
+}
@@ -0,0 +1,128 @@
+use rusqlite::{Connection, Row};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashItem {
+  pub id: i64,
+  pub storage_id: i64,
+  /// 删除前在存储内的相对路径
+  pub path: String,
+  pub is_dir: bool,
+  pub size: Option<u64>,
+  pub deleted_by: i64,
+  pub deleted_by_name: Option<String>,
+  pub deleted_at: String,
+}
+
+pub fn create_trash_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS trash (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      storage_id INTEGER NOT NULL,
+      path TEXT NOT NULL,
+      is_dir BOOLEAN NOT NULL DEFAULT FALSE,
+      size INTEGER,
+      deleted_by INTEGER NOT NULL,
+      deleted_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_trash_item(
+  conn: &Connection,
+  storage_id: i64,
+  path: &str,
+  is_dir: bool,
+  size: Option<u64>,
+  user_id: i64,
+) -> anyhow::Result<i64> {
+  conn.execute(
+    "INSERT INTO trash (storage_id, path, is_dir, size, deleted_by) VALUES (?, ?, ?, ?, ?)",
+    (storage_id, path, is_dir, size, user_id),
+  )?;
+  Ok(conn.last_insert_rowid())
+}
+
+pub fn get_trash_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<Vec<TrashItem>> {
+  let mut stmt = conn.prepare(
+    "SELECT t.*, u.name AS deleted_by_name FROM trash t
+      LEFT JOIN user u ON u.id = t.deleted_by
+      WHERE t.storage_id = ? ORDER BY t.id DESC",
+  )?;
+  let items = stmt
+    .query_map((storage_id,), map_trash_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(items)
+}
+
+pub fn get_trash_item(conn: &Connection, storage_id: i64, id: i64) -> anyhow::Result<TrashItem> {
+  let item = conn.query_row(
+    "SELECT t.*, u.name AS deleted_by_name FROM trash t
+      LEFT JOIN user u ON u.id = t.deleted_by
+      WHERE t.storage_id = ? AND t.id = ?",
+    (storage_id, id),
+    map_trash_row,
+  )?;
+  Ok(item)
+}
+
+/// 删除时间早于 `days` 天前的条目
+pub fn get_expired_trash(conn: &Connection, days: i64) -> anyhow::Result<Vec<TrashItem>> {
+  let mut stmt = conn.prepare(
+    "SELECT t.*, NULL AS deleted_by_name FROM trash t
+      WHERE t.deleted_at < datetime('now', ?)",
+  )?;
+  let items = stmt
+    .query_map((format!("-{} days", days),), map_trash_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(items)
+}
+
+pub fn delete_trash_item(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM trash WHERE id = ?", (id,))?;
+  Ok(())
+}
+
+pub fn delete_trash_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM trash WHERE storage_id = ?", (storage_id,))?;
+  Ok(())
+}
+
+fn map_trash_row(row: &Row) -> rusqlite::Result<TrashItem> {
+  Ok(TrashItem {
+    id: row.get("id")?,
+    storage_id: row.get("storage_id")?,
+    path: row.get("path")?,
+    is_dir: row.get("is_dir")?,
+    size: row.get("size")?,
+    deleted_by: row.get("deleted_by")?,
+    deleted_by_name: row.get("deleted_by_name")?,
+    deleted_at: row.get("deleted_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_get_expired_trash() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_trash_database(&conn).unwrap();
+    let old = create_trash_item(&conn, 1, "old.txt", false, Some(1), 1).unwrap();
+    create_trash_item(&conn, 1, "new.txt", false, Some(1), 1).unwrap();
+    conn
+      .execute(
+        "UPDATE trash SET deleted_at = datetime('now', '-31 days') WHERE id = ?",
+        (old,),
+      )
+      .unwrap();
+
+    let expired = get_expired_trash(&conn, 30).unwrap();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].path, "old.txt");
+  }
+}
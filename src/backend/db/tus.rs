@@ -0,0 +1,98 @@
+use rand::{Rng, distributions::Alphanumeric};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+/// tus 协议的上传，数据追加写入存储根目录的 `.storkitty/tus/{id}`，
+/// 当前偏移量即该文件的长度
+pub struct TusUpload {
+  pub id: String,
+  pub storage_id: i64,
+  pub user_id: i64,
+  /// 目标文件相对于存储根目录的路径
+  pub path: String,
+  pub length: u64,
+  /// 客户端创建时提交的 Upload-Metadata，原样返回
+  pub metadata: String,
+}
+
+pub struct CreateTusUpload<'a> {
+  pub storage_id: i64,
+  pub user_id: i64,
+  pub path: &'a str,
+  pub length: u64,
+  pub metadata: &'a str,
+}
+
+pub fn create_tus_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS tus_upload (
+      id TEXT PRIMARY KEY,
+      storage_id INTEGER NOT NULL,
+      user_id INTEGER NOT NULL,
+      path TEXT NOT NULL,
+      length INTEGER NOT NULL,
+      metadata TEXT NOT NULL DEFAULT '',
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+      updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_tus_upload(conn: &Connection, upload: CreateTusUpload) -> anyhow::Result<TusUpload> {
+  let id: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(32)
+    .map(char::from)
+    .collect();
+  conn.execute(
+    "INSERT INTO tus_upload (id, storage_id, user_id, path, length, metadata) VALUES (?, ?, ?, ?, ?, ?)",
+    (&id, upload.storage_id, upload.user_id, upload.path, upload.length, upload.metadata),
+  )?;
+  get_tus_upload(conn, &id)?.ok_or_else(|| anyhow::anyhow!("创建上传失败"))
+}
+
+pub fn get_tus_upload(conn: &Connection, id: &str) -> anyhow::Result<Option<TusUpload>> {
+  let upload = conn
+    .query_row(
+      "SELECT * FROM tus_upload WHERE id = ?",
+      (id,),
+      map_tus_upload_row,
+    )
+    .optional()?;
+  Ok(upload)
+}
+
+/// 上传已被删除时返回 false
+pub fn touch_tus_upload(conn: &Connection, id: &str) -> anyhow::Result<bool> {
+  let updated = conn.execute(
+    "UPDATE tus_upload SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (id,),
+  )?;
+  Ok(updated > 0)
+}
+
+/// 超过 `hours` 小时没有收到数据的上传
+pub fn get_expired_tus_uploads(conn: &Connection, hours: i64) -> anyhow::Result<Vec<TusUpload>> {
+  let mut stmt = conn.prepare("SELECT * FROM tus_upload WHERE updated_at < datetime('now', ?)")?;
+  let uploads = stmt
+    .query_map((format!("-{} hours", hours),), map_tus_upload_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(uploads)
+}
+
+pub fn delete_tus_upload(conn: &Connection, id: &str) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM tus_upload WHERE id = ?", (id,))?;
+  Ok(())
+}
+
+fn map_tus_upload_row(row: &Row) -> rusqlite::Result<TusUpload> {
+  Ok(TusUpload {
+    id: row.get("id")?,
+    storage_id: row.get("storage_id")?,
+    user_id: row.get("user_id")?,
+    path: row.get("path")?,
+    length: row.get("length")?,
+    metadata: row.get("metadata")?,
+  })
+}
@@ -0,0 +1,217 @@
+use rand::{Rng, distributions::Alphanumeric};
+use rusqlite::{Connection, OptionalExtension, Row};
+
+use crate::backend::utils::upload::OnConflict;
+
+/// 分片上传会话，分片保存在存储根目录的 `.storkitty/chunks/{id}` 下
+pub struct UploadSession {
+  pub id: String,
+  pub storage_id: i64,
+  pub user_id: i64,
+  /// 目标文件相对于存储根目录的路径
+  pub path: String,
+  pub size: u64,
+  pub chunk_size: u64,
+  /// 客户端声明的整个文件的 SHA-256
+  pub hash: Option<String>,
+  pub conflict: OnConflict,
+  pub created_at: String,
+  pub updated_at: String,
+}
+
+impl UploadSession {
+  pub fn total_chunks(&self) -> u64 {
+    self.size.div_ceil(self.chunk_size)
+  }
+
+  /// 第 `index` 个分片的长度，最后一个分片可能不满
+  pub fn chunk_len(&self, index: u64) -> u64 {
+    let start = index * self.chunk_size;
+    self.chunk_size.min(self.size.saturating_sub(start))
+  }
+}
+
+pub struct CreateUploadSession<'a> {
+  pub storage_id: i64,
+  pub user_id: i64,
+  pub path: &'a str,
+  pub size: u64,
+  pub chunk_size: u64,
+  pub hash: Option<&'a str>,
+  pub conflict: OnConflict,
+}
+
+pub fn create_upload_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS upload_session (
+      id TEXT PRIMARY KEY,
+      storage_id INTEGER NOT NULL,
+      user_id INTEGER NOT NULL,
+      path TEXT NOT NULL,
+      size INTEGER NOT NULL,
+      chunk_size INTEGER NOT NULL,
+      hash TEXT,
+      conflict TEXT NOT NULL DEFAULT 'fail',
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+      updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  migrate_conflict_column(conn)?;
+  Ok(())
+}
+
+/// 早期的上传会话没有 conflict 字段
+fn migrate_conflict_column(conn: &Connection) -> anyhow::Result<()> {
+  let has_conflict = conn
+    .prepare("SELECT 1 FROM pragma_table_info('upload_session') WHERE name = 'conflict'")?
+    .exists(())?;
+  if !has_conflict {
+    conn.execute(
+      "ALTER TABLE upload_session ADD COLUMN conflict TEXT NOT NULL DEFAULT 'fail'",
+      (),
+    )?;
+  }
+  Ok(())
+}
+
+pub fn create_upload_session(
+  conn: &Connection,
+  session: CreateUploadSession,
+) -> anyhow::Result<UploadSession> {
+  let id: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(32)
+    .map(char::from)
+    .collect();
+  conn.execute(
+    "INSERT INTO upload_session (id, storage_id, user_id, path, size, chunk_size, hash, conflict) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    (&id, session.storage_id, session.user_id, session.path, session.size, session.chunk_size, session.hash, session.conflict.as_str()),
+  )?;
+  get_upload_session(conn, &id)?.ok_or_else(|| anyhow::anyhow!("创建上传会话失败"))
+}
+
+pub fn get_upload_session(conn: &Connection, id: &str) -> anyhow::Result<Option<UploadSession>> {
+  let session = conn
+    .query_row(
+      "SELECT * FROM upload_session WHERE id = ?",
+      (id,),
+      map_upload_session_row,
+    )
+    .optional()?;
+  Ok(session)
+}
+
+/// 查找同一用户上传同一文件的未完成会话，用于客户端丢失会话 ID 后续传
+pub fn find_upload_session(
+  conn: &Connection,
+  session: &CreateUploadSession,
+) -> anyhow::Result<Option<UploadSession>> {
+  let Some(hash) = session.hash else {
+    return Ok(None);
+  };
+  let found = conn
+    .query_row(
+      "SELECT * FROM upload_session
+        WHERE storage_id = ? AND user_id = ? AND path = ? AND size = ? AND chunk_size = ? AND hash = ? AND conflict = ?
+        ORDER BY created_at DESC LIMIT 1",
+      (session.storage_id, session.user_id, session.path, session.size, session.chunk_size, hash, session.conflict.as_str()),
+      map_upload_session_row,
+    )
+    .optional()?;
+  Ok(found)
+}
+
+/// 会话已被删除时返回 false
+pub fn touch_upload_session(conn: &Connection, id: &str) -> anyhow::Result<bool> {
+  let updated = conn.execute(
+    "UPDATE upload_session SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (id,),
+  )?;
+  Ok(updated > 0)
+}
+
+/// 超过 `hours` 小时没有收到分片的会话
+pub fn get_expired_upload_sessions(
+  conn: &Connection,
+  hours: i64,
+) -> anyhow::Result<Vec<UploadSession>> {
+  let mut stmt =
+    conn.prepare("SELECT * FROM upload_session WHERE updated_at < datetime('now', ?)")?;
+  let sessions = stmt
+    .query_map((format!("-{} hours", hours),), map_upload_session_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(sessions)
+}
+
+pub fn delete_upload_session(conn: &Connection, id: &str) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM upload_session WHERE id = ?", (id,))?;
+  Ok(())
+}
+
+fn map_upload_session_row(row: &Row) -> rusqlite::Result<UploadSession> {
+  Ok(UploadSession {
+    id: row.get("id")?,
+    storage_id: row.get("storage_id")?,
+    user_id: row.get("user_id")?,
+    path: row.get("path")?,
+    size: row.get("size")?,
+    chunk_size: row.get("chunk_size")?,
+    hash: row.get("hash")?,
+    conflict: row
+      .get::<_, String>("conflict")?
+      .parse()
+      .unwrap_or_default(),
+    created_at: row.get("created_at")?,
+    updated_at: row.get("updated_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_upload_session() {
+    let conn = Connection::open_in_memory().unwrap();
+    create_upload_database(&conn).unwrap();
+
+    let dto = || CreateUploadSession {
+      storage_id: 1,
+      user_id: 1,
+      path: "folder/a.bin",
+      size: 25,
+      chunk_size: 10,
+      hash: Some("abc"),
+      conflict: OnConflict::Rename,
+    };
+    let session = create_upload_session(&conn, dto()).unwrap();
+    assert_eq!(session.id.len(), 32);
+    assert_eq!(session.total_chunks(), 3);
+    assert_eq!(session.chunk_len(1), 10);
+    assert_eq!(session.chunk_len(2), 5);
+    assert_eq!(session.conflict, OnConflict::Rename);
+
+    let found = find_upload_session(&conn, &dto()).unwrap().unwrap();
+    assert_eq!(found.id, session.id);
+    let other_user = CreateUploadSession {
+      user_id: 2,
+      ..dto()
+    };
+    assert!(find_upload_session(&conn, &other_user).unwrap().is_none());
+
+    assert!(get_expired_upload_sessions(&conn, 24).unwrap().is_empty());
+    conn
+      .execute(
+        "UPDATE upload_session SET updated_at = datetime('now', '-25 hours') WHERE id = ?",
+        (&session.id,),
+      )
+      .unwrap();
+    let expired = get_expired_upload_sessions(&conn, 24).unwrap();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, session.id);
+
+    delete_upload_session(&conn, &session.id).unwrap();
+    assert!(get_upload_session(&conn, &session.id).unwrap().is_none());
+  }
+}
@@ -1,12 +1,79 @@
-use rusqlite::Connection;
+use std::str::FromStr;
+
+use rusqlite::{
+  Connection, Row,
+  types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+};
 use serde::{Deserialize, Serialize};
 
+/// 用户角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+  /// 可以管理用户和存储
+  Admin,
+  #[default]
+  User,
+  /// 只读用户
+  Guest,
+}
+
+impl Role {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Role::Admin => "admin",
+      Role::User => "user",
+      Role::Guest => "guest",
+    }
+  }
+}
+
+impl FromStr for Role {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "admin" => Ok(Role::Admin),
+      "user" => Ok(Role::User),
+      "guest" => Ok(Role::Guest),
+      _ => Err(anyhow::anyhow!("未知的角色: {}", s)),
+    }
+  }
+}
+
+impl ToSql for Role {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
+  }
+}
+
+impl FromSql for Role {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    value
+      .as_str()?
+      .parse()
+      .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+  }
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateUserDto {
   pub name: String,
   pub username: String,
   pub password: String,
+  #[serde(default)]
+  pub role: Role,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserDto {
+  pub name: String,
+  pub username: String,
+  #[serde(default)]
+  pub avatar: String,
+  pub role: Role,
 }
 
 pub struct User {
@@ -15,6 +82,7 @@ pub struct User {
   pub username: String,
   pub password: String,
   pub avatar: String,
+  pub role: Role,
   pub disabled: bool,
   pub created_at: String,
   pub updated_at: String,
@@ -28,12 +96,33 @@ pub fn create_user_database(conn: &Connection) -> anyhow::Result<()> {
       username TEXT NOT NULL,
       password TEXT NOT NULL,
       avatar TEXT NOT NULL DEFAULT '',
+      role TEXT NOT NULL DEFAULT 'user',
       disabled BOOLEAN NOT NULL DEFAULT FALSE,
       created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
       updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
     )",
     (),
   )?;
+  migrate_role_column(conn)?;
+  Ok(())
+}
+
+/// 旧版本数据库没有 role 字段，补上字段并把最早的用户设为管理员
+fn migrate_role_column(conn: &Connection) -> anyhow::Result<()> {
+  let has_role = conn
+    .prepare("SELECT 1 FROM pragma_table_info('user') WHERE name = 'role'")?
+    .exists(())?;
+  if has_role {
+    return Ok(());
+  }
+  conn.execute(
+    "ALTER TABLE user ADD COLUMN role TEXT NOT NULL DEFAULT 'user'",
+    (),
+  )?;
+  conn.execute(
+    "UPDATE user SET role = 'admin' WHERE id = (SELECT MIN(id) FROM user)",
+    (),
+  )?;
   Ok(())
 }
 
@@ -45,48 +134,151 @@ pub fn is_no_user(conn: &Connection) -> anyhow::Result<bool> {
   Ok(user)
 }
 
-pub fn create_user(conn: &Connection, user: CreateUserDto) -> anyhow::Result<()> {
-  let password_hash = bcrypt::hash(user.password, bcrypt::DEFAULT_COST)?;
+/// `password_hash` 由 utils::auth::hash_password 生成，不要在持有数据库锁时计算
+pub fn create_user(
+  conn: &Connection,
+  user: CreateUserDto,
+  password_hash: &str,
+) -> anyhow::Result<()> {
+  if get_user_by_username(conn, &user.username).is_ok() {
+    return Err(anyhow::anyhow!("用户名已存在"));
+  }
 
   conn.execute(
-    "INSERT INTO user (name, username, password) VALUES (?, ?, ?)",
-    (user.name, user.username, password_hash),
+    "INSERT INTO user (name, username, password, role) VALUES (?, ?, ?, ?)",
+    (user.name, user.username, password_hash, user.role),
   )?;
   Ok(())
 }
 
+pub fn get_all_users(conn: &Connection) -> anyhow::Result<Vec<User>> {
+  let mut stmt = conn.prepare("SELECT * FROM user ORDER BY id")?;
+  let users = stmt
+    .query_map([], map_user_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(users)
+}
+
 pub fn get_user_by_username(conn: &Connection, username: &str) -> anyhow::Result<User> {
   let user = conn.query_row(
     "SELECT * FROM user WHERE username = ?",
     (username,),
-    |row| {
-      Ok(User {
-        id: row.get("id")?,
-        name: row.get("name")?,
-        username: row.get("username")?,
-        password: row.get("password")?,
-        avatar: row.get("avatar")?,
-        disabled: row.get("disabled")?,
-        created_at: row.get("created_at")?,
-        updated_at: row.get("updated_at")?,
-      })
-    },
+    map_user_row,
   )?;
   Ok(user)
 }
 
 pub fn get_user_by_id(conn: &Connection, user_id: i64) -> anyhow::Result<User> {
-  let user = conn.query_row("SELECT * FROM user WHERE id = ?", (user_id,), |row| {
-    Ok(User {
-      id: row.get("id")?,
-      name: row.get("name")?,
-      username: row.get("username")?,
-      password: row.get("password")?,
-      avatar: row.get("avatar")?,
-      disabled: row.get("disabled")?,
-      created_at: row.get("created_at")?,
-      updated_at: row.get("updated_at")?,
-    })
-  })?;
+  let user = conn.query_row("SELECT * FROM user WHERE id = ?", (user_id,), map_user_row)?;
   Ok(user)
 }
+
+pub fn update_user(conn: &Connection, user_id: i64, user: UpdateUserDto) -> anyhow::Result<()> {
+  if let Ok(exists) = get_user_by_username(conn, &user.username)
+    && exists.id != user_id
+  {
+    return Err(anyhow::anyhow!("用户名已存在"));
+  }
+  let updated = conn.execute(
+    "UPDATE user SET name = ?, username = ?, avatar = ?, role = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (user.name, user.username, user.avatar, user.role, user_id),
+  )?;
+  if updated == 0 {
+    return Err(anyhow::anyhow!("用户不存在"));
+  }
+  Ok(())
+}
+
+pub fn set_user_disabled(conn: &Connection, user_id: i64, disabled: bool) -> anyhow::Result<()> {
+  let updated = conn.execute(
+    "UPDATE user SET disabled = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (disabled, user_id),
+  )?;
+  if updated == 0 {
+    return Err(anyhow::anyhow!("用户不存在"));
+  }
+  Ok(())
+}
+
+pub fn update_password(conn: &Connection, user_id: i64, password_hash: &str) -> anyhow::Result<()> {
+  let updated = conn.execute(
+    "UPDATE user SET password = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    (password_hash, user_id),
+  )?;
+  if updated == 0 {
+    return Err(anyhow::anyhow!("用户不存在"));
+  }
+  Ok(())
+}
+
+pub fn delete_user(conn: &Connection, user_id: i64) -> anyhow::Result<()> {
+  let deleted = conn.execute("DELETE FROM user WHERE id = ?", (user_id,))?;
+  if deleted == 0 {
+    return Err(anyhow::anyhow!("用户不存在"));
+  }
+  super::permission::delete_permissions_by_user(conn, user_id)?;
+  super::share::delete_shares_by_user(conn, user_id)?;
+  super::access_key::delete_access_keys_by_user(conn, user_id)?;
+  Ok(())
+}
+
+/// 启用状态的管理员数量，用于防止删除 / 禁用最后一个管理员
+pub fn count_active_admins(conn: &Connection) -> anyhow::Result<i64> {
+  let count = conn.query_row(
+    "SELECT COUNT(*) FROM user WHERE role = 'admin' AND disabled = FALSE",
+    (),
+    |row| row.get(0),
+  )?;
+  Ok(count)
+}
+
+fn map_user_row(row: &Row) -> rusqlite::Result<User> {
+  Ok(User {
+    id: row.get("id")?,
+    name: row.get("name")?,
+    username: row.get("username")?,
+    password: row.get("password")?,
+    avatar: row.get("avatar")?,
+    role: row.get("role")?,
+    disabled: row.get("disabled")?,
+    created_at: row.get("created_at")?,
+    updated_at: row.get("updated_at")?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_migrate_role_column() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn
+      .execute(
+        "CREATE TABLE user (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          name TEXT NOT NULL,
+          username TEXT NOT NULL,
+          password TEXT NOT NULL,
+          avatar TEXT NOT NULL DEFAULT '',
+          disabled BOOLEAN NOT NULL DEFAULT FALSE,
+          created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+          updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        (),
+      )
+      .unwrap();
+    conn
+      .execute(
+        "INSERT INTO user (name, username, password) VALUES ('a', 'a', ''), ('b', 'b', '')",
+        (),
+      )
+      .unwrap();
+
+    create_user_database(&conn).unwrap();
+
+    assert_eq!(get_user_by_username(&conn, "a").unwrap().role, Role::Admin);
+    assert_eq!(get_user_by_username(&conn, "b").unwrap().role, Role::User);
+    assert_eq!(count_active_admins(&conn).unwrap(), 1);
+  }
+}
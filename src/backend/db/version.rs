@@ -0,0 +1,124 @@
+use rusqlite::{Connection, Row};
+use serde::Serialize;
+
+/// 文件被覆盖前的内容，保存在存储根目录的 `.storkitty/versions/{id}`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileVersion {
+  pub id: i64,
+  pub storage_id: i64,
+  /// 文件在存储内的相对路径
+  pub path: String,
+  pub size: u64,
+  /// 这个版本的内容最后修改的时间 (unix 秒)
+  pub modified: Option<i64>,
+  /// 覆盖这个版本的用户
+  pub created_by: i64,
+  pub created_by_name: Option<String>,
+  pub created_at: String,
+}
+
+pub fn create_version_database(conn: &Connection) -> anyhow::Result<()> {
+  conn.execute(
+    "CREATE TABLE IF NOT EXISTS file_version (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      storage_id INTEGER NOT NULL,
+      path TEXT NOT NULL,
+      size INTEGER NOT NULL,
+      modified INTEGER,
+      created_by INTEGER NOT NULL,
+      created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )",
+    (),
+  )?;
+  conn.execute(
+    "CREATE INDEX IF NOT EXISTS file_version_path ON file_version (storage_id, path)",
+    (),
+  )?;
+  Ok(())
+}
+
+pub fn create_version(
+  conn: &Connection,
+  storage_id: i64,
+  path: &str,
+  size: u64,
+  modified: Option<i64>,
+  user_id: i64,
+) -> anyhow::Result<i64> {
+  conn.execute(
+    "INSERT INTO file_version (storage_id, path, size, modified, created_by) VALUES (?, ?, ?, ?, ?)",
+    (storage_id, path, size, modified, user_id),
+  )?;
+  Ok(conn.last_insert_rowid())
+}
+
+/// 文件的所有历史版本，最新的在前
+pub fn get_versions(
+  conn: &Connection,
+  storage_id: i64,
+  path: &str,
+) -> anyhow::Result<Vec<FileVersion>> {
+  let mut stmt = conn.prepare(
+    "SELECT v.*, u.name AS created_by_name FROM file_version v
+      LEFT JOIN user u ON u.id = v.created_by
+      WHERE v.storage_id = ? AND v.path = ? ORDER BY v.id DESC",
+  )?;
+  let versions = stmt
+    .query_map((storage_id, path), map_version_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(versions)
+}
+
+pub fn get_version(conn: &Connection, storage_id: i64, id: i64) -> anyhow::Result<FileVersion> {
+  let version = conn.query_row(
+    "SELECT v.*, u.name AS created_by_name FROM file_version v
+      LEFT JOIN user u ON u.id = v.created_by
+      WHERE v.storage_id = ? AND v.id = ?",
+    (storage_id, id),
+    map_version_row,
+  )?;
+  Ok(version)
+}
+
+/// 存储中创建时间早于 `days` 天前的版本
+pub fn get_expired_versions(
+  conn: &Connection,
+  storage_id: i64,
+  days: u32,
+) -> anyhow::Result<Vec<FileVersion>> {
+  let mut stmt = conn.prepare(
+    "SELECT v.*, NULL AS created_by_name FROM file_version v
+      WHERE v.storage_id = ? AND v.created_at < datetime('now', ?)",
+  )?;
+  let versions = stmt
+    .query_map((storage_id, format!("-{} days", days)), map_version_row)?
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(versions)
+}
+
+pub fn delete_version(conn: &Connection, id: i64) -> anyhow::Result<()> {
+  conn.execute("DELETE FROM file_version WHERE id = ?", (id,))?;
+  Ok(())
+}
+
+pub fn delete_versions_by_storage(conn: &Connection, storage_id: i64) -> anyhow::Result<()> {
+  conn.execute(
+    "DELETE FROM file_version WHERE storage_id = ?",
+    (storage_id,),
+  )?;
+  Ok(())
+}
+
+fn map_version_row(row: &Row) -> rusqlite::Result<FileVersion> {
+  Ok(FileVersion {
+    id: row.get("id")?,
+    storage_id: row.get("storage_id")?,
+    path: row.get("path")?,
+    size: row.get("size")?,
+    modified: row.get("modified")?,
+    created_by: row.get("created_by")?,
+    created_by_name: row.get("created_by_name")?,
+    created_at: row.get("created_at")?,
+  })
+}
@@ -1,15 +1,39 @@
 use axum::{
+  Json,
   http::StatusCode,
   response::{IntoResponse, Response},
 };
+use serde::Serialize;
 
 // Make our own error that wraps anyhow::Error
-pub struct AppError(anyhow::Error);
+pub struct AppError {
+  error: anyhow::Error,
+  status: StatusCode,
+  code: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorBody {
+  code: &'static str,
+  message: String,
+}
 
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
   fn into_response(self) -> Response {
-    (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", self.0)).into_response()
+    match self.code {
+      // 带错误码的错误以 JSON 返回，方便前端区分处理
+      Some(code) => (
+        self.status,
+        Json(ErrorBody {
+          code,
+          message: format!("{}", self.error),
+        }),
+      )
+        .into_response(),
+      None => (self.status, format!("{}", self.error)).into_response(),
+    }
   }
 }
 
@@ -20,12 +44,44 @@ where
   E: Into<anyhow::Error>,
 {
   fn from(err: E) -> Self {
-    Self(err.into())
+    Self {
+      error: err.into(),
+      status: StatusCode::INTERNAL_SERVER_ERROR,
+      code: None,
+    }
   }
 }
 
 impl AppError {
   pub fn new(msg: &str) -> Self {
-    Self(anyhow::anyhow!("{}", msg))
+    Self::from(anyhow::anyhow!("{}", msg))
+  }
+
+  pub fn with_status(status: StatusCode, msg: &str) -> Self {
+    Self {
+      error: anyhow::anyhow!("{}", msg),
+      status,
+      code: None,
+    }
+  }
+
+  pub fn with_code(status: StatusCode, code: &'static str, msg: &str) -> Self {
+    Self {
+      error: anyhow::anyhow!("{}", msg),
+      status,
+      code: Some(code),
+    }
+  }
+
+  pub fn message(&self) -> String {
+    format!("{}", self.error)
+  }
+
+  pub fn code(&self) -> Option<&'static str> {
+    self.code
+  }
+
+  pub fn status(&self) -> StatusCode {
+    self.status
   }
 }
@@ -1,10 +1,168 @@
-use axum::http::StatusCode;
-use axum::{extract::Request, middleware::Next, response::Response};
+use axum::{
+  body::Body,
+  extract::{FromRequestParts, Path, Query, Request, State},
+  http::{HeaderMap, Method, StatusCode, header::WWW_AUTHENTICATE, request::Parts},
+  middleware::Next,
+  response::Response,
+};
 
-pub async fn auth_middleware(mut req: Request, next: Next) -> Result<Response, StatusCode> {
-  let uer_id = crate::backend::utils::auth::verify_token(req.headers())
-    .map_err(|_| StatusCode::UNAUTHORIZED)?;
+use serde::Deserialize;
 
-  req.extensions_mut().insert(uer_id);
+use crate::backend::{
+  db::{self, DBConnection, user::Role},
+  utils::auth::{self, DownloadSignature},
+};
+
+/// 当前登录的用户，由 auth_middleware 写入请求扩展
+#[derive(Clone)]
+pub struct AuthUser {
+  pub id: i64,
+  pub role: Role,
+}
+
+impl AuthUser {
+  pub fn is_admin(&self) -> bool {
+    self.role == Role::Admin
+  }
+}
+
+pub async fn auth_middleware(
+  State(conn): State<DBConnection>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, StatusCode> {
+  let user_id = auth::verify_token(req.headers()).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+  let user = load_auth_user(&conn, user_id).await?;
+  req.extensions_mut().insert(user);
+  Ok(next.run(req).await)
+}
+
+/// 下载鉴权：支持 Bearer token，或者由 /api/file/sign 生成的签名参数
+pub async fn download_auth_middleware(
+  State(conn): State<DBConnection>,
+  Path(path): Path<String>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, StatusCode> {
+  let user_id = match auth::verify_token(req.headers()) {
+    Ok(user_id) => user_id,
+    Err(_) => {
+      let Query(signature) = Query::<DownloadSignature>::try_from_uri(req.uri())
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+      auth::verify_download(&path, &signature).map_err(|_| StatusCode::UNAUTHORIZED)?
+    }
+  };
+
+  let user = load_auth_user(&conn, user_id).await?;
+  req.extensions_mut().insert(user);
+  Ok(next.run(req).await)
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+  token: String,
+}
+
+/// 事件订阅、缩略图和打包下载的鉴权：浏览器的 EventSource 和 `<img>` 不能设置请求头，
+/// 允许通过 `?token=` 传递由 /api/token 生成的短期 token，登录 token 只能放在请求头中
+pub async fn token_auth_middleware(
+  State(conn): State<DBConnection>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, StatusCode> {
+  let user_id = match auth::verify_token(req.headers()) {
+    Ok(user_id) => user_id,
+    Err(_) => {
+      if req.method() != Method::GET {
+        return Err(StatusCode::UNAUTHORIZED);
+      }
+      let Query(query) =
+        Query::<TokenQuery>::try_from_uri(req.uri()).map_err(|_| StatusCode::UNAUTHORIZED)?;
+      auth::verify_query_token(&query.token).map_err(|_| StatusCode::UNAUTHORIZED)?
+    }
+  };
+
+  let user = load_auth_user(&conn, user_id).await?;
+  req.extensions_mut().insert(user);
+  Ok(next.run(req).await)
+}
+
+/// WebDAV 等客户端使用 HTTP Basic 认证，账号与网页登录相同
+pub async fn basic_auth_middleware(
+  State(conn): State<DBConnection>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  let Some(user) = basic_auth_user(&conn, req.headers()).await else {
+    return Response::builder()
+      .status(StatusCode::UNAUTHORIZED)
+      .header(
+        WWW_AUTHENTICATE,
+        "Basic realm=\"Storkitty\", charset=\"UTF-8\"",
+      )
+      .body(Body::empty())
+      .unwrap_or_default();
+  };
+  req.extensions_mut().insert(user);
+  next.run(req).await
+}
+
+async fn basic_auth_user(conn: &DBConnection, headers: &HeaderMap) -> Option<AuthUser> {
+  let (username, password) = auth::parse_basic_auth(headers)?;
+  let user = {
+    let conn = conn.lock().await;
+    db::user::get_user_by_username(&conn, &username).ok()?
+  };
+  if user.disabled {
+    return None;
+  }
+  let hash = user.password.clone();
+  let valid = tokio::task::spawn_blocking(move || auth::verify_password_cached(&password, &hash))
+    .await
+    .unwrap_or(false);
+  valid.then_some(AuthUser {
+    id: user.id,
+    role: user.role,
+  })
+}
+
+/// 读取用户并确认没有被禁用，后台任务执行前也用它确认任务所有者
+pub async fn load_auth_user(conn: &DBConnection, user_id: i64) -> Result<AuthUser, StatusCode> {
+  let conn = conn.lock().await;
+  let user = db::user::get_user_by_id(&conn, user_id).map_err(|_| StatusCode::UNAUTHORIZED)?;
+  if user.disabled {
+    return Err(StatusCode::UNAUTHORIZED);
+  }
+  Ok(AuthUser {
+    id: user.id,
+    role: user.role,
+  })
+}
+
+/// 需要放在 auth_middleware 之后
+pub async fn admin_middleware(req: Request, next: Next) -> Result<Response, StatusCode> {
+  let is_admin = req
+    .extensions()
+    .get::<AuthUser>()
+    .is_some_and(|user| user.is_admin());
+  if !is_admin {
+    return Err(StatusCode::FORBIDDEN);
+  }
   Ok(next.run(req).await)
 }
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+  S: Send + Sync,
+{
+  type Rejection = StatusCode;
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    parts
+      .extensions
+      .get::<AuthUser>()
+      .cloned()
+      .ok_or(StatusCode::UNAUTHORIZED)
+  }
+}
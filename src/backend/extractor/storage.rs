@@ -1,17 +1,27 @@
-use std::path::PathBuf;
+use std::{
+  path::{Path as FsPath, PathBuf},
+  sync::Arc,
+};
 
-use anyhow::Context;
 use axum::{
   body::Body,
   extract::{FromRef, FromRequestParts, Path},
-  http::{StatusCode, request::Parts},
+  http::{Method, StatusCode, request::Parts},
   response::Response,
 };
 
+use rusqlite::Connection;
+
 use crate::backend::{
-  db::{self, DBConnection},
+  db::{self, DBConnection, permission::AccessLevel, storage::StorageKind},
   error::AppError,
-  utils::{self, path::split_path},
+  extractor::auth::AuthUser,
+  utils::{
+    self,
+    backend::{self as storage_backend, StorageBackend},
+    path::split_path,
+    policy::StoragePolicy,
+  },
 };
 
 // -------------------------------------------
@@ -42,8 +52,13 @@ impl SafePath {
 // -------------------------------------------
 
 struct StorageResolved {
+  pub id: i64,
   pub root: PathBuf,
   pub full: SafePath,
+  pub policy: StoragePolicy,
+  pub level: AccessLevel,
+  pub kind: StorageKind,
+  pub backend: Arc<dyn StorageBackend>,
 }
 
 async fn resolve_storage<S>(parts: &mut Parts, state: &S) -> Result<StorageResolved, Response>
@@ -61,65 +76,79 @@ where
         .unwrap()
     })?;
 
-  if !utils::validate::validate_path(&raw_path) {
-    return Err(
-      Response::builder()
-        .status(StatusCode::BAD_REQUEST)
-        .body(Body::from("存储路径不合法"))
-        .unwrap(),
-    );
-  }
+  let user = parts.extensions.get::<AuthUser>().cloned().ok_or_else(|| {
+    Response::builder()
+      .status(StatusCode::UNAUTHORIZED)
+      .body(Body::empty())
+      .unwrap()
+  })?;
 
-  // 2. 分割 path: storage_path + relative_path
+  // 读请求需要 read，其余需要 write
+  let required = required_level(&parts.method);
   let conn = DBConnection::from_ref(state);
   let conn = conn.lock().await;
 
-  let (storage_path, path) = split_path(&raw_path);
+  resolve_raw_path(&conn, &user, &raw_path, required).map_err(|(status, msg)| {
+    Response::builder()
+      .status(status)
+      .body(Body::from(msg))
+      .unwrap()
+  })
+}
 
-  let storage = db::storage::get_storage_by_path(&conn, &storage_path)
-    .context("存储不存在")
-    .map_err(|_| {
-      Response::builder()
-        .status(StatusCode::NOT_FOUND)
-        .body(Body::from("存储不存在"))
-        .unwrap()
-    })?;
+fn resolve_raw_path(
+  conn: &Connection,
+  user: &AuthUser,
+  raw_path: &str,
+  required: AccessLevel,
+) -> Result<StorageResolved, (StatusCode, &'static str)> {
+  if !utils::validate::validate_path(raw_path) {
+    return Err((StatusCode::BAD_REQUEST, "存储路径不合法"));
+  }
+
+  // 1. 分割 path: storage_path + relative_path
+  let (storage_path, path) = split_path(raw_path);
+
+  let storage = db::storage::get_storage_by_path(conn, &storage_path)
+    .map_err(|_| (StatusCode::NOT_FOUND, "存储不存在"))?;
 
   if storage.disabled {
-    return Err(
-      Response::builder()
-        .status(StatusCode::FORBIDDEN)
-        .body(Body::from("存储已禁用"))
-        .unwrap(),
-    );
+    return Err((StatusCode::FORBIDDEN, "存储已禁用"));
+  }
+
+  // 2. 校验当前用户对存储的权限
+  let level = db::permission::get_access_level(conn, user.id, user.role, storage.id)
+    .ok()
+    .flatten()
+    .ok_or((StatusCode::FORBIDDEN, "无权访问该存储"))?;
+  if level < required {
+    return Err((StatusCode::FORBIDDEN, "没有该存储的写入权限"));
   }
 
   // 3. 拼接真实路径
   let root_path = PathBuf::from(&storage.local_path);
   let full_path = SafePath::new(root_path.clone().join(path.unwrap_or_default()));
 
+  let backend = storage_backend::open(&storage).map_err(|err| {
+    log::error!("Failed to open storage backend {}: {err:#}", storage.id);
+    (StatusCode::INTERNAL_SERVER_ERROR, "存储配置不合法")
+  })?;
+
   Ok(StorageResolved {
+    id: storage.id,
     root: root_path,
     full: full_path,
+    policy: StoragePolicy::from_storage(&storage),
+    level,
+    kind: storage.kind,
+    backend,
   })
 }
 
-// -------------------------------------------
-// StoragePath Extractor
-// -------------------------------------------
-
-pub struct StoragePath(pub SafePath);
-
-impl<S> FromRequestParts<S> for StoragePath
-where
-  DBConnection: FromRef<S>,
-  S: Send + Sync,
-{
-  type Rejection = Response;
-
-  async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-    let resolved = resolve_storage(parts, state).await?;
-    Ok(Self(resolved.full))
+fn required_level(method: &Method) -> AccessLevel {
+  match *method {
+    Method::GET | Method::HEAD | Method::OPTIONS => AccessLevel::Read,
+    _ => AccessLevel::Write,
   }
 }
 
@@ -128,8 +157,15 @@ where
 // -------------------------------------------
 
 pub struct Storage {
+  pub id: i64,
+  /// 本地路径；远程存储只用于计算相对路径，见 `Storage::relative`
   pub path: SafePath,
   pub root: PathBuf,
+  pub policy: StoragePolicy,
+  /// 当前用户对该存储的权限
+  pub level: AccessLevel,
+  pub kind: StorageKind,
+  pub backend: Arc<dyn StorageBackend>,
 }
 
 impl<S> FromRequestParts<S> for Storage
@@ -141,10 +177,116 @@ where
 
   async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
     let resolved = resolve_storage(parts, state).await?;
+    Ok(Self::from(resolved))
+  }
+}
+
+impl Storage {
+  /// 解析请求体里带存储前缀的路径，例如 `data/folder/a.txt`
+  pub fn resolve(
+    conn: &Connection,
+    user: &AuthUser,
+    raw_path: &str,
+    required: AccessLevel,
+  ) -> Result<Self, AppError> {
+    resolve_raw_path(conn, user, raw_path, required)
+      .map(Self::from)
+      .map_err(|(status, msg)| AppError::with_status(status, msg))
+  }
+
+  /// 按存储 id 和存储内的相对路径解析，用于上传会话等只保存了存储 id 的场景
+  pub fn resolve_by_id(
+    conn: &Connection,
+    user: &AuthUser,
+    storage_id: i64,
+    path: &str,
+    required: AccessLevel,
+  ) -> Result<Self, AppError> {
+    let storage = db::storage::get_storage_by_id(conn, storage_id)
+      .map_err(|_| AppError::with_status(StatusCode::NOT_FOUND, "存储不存在"))?;
+    Self::resolve(conn, user, &format!("{}/{}", storage.path, path), required)
+  }
+
+  /// WebDAV、S3 接口、搜索等功能直接操作本地文件，远程存储不支持
+  pub fn require_local(&self) -> Result<(), AppError> {
+    if self.kind != StorageKind::Local {
+      return Err(AppError::with_status(
+        StatusCode::BAD_REQUEST,
+        "该功能仅支持本地存储",
+      ));
+    }
+    Ok(())
+  }
+}
+
+/// 存储根目录下的路径转换为以 `/` 分隔的相对路径
+pub fn relative_path(root: &FsPath, path: &FsPath) -> Result<String, AppError> {
+  let relative = path
+    .strip_prefix(root)
+    .map_err(|_| AppError::new("路径不合法"))?;
+  let parts = relative
+    .components()
+    .map(|component| {
+      component
+        .as_os_str()
+        .to_str()
+        .ok_or_else(|| AppError::new("路径不合法"))
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(parts.join("/"))
+}
 
-    Ok(Self {
+impl From<StorageResolved> for Storage {
+  fn from(resolved: StorageResolved) -> Self {
+    Self {
+      id: resolved.id,
       path: resolved.full,
       root: resolved.root,
-    })
+      policy: resolved.policy,
+      level: resolved.level,
+      kind: resolved.kind,
+      backend: resolved.backend,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backend::db::{storage::CreateStorageDto, user::Role};
+
+  #[test]
+  fn test_resolve_rejects_system_path() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::storage::create_storage_database(&conn).unwrap();
+    db::permission::create_permission_database(&conn).unwrap();
+    db::storage::create_storage(
+      &conn,
+      CreateStorageDto {
+        name: "data".to_string(),
+        path: "data".to_string(),
+        local_path: std::env::temp_dir().to_string_lossy().to_string(),
+        kind: StorageKind::Local,
+        config: Default::default(),
+        max_file_size: 0,
+        allow_extensions: String::new(),
+        block_extensions: String::new(),
+        max_versions: 10,
+        version_days: 30,
+        sort_index: 0,
+      },
+    )
+    .unwrap();
+    let user = AuthUser {
+      id: 1,
+      role: Role::Admin,
+    };
+
+    assert!(Storage::resolve(&conn, &user, "data/a.txt", AccessLevel::Read).is_ok());
+    let Err(err) = Storage::resolve(&conn, &user, "data/.storkitty/trash/1", AccessLevel::Read)
+    else {
+      panic!("system path should be rejected");
+    };
+    assert_eq!(err.message(), "存储路径不合法");
   }
 }
@@ -0,0 +1,631 @@
+use std::{
+  collections::BTreeSet,
+  fmt::Display,
+  fs::{self, File},
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::StatusCode;
+use chrono::{Local, NaiveDate, TimeZone};
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use sevenz_rust::{Password, SevenZReader};
+
+use crate::backend::{
+  error::AppError,
+  utils::{
+    file::is_system_file,
+    job::JOB_CANCELLED,
+    policy::StoragePolicy,
+    transfer::{ConflictPolicy, TransferProgress, unique_path},
+    validate::validate_path,
+  },
+};
+
+pub const UNSAFE_PATH: &str = "UNSAFE_PATH";
+pub const ARCHIVE_TOO_LARGE: &str = "ARCHIVE_TOO_LARGE";
+
+/// 解压时每次读写的块大小
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// 支持解压的格式，按扩展名识别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+  Zip,
+  Tar,
+  TarGz,
+  SevenZ,
+}
+
+const ARCHIVE_SUFFIXES: &[(&str, ArchiveKind)] = &[
+  (".zip", ArchiveKind::Zip),
+  (".tar", ArchiveKind::Tar),
+  (".tar.gz", ArchiveKind::TarGz),
+  (".tgz", ArchiveKind::TarGz),
+  (".7z", ArchiveKind::SevenZ),
+];
+
+impl ArchiveKind {
+  /// 返回格式和去掉扩展名后的名称，名称用作默认解压到的文件夹
+  pub fn detect(name: &str) -> Option<(ArchiveKind, &str)> {
+    let lowercase = name.to_ascii_lowercase();
+    ARCHIVE_SUFFIXES
+      .iter()
+      .filter(|(suffix, _)| lowercase.ends_with(suffix) && lowercase.len() > suffix.len())
+      .max_by_key(|(suffix, _)| suffix.len())
+      .map(|(suffix, kind)| (*kind, &name[..name.len() - suffix.len()]))
+  }
+}
+
+enum EntryKind {
+  File,
+  Dir,
+  /// 符号链接、硬链接等，不解压
+  Other,
+}
+
+/// 从压缩包中读取的原始条目
+struct RawEntry {
+  name: String,
+  kind: EntryKind,
+  size: u64,
+  modified: Option<SystemTime>,
+}
+
+/// 压缩包中的条目及其解压结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractEntry {
+  /// 压缩包内的路径
+  pub name: String,
+  pub is_dir: bool,
+  pub size: u64,
+  /// 目标位置已存在同名文件
+  pub exists: bool,
+  /// 不解压的原因
+  pub error: Option<String>,
+  pub code: Option<&'static str>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractReport {
+  pub files: u64,
+  pub bytes: u64,
+  /// 跳过或解压失败的条目
+  pub skipped: Vec<ExtractEntry>,
+  /// 写入的顶层条目名称，用于刷新索引和通知
+  #[serde(skip)]
+  pub written: BTreeSet<String>,
+}
+
+/// 解压的条目数和总大小上限，防止压缩炸弹
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+  pub entries: usize,
+  pub bytes: u64,
+}
+
+impl ExtractLimits {
+  /// 可以通过 EXTRACT_MAX_ENTRIES 和 EXTRACT_MAX_SIZE_GB 配置，默认 10 万个条目、50 GB
+  pub fn from_env() -> Self {
+    let entries = std::env::var("EXTRACT_MAX_ENTRIES")
+      .ok()
+      .and_then(|value| value.parse::<usize>().ok())
+      .unwrap_or(100_000);
+    let gigabytes = std::env::var("EXTRACT_MAX_SIZE_GB")
+      .ok()
+      .and_then(|value| value.parse::<u64>().ok())
+      .unwrap_or(50);
+    Self {
+      entries,
+      bytes: gigabytes.saturating_mul(1024 * 1024 * 1024),
+    }
+  }
+}
+
+fn too_large(message: &str) -> AppError {
+  AppError::with_code(StatusCode::UNPROCESSABLE_ENTITY, ARCHIVE_TOO_LARGE, message)
+}
+
+pub struct ExtractTask {
+  pub archive: PathBuf,
+  pub kind: ArchiveKind,
+  /// 解压到的文件夹，不存在时自动创建
+  pub destination: PathBuf,
+  pub conflict: ConflictPolicy,
+  pub policy: StoragePolicy,
+  /// 文件先写入这里再替换目标，需要和目标在同一文件系统中
+  pub temp_dir: PathBuf,
+  pub limits: ExtractLimits,
+}
+
+impl ExtractTask {
+  /// 列出压缩包的内容和解压时的处理方式，不写入任何文件
+  pub fn list(&self) -> Result<Vec<ExtractEntry>, AppError> {
+    let mut entries = Vec::new();
+    let mut count = 0;
+    for_each_entry(self.kind, &self.archive, false, |raw, _| {
+      count += 1;
+      if count > self.limits.entries {
+        return Err(too_large("压缩包中的条目过多"));
+      }
+      if let Some((entry, _)) = self.inspect(&raw) {
+        entries.push(entry);
+      }
+      Ok(())
+    })?;
+    Ok(entries)
+  }
+
+  /// 依次解压每个条目，路径不安全、不符合存储限制或已存在（跳过时）的条目不解压。
+  /// `on_progress` 返回错误时停止解压。覆盖已存在的目标前调用 `on_replace`，
+  /// 由它保存历史版本并把目标移走
+  pub fn run(
+    &self,
+    mut on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+    mut on_replace: impl FnMut(&Path) -> Result<(), AppError>,
+  ) -> Result<ExtractReport, AppError> {
+    let mut progress = TransferProgress::default();
+    for entry in self.list()? {
+      if entry.error.is_none() && !entry.is_dir {
+        progress.total_files += 1;
+        progress.total_bytes += entry.size;
+      }
+    }
+    if progress.total_bytes > self.limits.bytes {
+      return Err(too_large("压缩包解压后过大"));
+    }
+    on_progress(&progress)?;
+
+    fs::create_dir_all(&self.destination)?;
+    fs::create_dir_all(&self.temp_dir)?;
+    let mut report = ExtractReport::default();
+    for_each_entry(self.kind, &self.archive, true, |raw, data| {
+      let Some((mut entry, target)) = self.inspect(&raw) else {
+        return Ok(());
+      };
+      if entry.error.is_some() {
+        report.skipped.push(entry);
+        return Ok(());
+      }
+      if entry.is_dir {
+        // 同名文件已存在时无法创建文件夹，例如压缩包中同时有文件 `a` 和 `a/`
+        if fs::create_dir_all(&target).is_err() {
+          entry.error = Some("路径与已有文件冲突".to_string());
+          report.skipped.push(entry);
+          return Ok(());
+        }
+        report.written.insert(self.top_name(&target));
+        return Ok(());
+      }
+
+      progress.current = entry.name.clone();
+      on_progress(&progress)?;
+      let target = match (entry.exists, self.conflict) {
+        (false, _) => target,
+        (true, ConflictPolicy::Skip) => {
+          progress.done_files += 1;
+          progress.done_bytes += entry.size;
+          entry.error = Some("目标已存在".to_string());
+          report.skipped.push(entry);
+          return Ok(());
+        }
+        (true, ConflictPolicy::Overwrite) => target,
+        (true, ConflictPolicy::Rename) => {
+          let parent = target.parent().unwrap_or(&self.destination);
+          unique_path(
+            parent,
+            &target.file_name().unwrap_or_default().to_string_lossy(),
+          )
+        }
+      };
+      if let Some(parent) = target.parent()
+        && fs::create_dir_all(parent).is_err()
+      {
+        progress.done_files += 1;
+        progress.done_bytes += entry.size;
+        entry.error = Some("路径与已有文件冲突".to_string());
+        report.skipped.push(entry);
+        return Ok(());
+      }
+
+      // 先写入临时文件，中途失败时不会留下不完整的目标
+      let temp = self
+        .temp_dir
+        .join(format!("{}.part", hex::encode(rand::random::<[u8; 8]>())));
+      let done_bytes = progress.done_bytes;
+      let budget = self.limits.bytes.saturating_sub(report.bytes);
+      let written = self
+        .write_file(
+          &temp,
+          data,
+          raw.modified,
+          budget,
+          &mut progress,
+          &mut on_progress,
+        )
+        .and_then(|written| {
+          if target.exists() {
+            on_replace(&target)?;
+          }
+          fs::rename(&temp, &target)?;
+          Ok(written)
+        });
+      match written {
+        Ok(written) => {
+          report.files += 1;
+          report.bytes += written;
+          report.written.insert(self.top_name(&target));
+        }
+        // 实际大小超过存储限制时只跳过该文件
+        Err(err)
+          if err
+            .code()
+            .is_some_and(|code| code != JOB_CANCELLED && code != ARCHIVE_TOO_LARGE) =>
+        {
+          fs::remove_file(&temp).ok();
+          progress.done_bytes = done_bytes + entry.size;
+          entry.error = Some(err.message());
+          entry.code = err.code();
+          report.skipped.push(entry);
+        }
+        Err(err) => {
+          fs::remove_file(&temp).ok();
+          return Err(err);
+        }
+      }
+      progress.done_files += 1;
+      on_progress(&progress)
+    })?;
+    Ok(report)
+  }
+
+  /// 目标路径在解压文件夹下的第一级名称
+  fn top_name(&self, target: &Path) -> String {
+    target
+      .strip_prefix(&self.destination)
+      .ok()
+      .and_then(|path| path.iter().next())
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default()
+  }
+
+  /// 检查条目的路径和存储限制，返回条目和目标路径。系统文件直接忽略
+  fn inspect(&self, raw: &RawEntry) -> Option<(ExtractEntry, PathBuf)> {
+    let mut entry = ExtractEntry {
+      name: raw.name.clone(),
+      is_dir: matches!(raw.kind, EntryKind::Dir),
+      size: raw.size,
+      exists: false,
+      error: None,
+      code: None,
+    };
+    // 兼容 Windows 压缩软件使用的反斜杠分隔符
+    let name = raw.name.replace('\\', "/");
+    let name = name.trim_start_matches("./").trim_end_matches('/');
+    if name.is_empty() {
+      return None;
+    }
+    // 防止 zip slip：绝对路径和包含 `..` 的路径都不解压
+    let escapes = name.starts_with('/') || name.contains("..");
+    // `.DS_Store` 等系统文件直接忽略
+    if !escapes && name.split('/').any(is_system_file) {
+      return None;
+    }
+    if escapes || !validate_path(name) {
+      entry.error = Some("路径不安全".to_string());
+      entry.code = Some(UNSAFE_PATH);
+      return Some((entry, PathBuf::new()));
+    }
+    let target = self.destination.join(name);
+    entry.exists = target.exists();
+
+    let checked = match raw.kind {
+      EntryKind::Dir => Ok(()),
+      EntryKind::File => {
+        let file_name = name.rsplit('/').next().unwrap_or_default();
+        self
+          .policy
+          .check_name(file_name)
+          .and_then(|_| self.policy.check_size(raw.size))
+      }
+      EntryKind::Other => Err(AppError::new("不支持链接等特殊文件")),
+    };
+    if let Err(err) = checked {
+      entry.error = Some(err.message());
+      entry.code = err.code();
+    }
+    Some((entry, target))
+  }
+
+  /// 写入单个文件，条目声明的大小可能不准确，按实际写入的大小检查存储限制，
+  /// 超过剩余的 `budget` 时停止整个解压
+  fn write_file(
+    &self,
+    target: &Path,
+    data: &mut dyn Read,
+    modified: Option<SystemTime>,
+    budget: u64,
+    progress: &mut TransferProgress,
+    on_progress: &mut impl FnMut(&TransferProgress) -> Result<(), AppError>,
+  ) -> Result<u64, AppError> {
+    let mut file = File::create(target)?;
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut written = 0;
+    loop {
+      let read = data.read(&mut buffer).map_err(invalid_archive)?;
+      if read == 0 {
+        break;
+      }
+      written += read as u64;
+      if written > budget {
+        return Err(too_large("压缩包解压后过大"));
+      }
+      self.policy.check_size(written)?;
+      file.write_all(&buffer[..read])?;
+      progress.done_bytes += read as u64;
+      on_progress(progress)?;
+    }
+    if let Some(modified) = modified {
+      file.set_modified(modified).ok();
+    }
+    Ok(written)
+  }
+}
+
+fn invalid_archive(err: impl Display) -> AppError {
+  AppError::with_status(
+    StatusCode::UNPROCESSABLE_ENTITY,
+    &format!("无法读取压缩包: {}", err),
+  )
+}
+
+/// 依次读取压缩包中的条目。`read_data` 为 false 时只需要条目信息，尽量不解压数据
+fn for_each_entry(
+  kind: ArchiveKind,
+  path: &Path,
+  read_data: bool,
+  mut each: impl FnMut(RawEntry, &mut dyn Read) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+  match kind {
+    ArchiveKind::Zip => {
+      let mut zip = zip::ZipArchive::new(File::open(path)?).map_err(invalid_archive)?;
+      for index in 0..zip.len() {
+        let mut file = if read_data {
+          zip.by_index(index)
+        } else {
+          zip.by_index_raw(index)
+        }
+        .map_err(invalid_archive)?;
+        let kind = if file.is_dir() {
+          EntryKind::Dir
+        } else if file.is_symlink() {
+          EntryKind::Other
+        } else {
+          EntryKind::File
+        };
+        let raw = RawEntry {
+          name: file.name().to_string(),
+          kind,
+          size: file.size(),
+          modified: file.last_modified().and_then(zip_time),
+        };
+        each(raw, &mut file)?;
+      }
+    }
+    ArchiveKind::Tar | ArchiveKind::TarGz => {
+      let file = File::open(path)?;
+      let reader: Box<dyn Read> = match kind {
+        ArchiveKind::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+      };
+      let mut archive = tar::Archive::new(reader);
+      for entry in archive.entries().map_err(invalid_archive)? {
+        let mut entry = entry.map_err(invalid_archive)?;
+        let entry_type = entry.header().entry_type();
+        let kind = if entry_type.is_dir() {
+          EntryKind::Dir
+        } else if entry_type.is_file() {
+          EntryKind::File
+        } else if entry_type.is_pax_global_extensions() {
+          continue;
+        } else {
+          EntryKind::Other
+        };
+        let raw = RawEntry {
+          name: String::from_utf8_lossy(&entry.path_bytes()).to_string(),
+          kind,
+          size: entry.size(),
+          modified: entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime)),
+        };
+        each(raw, &mut entry)?;
+      }
+    }
+    ArchiveKind::SevenZ => {
+      let mut reader = SevenZReader::open(path, Password::empty()).map_err(invalid_archive)?;
+      let raw_entry = |entry: &sevenz_rust::SevenZArchiveEntry| RawEntry {
+        name: entry.name().to_string(),
+        kind: if entry.is_directory {
+          EntryKind::Dir
+        } else if entry.is_anti_item {
+          EntryKind::Other
+        } else {
+          EntryKind::File
+        },
+        size: entry.size,
+        modified: entry
+          .has_last_modified_date
+          .then(|| entry.last_modified_date.into()),
+      };
+      if !read_data {
+        for entry in &reader.archive().files {
+          each(raw_entry(entry), &mut io::empty())?;
+        }
+        return Ok(());
+      }
+      // 回调中只能返回 sevenz 的错误，先保存下来再停止遍历
+      let mut failed = None;
+      reader
+        .for_each_entries(|entry, data| match each(raw_entry(entry), data) {
+          Ok(()) => Ok(true),
+          Err(err) => {
+            failed = Some(err);
+            Ok(false)
+          }
+        })
+        .map_err(invalid_archive)?;
+      if let Some(err) = failed {
+        return Err(err);
+      }
+    }
+  }
+  Ok(())
+}
+
+/// ZIP 中的时间是没有时区的本地时间
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+  let time = NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+    .and_hms_opt(
+    time.hour().into(),
+    time.minute().into(),
+    time.second().into(),
+  )?;
+  Local
+    .from_local_datetime(&time)
+    .earliest()
+    .map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_detect() {
+    assert_eq!(ArchiveKind::detect("a.ZIP"), Some((ArchiveKind::Zip, "a")));
+    assert_eq!(
+      ArchiveKind::detect("a.b.tar.gz"),
+      Some((ArchiveKind::TarGz, "a.b"))
+    );
+    assert_eq!(
+      ArchiveKind::detect("a.7z"),
+      Some((ArchiveKind::SevenZ, "a"))
+    );
+    assert_eq!(ArchiveKind::detect(".zip"), None);
+    assert_eq!(ArchiveKind::detect("a.rar"), None);
+  }
+
+  #[test]
+  fn test_extract_zip_slip() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-extract-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    fs::create_dir_all(&root).unwrap();
+    let archive = root.join("a.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for name in [
+      "../evil.txt",
+      "/abs.txt",
+      "ok/a.txt",
+      "ok/b.exe",
+      ".storkitty/x",
+    ] {
+      zip.start_file(name, options).unwrap();
+      zip.write_all(b"data").unwrap();
+    }
+    zip.finish().unwrap();
+
+    let task = ExtractTask {
+      archive,
+      kind: ArchiveKind::Zip,
+      destination: root.join("out"),
+      conflict: ConflictPolicy::Skip,
+      policy: StoragePolicy {
+        block_extensions: vec!["exe".to_string()],
+        ..Default::default()
+      },
+      temp_dir: root.join("tmp"),
+      limits: ExtractLimits::from_env(),
+    };
+    let Ok(entries) = task.list() else {
+      panic!("failed to list archive");
+    };
+    let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["../evil.txt", "/abs.txt", "ok/a.txt", "ok/b.exe"]);
+    assert_eq!(entries[0].code, Some(UNSAFE_PATH));
+    assert_eq!(entries[1].code, Some(UNSAFE_PATH));
+    assert!(entries[2].error.is_none());
+
+    let Ok(report) = task.run(|_| Ok(()), |_| Ok(())) else {
+      panic!("failed to extract archive");
+    };
+    assert_eq!(report.files, 1);
+    assert_eq!(report.skipped.len(), 3);
+    assert_eq!(fs::read(root.join("out/ok/a.txt")).unwrap(), b"data");
+    assert!(!root.join("evil.txt").exists());
+    assert!(!root.join("out/ok/b.exe").exists());
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_extract_conflicts_and_limits() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-extract-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    fs::create_dir_all(&root).unwrap();
+    let archive = root.join("a.zip");
+    let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, data) in [("a", "data"), ("a/b", "data"), ("c", "more data")] {
+      zip.start_file(name, options).unwrap();
+      zip.write_all(data.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let mut task = ExtractTask {
+      archive,
+      kind: ArchiveKind::Zip,
+      destination: root.join("out"),
+      conflict: ConflictPolicy::Overwrite,
+      policy: StoragePolicy::default(),
+      temp_dir: root.join("tmp"),
+      limits: ExtractLimits {
+        entries: 3,
+        bytes: 1024,
+      },
+    };
+    let Ok(report) = task.run(|_| Ok(()), |_| Ok(())) else {
+      panic!("failed to extract archive");
+    };
+    assert_eq!(report.files, 2);
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].name, "a/b");
+    assert_eq!(fs::read(root.join("out/a")).unwrap(), b"data");
+
+    task.limits.entries = 2;
+    let Err(err) = task.run(|_| Ok(()), |_| Ok(())) else {
+      panic!("too many entries should be rejected");
+    };
+    assert_eq!(err.code(), Some(ARCHIVE_TOO_LARGE));
+
+    task.limits = ExtractLimits {
+      entries: 3,
+      bytes: 10,
+    };
+    let Err(err) = task.run(|_| Ok(()), |_| Ok(())) else {
+      panic!("too large archive should be rejected");
+    };
+    assert_eq!(err.code(), Some(ARCHIVE_TOO_LARGE));
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -0,0 +1,299 @@
+mod extract;
+mod zip_stream;
+use std::{
+  io::{self, Write},
+  sync::Arc,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::body::Bytes;
+use flate2::{Compression, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use tokio::{runtime::Handle, sync::mpsc};
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+use crate::backend::utils::{
+  backend::{StorageBackend, join},
+  file::is_system_file,
+};
+
+pub use extract::{ArchiveKind, ExtractLimits, ExtractReport, ExtractTask};
+use zip_stream::ZipStream;
+
+/// 发送给客户端的数据块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveFormat {
+  #[default]
+  Zip,
+  TarGz,
+}
+
+impl ArchiveFormat {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "zip",
+      ArchiveFormat::TarGz => "tar.gz",
+    }
+  }
+
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "application/zip",
+      ArchiveFormat::TarGz => "application/gzip",
+    }
+  }
+}
+
+/// 打包的条目
+#[derive(Debug)]
+pub struct ArchiveEntry {
+  /// 相对于存储根目录的路径
+  pub path: String,
+  /// 在压缩包内的路径
+  pub name: String,
+  pub is_dir: bool,
+  pub size: u64,
+  pub modified: Option<SystemTime>,
+}
+
+/// 递归列出 `base` 下的 `targets`，跳过系统文件和符号链接。压缩包内的路径以 target 名称开头
+pub async fn collect_entries(
+  backend: &dyn StorageBackend,
+  base: &str,
+  targets: &[String],
+) -> io::Result<Vec<ArchiveEntry>> {
+  let mut entries = Vec::new();
+  let mut pending = Vec::new();
+  for target in targets {
+    let path = join(base, target);
+    let entry = backend
+      .try_stat(&path)
+      .await?
+      .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("目标不存在: {}", target)))?;
+    if entry.is_symlink {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("不支持打包链接等特殊文件: {}", target),
+      ));
+    }
+    entries.push(ArchiveEntry {
+      path: path.clone(),
+      name: target.clone(),
+      is_dir: entry.is_dir,
+      size: entry.size,
+      modified: entry.modified,
+    });
+    if entry.is_dir {
+      pending.push((path, target.clone()));
+    }
+  }
+
+  while let Some((dir, name)) = pending.pop() {
+    let mut children = backend.list(&dir).await?;
+    children.sort_by(|a, b| a.name.cmp(&b.name));
+    for child in children {
+      if is_system_file(&child.name) || child.is_symlink {
+        continue;
+      }
+      let path = join(&dir, &child.name);
+      let child_name = format!("{}/{}", name, child.name);
+      if child.is_dir {
+        pending.push((path.clone(), child_name.clone()));
+      }
+      entries.push(ArchiveEntry {
+        path,
+        name: child_name,
+        is_dir: child.is_dir,
+        size: child.size,
+        modified: child.modified,
+      });
+    }
+  }
+  Ok(entries)
+}
+
+/// 按顺序把条目写入压缩包，文件内容从存储中流式读取，每写完一个条目调用 `on_entry`，
+/// 它返回错误时停止写入。
+/// 需要在阻塞线程中调用，`handle` 用于读取存储
+pub fn write_archive<W: Write>(
+  writer: W,
+  format: ArchiveFormat,
+  entries: &[ArchiveEntry],
+  backend: Arc<dyn StorageBackend>,
+  handle: Handle,
+  mut on_entry: impl FnMut(&ArchiveEntry) -> io::Result<()>,
+) -> io::Result<W> {
+  let open = |path: &str| -> io::Result<_> {
+    let stream = handle.block_on(backend.read(path, None))?;
+    Ok(SyncIoBridge::new_with_handle(
+      StreamReader::new(stream),
+      handle.clone(),
+    ))
+  };
+
+  match format {
+    ArchiveFormat::Zip => {
+      let mut zip = ZipStream::new(writer);
+      for entry in entries {
+        if entry.is_dir {
+          zip.add_directory(entry)?;
+        } else {
+          zip.add_file(entry, open(&entry.path)?)?;
+        }
+        on_entry(entry)?;
+      }
+      zip.finish()
+    }
+    ArchiveFormat::TarGz => {
+      let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::fast()));
+      for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(
+          entry
+            .modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        );
+        if entry.is_dir {
+          header.set_entry_type(tar::EntryType::Directory);
+          header.set_mode(0o755);
+          header.set_size(0);
+          tar.append_data(&mut header, &entry.name, io::empty())?;
+        } else {
+          header.set_mode(0o644);
+          header.set_size(entry.size);
+          tar.append_data(&mut header, &entry.name, open(&entry.path)?)?;
+        }
+        on_entry(entry)?;
+      }
+      tar.into_inner()?.finish()
+    }
+  }
+}
+
+/// 把写入的数据分块发送到通道，接收端断开（客户端取消下载）时写入失败，打包随之停止
+pub struct ChannelWriter {
+  tx: mpsc::Sender<io::Result<Bytes>>,
+  buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+  pub fn new(tx: mpsc::Sender<io::Result<Bytes>>) -> Self {
+    Self {
+      tx,
+      buffer: Vec::with_capacity(CHUNK_SIZE),
+    }
+  }
+
+  fn send(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() {
+      return Ok(());
+    }
+    let data = Bytes::from(std::mem::replace(
+      &mut self.buffer,
+      Vec::with_capacity(CHUNK_SIZE),
+    ));
+    self
+      .tx
+      .blocking_send(Ok(data))
+      .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "下载已取消"))
+  }
+}
+
+impl Write for ChannelWriter {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    self.buffer.extend_from_slice(data);
+    if self.buffer.len() >= CHUNK_SIZE {
+      self.send()?;
+    }
+    Ok(data.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.send()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use super::*;
+  use crate::backend::utils::backend::{LocalBackend, once};
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_write_archive() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-archive-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    std::fs::create_dir_all(root.join("docs/.git")).unwrap();
+    let backend: Arc<dyn StorageBackend> = Arc::new(LocalBackend::new(root.clone()));
+    backend
+      .write("docs/a.txt", once("hello".into()))
+      .await
+      .unwrap();
+    backend.write("b.txt", once("world".into())).await.unwrap();
+
+    let targets = vec!["docs".to_string(), "b.txt".to_string()];
+    let entries = collect_entries(&*backend, "", &targets).await.unwrap();
+    let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["docs", "b.txt", "docs/a.txt"]);
+
+    let handle = Handle::current();
+    let data = tokio::task::spawn_blocking(move || {
+      write_archive(
+        Vec::new(),
+        ArchiveFormat::Zip,
+        &entries,
+        backend,
+        handle,
+        |_| Ok(()),
+      )
+      .unwrap()
+    })
+    .await
+    .unwrap();
+    let mut zip = zip::ZipArchive::new(io::Cursor::new(data)).unwrap();
+    let mut content = String::new();
+    zip
+      .by_name("docs/a.txt")
+      .unwrap()
+      .read_to_string(&mut content)
+      .unwrap();
+    assert_eq!(content, "hello");
+    assert!(zip.by_name("docs/").unwrap().is_dir());
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn test_collect_skips_symlinks() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-archive-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    std::fs::create_dir_all(root.join("docs")).unwrap();
+    std::fs::write(root.join("docs/a.txt"), "hello").unwrap();
+    // 指向上级目录会形成循环，指向存储之外会泄露文件
+    std::os::unix::fs::symlink(&root, root.join("docs/loop")).unwrap();
+    std::os::unix::fs::symlink("/etc/hostname", root.join("docs/outside")).unwrap();
+    let backend = LocalBackend::new(root.clone());
+
+    let entries = collect_entries(&backend, "", &["docs".to_string()])
+      .await
+      .unwrap();
+    let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["docs", "docs/a.txt"]);
+    assert!(
+      collect_entries(&backend, "docs", &["loop".to_string()])
+        .await
+        .is_err()
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
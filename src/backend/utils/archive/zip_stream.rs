@@ -0,0 +1,270 @@
+use std::{
+  io::{self, Write},
+  time::SystemTime,
+};
+
+use chrono::{Datelike, Local, Timelike};
+use flate2::{Compression, Crc, write::DeflateEncoder};
+
+use super::{ArchiveEntry, CHUNK_SIZE};
+
+/// 超过该大小的文件在 ZIP 中使用 ZIP64。流式写入时需要在文件头中提前声明，
+/// 压缩后的大小可能略大于原文件，留出余量
+const ZIP64_THRESHOLD: u64 = 0xF000_0000;
+/// ZIP 中 32 位字段的上限，达到该值时改用 ZIP64 扩展字段
+const ZIP32_MAX: u64 = u32::MAX as u64;
+/// 记录写入位置的 Writer
+struct CountingWriter<W> {
+  inner: W,
+  position: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    let written = self.inner.write(data)?;
+    self.position += written as u64;
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+/// 已写入的条目，用于生成中央目录
+struct ZipRecord {
+  name: String,
+  is_dir: bool,
+  zip64: bool,
+  time: (u16, u16),
+  crc: u32,
+  compressed_size: u64,
+  size: u64,
+  offset: u64,
+}
+
+/// 只向前写入的 ZIP。文件头中的大小和 CRC 留空，写在数据之后的 data descriptor 中，
+/// 中央目录记录真实的值，超过 32 位时使用 ZIP64 扩展字段（APPNOTE 4.5.3）。
+/// zip crate 的流式模式不会回填 ZIP64 扩展字段中的大小，大文件无法正确解压，所以自行实现
+pub struct ZipStream<W> {
+  writer: CountingWriter<W>,
+  records: Vec<ZipRecord>,
+}
+
+impl<W: Write> ZipStream<W> {
+  const VERSION_MADE_BY: u16 = 3 << 8 | 45; // Unix, 4.5
+  const FLAG_DESCRIPTOR: u16 = 1 << 3;
+  const FLAG_UTF8: u16 = 1 << 11;
+  const STORED: u16 = 0;
+  const DEFLATED: u16 = 8;
+
+  pub fn new(writer: W) -> Self {
+    Self {
+      writer: CountingWriter {
+        inner: writer,
+        position: 0,
+      },
+      records: Vec::new(),
+    }
+  }
+
+  pub fn add_directory(&mut self, entry: &ArchiveEntry) -> io::Result<()> {
+    let record = ZipRecord {
+      name: format!("{}/", entry.name),
+      is_dir: true,
+      zip64: false,
+      time: dos_time(entry.modified),
+      crc: 0,
+      compressed_size: 0,
+      size: 0,
+      offset: self.writer.position,
+    };
+    self.write_local_header(&record)?;
+    self.records.push(record);
+    Ok(())
+  }
+
+  pub fn add_file(&mut self, entry: &ArchiveEntry, mut reader: impl io::Read) -> io::Result<()> {
+    let mut record = ZipRecord {
+      name: entry.name.clone(),
+      is_dir: false,
+      zip64: entry.size >= ZIP64_THRESHOLD,
+      time: dos_time(entry.modified),
+      crc: 0,
+      compressed_size: 0,
+      size: 0,
+      offset: self.writer.position,
+    };
+    self.write_local_header(&record)?;
+
+    let start = self.writer.position;
+    let mut crc = Crc::new();
+    let mut encoder = DeflateEncoder::new(&mut self.writer, Compression::fast());
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+      let read = match reader.read(&mut buffer) {
+        Ok(0) => break,
+        Ok(read) => read,
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+        Err(err) => return Err(err),
+      };
+      crc.update(&buffer[..read]);
+      encoder.write_all(&buffer[..read])?;
+      record.size += read as u64;
+    }
+    encoder.finish()?;
+    record.crc = crc.sum();
+    record.compressed_size = self.writer.position - start;
+
+    // 文件在打包期间变大，文件头中没有声明 ZIP64
+    if !record.zip64 && record.compressed_size.max(record.size) >= ZIP32_MAX {
+      return Err(io::Error::other(format!("文件大小已变化: {}", entry.name)));
+    }
+    let mut descriptor = Vec::with_capacity(24);
+    descriptor.extend(0x0807_4b50u32.to_le_bytes());
+    descriptor.extend(record.crc.to_le_bytes());
+    if record.zip64 {
+      descriptor.extend(record.compressed_size.to_le_bytes());
+      descriptor.extend(record.size.to_le_bytes());
+    } else {
+      descriptor.extend((record.compressed_size as u32).to_le_bytes());
+      descriptor.extend((record.size as u32).to_le_bytes());
+    }
+    self.writer.write_all(&descriptor)?;
+    self.records.push(record);
+    Ok(())
+  }
+
+  fn write_local_header(&mut self, record: &ZipRecord) -> io::Result<()> {
+    let (flags, method) = if record.is_dir {
+      (Self::FLAG_UTF8, Self::STORED)
+    } else {
+      (Self::FLAG_UTF8 | Self::FLAG_DESCRIPTOR, Self::DEFLATED)
+    };
+    let mut header = Vec::with_capacity(50 + record.name.len());
+    header.extend(0x0403_4b50u32.to_le_bytes());
+    header.extend(version_needed(record.zip64).to_le_bytes());
+    header.extend(flags.to_le_bytes());
+    header.extend(method.to_le_bytes());
+    header.extend(record.time.0.to_le_bytes());
+    header.extend(record.time.1.to_le_bytes());
+    header.extend(0u32.to_le_bytes());
+    if record.zip64 {
+      // 大小在 data descriptor 中，这里只声明使用 ZIP64
+      header.extend(u32::MAX.to_le_bytes());
+      header.extend(u32::MAX.to_le_bytes());
+    } else {
+      header.extend(0u64.to_le_bytes());
+    }
+    header.extend((record.name.len() as u16).to_le_bytes());
+    header.extend((if record.zip64 { 20u16 } else { 0 }).to_le_bytes());
+    header.extend(record.name.as_bytes());
+    if record.zip64 {
+      header.extend(0x0001u16.to_le_bytes());
+      header.extend(16u16.to_le_bytes());
+      header.extend([0; 16]);
+    }
+    self.writer.write_all(&header)
+  }
+
+  /// 写入中央目录和目录结束记录，条目数或偏移超过 32 位时加上 ZIP64 结束记录
+  pub fn finish(mut self) -> io::Result<W> {
+    let start = self.writer.position;
+    for record in &self.records {
+      let mut extra = Vec::new();
+      for value in [record.size, record.compressed_size, record.offset] {
+        if value >= ZIP32_MAX {
+          extra.extend(value.to_le_bytes());
+        }
+      }
+      let (flags, method, attributes) = if record.is_dir {
+        (Self::FLAG_UTF8, Self::STORED, 0o40755 << 16 | 0x10)
+      } else {
+        (
+          Self::FLAG_UTF8 | Self::FLAG_DESCRIPTOR,
+          Self::DEFLATED,
+          0o100644 << 16,
+        )
+      };
+      let zip64 = record.zip64 || !extra.is_empty();
+      let mut header = Vec::with_capacity(46 + record.name.len() + 28);
+      header.extend(0x0201_4b50u32.to_le_bytes());
+      header.extend(Self::VERSION_MADE_BY.to_le_bytes());
+      header.extend(version_needed(zip64).to_le_bytes());
+      header.extend(flags.to_le_bytes());
+      header.extend(method.to_le_bytes());
+      header.extend(record.time.0.to_le_bytes());
+      header.extend(record.time.1.to_le_bytes());
+      header.extend(record.crc.to_le_bytes());
+      header.extend(zip32(record.compressed_size).to_le_bytes());
+      header.extend(zip32(record.size).to_le_bytes());
+      header.extend((record.name.len() as u16).to_le_bytes());
+      let extra_len = if extra.is_empty() { 0 } else { extra.len() + 4 };
+      header.extend((extra_len as u16).to_le_bytes());
+      header.extend([0; 6]); // 注释长度、磁盘号、内部属性
+      header.extend((attributes as u32).to_le_bytes());
+      header.extend(zip32(record.offset).to_le_bytes());
+      header.extend(record.name.as_bytes());
+      if !extra.is_empty() {
+        header.extend(0x0001u16.to_le_bytes());
+        header.extend((extra.len() as u16).to_le_bytes());
+        header.extend(extra);
+      }
+      self.writer.write_all(&header)?;
+    }
+
+    let end = self.writer.position;
+    let size = end - start;
+    let count = self.records.len() as u64;
+    let mut footer = Vec::with_capacity(98);
+    if count >= u16::MAX as u64 || size >= ZIP32_MAX || start >= ZIP32_MAX {
+      footer.extend(0x0606_4b50u32.to_le_bytes());
+      footer.extend(44u64.to_le_bytes());
+      footer.extend(Self::VERSION_MADE_BY.to_le_bytes());
+      footer.extend(version_needed(true).to_le_bytes());
+      footer.extend([0; 8]); // 磁盘号
+      footer.extend(count.to_le_bytes());
+      footer.extend(count.to_le_bytes());
+      footer.extend(size.to_le_bytes());
+      footer.extend(start.to_le_bytes());
+      footer.extend(0x0706_4b50u32.to_le_bytes());
+      footer.extend(0u32.to_le_bytes());
+      footer.extend(end.to_le_bytes());
+      footer.extend(1u32.to_le_bytes());
+    }
+    let count = count.min(u16::MAX as u64) as u16;
+    footer.extend(0x0605_4b50u32.to_le_bytes());
+    footer.extend([0; 4]); // 磁盘号
+    footer.extend(count.to_le_bytes());
+    footer.extend(count.to_le_bytes());
+    footer.extend(zip32(size).to_le_bytes());
+    footer.extend(zip32(start).to_le_bytes());
+    footer.extend(0u16.to_le_bytes());
+    self.writer.write_all(&footer)?;
+    self.writer.flush()?;
+    Ok(self.writer.inner)
+  }
+}
+
+fn version_needed(zip64: bool) -> u16 {
+  if zip64 { 45 } else { 20 }
+}
+
+/// 超过 32 位的值写为 0xFFFFFFFF，实际的值在 ZIP64 扩展字段中
+fn zip32(value: u64) -> u32 {
+  value.min(ZIP32_MAX) as u32
+}
+
+/// ZIP 使用本地时间的 MS-DOS 格式 (时间, 日期)，只能表示 1980 到 2107 年
+fn dos_time(modified: Option<SystemTime>) -> (u16, u16) {
+  let Some(time) = modified
+    .map(chrono::DateTime::<Local>::from)
+    .filter(|time| (1980..=2107).contains(&time.year()))
+  else {
+    return (0, 1 << 5 | 1);
+  };
+  (
+    (time.hour() << 11 | time.minute() << 5 | (time.second() / 2)) as u16,
+    ((time.year() as u32 - 1980) << 9 | time.month() << 5 | time.day()) as u16,
+  )
+}
@@ -1,16 +1,33 @@
+use std::{
+  collections::HashMap,
+  sync::{LazyLock, Mutex},
+  time::{Duration, Instant},
+};
+
 use anyhow::Context;
 use axum::http::{HeaderMap, header::AUTHORIZATION};
+use base64::{Engine, prelude::BASE64_STANDARD};
 use chrono::Utc;
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
   pub sub: String, // username
   pub exp: usize,  // expiration time
   pub iat: usize,  // issued at
+  /// 限定用途的 token 才有，登录 token 没有
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub scope: Option<String>,
 }
 
+/// 只能放在 `?token=` 中访问事件订阅、缩略图和打包下载的 token
+const QUERY_TOKEN_SCOPE: &str = "query";
+/// 查询参数会出现在访问日志和浏览器历史中，有效期很短
+pub const QUERY_TOKEN_TTL: i64 = 10 * 60;
+
 pub fn generate_token(user_id: i64) -> anyhow::Result<String> {
   let now = Utc::now();
   let expiration_days = std::env::var("JWT_EXPIRATION_DAYS")
@@ -24,37 +41,206 @@ pub fn generate_token(user_id: i64) -> anyhow::Result<String> {
     sub: user_id.to_string(),
     exp: exp.timestamp() as usize,
     iat: now.timestamp() as usize,
+    scope: None,
   };
 
   let token = jsonwebtoken::encode(
     &Header::default(),
     &claims,
-    &EncodingKey::from_secret(
-      std::env::var("JWT_SECRET_KEY")
-        .unwrap_or("storkitty-secret-key".to_string())
-        .as_ref(),
-    ),
+    &EncodingKey::from_secret(secret_key().as_ref()),
   )?;
 
   Ok(token)
 }
 
+/// 生成查询参数使用的 token，返回 token 和过期时间
+pub fn generate_query_token(user_id: i64) -> anyhow::Result<(String, i64)> {
+  let now = Utc::now().timestamp();
+  let expires = now + QUERY_TOKEN_TTL;
+  let claims = Claims {
+    sub: user_id.to_string(),
+    exp: expires as usize,
+    iat: now as usize,
+    scope: Some(QUERY_TOKEN_SCOPE.to_string()),
+  };
+  let token = jsonwebtoken::encode(
+    &Header::default(),
+    &claims,
+    &EncodingKey::from_secret(secret_key().as_ref()),
+  )?;
+  Ok((token, expires))
+}
+
 pub fn verify_token(headers: &HeaderMap) -> anyhow::Result<i64> {
   let token = headers
     .get(AUTHORIZATION)
     .and_then(|value| value.to_str().ok())
     .and_then(|value| value.strip_prefix("Bearer "))
     .ok_or(anyhow::anyhow!("No token provided"))?;
+  verify_token_str(token)
+}
+
+/// 校验登录 token，限定用途的 token 不能当作登录 token 使用
+pub fn verify_token_str(token: &str) -> anyhow::Result<i64> {
+  let claims = decode_claims(token)?;
+  if claims.scope.is_some() {
+    return Err(anyhow::anyhow!("Invalid token"));
+  }
+  parse_user_id(&claims)
+}
+
+/// 校验 `?token=` 中的 token，只接受 [`generate_query_token`] 生成的
+pub fn verify_query_token(token: &str) -> anyhow::Result<i64> {
+  let claims = decode_claims(token)?;
+  if claims.scope.as_deref() != Some(QUERY_TOKEN_SCOPE) {
+    return Err(anyhow::anyhow!("Invalid token"));
+  }
+  parse_user_id(&claims)
+}
+
+fn decode_claims(token: &str) -> anyhow::Result<Claims> {
   let token_data: jsonwebtoken::TokenData<Claims> = jsonwebtoken::decode(
     token,
-    &DecodingKey::from_secret(
-      std::env::var("JWT_SECRET_KEY")
-        .unwrap_or("storkitty-secret-key".to_string())
-        .as_ref(),
-    ),
+    &DecodingKey::from_secret(secret_key().as_ref()),
     &jsonwebtoken::Validation::default(),
   )
   .map_err(|_| anyhow::anyhow!("Invalid token"))?;
+  Ok(token_data.claims)
+}
+
+fn parse_user_id(claims: &Claims) -> anyhow::Result<i64> {
+  claims
+    .sub
+    .parse::<i64>()
+    .map_err(|_| anyhow::anyhow!("Invalid token"))
+}
+
+/// 解析 `Authorization: Basic ...`，返回用户名和密码
+pub fn parse_basic_auth(headers: &HeaderMap) -> Option<(String, String)> {
+  let encoded = headers
+    .get(AUTHORIZATION)?
+    .to_str()
+    .ok()?
+    .strip_prefix("Basic ")?;
+  let decoded = String::from_utf8(BASE64_STANDARD.decode(encoded.trim()).ok()?).ok()?;
+  let (username, password) = decoded.split_once(':')?;
+  Some((username.to_string(), password.to_string()))
+}
+
+/// bcrypt 很慢，在阻塞线程中计算哈希，调用前不要持有数据库锁
+pub async fn hash_password(password: String) -> anyhow::Result<String> {
+  let hash =
+    tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST)).await??;
+  Ok(hash)
+}
+
+/// Basic 认证的客户端每个请求都会带上密码，bcrypt 校验很慢，缓存校验成功的结果
+///
+/// 缓存键包含密码哈希，修改密码后旧密码立即失效
+pub fn verify_password_cached(password: &str, hash: &str) -> bool {
+  let key = hex::encode(Sha256::digest(format!("{}\0{}", hash, password)));
+  let now = Instant::now();
+  let cached = PASSWORD_CACHE
+    .lock()
+    .unwrap_or_else(|err| err.into_inner())
+    .get(&key)
+    .is_some_and(|expires| *expires > now);
+  if cached {
+    return true;
+  }
+  if !bcrypt::verify(password, hash).unwrap_or(false) {
+    return false;
+  }
+  let mut cache = PASSWORD_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+  cache.retain(|_, expires| *expires > now);
+  cache.insert(key, now + PASSWORD_CACHE_TTL);
+  true
+}
+
+const PASSWORD_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+static PASSWORD_CACHE: LazyLock<Mutex<HashMap<String, Instant>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 下载链接签名参数
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadSignature {
+  pub uid: i64,
+  pub expires: i64,
+  pub sign: String,
+}
+
+/// 为下载路径生成签名，签名绑定路径、用户和过期时间
+pub fn sign_download(path: &str, user_id: i64, expires: i64) -> anyhow::Result<String> {
+  let mac = download_mac(path, user_id, expires)?;
+  Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// 校验下载签名，返回签名对应的用户 id
+pub fn verify_download(path: &str, signature: &DownloadSignature) -> anyhow::Result<i64> {
+  if signature.expires < Utc::now().timestamp() {
+    return Err(anyhow::anyhow!("Signature expired"));
+  }
+  let sign = hex::decode(&signature.sign).map_err(|_| anyhow::anyhow!("Invalid signature"))?;
+  download_mac(path, signature.uid, signature.expires)?
+    .verify_slice(&sign)
+    .map_err(|_| anyhow::anyhow!("Invalid signature"))?;
+  Ok(signature.uid)
+}
+
+fn download_mac(path: &str, user_id: i64, expires: i64) -> anyhow::Result<Hmac<Sha256>> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret_key().as_bytes())?;
+  mac.update(format!("{}\n{}\n{}", path.trim_start_matches('/'), user_id, expires).as_bytes());
+  Ok(mac)
+}
+
+fn secret_key() -> String {
+  std::env::var("JWT_SECRET_KEY").unwrap_or("storkitty-secret-key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_verify_download() {
+    let expires = Utc::now().timestamp() + 60;
+    let sign = sign_download("data/a.txt", 1, expires).unwrap();
+    let signature = DownloadSignature {
+      uid: 1,
+      expires,
+      sign,
+    };
+    assert_eq!(verify_download("/data/a.txt", &signature).unwrap(), 1);
+    assert!(verify_download("data/b.txt", &signature).is_err());
+
+    let signature = DownloadSignature {
+      uid: 2,
+      ..signature
+    };
+    assert!(verify_download("data/a.txt", &signature).is_err());
+  }
+
+  #[test]
+  fn test_query_token_scope() {
+    let (query_token, _) = generate_query_token(1).unwrap();
+    assert_eq!(verify_query_token(&query_token).unwrap(), 1);
+    assert!(verify_token_str(&query_token).is_err());
+
+    let login_token = generate_token(1).unwrap();
+    assert_eq!(verify_token_str(&login_token).unwrap(), 1);
+    assert!(verify_query_token(&login_token).is_err());
+  }
 
-  Ok(token_data.claims.sub.parse::<i64>().unwrap())
+  #[test]
+  fn test_verify_download_expired() {
+    let expires = Utc::now().timestamp() - 1;
+    let sign = sign_download("data/a.txt", 1, expires).unwrap();
+    let signature = DownloadSignature {
+      uid: 1,
+      expires,
+      sign,
+    };
+    assert!(verify_download("data/a.txt", &signature).is_err());
+  }
 }
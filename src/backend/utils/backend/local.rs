@@ -0,0 +1,230 @@
+use std::{
+  fs::Metadata,
+  io::{self, SeekFrom},
+  path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use futures_util::{StreamExt, TryStreamExt};
+use tokio::{
+  fs,
+  io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::backend::utils;
+
+use super::{ByteStream, Entry, StorageBackend, temp_file};
+
+/// 本地目录，默认的存储后端
+pub struct LocalBackend {
+  root: PathBuf,
+}
+
+impl LocalBackend {
+  pub fn new(root: PathBuf) -> Self {
+    Self { root }
+  }
+
+  fn resolve(&self, path: &str) -> PathBuf {
+    match path {
+      "" => self.root.clone(),
+      path => self.root.join(path),
+    }
+  }
+}
+
+#[async_trait]
+impl StorageBackend for LocalBackend {
+  async fn list(&self, path: &str) -> io::Result<Vec<Entry>> {
+    let dir = self.resolve(path);
+    tokio::task::spawn_blocking(move || {
+      let mut entries = Vec::new();
+      for entry in std::fs::read_dir(&dir)?.flatten() {
+        // 非 UTF-8 跳过
+        let Ok(name) = entry.file_name().into_string() else {
+          continue;
+        };
+        let metadata = match entry.metadata() {
+          Ok(metadata) => metadata,
+          Err(err) => {
+            log::warn!("Failed to read metadata for {:?}: {err}", entry.path());
+            continue;
+          }
+        };
+        let is_symlink = entry
+          .file_type()
+          .is_ok_and(|file_type| file_type.is_symlink());
+        let items = metadata.is_dir().then(|| count_items(&entry.path()));
+        entries.push(Entry {
+          items,
+          is_symlink,
+          ..to_entry(name, &metadata)
+        });
+      }
+      Ok(entries)
+    })
+    .await?
+  }
+
+  async fn stat(&self, path: &str) -> io::Result<Entry> {
+    let path_buf = self.resolve(path);
+    let is_symlink = fs::symlink_metadata(&path_buf).await?.is_symlink();
+    let metadata = fs::metadata(&path_buf).await?;
+    Ok(Entry {
+      is_symlink,
+      ..to_entry(file_name(path), &metadata)
+    })
+  }
+
+  async fn read(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+    let path = self.resolve(path);
+    if fs::metadata(&path).await?.is_dir() {
+      return Err(io::Error::other("目标是文件夹"));
+    }
+    let mut file = fs::File::open(&path).await?;
+    match range {
+      Some((start, length)) => {
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(ReaderStream::new(file.take(length)).boxed())
+      }
+      None => Ok(ReaderStream::new(file).boxed()),
+    }
+  }
+
+  async fn write(&self, path: &str, mut data: ByteStream) -> io::Result<u64> {
+    // 先写入临时文件，完成后重命名到目标位置
+    let temp = temp_file(&self.root).await?;
+    let written = async {
+      let mut file = fs::File::create(&temp).await?;
+      let mut size = 0;
+      while let Some(chunk) = data.try_next().await? {
+        size += chunk.len() as u64;
+        file.write_all(&chunk).await?;
+      }
+      file.sync_all().await?;
+      Ok(size)
+    }
+    .await;
+    match written {
+      Ok(size) => self.put_file(path, &temp).await.map(|_| size),
+      Err(err) => {
+        fs::remove_file(&temp).await.ok();
+        Err(err)
+      }
+    }
+  }
+
+  async fn put_file(&self, path: &str, file: &Path) -> io::Result<u64> {
+    let target = self.resolve(path);
+    let size = fs::metadata(file).await?.len();
+    match fs::rename(file, &target).await {
+      // 目标位置挂载了其它文件系统时退回到复制
+      Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+        let copy =
+          target.with_file_name(format!(".{}.part", hex::encode(rand::random::<[u8; 8]>())));
+        fs::copy(file, &copy).await?;
+        fs::rename(&copy, &target).await?;
+        fs::remove_file(file).await?;
+      }
+      result => result?,
+    }
+    Ok(size)
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+    fs::rename(self.resolve(from), self.resolve(to)).await
+  }
+
+  async fn delete(&self, path: &str) -> io::Result<()> {
+    let path = self.resolve(path);
+    if fs::metadata(&path).await?.is_dir() {
+      fs::remove_dir_all(&path).await
+    } else {
+      fs::remove_file(&path).await
+    }
+  }
+
+  async fn mkdir(&self, path: &str) -> io::Result<()> {
+    fs::create_dir_all(self.resolve(path)).await
+  }
+}
+
+fn to_entry(name: String, metadata: &Metadata) -> Entry {
+  Entry {
+    name,
+    is_dir: metadata.is_dir(),
+    size: if metadata.is_dir() { 0 } else { metadata.len() },
+    modified: metadata.modified().ok(),
+    items: None,
+    is_symlink: false,
+  }
+}
+
+fn file_name(path: &str) -> String {
+  path.rsplit('/').next().unwrap_or_default().to_string()
+}
+
+/// 文件夹内的条目数，不包括系统文件
+fn count_items(dir: &Path) -> usize {
+  std::fs::read_dir(dir)
+    .map(|it| {
+      it.flatten()
+        .filter(|e| {
+          e.file_name()
+            .to_str()
+            .is_some_and(|n| !utils::file::is_system_file(n))
+        })
+        .count()
+    })
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backend::utils::backend::{once, read_all};
+
+  #[tokio::test]
+  async fn test_local_backend() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-backend-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    std::fs::create_dir_all(&root).unwrap();
+    let backend = LocalBackend::new(root.clone());
+
+    backend.mkdir("a/b").await.unwrap();
+    let size = backend
+      .write("a/b/c.txt", once("hello world".into()))
+      .await
+      .unwrap();
+    assert_eq!(size, 11);
+
+    let entries = backend.list("a").await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].is_dir);
+    assert_eq!(entries[0].items, Some(1));
+
+    let entry = backend.stat("a/b/c.txt").await.unwrap();
+    assert_eq!((entry.name.as_str(), entry.size), ("c.txt", 11));
+
+    let mut stream = backend.read("a/b/c.txt", Some((6, 3))).await.unwrap();
+    let chunk = stream.next().await.unwrap().unwrap();
+    assert_eq!(&chunk[..], b"wor");
+
+    backend.rename("a/b", "a/d").await.unwrap();
+    assert_eq!(
+      read_all(&backend, "a/d/c.txt").await.unwrap(),
+      b"hello world"
+    );
+    assert!(!backend.exists("a/b").await.unwrap());
+
+    backend.delete("a").await.unwrap();
+    assert_eq!(
+      backend.stat("a").await.unwrap_err().kind(),
+      io::ErrorKind::NotFound
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -0,0 +1,225 @@
+pub mod local;
+pub mod s3;
+pub mod sftp;
+use std::{
+  collections::HashMap,
+  io,
+  path::{Path, PathBuf},
+  sync::{Arc, LazyLock, Mutex},
+  time::SystemTime,
+};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::{StreamExt, stream::BoxStream};
+use tokio_util::io::ReaderStream;
+
+use crate::backend::db::storage::{StorageDatabase, StorageKind};
+
+pub use local::LocalBackend;
+pub use s3::S3Backend;
+pub use sftp::SftpBackend;
+
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// 文件或文件夹的信息
+#[derive(Debug, Clone)]
+pub struct Entry {
+  pub name: String,
+  pub is_dir: bool,
+  /// 文件夹为 0
+  pub size: u64,
+  pub modified: Option<SystemTime>,
+  /// 文件夹内的条目数，只有能直接取到时才有值
+  pub items: Option<usize>,
+  /// 符号链接，`is_dir` 等信息是链接目标的。递归遍历时需要跳过，
+  /// 避免循环或者读到存储之外的文件
+  pub is_symlink: bool,
+}
+
+/// 存储后端。路径都是相对于存储根目录、以 `/` 分隔的路径，空字符串表示根目录，
+/// 调用方负责校验路径。不存在的路径返回 `io::ErrorKind::NotFound`
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+  /// 列出文件夹下的直接子项
+  async fn list(&self, path: &str) -> io::Result<Vec<Entry>>;
+
+  async fn stat(&self, path: &str) -> io::Result<Entry>;
+
+  /// 读取文件，`range` 为起始位置和长度
+  async fn read(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream>;
+
+  /// 写入文件，已存在时覆盖，返回写入的字节数。上级文件夹需要已经存在。
+  /// 失败时原文件保持不变，读取方不会看到写了一半的文件。本地和 S3 的替换是原子的，
+  /// SFTP 替换已有文件时先把原文件移开，期间读取可能短暂地找不到文件
+  async fn write(&self, path: &str, data: ByteStream) -> io::Result<u64>;
+
+  /// 把本地临时文件写入 `path`，完成后删除临时文件。临时文件需要在存储根目录的
+  /// `.storkitty` 下，本地后端直接重命名
+  async fn put_file(&self, path: &str, file: &Path) -> io::Result<u64> {
+    let data = tokio::fs::File::open(file).await?;
+    let size = self.write(path, ReaderStream::new(data).boxed()).await?;
+    tokio::fs::remove_file(file).await?;
+    Ok(size)
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+  /// 删除文件或整个文件夹
+  async fn delete(&self, path: &str) -> io::Result<()>;
+
+  /// 创建文件夹，上级文件夹不存在时一并创建
+  async fn mkdir(&self, path: &str) -> io::Result<()>;
+
+  /// 不存在时返回 None
+  async fn try_stat(&self, path: &str) -> io::Result<Option<Entry>> {
+    match self.stat(path).await {
+      Ok(entry) => Ok(Some(entry)),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+
+  async fn exists(&self, path: &str) -> io::Result<bool> {
+    Ok(self.try_stat(path).await?.is_some())
+  }
+}
+
+/// 按存储配置取得后端。远程后端持有连接，按存储 id 缓存，配置修改后重新创建
+pub fn open(storage: &StorageDatabase) -> anyhow::Result<Arc<dyn StorageBackend>> {
+  /// 存储 id -> (updated_at, 后端)
+  type Cache = HashMap<i64, (String, Arc<dyn StorageBackend>)>;
+  static BACKENDS: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+  if storage.kind == StorageKind::Local {
+    return create(storage.kind, &storage.config, &storage.local_path);
+  }
+
+  let mut backends = BACKENDS.lock().unwrap_or_else(|err| err.into_inner());
+  if let Some((version, backend)) = backends.get(&storage.id)
+    && *version == storage.updated_at
+  {
+    return Ok(backend.clone());
+  }
+  let backend = create(storage.kind, &storage.config, &storage.local_path)?;
+  backends.insert(storage.id, (storage.updated_at.clone(), backend.clone()));
+  Ok(backend)
+}
+
+/// 根据类型和配置创建后端，不会建立连接
+pub fn create(
+  kind: StorageKind,
+  config: &serde_json::Value,
+  local_path: &str,
+) -> anyhow::Result<Arc<dyn StorageBackend>> {
+  let backend: Arc<dyn StorageBackend> = match kind {
+    StorageKind::Local => Arc::new(LocalBackend::new(PathBuf::from(local_path))),
+    StorageKind::S3 => Arc::new(S3Backend::new(
+      serde_json::from_value(config.clone())
+        .map_err(|err| anyhow::anyhow!("S3 存储配置不合法: {}", err))?,
+    )?),
+    StorageKind::Sftp => Arc::new(SftpBackend::new(
+      serde_json::from_value(config.clone())
+        .map_err(|err| anyhow::anyhow!("SFTP 存储配置不合法: {}", err))?,
+    )),
+  };
+  Ok(backend)
+}
+
+/// 保存存储配置前检查后端可以访问。SFTP 存储没有配置服务器公钥指纹时，
+/// 记录这次连接到的指纹，之后的连接都要求一致
+pub async fn verify(
+  kind: StorageKind,
+  config: &mut serde_json::Value,
+  local_path: &str,
+) -> anyhow::Result<()> {
+  if kind == StorageKind::Sftp {
+    sftp::pin_host_key(config).await?;
+  }
+  let backend = create(kind, config, local_path)?;
+  backend
+    .list("")
+    .await
+    .map_err(|err| anyhow::anyhow!("无法访问存储: {}", err))?;
+  Ok(())
+}
+
+/// 配置中的密钥字段，不返回给前端
+const SECRET_FIELDS: [&str; 3] = ["secretAccessKey", "password", "privateKey"];
+
+/// 去掉配置中的密钥
+pub fn redact_config(config: &mut serde_json::Value) {
+  if let Some(config) = config.as_object_mut() {
+    for field in SECRET_FIELDS {
+      config.remove(field);
+    }
+  }
+}
+
+/// 更新配置时没有提交的密钥沿用 `existing` 中的值
+pub fn keep_secrets(config: &mut serde_json::Value, existing: &serde_json::Value) {
+  let Some(config) = config.as_object_mut() else {
+    return;
+  };
+  for field in SECRET_FIELDS {
+    if config.get(field).is_none_or(serde_json::Value::is_null)
+      && let Some(value) = existing.get(field)
+    {
+      config.insert(field.to_string(), value.clone());
+    }
+  }
+}
+
+/// 拼接相对路径
+pub fn join(dir: &str, name: &str) -> String {
+  match dir.trim_end_matches('/') {
+    "" => name.to_string(),
+    dir => format!("{}/{}", dir, name),
+  }
+}
+
+/// 存储根目录下存放临时文件的目录，与存储在同一文件系统中，可以直接重命名
+pub fn temp_dir(root: &Path) -> PathBuf {
+  root.join(".storkitty").join("tmp")
+}
+
+/// 在 [`temp_dir`] 中生成一个临时文件路径
+pub async fn temp_file(root: &Path) -> io::Result<PathBuf> {
+  let dir = temp_dir(root);
+  tokio::fs::create_dir_all(&dir).await?;
+  Ok(dir.join(format!("{}.part", hex::encode(rand::random::<[u8; 8]>()))))
+}
+
+/// 单块数据作为写入内容
+pub fn once(data: Bytes) -> ByteStream {
+  futures_util::stream::once(async move { Ok(data) }).boxed()
+}
+
+/// 读取整个文件
+pub async fn read_all(backend: &dyn StorageBackend, path: &str) -> io::Result<Vec<u8>> {
+  let mut stream = backend.read(path, None).await?;
+  let mut data = Vec::new();
+  while let Some(chunk) = stream.next().await {
+    data.extend_from_slice(&chunk?);
+  }
+  Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_secrets() {
+    let existing = serde_json::json!({ "host": "a", "username": "u", "password": "old" });
+    let mut config = existing.clone();
+    redact_config(&mut config);
+    assert_eq!(config, serde_json::json!({ "host": "a", "username": "u" }));
+
+    keep_secrets(&mut config, &existing);
+    assert_eq!(config["password"], "old");
+    let mut config = serde_json::json!({ "host": "a", "password": "new" });
+    keep_secrets(&mut config, &existing);
+    assert_eq!(config["password"], "new");
+  }
+}
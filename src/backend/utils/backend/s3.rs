@@ -0,0 +1,554 @@
+use std::{
+  io,
+  time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::backend::utils::{
+  sigv4::{self, Credentials, encode_query, uri_encode},
+  xml::{escape_xml, unescape_xml},
+};
+
+use super::{ByteStream, Entry, StorageBackend, join};
+
+/// 超过该大小时使用分片上传
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Config {
+  /// 例如 `https://s3.us-east-1.amazonaws.com`、`http://127.0.0.1:9000`
+  pub endpoint: String,
+  #[serde(default = "default_region")]
+  pub region: String,
+  pub bucket: String,
+  /// 存储在 bucket 内的前缀，为空时使用整个 bucket
+  #[serde(default)]
+  pub prefix: String,
+  pub access_key_id: String,
+  pub secret_access_key: String,
+}
+
+fn default_region() -> String {
+  "us-east-1".to_string()
+}
+
+/// S3 兼容的对象存储，以 path-style 访问。
+/// 文件夹对应以 `/` 结尾的前缀，空文件夹用同名的空对象标记
+pub struct S3Backend {
+  config: S3Config,
+  client: Client,
+  endpoint: Url,
+  host: String,
+}
+
+/// ListObjectsV2 的一页结果
+#[derive(Default)]
+struct ObjectPage {
+  objects: Vec<(String, u64, Option<SystemTime>)>,
+  prefixes: Vec<String>,
+  next_token: Option<String>,
+}
+
+impl S3Backend {
+  pub fn new(mut config: S3Config) -> anyhow::Result<Self> {
+    let endpoint = Url::parse(&config.endpoint).context("S3 endpoint 不合法")?;
+    let host = match (endpoint.host_str(), endpoint.port()) {
+      (Some(host), Some(port)) => format!("{}:{}", host, port),
+      (Some(host), None) => host.to_string(),
+      _ => anyhow::bail!("S3 endpoint 不合法"),
+    };
+    config.prefix = config.prefix.trim_matches('/').to_string();
+    let client = Client::builder()
+      .connect_timeout(Duration::from_secs(10))
+      .build()
+      .context("创建 HTTP 客户端失败")?;
+    Ok(Self {
+      config,
+      client,
+      endpoint,
+      host,
+    })
+  }
+
+  fn key(&self, path: &str) -> String {
+    join(&self.config.prefix, path.trim_matches('/'))
+  }
+
+  /// 文件夹下的对象都以该前缀开头，根目录为 bucket 前缀
+  fn dir_prefix(&self, path: &str) -> String {
+    match self.key(path).as_str() {
+      "" => String::new(),
+      key => format!("{}/", key),
+    }
+  }
+
+  async fn request(
+    &self,
+    method: Method,
+    key: &str,
+    query: &[(&str, &str)],
+    headers: &[(&str, &str)],
+    body: Option<Bytes>,
+  ) -> io::Result<Response> {
+    let path = format!(
+      "{}/{}/{}",
+      self.endpoint.path().trim_end_matches('/'),
+      uri_encode(&self.config.bucket, true),
+      uri_encode(key, false)
+    );
+    let query = encode_query(query);
+    let body = body.unwrap_or_default();
+    let payload_hash = hex::encode(Sha256::digest(&body));
+    let credentials = Credentials {
+      access_key_id: &self.config.access_key_id,
+      secret_access_key: &self.config.secret_access_key,
+      region: &self.config.region,
+    };
+    let signed = sigv4::sign_request(
+      &credentials,
+      method.as_str(),
+      &self.host,
+      &path,
+      &query,
+      headers,
+      &payload_hash,
+      chrono::Utc::now(),
+    );
+
+    let mut url = self.endpoint.clone();
+    url.set_path(&path);
+    url.set_query((!query.is_empty()).then_some(query.as_str()));
+    let mut builder = self.client.request(method, url).body(body);
+    for (name, value) in headers {
+      builder = builder.header(*name, *value);
+    }
+    for (name, value) in signed {
+      builder = builder.header(name, value);
+    }
+    let response = builder.send().await.map_err(io::Error::other)?;
+    check(response).await
+  }
+
+  async fn list_page(
+    &self,
+    prefix: &str,
+    delimiter: bool,
+    token: Option<&str>,
+  ) -> io::Result<ObjectPage> {
+    let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+    if delimiter {
+      query.push(("delimiter", "/"));
+    }
+    if let Some(token) = token {
+      query.push(("continuation-token", token));
+    }
+    let body = self
+      .request(Method::GET, "", &query, &[], None)
+      .await?
+      .text()
+      .await
+      .map_err(io::Error::other)?;
+
+    let mut page = ObjectPage::default();
+    for contents in find_all(&body, "Contents") {
+      let Some(key) = find(contents, "Key") else {
+        continue;
+      };
+      let size = find(contents, "Size")
+        .and_then(|size| size.parse().ok())
+        .unwrap_or_default();
+      let modified = find(contents, "LastModified")
+        .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+        .map(SystemTime::from);
+      page.objects.push((unescape_xml(key), size, modified));
+    }
+    for common in find_all(&body, "CommonPrefixes") {
+      if let Some(prefix) = find(common, "Prefix") {
+        page.prefixes.push(unescape_xml(prefix));
+      }
+    }
+    if find(&body, "IsTruncated") == Some("true") {
+      page.next_token = find(&body, "NextContinuationToken").map(unescape_xml);
+    }
+    Ok(page)
+  }
+
+  /// 前缀下所有对象的键
+  async fn list_keys(&self, prefix: &str) -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut token = None;
+    loop {
+      let page = self.list_page(prefix, false, token.as_deref()).await?;
+      keys.extend(page.objects.into_iter().map(|(key, ..)| key));
+      match page.next_token {
+        Some(next) => token = Some(next),
+        None => return Ok(keys),
+      }
+    }
+  }
+
+  async fn copy_object(&self, from: &str, to: &str) -> io::Result<()> {
+    let source = format!(
+      "/{}/{}",
+      uri_encode(&self.config.bucket, true),
+      uri_encode(from, false)
+    );
+    let response = self
+      .request(
+        Method::PUT,
+        to,
+        &[],
+        &[("x-amz-copy-source", &source)],
+        None,
+      )
+      .await?;
+    // 复制失败时也可能返回 200，需要检查响应体
+    let body = response.text().await.map_err(io::Error::other)?;
+    if let Some(code) = find(&body, "Code") {
+      return Err(io::Error::other(format!("S3 复制对象失败: {}", code)));
+    }
+    Ok(())
+  }
+
+  async fn put_object(&self, key: &str, data: Bytes) -> io::Result<()> {
+    self.request(Method::PUT, key, &[], &[], Some(data)).await?;
+    Ok(())
+  }
+
+  async fn delete_object(&self, key: &str) -> io::Result<()> {
+    self.request(Method::DELETE, key, &[], &[], None).await?;
+    Ok(())
+  }
+
+  /// 倒序删除，保证文件夹标记在文件夹内的对象之后删除。
+  /// 有的服务不会列出非空文件夹的标记，最后再单独删除一次
+  async fn delete_dir(&self, prefix: &str, keys: &[String]) -> io::Result<()> {
+    for key in keys.iter().rev() {
+      self.delete_object(key).await?;
+    }
+    if !keys.iter().any(|key| key == prefix) {
+      self.delete_object(prefix).await?;
+    }
+    Ok(())
+  }
+
+  async fn multipart_upload(
+    &self,
+    key: &str,
+    first: Vec<u8>,
+    data: &mut ByteStream,
+  ) -> io::Result<u64> {
+    let body = self
+      .request(Method::POST, key, &[("uploads", "")], &[], None)
+      .await?
+      .text()
+      .await
+      .map_err(io::Error::other)?;
+    let upload_id = find(&body, "UploadId")
+      .map(unescape_xml)
+      .ok_or_else(|| io::Error::other("S3 未返回 UploadId"))?;
+
+    let result = async {
+      let mut size = first.len() as u64;
+      let mut etags = vec![self.upload_part(key, &upload_id, 1, first).await?];
+      loop {
+        let (part, ended) = next_part(data).await?;
+        if !part.is_empty() {
+          size += part.len() as u64;
+          let number = etags.len() + 1;
+          etags.push(self.upload_part(key, &upload_id, number, part).await?);
+        }
+        if ended {
+          break;
+        }
+      }
+
+      let mut complete = String::from("<CompleteMultipartUpload>");
+      for (index, etag) in etags.iter().enumerate() {
+        complete.push_str(&format!(
+          "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+          index + 1,
+          escape_xml(etag)
+        ));
+      }
+      complete.push_str("</CompleteMultipartUpload>");
+      let response = self
+        .request(
+          Method::POST,
+          key,
+          &[("uploadId", &upload_id)],
+          &[],
+          Some(complete.into()),
+        )
+        .await?;
+      let body = response.text().await.map_err(io::Error::other)?;
+      if let Some(code) = find(&body, "Code") {
+        return Err(io::Error::other(format!("S3 合并分片失败: {}", code)));
+      }
+      Ok(size)
+    }
+    .await;
+
+    if result.is_err() {
+      let abort = self
+        .request(Method::DELETE, key, &[("uploadId", &upload_id)], &[], None)
+        .await;
+      if let Err(err) = abort {
+        log::warn!("Failed to abort multipart upload {}: {err}", upload_id);
+      }
+    }
+    result
+  }
+
+  async fn upload_part(
+    &self,
+    key: &str,
+    upload_id: &str,
+    number: usize,
+    data: Vec<u8>,
+  ) -> io::Result<String> {
+    let number = number.to_string();
+    let response = self
+      .request(
+        Method::PUT,
+        key,
+        &[("partNumber", &number), ("uploadId", upload_id)],
+        &[],
+        Some(data.into()),
+      )
+      .await?;
+    response
+      .headers()
+      .get("etag")
+      .and_then(|value| value.to_str().ok())
+      .map(|value| value.to_string())
+      .ok_or_else(|| io::Error::other("S3 未返回分片的 ETag"))
+  }
+}
+
+#[async_trait]
+impl StorageBackend for S3Backend {
+  async fn list(&self, path: &str) -> io::Result<Vec<Entry>> {
+    let prefix = self.dir_prefix(path);
+    let mut entries = Vec::new();
+    let mut found = path.is_empty();
+    let mut token = None;
+    loop {
+      let page = self.list_page(&prefix, true, token.as_deref()).await?;
+      found |= !page.objects.is_empty() || !page.prefixes.is_empty();
+      for (key, size, modified) in page.objects {
+        let name = key.strip_prefix(&prefix).unwrap_or(&key);
+        // 跳过文件夹自身的标记对象
+        if name.is_empty() || name.ends_with('/') {
+          continue;
+        }
+        entries.push(Entry {
+          name: name.to_string(),
+          is_dir: false,
+          size,
+          modified,
+          items: None,
+          is_symlink: false,
+        });
+      }
+      for dir in page.prefixes {
+        let name = dir.strip_prefix(&prefix).unwrap_or(&dir);
+        entries.push(Entry {
+          name: name.trim_end_matches('/').to_string(),
+          is_dir: true,
+          size: 0,
+          modified: None,
+          items: None,
+          is_symlink: false,
+        });
+      }
+      match page.next_token {
+        Some(next) => token = Some(next),
+        None => break,
+      }
+    }
+    if !found {
+      return Err(io::ErrorKind::NotFound.into());
+    }
+    Ok(entries)
+  }
+
+  async fn stat(&self, path: &str) -> io::Result<Entry> {
+    let name = path.rsplit('/').next().unwrap_or_default().to_string();
+    let dir = Entry {
+      name: name.clone(),
+      is_dir: true,
+      size: 0,
+      modified: None,
+      items: None,
+      is_symlink: false,
+    };
+    if path.is_empty() {
+      return Ok(dir);
+    }
+
+    match self
+      .request(Method::HEAD, &self.key(path), &[], &[], None)
+      .await
+    {
+      Ok(response) => {
+        let header = |name: &str| {
+          response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+        };
+        return Ok(Entry {
+          name,
+          is_dir: false,
+          size: header("content-length")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or_default(),
+          modified: header("last-modified").and_then(|time| httpdate::parse_http_date(time).ok()),
+          items: None,
+          is_symlink: false,
+        });
+      }
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+      Err(err) => return Err(err),
+    }
+
+    let page = self.list_page(&self.dir_prefix(path), true, None).await?;
+    if page.objects.is_empty() && page.prefixes.is_empty() {
+      return Err(io::ErrorKind::NotFound.into());
+    }
+    Ok(dir)
+  }
+
+  async fn read(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+    let range = match range {
+      Some((_, 0)) => return Ok(futures_util::stream::empty().boxed()),
+      Some((start, length)) => Some(format!("bytes={}-{}", start, start + length - 1)),
+      None => None,
+    };
+    let mut headers = Vec::new();
+    if let Some(range) = &range {
+      headers.push(("range", range.as_str()));
+    }
+    let response = self
+      .request(Method::GET, &self.key(path), &[], &headers, None)
+      .await?;
+    Ok(response.bytes_stream().map_err(io::Error::other).boxed())
+  }
+
+  async fn write(&self, path: &str, mut data: ByteStream) -> io::Result<u64> {
+    let key = self.key(path);
+    let (first, ended) = next_part(&mut data).await?;
+    if !ended {
+      return self.multipart_upload(&key, first, &mut data).await;
+    }
+    let size = first.len() as u64;
+    self.put_object(&key, first.into()).await?;
+    Ok(size)
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+    if !self.stat(from).await?.is_dir {
+      let (from, to) = (self.key(from), self.key(to));
+      self.copy_object(&from, &to).await?;
+      return self.delete_object(&from).await;
+    }
+
+    // S3 没有重命名，逐个复制文件夹下的对象后再删除
+    let (from, to) = (self.dir_prefix(from), self.dir_prefix(to));
+    let keys = self.list_keys(&from).await?;
+    for key in &keys {
+      let target = format!("{}{}", to, &key[from.len()..]);
+      self.copy_object(key, &target).await?;
+    }
+    if !keys.contains(&from) {
+      self.put_object(&to, Bytes::new()).await?;
+    }
+    self.delete_dir(&from, &keys).await
+  }
+
+  async fn delete(&self, path: &str) -> io::Result<()> {
+    if !self.stat(path).await?.is_dir {
+      return self.delete_object(&self.key(path)).await;
+    }
+    let prefix = self.dir_prefix(path);
+    let keys = self.list_keys(&prefix).await?;
+    self.delete_dir(&prefix, &keys).await
+  }
+
+  async fn mkdir(&self, path: &str) -> io::Result<()> {
+    if path.is_empty() {
+      return Ok(());
+    }
+    self.put_object(&self.dir_prefix(path), Bytes::new()).await
+  }
+}
+
+/// 读取不少于 `PART_SIZE` 的数据，第二个值表示数据是否已经读完
+async fn next_part(data: &mut ByteStream) -> io::Result<(Vec<u8>, bool)> {
+  let mut part = Vec::new();
+  while part.len() < PART_SIZE {
+    match data.try_next().await? {
+      Some(chunk) => part.extend_from_slice(&chunk),
+      None => return Ok((part, true)),
+    }
+  }
+  Ok((part, false))
+}
+
+async fn check(response: Response) -> io::Result<Response> {
+  let status = response.status();
+  if status.is_success() {
+    return Ok(response);
+  }
+  if status == StatusCode::NOT_FOUND {
+    return Err(io::ErrorKind::NotFound.into());
+  }
+  let body = response.text().await.unwrap_or_default();
+  let code = find(&body, "Code").unwrap_or(status.as_str());
+  Err(io::Error::other(format!(
+    "S3 请求失败: {} {}",
+    status, code
+  )))
+}
+
+/// 取第一个 `<tag>...</tag>` 的内容
+fn find<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+  find_all(xml, tag).next()
+}
+
+fn find_all<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+  let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+  let mut rest = xml;
+  std::iter::from_fn(move || {
+    let start = rest.find(&open)? + open.len();
+    let end = start + rest[start..].find(&close)?;
+    let value = &rest[start..end];
+    rest = &rest[end + close.len()..];
+    Some(value)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_find_tags() {
+    let xml = "<ListBucketResult><IsTruncated>false</IsTruncated><Contents><Key>a&amp;b.txt</Key><Size>3</Size></Contents><Contents><Key>c/</Key></Contents></ListBucketResult>";
+    let keys: Vec<&str> = find_all(xml, "Contents")
+      .filter_map(|contents| find(contents, "Key"))
+      .collect();
+    assert_eq!(keys, ["a&amp;b.txt", "c/"]);
+    assert_eq!(find(xml, "IsTruncated"), Some("false"));
+    assert_eq!(find(xml, "NextContinuationToken"), None);
+  }
+}
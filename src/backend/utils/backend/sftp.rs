@@ -0,0 +1,425 @@
+use std::{
+  io::{self, Read, Seek, SeekFrom, Write},
+  net::TcpStream,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  time::{Duration, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use ssh2::{FileStat, HashType, Session, Sftp};
+use tokio::sync::mpsc;
+
+use super::{ByteStream, Entry, StorageBackend};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SftpConfig {
+  pub host: String,
+  #[serde(default = "default_port")]
+  pub port: u16,
+  pub username: String,
+  #[serde(default)]
+  pub password: Option<String>,
+  /// PEM 格式的私钥，与密码二选一
+  #[serde(default)]
+  pub private_key: Option<String>,
+  /// 服务器公钥的 SHA256 指纹，格式与 `ssh-keygen -l` 输出的一致，例如 `SHA256:...`。
+  /// 保存存储时为空则记录当时服务器的指纹，没有指纹时拒绝连接
+  #[serde(default)]
+  pub host_key_sha256: Option<String>,
+  /// 服务器上作为存储根目录的路径
+  #[serde(default = "default_root")]
+  pub root: String,
+}
+
+fn default_port() -> u16 {
+  22
+}
+
+fn default_root() -> String {
+  ".".to_string()
+}
+
+/// SFTP 服务器上的目录。libssh2 是阻塞的，所有操作都在 `spawn_blocking` 中执行
+pub struct SftpBackend {
+  inner: Arc<Inner>,
+}
+
+struct Inner {
+  config: SftpConfig,
+  /// 空闲的连接，用完后放回，并发时临时建立新连接
+  idle: Mutex<Option<Sftp>>,
+}
+
+impl SftpBackend {
+  pub fn new(config: SftpConfig) -> Self {
+    Self {
+      inner: Arc::new(Inner {
+        config,
+        idle: Mutex::new(None),
+      }),
+    }
+  }
+
+  /// 在阻塞线程中使用一个连接执行操作，出错时丢弃该连接
+  async fn run<T, F>(&self, path: &str, f: F) -> io::Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce(&Sftp, PathBuf) -> io::Result<T> + Send + 'static,
+  {
+    let inner = self.inner.clone();
+    let path = inner.resolve(path);
+    tokio::task::spawn_blocking(move || {
+      let sftp = inner.take()?;
+      let result = f(&sftp, path);
+      if keeps_connection(&result) {
+        inner.give_back(sftp);
+      }
+      result
+    })
+    .await?
+  }
+}
+
+impl Inner {
+  fn resolve(&self, path: &str) -> PathBuf {
+    match path {
+      "" => PathBuf::from(&self.config.root),
+      path => Path::new(&self.config.root).join(path),
+    }
+  }
+
+  fn take(&self) -> io::Result<Sftp> {
+    let idle = self
+      .idle
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .take();
+    match idle {
+      Some(sftp) => Ok(sftp),
+      None => self.connect(),
+    }
+  }
+
+  fn give_back(&self, sftp: Sftp) {
+    let mut idle = self.idle.lock().unwrap_or_else(|err| err.into_inner());
+    if idle.is_none() {
+      *idle = Some(sftp);
+    }
+  }
+
+  fn connect(&self) -> io::Result<Sftp> {
+    let config = &self.config;
+    let session = handshake(config)?;
+    let Some(expected) = config
+      .host_key_sha256
+      .as_deref()
+      .filter(|expected| !expected.trim().is_empty())
+    else {
+      return Err(io::Error::other("SFTP 存储没有配置服务器公钥指纹"));
+    };
+    let expected = expected
+      .trim()
+      .trim_start_matches("SHA256:")
+      .trim_end_matches('=');
+    if host_key_sha256(&session).trim_start_matches("SHA256:") != expected {
+      return Err(io::Error::other("SFTP 服务器公钥指纹不一致"));
+    }
+
+    match (&config.private_key, &config.password) {
+      (Some(key), password) if !key.is_empty() => {
+        session.userauth_pubkey_memory(&config.username, None, key, password.as_deref())?
+      }
+      (_, Some(password)) => session.userauth_password(&config.username, password)?,
+      _ => return Err(io::Error::other("SFTP 存储需要配置密码或私钥")),
+    }
+    // 连接建立后不再限制单次操作的时间，避免大文件传输超时
+    session.set_timeout(0);
+    Ok(session.sftp()?)
+  }
+}
+
+fn handshake(config: &SftpConfig) -> io::Result<Session> {
+  let addr = std::net::ToSocketAddrs::to_socket_addrs(&(config.host.as_str(), config.port))?
+    .next()
+    .ok_or_else(|| io::Error::other("无法解析 SFTP 服务器地址"))?;
+  let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+  let mut session = Session::new()?;
+  session.set_tcp_stream(tcp);
+  session.set_timeout(CONNECT_TIMEOUT.as_millis() as u32);
+  session.handshake()?;
+  Ok(session)
+}
+
+/// `SHA256:...` 格式的服务器公钥指纹
+fn host_key_sha256(session: &Session) -> String {
+  let hash = session
+    .host_key_hash(HashType::Sha256)
+    .map(|hash| STANDARD_NO_PAD.encode(hash))
+    .unwrap_or_default();
+  format!("SHA256:{}", hash)
+}
+
+/// 配置中没有服务器公钥指纹时连接服务器，把当前的指纹写入配置
+pub async fn pin_host_key(config: &mut serde_json::Value) -> anyhow::Result<()> {
+  let parsed: SftpConfig = serde_json::from_value(config.clone())
+    .map_err(|err| anyhow::anyhow!("SFTP 存储配置不合法: {}", err))?;
+  if parsed
+    .host_key_sha256
+    .as_deref()
+    .is_some_and(|expected| !expected.trim().is_empty())
+  {
+    return Ok(());
+  }
+  let fingerprint = tokio::task::spawn_blocking(move || {
+    handshake(&parsed).map(|session| host_key_sha256(&session))
+  })
+  .await?
+  .map_err(|err| anyhow::anyhow!("无法连接 SFTP 服务器: {}", err))?;
+  if let Some(config) = config.as_object_mut() {
+    config.insert("hostKeySha256".to_string(), fingerprint.into());
+  }
+  Ok(())
+}
+
+/// 同一目录下带随机后缀的隐藏文件名
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(
+    ".{}.{}.{}",
+    name,
+    hex::encode(rand::random::<[u8; 4]>()),
+    suffix
+  ))
+}
+
+/// 用 `temp` 替换 `path`。SFTP v3 的 rename 不能覆盖已有文件，目标存在时
+/// 先把原文件移到一旁，新文件就位后再删除，失败时移回原文件，原文件不会丢失
+fn replace(sftp: &Sftp, temp: &Path, path: &Path) -> io::Result<()> {
+  let Err(err) = sftp.rename(temp, path, None) else {
+    return Ok(());
+  };
+  let backup = sibling(path, "old");
+  if sftp.rename(path, &backup, None).is_err() {
+    return Err(err.into());
+  }
+  if let Err(err) = sftp.rename(temp, path, None) {
+    sftp.rename(&backup, path, None).ok();
+    return Err(err.into());
+  }
+  sftp.unlink(&backup).ok();
+  Ok(())
+}
+
+/// 文件不存在不代表连接有问题，其它错误时丢弃连接
+fn keeps_connection<T>(result: &io::Result<T>) -> bool {
+  match result {
+    Ok(_) => true,
+    Err(err) => err.kind() == io::ErrorKind::NotFound,
+  }
+}
+
+#[async_trait]
+impl StorageBackend for SftpBackend {
+  async fn list(&self, path: &str) -> io::Result<Vec<Entry>> {
+    self
+      .run(path, |sftp, dir| {
+        Ok(
+          sftp
+            .readdir(&dir)?
+            .into_iter()
+            .filter_map(|(path, stat)| {
+              let name = path.file_name()?.to_str()?.to_string();
+              Some(to_entry(name, &stat))
+            })
+            .collect(),
+        )
+      })
+      .await
+  }
+
+  async fn stat(&self, path: &str) -> io::Result<Entry> {
+    let name = path.rsplit('/').next().unwrap_or_default().to_string();
+    self
+      .run(path, move |sftp, path| {
+        Ok(to_entry(name, &sftp.stat(&path)?))
+      })
+      .await
+  }
+
+  async fn read(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ByteStream> {
+    // 先在当前连接上打开文件，出错时直接返回，之后在单独的线程中读取
+    let inner = self.inner.clone();
+    let path = inner.resolve(path);
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(4);
+    let (opened_tx, opened_rx) = tokio::sync::oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+      let opened = (|| {
+        let sftp = inner.take()?;
+        if sftp.stat(&path)?.is_dir() {
+          return Err(io::Error::other("目标是文件夹"));
+        }
+        let mut file = sftp.open(&path)?;
+        if let Some((start, _)) = range {
+          file.seek(SeekFrom::Start(start))?;
+        }
+        Ok((sftp, file))
+      })();
+      let (sftp, mut file) = match opened {
+        Ok(opened) => {
+          let _ = opened_tx.send(Ok(()));
+          opened
+        }
+        Err(err) => {
+          let _ = opened_tx.send(Err(err));
+          return;
+        }
+      };
+
+      let mut remaining = range.map(|(_, length)| length).unwrap_or(u64::MAX);
+      let mut buffer = vec![0; READ_BUFFER_SIZE];
+      let mut ok = true;
+      while remaining > 0 {
+        let size = buffer.len().min(remaining.min(usize::MAX as u64) as usize);
+        match file.read(&mut buffer[..size]) {
+          Ok(0) => break,
+          Ok(read) => {
+            remaining -= read as u64;
+            if tx
+              .blocking_send(Ok(Bytes::copy_from_slice(&buffer[..read])))
+              .is_err()
+            {
+              // 客户端已断开
+              break;
+            }
+          }
+          Err(err) => {
+            ok = false;
+            let _ = tx.blocking_send(Err(err));
+            break;
+          }
+        }
+      }
+      drop(file);
+      if ok {
+        inner.give_back(sftp);
+      }
+    });
+
+    opened_rx
+      .await
+      .map_err(|_| io::Error::other("SFTP 读取线程异常退出"))??;
+    Ok(
+      futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+      })
+      .boxed(),
+    )
+  }
+
+  async fn write(&self, path: &str, mut data: ByteStream) -> io::Result<u64> {
+    let (tx, mut rx) = mpsc::channel::<Bytes>(4);
+    // 先写入同一目录下的临时文件，完成后重命名
+    let writer = self.run(path, move |sftp, path| {
+      let temp = sibling(&path, "part");
+      let written = (|| {
+        let mut file = sftp.create(&temp)?;
+        let mut size = 0;
+        while let Some(chunk) = rx.blocking_recv() {
+          size += chunk.len() as u64;
+          file.write_all(&chunk)?;
+        }
+        file.flush()?;
+        Ok(size)
+      })();
+      let result = written.and_then(|size| {
+        replace(sftp, &temp, &path)?;
+        Ok(size)
+      });
+      if result.is_err() {
+        sftp.unlink(&temp).ok();
+      }
+      result
+    });
+    let sender = async move {
+      while let Some(chunk) = data.try_next().await? {
+        if tx.send(chunk).await.is_err() {
+          // 写入线程已经出错退出，错误由写入线程返回
+          break;
+        }
+      }
+      io::Result::Ok(())
+    };
+    let (written, sent) = tokio::join!(writer, sender);
+    let size = written?;
+    sent?;
+    Ok(size)
+  }
+
+  async fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+    let to = self.inner.resolve(to);
+    self
+      .run(from, move |sftp, from| Ok(sftp.rename(&from, &to, None)?))
+      .await
+  }
+
+  async fn delete(&self, path: &str) -> io::Result<()> {
+    self.run(path, |sftp, path| remove_all(sftp, &path)).await
+  }
+
+  async fn mkdir(&self, path: &str) -> io::Result<()> {
+    self
+      .run(path, |sftp, path| {
+        let mut current = PathBuf::new();
+        for component in path.components() {
+          current.push(component);
+          match sftp.stat(&current) {
+            Ok(stat) if stat.is_dir() => continue,
+            Ok(_) => return Err(io::Error::other("路径中存在同名的文件")),
+            Err(_) => sftp.mkdir(&current, 0o755)?,
+          }
+        }
+        Ok(())
+      })
+      .await
+  }
+}
+
+fn remove_all(sftp: &Sftp, path: &Path) -> io::Result<()> {
+  if !sftp.stat(path)?.is_dir() {
+    return Ok(sftp.unlink(path)?);
+  }
+  for (child, stat) in sftp.readdir(path)? {
+    if stat.is_dir() {
+      remove_all(sftp, &child)?;
+    } else {
+      sftp.unlink(&child)?;
+    }
+  }
+  Ok(sftp.rmdir(path)?)
+}
+
+fn to_entry(name: String, stat: &FileStat) -> Entry {
+  Entry {
+    name,
+    is_symlink: stat.file_type().is_symlink(),
+    is_dir: stat.is_dir(),
+    size: if stat.is_dir() {
+      0
+    } else {
+      stat.size.unwrap_or_default()
+    },
+    modified: stat
+      .mtime
+      .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime)),
+    items: None,
+  }
+}
@@ -0,0 +1,120 @@
+use std::{path::Path, sync::LazyLock};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// 推送给客户端的文件变更
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEvent {
+  pub storage_id: i64,
+  /// 相对于存储根目录的路径
+  pub path: String,
+  #[serde(flatten)]
+  pub kind: ChangeKind,
+  /// 触发变更的用户，客户端可以据此忽略自己的操作
+  pub user_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChangeKind {
+  Created,
+  Modified,
+  Deleted,
+  #[serde(rename_all = "camelCase")]
+  Renamed {
+    from: String,
+  },
+  /// 分片上传进度，上传完成后还会发送 Created
+  #[serde(rename_all = "camelCase")]
+  UploadProgress {
+    uploaded_chunks: usize,
+    total_chunks: usize,
+  },
+}
+
+impl ChangeKind {
+  /// SSE 的 event 名称
+  pub fn name(&self) -> &'static str {
+    match self {
+      ChangeKind::Created => "created",
+      ChangeKind::Modified => "modified",
+      ChangeKind::Deleted => "deleted",
+      ChangeKind::Renamed { .. } => "renamed",
+      ChangeKind::UploadProgress { .. } => "uploadProgress",
+    }
+  }
+}
+
+impl ChangeEvent {
+  /// 事件路径（重命名时包括原路径）是否在 `scope` 下
+  pub fn is_within(&self, scope: &str) -> bool {
+    let scope = scope.trim_matches('/');
+    let within = |path: &str| {
+      scope.is_empty()
+        || path == scope
+        || path
+          .strip_prefix(scope)
+          .is_some_and(|rest| rest.starts_with('/'))
+    };
+    within(&self.path) || matches!(&self.kind, ChangeKind::Renamed { from } if within(from))
+  }
+}
+
+static EVENTS: LazyLock<broadcast::Sender<ChangeEvent>> =
+  LazyLock::new(|| broadcast::channel(1024).0);
+
+pub fn subscribe() -> broadcast::Receiver<ChangeEvent> {
+  EVENTS.subscribe()
+}
+
+/// 发布变更，`target` 为本地绝对路径
+pub fn publish(storage_id: i64, root: &Path, target: &Path, kind: ChangeKind, user_id: i64) {
+  let Some(path) = relative_path(root, target) else {
+    return;
+  };
+  // 没有客户端订阅时发送失败，可以忽略
+  let _ = EVENTS.send(ChangeEvent {
+    storage_id,
+    path,
+    kind,
+    user_id,
+  });
+}
+
+/// 发布重命名或移动
+pub fn publish_rename(storage_id: i64, root: &Path, from: &Path, to: &Path, user_id: i64) {
+  if let Some(from) = relative_path(root, from) {
+    publish(storage_id, root, to, ChangeKind::Renamed { from }, user_id);
+  }
+}
+
+fn relative_path(root: &Path, target: &Path) -> Option<String> {
+  target
+    .strip_prefix(root)
+    .ok()
+    .map(|path| path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_within() {
+    let event = ChangeEvent {
+      storage_id: 1,
+      path: "docs/a.txt".to_string(),
+      kind: ChangeKind::Renamed {
+        from: "tmp/a.txt".to_string(),
+      },
+      user_id: 1,
+    };
+    assert!(event.is_within(""));
+    assert!(event.is_within("docs"));
+    assert!(event.is_within("/tmp/"));
+    assert!(!event.is_within("doc"));
+    assert!(!event.is_within("docs/b"));
+  }
+}
@@ -0,0 +1,278 @@
+use std::{
+  collections::HashMap,
+  io::{self, Write},
+  sync::{Arc, LazyLock, Mutex},
+  time::Duration,
+};
+
+use axum::http::StatusCode;
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Semaphore, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::{
+  db::{self, DBConnection, job::JobStatus},
+  error::AppError,
+};
+
+pub const JOB_CANCELLED: &str = "JOB_CANCELLED";
+
+/// 进度写入数据库的间隔，执行期间的进度更新只保留最新的一次
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// 清理过期任务的间隔
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_CONCURRENCY: usize = 2;
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+
+/// 同时执行的任务数，其它任务排队等待
+pub fn concurrency() -> usize {
+  std::env::var("JOB_CONCURRENCY")
+    .unwrap_or(DEFAULT_CONCURRENCY.to_string())
+    .parse::<usize>()
+    .unwrap_or(DEFAULT_CONCURRENCY)
+    .max(1)
+}
+
+/// 已结束的任务保留的天数，小于等于 0 时不清理
+pub fn retention_days() -> i64 {
+  std::env::var("JOB_RETENTION_DAYS")
+    .unwrap_or(DEFAULT_RETENTION_DAYS.to_string())
+    .parse::<i64>()
+    .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+static SLOTS: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(concurrency()));
+
+/// 排队或执行中的任务
+static ACTIVE: LazyLock<Mutex<HashMap<i64, CancellationToken>>> = LazyLock::new(Default::default);
+
+pub type JobFuture = BoxFuture<'static, Result<Value, AppError>>;
+
+/// 传给任务执行者，用于上报进度和检查任务是否已被取消
+#[derive(Clone)]
+pub struct JobContext {
+  token: CancellationToken,
+  progress: Arc<watch::Sender<Option<Value>>>,
+}
+
+impl JobContext {
+  pub fn is_cancelled(&self) -> bool {
+    self.token.is_cancelled()
+  }
+
+  /// 任务已被取消时返回错误，执行者用 `?` 尽快停止
+  pub fn check(&self) -> Result<(), AppError> {
+    if self.is_cancelled() {
+      return Err(cancelled());
+    }
+    Ok(())
+  }
+
+  /// 上报进度，可以在阻塞线程中频繁调用。任务已被取消时返回错误
+  pub fn report(&self, progress: &impl Serialize) -> Result<(), AppError> {
+    self.check()?;
+    if let Ok(progress) = serde_json::to_value(progress) {
+      self.progress.send_replace(Some(progress));
+    }
+    Ok(())
+  }
+}
+
+/// 每次写入前检查任务是否已被取消，用于打包等按条目上报进度、单个条目可能很大的操作
+pub struct CancellableWriter<W> {
+  inner: W,
+  context: Option<JobContext>,
+}
+
+impl<W> CancellableWriter<W> {
+  /// `context` 为空时不检查
+  pub fn new(inner: W, context: Option<JobContext>) -> Self {
+    Self { inner, context }
+  }
+
+  pub fn into_inner(self) -> W {
+    self.inner
+  }
+}
+
+impl<W: Write> Write for CancellableWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.context.as_ref().is_some_and(JobContext::is_cancelled) {
+      return Err(io::Error::other(cancelled().message()));
+    }
+    self.inner.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+pub fn cancelled() -> AppError {
+  AppError::with_code(StatusCode::CONFLICT, JOB_CANCELLED, "任务已取消")
+}
+
+/// 在后台执行任务：等待空闲的执行位，执行期间定期保存进度，结束后记录结果或错误。
+/// 排队期间被取消的任务不会执行
+pub fn start(
+  conn: DBConnection,
+  id: i64,
+  run: impl FnOnce(JobContext) -> JobFuture + Send + 'static,
+) {
+  let token = CancellationToken::new();
+  ACTIVE
+    .lock()
+    .unwrap_or_else(|err| err.into_inner())
+    .insert(id, token.clone());
+  tokio::spawn(async move {
+    let result = tokio::select! {
+      permit = SLOTS.acquire() => match permit {
+        Ok(_permit) => execute(&conn, id, token.clone(), run).await,
+        Err(err) => Err(err.into()),
+      },
+      _ = token.cancelled() => Err(cancelled()),
+    };
+
+    // 取消后仍然完成的任务按完成记录
+    let status = match &result {
+      Ok(_) => JobStatus::Completed,
+      Err(_) if token.is_cancelled() => JobStatus::Cancelled,
+      Err(_) => JobStatus::Failed,
+    };
+    let (result, error) = match result {
+      Ok(result) => (Some(result.to_string()), None),
+      Err(err) => (None, Some(err.message())),
+    };
+    log::info!("Job {} {}", id, status.as_str());
+    if let Err(err) = db::job::finish_job(
+      &*conn.lock().await,
+      id,
+      status,
+      result.as_deref(),
+      error.as_deref(),
+    ) {
+      log::warn!("Failed to save job {}: {err}", id);
+    }
+    ACTIVE
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .remove(&id);
+  });
+}
+
+async fn execute(
+  conn: &DBConnection,
+  id: i64,
+  token: CancellationToken,
+  run: impl FnOnce(JobContext) -> JobFuture,
+) -> Result<Value, AppError> {
+  db::job::set_job_running(&*conn.lock().await, id)?;
+  let (sender, mut receiver) = watch::channel(None);
+  let context = JobContext {
+    token,
+    progress: Arc::new(sender),
+  };
+  let mut job = run(context.clone());
+  let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+  let result = loop {
+    tokio::select! {
+      result = &mut job => break result,
+      _ = interval.tick() => save_progress(conn, id, &mut receiver).await,
+    }
+  };
+  save_progress(conn, id, &mut receiver).await;
+  result
+}
+
+async fn save_progress(
+  conn: &DBConnection,
+  id: i64,
+  receiver: &mut watch::Receiver<Option<Value>>,
+) {
+  if !receiver.has_changed().unwrap_or(false) {
+    return;
+  }
+  let Some(progress) = receiver.borrow_and_update().clone() else {
+    return;
+  };
+  if let Err(err) = db::job::update_job_progress(&*conn.lock().await, id, &progress.to_string()) {
+    log::warn!("Failed to save progress of job {}: {err}", id);
+  }
+}
+
+/// 通知任务停止，返回任务是否正在排队或执行
+pub fn cancel(id: i64) -> bool {
+  match ACTIVE
+    .lock()
+    .unwrap_or_else(|err| err.into_inner())
+    .get(&id)
+  {
+    Some(token) => {
+      token.cancel();
+      true
+    }
+    None => false,
+  }
+}
+
+pub fn is_active(id: i64) -> bool {
+  ACTIVE
+    .lock()
+    .unwrap_or_else(|err| err.into_inner())
+    .contains_key(&id)
+}
+
+pub fn spawn_job_cleaner(conn: DBConnection) {
+  let days = retention_days();
+  if days <= 0 {
+    return;
+  }
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(CLEAN_INTERVAL);
+    loop {
+      interval.tick().await;
+      match db::job::delete_expired_jobs(&*conn.lock().await, days) {
+        Ok(0) => {}
+        Ok(count) => log::info!("Deleted {} expired jobs", count),
+        Err(err) => log::warn!("Failed to delete expired jobs: {err}"),
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_cancel_job() {
+    let conn: DBConnection = Arc::new(tokio::sync::Mutex::new(
+      rusqlite::Connection::open_in_memory().unwrap(),
+    ));
+    db::job::create_job_database(&*conn.lock().await).unwrap();
+    let id = db::job::create_job(&*conn.lock().await, 1, "test", "{}").unwrap();
+
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+    start(conn.clone(), id, move |context| {
+      Box::pin(async move {
+        context.report(&serde_json::json!({ "step": 1 }))?;
+        let _ = started_tx.send(());
+        context.token.cancelled().await;
+        context.check()?;
+        Ok(Value::Null)
+      })
+    });
+    started_rx.await.unwrap();
+    assert!(cancel(id));
+    while is_active(id) {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let job = db::job::get_job(&*conn.lock().await, id).unwrap().unwrap();
+    assert_eq!(job.status, JobStatus::Cancelled);
+    assert_eq!(job.progress.unwrap()["step"], 1);
+    assert_eq!(job.error.as_deref(), Some("任务已取消"));
+  }
+}
@@ -1,5 +1,20 @@
+pub mod archive;
 pub mod auth;
+pub mod backend;
+pub mod events;
 pub mod file;
+pub mod job;
 pub mod path;
+pub mod policy;
+pub mod range;
+pub mod search;
+pub mod sigv4;
+pub mod thumb;
 pub mod time;
+pub mod transfer;
+pub mod trash;
+pub mod upload;
 pub mod validate;
+pub mod version;
+pub mod watcher;
+pub mod xml;
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use axum::http::StatusCode;
+
+use crate::backend::{db::storage::StorageDatabase, error::AppError};
+
+pub const FILE_TOO_LARGE: &str = "FILE_TOO_LARGE";
+pub const EXTENSION_NOT_ALLOWED: &str = "EXTENSION_NOT_ALLOWED";
+pub const EXTENSION_BLOCKED: &str = "EXTENSION_BLOCKED";
+
+/// 存储的上传 / 命名限制
+#[derive(Clone, Default)]
+pub struct StoragePolicy {
+  /// 单文件最大字节数，0 表示不限制
+  pub max_file_size: u64,
+  /// 允许的扩展名，为空表示不限制
+  pub allow_extensions: Vec<String>,
+  pub block_extensions: Vec<String>,
+}
+
+impl StoragePolicy {
+  pub fn from_storage(storage: &StorageDatabase) -> Self {
+    Self {
+      max_file_size: storage.max_file_size,
+      allow_extensions: parse_extensions(&storage.allow_extensions),
+      block_extensions: parse_extensions(&storage.block_extensions),
+    }
+  }
+
+  pub fn check_size(&self, size: u64) -> Result<(), AppError> {
+    if self.max_file_size > 0 && size > self.max_file_size {
+      return Err(AppError::with_code(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        FILE_TOO_LARGE,
+        &format!("文件大小超过限制: {} 字节", self.max_file_size),
+      ));
+    }
+    Ok(())
+  }
+
+  pub fn check_name(&self, name: &str) -> Result<(), AppError> {
+    let extension = Path::new(name)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .map(|ext| ext.to_lowercase())
+      .unwrap_or_default();
+
+    if !extension.is_empty() && self.block_extensions.contains(&extension) {
+      return Err(AppError::with_code(
+        StatusCode::FORBIDDEN,
+        EXTENSION_BLOCKED,
+        &format!("禁止的文件类型: {}", extension),
+      ));
+    }
+
+    if !self.allow_extensions.is_empty() && !self.allow_extensions.contains(&extension) {
+      return Err(AppError::with_code(
+        StatusCode::FORBIDDEN,
+        EXTENSION_NOT_ALLOWED,
+        &format!("不允许的文件类型: {}", extension),
+      ));
+    }
+
+    Ok(())
+  }
+}
+
+/// 解析形如 `jpg, .PNG;gif` 的扩展名列表
+fn parse_extensions(value: &str) -> Vec<String> {
+  value
+    .split([',', ';', ' ', '\n'])
+    .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+    .filter(|ext| !ext.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn policy(max: u64, allow: &str, block: &str) -> StoragePolicy {
+    StoragePolicy {
+      max_file_size: max,
+      allow_extensions: parse_extensions(allow),
+      block_extensions: parse_extensions(block),
+    }
+  }
+
+  #[test]
+  fn test_parse_extensions() {
+    assert_eq!(
+      parse_extensions(" jpg, .PNG;gif "),
+      vec!["jpg", "png", "gif"]
+    );
+    assert!(parse_extensions("").is_empty());
+  }
+
+  #[test]
+  fn test_check_size() {
+    assert!(policy(0, "", "").check_size(u64::MAX).is_ok());
+    assert!(policy(10, "", "").check_size(10).is_ok());
+    assert!(policy(10, "", "").check_size(11).is_err());
+  }
+
+  #[test]
+  fn test_check_name() {
+    let p = policy(0, "jpg,png", "");
+    assert!(p.check_name("a.JPG").is_ok());
+    assert!(p.check_name("a.exe").is_err());
+    assert!(p.check_name("README").is_err());
+
+    let p = policy(0, "", "exe");
+    assert!(p.check_name("a.txt").is_ok());
+    assert!(p.check_name("README").is_ok());
+    assert!(p.check_name("a.EXE").is_err());
+  }
+}
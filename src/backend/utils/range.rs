@@ -0,0 +1,101 @@
+/// 单次请求最多允许的区间数量，超过时按完整文件返回
+const MAX_RANGES: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+  /// 没有可用的 Range，返回完整文件
+  Full,
+  /// 闭区间 `(start, end)`
+  Partial(Vec<(u64, u64)>),
+  Unsatisfiable,
+}
+
+/// 解析 `Range: bytes=0-99,200-,-50`，语法错误时忽略 Range 返回完整文件
+pub fn parse_range(value: &str, size: u64) -> RangeRequest {
+  let Some(spec) = value.trim().strip_prefix("bytes=") else {
+    return RangeRequest::Full;
+  };
+
+  let specs = spec.split(',').map(str::trim).collect::<Vec<_>>();
+  if specs.is_empty() || specs.len() > MAX_RANGES {
+    return RangeRequest::Full;
+  }
+
+  let mut ranges = Vec::new();
+  for spec in specs {
+    let Some((start, end)) = spec.split_once('-') else {
+      return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+      // 后缀区间: 最后 n 个字节
+      let Ok(suffix) = end.parse::<u64>() else {
+        return RangeRequest::Full;
+      };
+      if suffix > 0 && size > 0 {
+        ranges.push((size - suffix.min(size), size - 1));
+      }
+      continue;
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+      return RangeRequest::Full;
+    };
+    let end = if end.is_empty() {
+      u64::MAX
+    } else {
+      match end.parse::<u64>() {
+        Ok(end) if end >= start => end,
+        _ => return RangeRequest::Full,
+      }
+    };
+    if start < size {
+      ranges.push((start, end.min(size - 1)));
+    }
+  }
+
+  if ranges.is_empty() {
+    RangeRequest::Unsatisfiable
+  } else {
+    RangeRequest::Partial(ranges)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_range() {
+    assert_eq!(
+      parse_range("bytes=0-99", 1000),
+      RangeRequest::Partial(vec![(0, 99)])
+    );
+    assert_eq!(
+      parse_range("bytes=900-", 1000),
+      RangeRequest::Partial(vec![(900, 999)])
+    );
+    assert_eq!(
+      parse_range("bytes=-100", 1000),
+      RangeRequest::Partial(vec![(900, 999)])
+    );
+    assert_eq!(
+      parse_range("bytes=0-9, 990-2000", 1000),
+      RangeRequest::Partial(vec![(0, 9), (990, 999)])
+    );
+  }
+
+  #[test]
+  fn test_parse_range_invalid() {
+    assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Full);
+    assert_eq!(parse_range("bytes=10-1", 1000), RangeRequest::Full);
+    assert_eq!(parse_range("bytes=a-", 1000), RangeRequest::Full);
+    assert_eq!(
+      parse_range("bytes=1000-", 1000),
+      RangeRequest::Unsatisfiable
+    );
+    assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+    assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+  }
+}
@@ -0,0 +1,225 @@
+use std::{
+  collections::HashSet,
+  fs,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::backend::{
+  db::{self, DBConnection, search::IndexEntry, storage::StorageKind},
+  utils::{file::is_system_file, watcher},
+};
+
+/// 超过该大小的文本文件不索引内容
+const MAX_CONTENT_SIZE: u64 = 1024 * 1024;
+/// 扫描结果分批写入，每批最多这么多条目或这么多内容，写完一批就释放数据库锁
+const BATCH_SIZE: usize = 500;
+const BATCH_CONTENT_SIZE: usize = 16 * 1024 * 1024;
+
+const TEXT_EXTENSIONS: &[&str] = &[
+  "txt", "md", "markdown", "csv", "log", "json", "yaml", "yml", "toml", "ini", "xml", "html",
+  "htm", "css", "js", "ts", "jsx", "tsx", "rs", "py", "go", "java", "c", "h", "cpp", "hpp", "sh",
+  "sql",
+];
+
+/// 是否索引文本文件内容，通过 SEARCH_INDEX_CONTENT=true 开启
+fn index_content() -> bool {
+  std::env::var("SEARCH_INDEX_CONTENT")
+    .map(|value| value == "true" || value == "1")
+    .unwrap_or(false)
+}
+
+/// 扫描 `target` 及其子路径，每个条目交给 `each`，`target` 不存在时不产生条目
+pub fn scan(root: &Path, target: &Path, each: &mut impl FnMut(IndexEntry)) {
+  let with_content = index_content();
+  if target != root {
+    scan_entry(root, target, with_content, each);
+  } else if let Ok(children) = fs::read_dir(root) {
+    for child in children.flatten() {
+      scan_entry(root, &child.path(), with_content, each);
+    }
+  }
+}
+
+fn scan_entry(root: &Path, path: &Path, with_content: bool, each: &mut impl FnMut(IndexEntry)) {
+  let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+    return;
+  };
+  if is_system_file(name) {
+    return;
+  }
+  let (Ok(metadata), Ok(relative_path)) = (fs::symlink_metadata(path), path.strip_prefix(root))
+  else {
+    return;
+  };
+  if metadata.file_type().is_symlink() {
+    return;
+  }
+
+  let extension = if metadata.is_dir() {
+    String::new()
+  } else {
+    Path::new(name)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .map(|ext| ext.to_lowercase())
+      .unwrap_or_default()
+  };
+  let content = (with_content
+    && metadata.is_file()
+    && metadata.len() <= MAX_CONTENT_SIZE
+    && TEXT_EXTENSIONS.contains(&extension.as_str()))
+  .then(|| fs::read(path).ok())
+  .flatten()
+  .map(|bytes| String::from_utf8_lossy(&bytes).to_string());
+
+  each(IndexEntry {
+    path: relative_path.to_string_lossy().to_string(),
+    name: name.to_string(),
+    extension,
+    is_dir: metadata.is_dir(),
+    size: if metadata.is_dir() { 0 } else { metadata.len() },
+    modified: metadata
+      .modified()
+      .ok()
+      .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+      .unwrap_or_default()
+      .as_secs() as i64,
+    content,
+  });
+
+  if metadata.is_dir()
+    && let Ok(children) = fs::read_dir(path)
+  {
+    for child in children.flatten() {
+      scan_entry(root, &child.path(), with_content, each);
+    }
+  }
+}
+
+/// 文件变更后刷新索引：交给索引任务重新扫描 `target`，不存在时删除对应索引
+///
+/// 扫描在后台进行，不阻塞请求，也不占用数据库连接
+pub fn refresh(storage_id: i64, root: &Path, target: &Path) {
+  if target.starts_with(root) {
+    watcher::notify(storage_id, root, vec![target.to_path_buf()]);
+  }
+}
+
+/// 根据文件系统变更更新索引，扫描期间不占用数据库连接
+pub fn spawn_indexer(conn: DBConnection) {
+  let mut changes = watcher::subscribe();
+  tokio::spawn(async move {
+    loop {
+      let change = match changes.recv().await {
+        Ok(change) => change,
+        // 积压过多时直接全量重建
+        Err(RecvError::Lagged(skipped)) => {
+          log::warn!("Search indexer lagged {skipped} changes, rebuilding");
+          let storages =
+            db::storage::get_all_enabled_storage(&*conn.lock().await).unwrap_or_default();
+          for storage in storages
+            .into_iter()
+            .filter(|storage| storage.kind == StorageKind::Local)
+          {
+            rebuild(&conn, storage.id, &PathBuf::from(&storage.local_path)).await;
+          }
+          continue;
+        }
+        Err(RecvError::Closed) => break,
+      };
+      update_index(&conn, change.storage_id, &change.root, change.paths).await;
+    }
+  });
+}
+
+/// 全量重建存储的索引，返回索引的条目数
+pub async fn rebuild(conn: &DBConnection, storage_id: i64, root: &Path) -> usize {
+  update_index(conn, storage_id, root, vec![root.to_path_buf()]).await
+}
+
+async fn update_index(
+  conn: &DBConnection,
+  storage_id: i64,
+  root: &Path,
+  paths: Vec<PathBuf>,
+) -> usize {
+  let started = SystemTime::now();
+  let mut count = 0;
+  for path in paths {
+    let Ok(prefix) = path.strip_prefix(root) else {
+      continue;
+    };
+    let prefix = prefix.to_string_lossy().to_string();
+    count += index_path(conn, storage_id, root, &path, &prefix).await;
+  }
+  log::debug!(
+    "Indexed {} entries of {} in {:?}",
+    count,
+    root.display(),
+    started.elapsed().unwrap_or_default()
+  );
+  count
+}
+
+/// 边扫描边分批写入，每批之间释放数据库锁，最后删除扫描中没有出现的路径。
+/// 写入期间旧的索引仍然可以搜索
+async fn index_path(
+  conn: &DBConnection,
+  storage_id: i64,
+  root: &Path,
+  target: &Path,
+  prefix: &str,
+) -> usize {
+  // 通道有界，写入跟不上时扫描会等待，内存中最多只有几批条目
+  let (sender, mut receiver) = tokio::sync::mpsc::channel::<Vec<IndexEntry>>(2);
+  let (scan_root, scan_target) = (root.to_path_buf(), target.to_path_buf());
+  let scanner = tokio::task::spawn_blocking(move || {
+    let mut batch = Vec::new();
+    let mut content_size = 0;
+    scan(&scan_root, &scan_target, &mut |entry| {
+      content_size += entry.content.as_ref().map_or(0, String::len);
+      batch.push(entry);
+      if batch.len() >= BATCH_SIZE || content_size >= BATCH_CONTENT_SIZE {
+        content_size = 0;
+        sender.blocking_send(std::mem::take(&mut batch)).ok();
+      }
+    });
+    if !batch.is_empty() {
+      sender.blocking_send(batch).ok();
+    }
+  });
+
+  let mut seen = HashSet::new();
+  while let Some(batch) = receiver.recv().await {
+    seen.extend(batch.iter().map(|entry| entry.path.clone()));
+    if let Err(err) = db::search::upsert_entries(&*conn.lock().await, storage_id, &batch) {
+      log::warn!("Failed to update search index for {:?}: {err}", target);
+    }
+  }
+  if scanner.await.is_err() {
+    // 扫描中途失败时不知道哪些路径已经不存在，保留旧的索引
+    return seen.len();
+  }
+
+  let indexed = match db::search::list_paths(&*conn.lock().await, storage_id, prefix) {
+    Ok(indexed) => indexed,
+    Err(err) => {
+      log::warn!("Failed to list search index for {:?}: {err}", target);
+      return seen.len();
+    }
+  };
+  let stale = indexed
+    .into_iter()
+    .filter(|(_, path)| !seen.contains(path))
+    .map(|(id, _)| id)
+    .collect::<Vec<_>>();
+  for ids in stale.chunks(BATCH_SIZE) {
+    if let Err(err) = db::search::delete_by_ids(&*conn.lock().await, ids) {
+      log::warn!("Failed to remove search index for {:?}: {err}", target);
+    }
+  }
+  seen.len()
+}
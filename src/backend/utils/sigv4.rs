@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 访问 S3 兼容服务使用的凭证
+pub struct Credentials<'a> {
+  pub access_key_id: &'a str,
+  pub secret_access_key: &'a str,
+  pub region: &'a str,
+}
+
+/// 为发往 S3 的请求签名，签名 host、x-amz-content-sha256、x-amz-date 和 `headers`
+/// 中的请求头，返回需要附加到请求上的请求头。`path` 和 `query` 需要是已编码的形式，
+/// `headers` 的名称需要是小写
+#[allow(clippy::too_many_arguments)]
+pub fn sign_request(
+  credentials: &Credentials,
+  method: &str,
+  host: &str,
+  path: &str,
+  query: &str,
+  headers: &[(&str, &str)],
+  payload_hash: &str,
+  now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+  let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+  let date = now.format("%Y%m%d").to_string();
+  let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+
+  let mut signed = vec![
+    ("host", host),
+    ("x-amz-content-sha256", payload_hash),
+    ("x-amz-date", amz_date.as_str()),
+  ];
+  signed.extend_from_slice(headers);
+  signed.sort();
+  let canonical_headers: String = signed
+    .iter()
+    .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+    .collect();
+  let signed_headers = signed
+    .iter()
+    .map(|(name, _)| *name)
+    .collect::<Vec<_>>()
+    .join(";");
+
+  let canonical_request = format!(
+    "{}\n{}\n{}\n{}\n{}\n{}",
+    method, path, query, canonical_headers, signed_headers, payload_hash
+  );
+  let string_to_sign = format!(
+    "{}\n{}\n{}\n{}",
+    ALGORITHM,
+    amz_date,
+    scope,
+    hex::encode(Sha256::digest(canonical_request.as_bytes()))
+  );
+  let key = signing_key(
+    credentials.secret_access_key,
+    &date,
+    credentials.region,
+    "s3",
+  );
+  let signature = hex::encode(hmac(&key, &string_to_sign));
+
+  vec![
+    (
+      "authorization",
+      format!(
+        "{} Credential={}/{},SignedHeaders={},Signature={}",
+        ALGORITHM, credentials.access_key_id, scope, signed_headers, signature
+      ),
+    ),
+    ("x-amz-content-sha256", payload_hash.to_string()),
+    ("x-amz-date", amz_date),
+  ]
+}
+
+/// 按参数名排序并编码查询参数，结果既可以用于 URL 也可以用于签名
+pub fn encode_query(params: &[(&str, &str)]) -> String {
+  let mut params: Vec<(String, String)> = params
+    .iter()
+    .map(|(key, value)| (uri_encode(key, true), uri_encode(value, true)))
+    .collect();
+  params.sort();
+  params
+    .into_iter()
+    .map(|(key, value)| format!("{}={}", key, value))
+    .collect::<Vec<_>>()
+    .join("&")
+}
+
+pub fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+  let key = hmac(format!("AWS4{}", secret).as_bytes(), date);
+  let key = hmac(&key, region);
+  let key = hmac(&key, service);
+  hmac(&key, "aws4_request")
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+  let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+  mac.update(data.as_bytes());
+  mac.finalize().into_bytes().to_vec()
+}
+
+/// S3 的 URI 编码：只保留 `A-Za-z0-9-_.~`，路径中可以保留 `/`
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+  let mut encoded = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+        encoded.push(byte as char)
+      }
+      b'/' if !encode_slash => encoded.push('/'),
+      _ => encoded.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  encoded
+}
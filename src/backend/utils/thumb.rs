@@ -0,0 +1,296 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  sync::LazyLock,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::StatusCode;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, codecs::jpeg::JpegEncoder};
+use serde::Deserialize;
+use tokio::sync::{Semaphore, broadcast::error::RecvError};
+
+use crate::backend::{
+  db::{self, DBConnection},
+  error::AppError,
+  utils::{
+    backend::{self, Entry, StorageBackend},
+    events::{self, ChangeKind},
+    watcher,
+  },
+};
+
+/// 超过该大小的图片不生成缩略图
+const MAX_SOURCE_SIZE: u64 = 64 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 80;
+/// 解码时允许的最大宽高和内存，防止小文件声明超大尺寸耗尽内存
+const MAX_DIMENSION: u32 = 16384;
+const MAX_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// 同时生成的缩略图数量，每个都需要把原图读入内存并解码
+static GENERATING: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(4));
+
+/// 支持生成缩略图的扩展名，GIF 只取第一帧
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbSize {
+  Small,
+  #[default]
+  Medium,
+  Large,
+}
+
+impl ThumbSize {
+  /// 缩略图的最长边
+  fn pixels(&self) -> u32 {
+    match self {
+      ThumbSize::Small => 128,
+      ThumbSize::Medium => 256,
+      ThumbSize::Large => 1024,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ThumbSize::Small => "small",
+      ThumbSize::Medium => "medium",
+      ThumbSize::Large => "large",
+    }
+  }
+}
+
+pub fn supports(name: &str) -> bool {
+  name.rsplit_once('.').is_some_and(|(_, extension)| {
+    IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+  })
+}
+
+pub fn thumbs_dir(root: &Path) -> PathBuf {
+  root.join(".storkitty").join("thumbs")
+}
+
+/// 缩略图按原文件的路径存放在 `thumbs/{path}/` 下，文件名为 `{size}-{mtime}.{ext}`，
+/// 原文件修改后 mtime 变化，旧的缩略图自然失效
+fn cache_key(size: ThumbSize, modified: Option<SystemTime>) -> String {
+  format!("{}-{}", size.as_str(), mtime_key(modified))
+}
+
+fn mtime_key(modified: Option<SystemTime>) -> String {
+  let modified = modified
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|duration| duration.as_nanos())
+    .unwrap_or_default();
+  format!("{:x}", modified)
+}
+
+/// 从缩略图的文件名中取出 mtime
+fn parse_mtime(name: &str) -> Option<&str> {
+  let (_, rest) = name.split_once('-')?;
+  Some(rest.split_once('.')?.0)
+}
+
+/// 返回缓存的缩略图及其 Content-Type，没有缓存时从原图生成
+pub async fn get_or_create(
+  backend: &dyn StorageBackend,
+  root: &Path,
+  path: &str,
+  entry: &Entry,
+  size: ThumbSize,
+) -> Result<(PathBuf, &'static str), AppError> {
+  let dir = thumbs_dir(root).join(path);
+  let key = cache_key(size, entry.modified);
+  for format in [ImageFormat::Jpeg, ImageFormat::Png] {
+    let file = dir.join(format!("{}.{}", key, format.extensions_str()[0]));
+    if tokio::fs::try_exists(&file).await? {
+      return Ok((file, format.to_mime_type()));
+    }
+  }
+
+  if entry.size > MAX_SOURCE_SIZE {
+    return Err(AppError::with_status(
+      StatusCode::PAYLOAD_TOO_LARGE,
+      "图片过大，无法生成缩略图",
+    ));
+  }
+  let _permit = GENERATING.acquire().await.map_err(anyhow::Error::from)?;
+  let data = backend::read_all(backend, path).await?;
+  let entry_modified = entry.modified;
+  let generated = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+    let (data, format) = render(&data, size.pixels())?;
+    fs::create_dir_all(&dir)?;
+    remove_stale(&dir, &mtime_key(entry_modified))?;
+    let file = dir.join(format!("{}.{}", key, format.extensions_str()[0]));
+    // 并发请求可能同时生成同一缩略图，先写临时文件再重命名
+    let temp = dir.join(format!(".{}.tmp", hex::encode(rand::random::<[u8; 4]>())));
+    fs::write(&temp, data)?;
+    fs::rename(&temp, &file)?;
+    Ok((file, format.to_mime_type()))
+  })
+  .await?;
+  generated.map_err(|err| {
+    log::warn!("Failed to generate thumbnail for {}: {err}", path);
+    AppError::with_status(StatusCode::UNPROCESSABLE_ENTITY, "无法生成缩略图")
+  })
+}
+
+/// 缩放到最长边不超过 `pixels`，不放大。有透明通道时输出 PNG，否则输出 JPEG
+fn render(data: &[u8], pixels: u32) -> anyhow::Result<(Vec<u8>, ImageFormat)> {
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_DIMENSION);
+  limits.max_image_height = Some(MAX_DIMENSION);
+  limits.max_alloc = Some(MAX_ALLOC);
+  let mut reader = ImageReader::new(io::Cursor::new(data)).with_guessed_format()?;
+  reader.limits(limits);
+  let image = reader.decode()?;
+  let image = if image.width() > pixels || image.height() > pixels {
+    image.thumbnail(pixels, pixels)
+  } else {
+    image
+  };
+  let mut output = Vec::new();
+  if image.color().has_alpha() {
+    image.write_to(&mut io::Cursor::new(&mut output), ImageFormat::Png)?;
+    Ok((output, ImageFormat::Png))
+  } else {
+    let image = DynamicImage::ImageRgb8(image.to_rgb8());
+    image.write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY))?;
+    Ok((output, ImageFormat::Jpeg))
+  }
+}
+
+/// 删除同一文件其他 mtime 的缩略图，保留当前版本的其他尺寸
+fn remove_stale(dir: &Path, current: &str) -> io::Result<()> {
+  for entry in fs::read_dir(dir)?.flatten() {
+    let is_stale = entry.file_type().is_ok_and(|file_type| file_type.is_file())
+      && parse_mtime(&entry.file_name().to_string_lossy()).is_some_and(|mtime| mtime != current);
+    if is_stale {
+      fs::remove_file(entry.path()).ok();
+    }
+  }
+  Ok(())
+}
+
+/// 删除路径（文件或整个文件夹）下的所有缩略图
+pub fn invalidate(root: &Path, path: &str) {
+  let dir = thumbs_dir(root).join(path.trim_matches('/'));
+  if path.trim_matches('/').is_empty() || !dir.is_dir() {
+    return;
+  }
+  if let Err(err) = fs::remove_dir_all(&dir) {
+    log::warn!("Failed to remove thumbnails for {}: {err}", path);
+  }
+}
+
+/// 删除 `target` 下原文件已删除或已修改的缩略图，用于外部修改的本地文件
+fn prune(root: &Path, target: &Path) {
+  let Ok(relative_path) = target.strip_prefix(root) else {
+    return;
+  };
+  prune_dir(root, &thumbs_dir(root).join(relative_path), relative_path);
+}
+
+fn prune_dir(root: &Path, dir: &Path, relative_path: &Path) {
+  let Ok(entries) = fs::read_dir(dir) else {
+    return;
+  };
+  // 原文件不存在或已变为文件夹时，这一层的缩略图全部删除
+  let current = fs::metadata(root.join(relative_path))
+    .ok()
+    .filter(|metadata| metadata.is_file())
+    .map(|metadata| mtime_key(metadata.modified().ok()));
+  for entry in entries.flatten() {
+    let name = entry.file_name();
+    if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+      prune_dir(root, &entry.path(), &relative_path.join(&name));
+      continue;
+    }
+    let mtime = parse_mtime(&name.to_string_lossy()).map(str::to_string);
+    if current.is_none() || mtime != current {
+      fs::remove_file(entry.path()).ok();
+    }
+  }
+  fs::remove_dir(dir).ok();
+}
+
+/// 文件修改、删除或移动后清理缩略图。通过接口的操作从变更事件获取，
+/// 本地存储中被外部修改的文件从文件监听获取
+pub fn spawn_thumb_cleaner(conn: DBConnection) {
+  let mut changes = watcher::subscribe();
+  let mut events = events::subscribe();
+  tokio::spawn(async move {
+    loop {
+      tokio::select! {
+        change = changes.recv() => match change {
+          Ok(change) => {
+            tokio::task::spawn_blocking(move || {
+              for path in &change.paths {
+                prune(&change.root, path);
+              }
+            });
+          }
+          Err(RecvError::Lagged(_)) => continue,
+          Err(RecvError::Closed) => break,
+        },
+        event = events.recv() => match event {
+          Ok(event) => {
+            let paths = match event.kind {
+              ChangeKind::Modified | ChangeKind::Deleted => vec![event.path],
+              ChangeKind::Renamed { from } => vec![from, event.path],
+              _ => continue,
+            };
+            let Ok(storage) = db::storage::get_storage_by_id(&*conn.lock().await, event.storage_id)
+            else {
+              continue;
+            };
+            let root = PathBuf::from(storage.local_path);
+            for path in paths {
+              invalidate(&root, &path);
+            }
+          }
+          Err(RecvError::Lagged(_)) => continue,
+          Err(RecvError::Closed) => break,
+        },
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_render() {
+    let image = DynamicImage::new_rgb8(600, 300);
+    let mut data = Vec::new();
+    image
+      .write_to(&mut io::Cursor::new(&mut data), ImageFormat::Png)
+      .unwrap();
+
+    let (output, format) = render(&data, 256).unwrap();
+    assert_eq!(format, ImageFormat::Jpeg);
+    let thumb = image::load_from_memory(&output).unwrap();
+    assert_eq!((thumb.width(), thumb.height()), (256, 128));
+
+    let transparent = DynamicImage::new_rgba8(64, 64);
+    let mut data = Vec::new();
+    transparent
+      .write_to(&mut io::Cursor::new(&mut data), ImageFormat::Png)
+      .unwrap();
+    let (output, format) = render(&data, 256).unwrap();
+    assert_eq!(format, ImageFormat::Png);
+    assert_eq!(image::load_from_memory(&output).unwrap().width(), 64);
+
+    let wide = DynamicImage::new_rgb8(MAX_DIMENSION + 1, 1);
+    let mut data = Vec::new();
+    wide
+      .write_to(&mut io::Cursor::new(&mut data), ImageFormat::Png)
+      .unwrap();
+    assert!(render(&data, 256).is_err());
+
+    assert!(supports("a.JPG"));
+    assert!(!supports("a.svg"));
+  }
+}
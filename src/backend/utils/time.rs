@@ -11,3 +11,39 @@ pub fn format_modified_time(time: SystemTime) -> String {
     .format("%Y-%m-%d %H:%M:%S")
     .to_string()
 }
+
+/// 解析查询参数中的时间，支持 Unix 秒、RFC 3339、`2024-01-01 12:00:00` 和 `2024-01-01`（本地时间）
+pub fn parse_time(value: &str) -> Option<i64> {
+  let value = value.trim();
+  if let Ok(secs) = value.parse::<i64>() {
+    return Some(secs);
+  }
+  if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+    return Some(datetime.timestamp());
+  }
+  let datetime = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+    .ok()
+    .or_else(|| {
+      chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+  datetime
+    .and_local_timezone(chrono::Local)
+    .earliest()
+    .map(|datetime| datetime.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_time() {
+    assert_eq!(parse_time("1700000000"), Some(1700000000));
+    assert_eq!(parse_time("2024-01-01T00:00:00Z"), Some(1704067200));
+    assert!(parse_time("2024-01-01").is_some());
+    assert!(parse_time("2024-01-01 08:30:00").is_some());
+    assert_eq!(parse_time("yesterday"), None);
+  }
+}
@@ -0,0 +1,417 @@
+use std::{
+  fs,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{error::AppError, utils::file::is_system_file, utils::policy::StoragePolicy};
+
+/// 复制时每次读写的块大小
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferMode {
+  #[default]
+  Move,
+  Copy,
+}
+
+/// 目标位置已存在同名文件时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+  #[default]
+  Skip,
+  Overwrite,
+  /// 自动重命名为 `name (1).ext`
+  Rename,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+  pub total_files: u64,
+  pub total_bytes: u64,
+  pub done_files: u64,
+  pub done_bytes: u64,
+  /// 正在处理的源路径
+  pub current: String,
+}
+
+/// 单个源的处理结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResult {
+  pub source: String,
+  /// 实际写入的目标名称，跳过或失败时为空
+  pub target: Option<String>,
+  pub skipped: bool,
+  pub error: Option<String>,
+  /// 存储限制等带错误码的失败
+  pub code: Option<&'static str>,
+}
+
+pub struct TransferSource {
+  /// 请求中的原始路径，用于结果展示
+  pub name: String,
+  pub path: PathBuf,
+}
+
+pub struct TransferTask {
+  pub mode: TransferMode,
+  pub conflict: ConflictPolicy,
+  pub sources: Vec<TransferSource>,
+  pub destination: PathBuf,
+  /// 跨存储时需要按目标存储的限制检查文件
+  pub policy: Option<StoragePolicy>,
+}
+
+impl TransferTask {
+  /// 依次处理每个源，单个源失败不影响其它源。`on_progress` 返回错误时停止处理当前的源，
+  /// 并把错误记录为它的结果，用于取消后台任务。
+  /// 覆盖已存在的目标前调用 `on_replace` 移走目标，例如移动到回收站
+  pub fn run(
+    &self,
+    mut on_progress: impl FnMut(&TransferProgress) -> Result<(), AppError>,
+    mut on_replace: impl FnMut(&Path) -> Result<(), AppError>,
+  ) -> Vec<TransferResult> {
+    let mut progress = TransferProgress::default();
+    for source in &self.sources {
+      let (files, bytes) = count_tree(&source.path);
+      progress.total_files += files;
+      progress.total_bytes += bytes;
+    }
+    let mut results = Vec::new();
+    for source in &self.sources {
+      progress.current = source.name.clone();
+      let result = match on_progress(&progress).and_then(|()| {
+        self.transfer_one(
+          &source.path,
+          &mut progress,
+          &mut on_progress,
+          &mut on_replace,
+        )
+      }) {
+        Ok(Some(target)) => TransferResult {
+          source: source.name.clone(),
+          target: Some(target),
+          skipped: false,
+          error: None,
+          code: None,
+        },
+        Ok(None) => TransferResult {
+          source: source.name.clone(),
+          target: None,
+          skipped: true,
+          error: None,
+          code: None,
+        },
+        Err(err) => TransferResult {
+          source: source.name.clone(),
+          target: None,
+          skipped: false,
+          error: Some(err.message()),
+          code: err.code(),
+        },
+      };
+      results.push(result);
+    }
+    results
+  }
+
+  fn transfer_one(
+    &self,
+    source: &Path,
+    progress: &mut TransferProgress,
+    on_progress: &mut impl FnMut(&TransferProgress) -> Result<(), AppError>,
+    on_replace: &mut impl FnMut(&Path) -> Result<(), AppError>,
+  ) -> Result<Option<String>, AppError> {
+    let name = source
+      .file_name()
+      .and_then(|name| name.to_str())
+      .ok_or_else(|| AppError::new("路径不合法"))?;
+    let metadata = fs::symlink_metadata(source).map_err(|_| AppError::new("文件不存在"))?;
+    // 链接可能指向存储之外，复制时会把外部的内容带进存储
+    if metadata.is_symlink() {
+      return Err(AppError::new("不支持链接等特殊文件"));
+    }
+    if source.is_dir() && self.destination.starts_with(source) {
+      return Err(AppError::new("不能移动或复制到自身的子文件夹中"));
+    }
+    if let Some(policy) = &self.policy {
+      check_tree(source, policy)?;
+    }
+
+    let mut target = self.destination.join(name);
+    if target == source {
+      // 原地复制只能生成副本，原地移动没有意义
+      if self.mode == TransferMode::Move || self.conflict != ConflictPolicy::Rename {
+        return Ok(None);
+      }
+    }
+    if target.exists() {
+      match self.conflict {
+        ConflictPolicy::Skip => {
+          let (files, bytes) = count_tree(source);
+          progress.done_files += files;
+          progress.done_bytes += bytes;
+          on_progress(progress)?;
+          return Ok(None);
+        }
+        ConflictPolicy::Overwrite => {
+          // 目标是源所在的文件夹时，移走目标会连同源一起移走
+          if source.starts_with(&target) {
+            return Err(AppError::new("不能覆盖源文件所在的文件夹"));
+          }
+          on_replace(&target)?;
+        }
+        ConflictPolicy::Rename => target = unique_path(&self.destination, name),
+      }
+    }
+
+    match self.mode {
+      TransferMode::Copy => {
+        if let Err(err) = copy_tree(source, &target, progress, on_progress) {
+          // 目标总是新建的，复制中途失败或被取消时不保留不完整的副本，避免重试时被跳过
+          remove_path(&target).ok();
+          return Err(err);
+        }
+      }
+      TransferMode::Move => move_tree(source, &target, progress, on_progress)?,
+    }
+
+    Ok(
+      target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string()),
+    )
+  }
+}
+
+/// 复制文件或文件夹，不需要进度时使用
+pub fn copy_path(source: &Path, target: &Path) -> Result<(), AppError> {
+  copy_tree(
+    source,
+    target,
+    &mut TransferProgress::default(),
+    &mut |_: &TransferProgress| Ok(()),
+  )
+}
+
+/// 移动文件或文件夹，不需要进度时使用
+pub fn move_path(source: &Path, target: &Path) -> Result<(), AppError> {
+  move_tree(
+    source,
+    target,
+    &mut TransferProgress::default(),
+    &mut |_: &TransferProgress| Ok(()),
+  )
+}
+
+fn move_tree(
+  source: &Path,
+  target: &Path,
+  progress: &mut TransferProgress,
+  on_progress: &mut impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+  match fs::rename(source, target) {
+    Ok(()) => {
+      let (files, bytes) = count_tree(target);
+      progress.done_files += files;
+      progress.done_bytes += bytes;
+      // 已经移动完成，停止只影响后续的源
+      on_progress(progress).ok();
+    }
+    // 跨文件系统时无法直接 rename，先复制再删除
+    Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+      copy_tree(source, target, progress, on_progress)?;
+      remove_path(source)?;
+    }
+    Err(err) => return Err(err.into()),
+  }
+  Ok(())
+}
+
+/// 统计文件数量和总大小，跳过系统文件和链接
+fn count_tree(path: &Path) -> (u64, u64) {
+  let Ok(metadata) = fs::symlink_metadata(path) else {
+    return (0, 0);
+  };
+  if metadata.is_symlink() {
+    return (0, 0);
+  }
+  if !metadata.is_dir() {
+    return (1, metadata.len());
+  }
+  let Ok(entries) = fs::read_dir(path) else {
+    return (0, 0);
+  };
+  entries
+    .flatten()
+    .filter(|entry| !is_system_file(&entry.file_name().to_string_lossy()))
+    .map(|entry| count_tree(&entry.path()))
+    .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b))
+}
+
+/// 按存储限制检查文件或文件夹内的所有文件，链接不会被复制，直接跳过
+pub fn check_tree(path: &Path, policy: &StoragePolicy) -> Result<(), AppError> {
+  let metadata = fs::symlink_metadata(path)?;
+  if metadata.is_symlink() {
+    return Ok(());
+  }
+  if !metadata.is_dir() {
+    policy.check_name(&path.file_name().unwrap_or_default().to_string_lossy())?;
+    return policy.check_size(metadata.len());
+  }
+  for entry in fs::read_dir(path)?.flatten() {
+    if is_system_file(&entry.file_name().to_string_lossy()) {
+      continue;
+    }
+    check_tree(&entry.path(), policy)?;
+  }
+  Ok(())
+}
+
+/// 复制文件或文件夹，跳过系统文件和链接，链接可能指向存储之外
+fn copy_tree(
+  source: &Path,
+  target: &Path,
+  progress: &mut TransferProgress,
+  on_progress: &mut impl FnMut(&TransferProgress) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+  let metadata = fs::symlink_metadata(source)?;
+  if metadata.is_symlink() {
+    return Ok(());
+  }
+  if metadata.is_dir() {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)?.flatten() {
+      let name = entry.file_name();
+      if is_system_file(&name.to_string_lossy()) {
+        continue;
+      }
+      copy_tree(&entry.path(), &target.join(&name), progress, on_progress)?;
+    }
+    return Ok(());
+  }
+
+  let mut reader = fs::File::open(source)?;
+  let mut writer = fs::File::create(target)?;
+  let mut buffer = vec![0; COPY_BUFFER_SIZE];
+  loop {
+    let read = reader.read(&mut buffer)?;
+    if read == 0 {
+      break;
+    }
+    writer.write_all(&buffer[..read])?;
+    progress.done_bytes += read as u64;
+    on_progress(progress)?;
+  }
+  if let Ok(modified) = metadata.modified() {
+    let _ = writer.set_modified(modified);
+  }
+  progress.done_files += 1;
+  on_progress(progress)
+}
+
+pub fn remove_path(path: &Path) -> io::Result<()> {
+  if fs::symlink_metadata(path)?.is_dir() {
+    fs::remove_dir_all(path)
+  } else {
+    fs::remove_file(path)
+  }
+}
+
+/// 生成不冲突的名称：`a.txt` -> `a (1).txt`
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+  (1..)
+    .map(|index| dir.join(numbered_name(name, index)))
+    .find(|path| !path.exists())
+    .unwrap()
+}
+
+/// 在扩展名前加上序号：`a.txt`, 1 -> `a (1).txt`
+pub fn numbered_name(name: &str, index: usize) -> String {
+  let (stem, extension) = match name.rfind('.') {
+    Some(index) if index > 0 => (&name[..index], &name[index..]),
+    _ => (name, ""),
+  };
+  format!("{} ({}){}", stem, index, extension)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_overwrite() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-transfer-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    fs::create_dir_all(root.join("x/x")).unwrap();
+    fs::write(root.join("a.txt"), "new").unwrap();
+    fs::create_dir_all(root.join("dir")).unwrap();
+    fs::write(root.join("dir/a.txt"), "old").unwrap();
+    let task = |sources: &[&str], destination: &str| TransferTask {
+      mode: TransferMode::Move,
+      conflict: ConflictPolicy::Overwrite,
+      sources: sources
+        .iter()
+        .map(|name| TransferSource {
+          name: name.to_string(),
+          path: root.join(name),
+        })
+        .collect(),
+      destination: root.join(destination),
+      policy: None,
+    };
+
+    // 被覆盖的目标交给 on_replace 处理，而不是直接删除
+    let trash = root.join("trash");
+    let results = task(&["a.txt"], "dir").run(|_| Ok(()), |target| Ok(fs::rename(target, &trash)?));
+    assert!(results[0].error.is_none());
+    assert_eq!(fs::read_to_string(root.join("dir/a.txt")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(&trash).unwrap(), "old");
+
+    // 把 x/x 移动到根目录会覆盖它所在的 x
+    let results = task(&["x/x"], "").run(|_| Ok(()), |_| panic!("target should be kept"));
+    assert!(results[0].error.is_some());
+    assert!(root.join("x/x").is_dir());
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_copy_skips_symlinks() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-transfer-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    fs::create_dir_all(root.join("outside")).unwrap();
+    fs::write(root.join("outside/secret.txt"), "secret").unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/a.txt"), "a").unwrap();
+    std::os::unix::fs::symlink(root.join("outside"), root.join("src/link")).unwrap();
+    std::os::unix::fs::symlink(&root, root.join("src/loop")).unwrap();
+
+    assert_eq!(count_tree(&root.join("src")), (1, 1));
+    assert!(copy_path(&root.join("src"), &root.join("dst")).is_ok());
+    assert_eq!(fs::read_to_string(root.join("dst/a.txt")).unwrap(), "a");
+    assert!(fs::symlink_metadata(root.join("dst/link")).is_err());
+    assert!(fs::symlink_metadata(root.join("dst/loop")).is_err());
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_unique_path() {
+    let dir = Path::new("/storkitty-not-exists");
+    assert_eq!(unique_path(dir, "a.txt"), dir.join("a (1).txt"));
+    assert_eq!(unique_path(dir, "a"), dir.join("a (1)"));
+    assert_eq!(unique_path(dir, ".env"), dir.join(".env (1)"));
+    assert_eq!(unique_path(dir, "a.tar.gz"), dir.join("a.tar (1).gz"));
+  }
+}
@@ -0,0 +1,147 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::backend::{
+  db::{self, DBConnection},
+  utils::search,
+};
+
+/// 清理过期回收站条目的间隔
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn trash_dir(root: &Path) -> PathBuf {
+  root.join(".storkitty").join("trash")
+}
+
+/// 把文件或文件夹移动到回收站，并记录原路径、删除人和删除时间。
+/// 文件操作在阻塞线程中执行，期间不持有数据库锁
+pub async fn move_to_trash(
+  conn: &DBConnection,
+  storage_id: i64,
+  root: &Path,
+  target: &Path,
+  user_id: i64,
+) -> anyhow::Result<()> {
+  let relative_path = target
+    .strip_prefix(root)
+    .context("路径不合法")?
+    .to_string_lossy()
+    .to_string();
+  let metadata = tokio::fs::metadata(target).await?;
+  let size = metadata.is_file().then_some(metadata.len());
+
+  let id = db::trash::create_trash_item(
+    &*conn.lock().await,
+    storage_id,
+    &relative_path,
+    metadata.is_dir(),
+    size,
+    user_id,
+  )?;
+  let (source, trash_dir) = (target.to_path_buf(), trash_dir(root));
+  let result = tokio::task::spawn_blocking(move || {
+    fs::create_dir_all(&trash_dir)?;
+    fs::rename(source, trash_dir.join(id.to_string()))
+  })
+  .await?;
+  let conn = conn.lock().await;
+  if let Err(err) = result {
+    db::trash::delete_trash_item(&conn, id)?;
+    return Err(err.into());
+  }
+  search::refresh(storage_id, root, target);
+  Ok(())
+}
+
+/// 还原到原路径，原路径已存在时报错，返回还原后的路径
+pub async fn restore(
+  conn: &DBConnection,
+  storage_id: i64,
+  root: &Path,
+  id: i64,
+) -> anyhow::Result<PathBuf> {
+  let item =
+    db::trash::get_trash_item(&*conn.lock().await, storage_id, id).context("回收站条目不存在")?;
+  let target = root.join(&item.path);
+  let (source, restored) = (trash_dir(root).join(id.to_string()), target.clone());
+  tokio::task::spawn_blocking(move || {
+    if restored.exists() {
+      return Err(anyhow::anyhow!("原位置已存在同名文件: {}", item.path));
+    }
+    if let Some(parent) = restored.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    fs::rename(source, &restored)?;
+    Ok(())
+  })
+  .await??;
+  let conn = conn.lock().await;
+  db::trash::delete_trash_item(&conn, id)?;
+  search::refresh(storage_id, root, &target);
+  Ok(target)
+}
+
+/// 彻底删除
+pub async fn purge(conn: &DBConnection, root: &Path, id: i64) -> anyhow::Result<()> {
+  let path = trash_dir(root).join(id.to_string());
+  tokio::task::spawn_blocking(move || {
+    if path.is_dir() {
+      fs::remove_dir_all(&path)
+    } else if path.exists() {
+      fs::remove_file(&path)
+    } else {
+      Ok(())
+    }
+  })
+  .await??;
+  db::trash::delete_trash_item(&*conn.lock().await, id)?;
+  Ok(())
+}
+
+/// 删除超过保留天数的条目，单个条目失败时记录日志并继续
+pub async fn purge_expired(conn: &DBConnection, days: i64) -> anyhow::Result<()> {
+  let items = db::trash::get_expired_trash(&*conn.lock().await, days)?;
+  for item in items {
+    let storage = db::storage::get_storage_by_id(&*conn.lock().await, item.storage_id);
+    let result = match storage {
+      Ok(storage) => {
+        log::info!("Purge expired trash: {} ({})", item.path, storage.path);
+        purge(conn, Path::new(&storage.local_path), item.id).await
+      }
+      Err(_) => db::trash::delete_trash_item(&*conn.lock().await, item.id),
+    };
+    if let Err(err) = result {
+      log::warn!("Failed to purge expired trash {}: {err}", item.id);
+    }
+  }
+  Ok(())
+}
+
+/// 回收站保留天数，可以通过 TRASH_RETENTION_DAYS 配置，0 表示不自动清理
+pub fn retention_days() -> i64 {
+  std::env::var("TRASH_RETENTION_DAYS")
+    .unwrap_or("30".to_string())
+    .parse::<i64>()
+    .unwrap_or(30)
+}
+
+pub fn spawn_trash_cleaner(conn: DBConnection) {
+  let days = retention_days();
+  if days <= 0 {
+    return;
+  }
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(CLEAN_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = purge_expired(&conn, days).await {
+        log::warn!("Failed to purge expired trash: {err}");
+      }
+    }
+  });
+}
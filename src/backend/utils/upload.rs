@@ -0,0 +1,408 @@
+use std::{
+  collections::HashMap,
+  fs, io,
+  io::Read,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::{LazyLock, Mutex},
+  time::{Duration, SystemTime},
+};
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::{
+  db::{self, DBConnection},
+  error::AppError,
+  utils::{
+    backend::{StorageBackend, temp_dir},
+    transfer::numbered_name,
+  },
+};
+
+pub const FILE_EXISTS: &str = "FILE_EXISTS";
+pub const CHECKSUM_MISMATCH: &str = "CHECKSUM_MISMATCH";
+
+/// 清理过期上传的间隔
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_TTL_HOURS: i64 = 24;
+
+/// 分片上传会话的分片目录，远程存储同样保存在本地目录中
+pub fn chunks_dir(root: &Path, id: &str) -> PathBuf {
+  root.join(".storkitty").join("chunks").join(id)
+}
+
+/// tus 上传的数据文件，远程存储同样保存在本地目录中
+pub fn tus_path(root: &Path, id: &str) -> PathBuf {
+  root.join(".storkitty").join("tus").join(id)
+}
+
+/// 暂存文件所在的文件夹，以及其中的条目是否需要有对应的上传记录
+fn staging_dirs(root: &Path) -> [(PathBuf, Option<&'static str>); 3] {
+  let staging = root.join(".storkitty");
+  [
+    (staging.join("chunks"), Some("upload_session")),
+    (staging.join("tus"), Some("tus_upload")),
+    (temp_dir(root), None),
+  ]
+}
+
+/// 上传的目标文件已存在时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OnConflict {
+  #[default]
+  Fail,
+  Overwrite,
+  /// 自动重命名为 `name (1).ext`
+  Rename,
+}
+
+impl OnConflict {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      OnConflict::Fail => "fail",
+      OnConflict::Overwrite => "overwrite",
+      OnConflict::Rename => "rename",
+    }
+  }
+}
+
+impl FromStr for OnConflict {
+  type Err = anyhow::Error;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "fail" => Ok(OnConflict::Fail),
+      "overwrite" => Ok(OnConflict::Overwrite),
+      "rename" => Ok(OnConflict::Rename),
+      _ => Err(anyhow::anyhow!("未知的冲突处理方式: {}", value)),
+    }
+  }
+}
+
+/// 按冲突策略确定最终写入的相对路径。同名文件夹总是冲突
+pub async fn resolve_target(
+  backend: &dyn StorageBackend,
+  path: &str,
+  conflict: OnConflict,
+) -> Result<String, AppError> {
+  let Some(entry) = backend.try_stat(path).await? else {
+    return Ok(path.to_string());
+  };
+  match conflict {
+    OnConflict::Overwrite if !entry.is_dir => Ok(path.to_string()),
+    OnConflict::Rename => {
+      let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+      for index in 1.. {
+        let candidate = super::backend::join(dir, &numbered_name(name, index));
+        if !backend.exists(&candidate).await? {
+          return Ok(candidate);
+        }
+      }
+      unreachable!()
+    }
+    _ => Err(AppError::with_code(
+      StatusCode::CONFLICT,
+      FILE_EXISTS,
+      "文件已存在",
+    )),
+  }
+}
+
+/// 按顺序计算多个本地文件拼接后的 SHA-256 (hex)
+pub async fn sha256_files(files: Vec<PathBuf>) -> Result<String, AppError> {
+  let hash = tokio::task::spawn_blocking(move || {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    for path in files {
+      let mut file = std::fs::File::open(path)?;
+      loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+          break;
+        }
+        hasher.update(&buffer[..read]);
+      }
+    }
+    anyhow::Ok(hex::encode(hasher.finalize()))
+  })
+  .await??;
+  Ok(hash)
+}
+
+/// 比较客户端提供的 SHA-256，不区分大小写
+pub fn check_sha256(actual: &str, expected: &str) -> Result<(), AppError> {
+  if !actual.eq_ignore_ascii_case(expected.trim()) {
+    return Err(AppError::with_code(
+      StatusCode::UNPROCESSABLE_ENTITY,
+      CHECKSUM_MISMATCH,
+      "文件校验失败，内容与提供的 SHA-256 不一致",
+    ));
+  }
+  Ok(())
+}
+
+/// 正在接收数据的上传及其写入方数量
+static ACTIVE: LazyLock<Mutex<HashMap<String, (usize, CancellationToken)>>> =
+  LazyLock::new(Default::default);
+
+/// 登记一个正在写入的上传，释放时注销。上传被取消或清理时，
+/// 写入方通过 [`ActiveUpload::cancelled`] 立即停止接收
+pub struct ActiveUpload {
+  id: String,
+  token: CancellationToken,
+}
+
+impl ActiveUpload {
+  pub fn track(id: &str) -> Self {
+    let mut active = ACTIVE.lock().unwrap_or_else(|err| err.into_inner());
+    let (count, token) = active.entry(id.to_string()).or_default();
+    *count += 1;
+    Self {
+      id: id.to_string(),
+      token: token.clone(),
+    }
+  }
+
+  pub async fn cancelled(&self) {
+    self.token.cancelled().await
+  }
+
+  fn is_active(id: &str) -> bool {
+    ACTIVE
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .contains_key(id)
+  }
+}
+
+impl Drop for ActiveUpload {
+  fn drop(&mut self) {
+    let mut active = ACTIVE.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((count, _)) = active.get_mut(&self.id) {
+      *count -= 1;
+      if *count == 0 {
+        active.remove(&self.id);
+      }
+    }
+  }
+}
+
+/// 通知上传的所有写入方停止，没有正在写入的请求时什么也不做
+pub fn cancel(id: &str) {
+  if let Some((_, token)) = ACTIVE.lock().unwrap_or_else(|err| err.into_inner()).get(id) {
+    token.cancel();
+  }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupReport {
+  /// 删除的过期分片上传会话和 tus 上传
+  pub uploads: usize,
+  /// 删除的没有对应记录的暂存文件
+  pub orphans: usize,
+  /// 回收的空间 (bytes)
+  pub bytes: u64,
+}
+
+/// 未完成的上传的保留时间，可以通过 UPLOAD_TTL_HOURS 配置，0 表示不自动清理
+pub fn ttl_hours() -> i64 {
+  std::env::var("UPLOAD_TTL_HOURS")
+    .unwrap_or(DEFAULT_TTL_HOURS.to_string())
+    .parse::<i64>()
+    .unwrap_or(DEFAULT_TTL_HOURS)
+}
+
+/// 删除超过 `hours` 小时没有收到数据的上传，以及服务中断等原因遗留、
+/// 已没有对应记录的暂存文件。正在写入的上传不会被删除。
+/// 只在查询和删除记录时持有数据库锁，文件在释放锁之后删除
+pub fn purge_expired(conn: &DBConnection, hours: i64) -> anyhow::Result<CleanupReport> {
+  let mut report = CleanupReport::default();
+  let mut expired = Vec::new();
+  let storages = {
+    let conn = conn.blocking_lock();
+    let storages = db::storage::get_all_storage(&conn)?;
+    let root_of = |storage_id: i64| {
+      storages
+        .iter()
+        .find(|storage| storage.id == storage_id)
+        .map(|storage| Path::new(&storage.local_path))
+    };
+    for session in db::upload::get_expired_upload_sessions(&conn, hours)? {
+      if ActiveUpload::is_active(&session.id) {
+        continue;
+      }
+      log::info!("Purge expired upload session: {}", session.path);
+      db::upload::delete_upload_session(&conn, &session.id)?;
+      if let Some(root) = root_of(session.storage_id) {
+        expired.push(chunks_dir(root, &session.id));
+      }
+      report.uploads += 1;
+    }
+    for upload in db::tus::get_expired_tus_uploads(&conn, hours)? {
+      if ActiveUpload::is_active(&upload.id) {
+        continue;
+      }
+      log::info!("Purge expired tus upload: {}", upload.path);
+      db::tus::delete_tus_upload(&conn, &upload.id)?;
+      if let Some(root) = root_of(upload.storage_id) {
+        expired.push(tus_path(root, &upload.id));
+      }
+      report.uploads += 1;
+    }
+    storages
+  };
+  for path in expired {
+    report.bytes += remove_path(&path)?;
+  }
+
+  // 先扫描出足够旧的暂存文件，再在一次加锁中确认哪些没有对应的记录
+  let expires = SystemTime::now() - Duration::from_secs(hours as u64 * 60 * 60);
+  let mut candidates = Vec::new();
+  for storage in &storages {
+    let root = Path::new(&storage.local_path);
+    for (dir, table) in staging_dirs(root) {
+      let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+        Err(err) => return Err(err.into()),
+      };
+      for entry in entries {
+        let entry = entry?;
+        if entry.metadata()?.modified()? > expires {
+          continue;
+        }
+        let id = entry.file_name().to_string_lossy().to_string();
+        candidates.push((entry.path(), id, table));
+      }
+    }
+  }
+  let orphans = {
+    let conn = conn.blocking_lock();
+    let mut orphans = Vec::new();
+    for (path, id, table) in candidates {
+      if let Some(table) = table {
+        let exists = conn
+          .prepare(&format!("SELECT 1 FROM {table} WHERE id = ?"))?
+          .exists((&id,))?;
+        if exists || ActiveUpload::is_active(&id) {
+          continue;
+        }
+      }
+      orphans.push(path);
+    }
+    orphans
+  };
+  for path in orphans {
+    report.bytes += remove_path(&path)?;
+    report.orphans += 1;
+  }
+  Ok(report)
+}
+
+/// 删除文件或文件夹，返回释放的大小
+fn remove_path(path: &Path) -> io::Result<u64> {
+  let metadata = match fs::symlink_metadata(path) {
+    Ok(metadata) => metadata,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+    Err(err) => return Err(err),
+  };
+  if !metadata.is_dir() {
+    fs::remove_file(path)?;
+    return Ok(metadata.len());
+  }
+  let mut size = 0;
+  for entry in fs::read_dir(path)? {
+    size += remove_path(&entry?.path())?;
+  }
+  fs::remove_dir(path)?;
+  Ok(size)
+}
+
+/// 立即清理一次并记录回收的空间。未配置保留时间时按默认的 24 小时
+pub async fn cleanup(conn: &DBConnection) -> anyhow::Result<CleanupReport> {
+  let hours = match ttl_hours() {
+    hours if hours > 0 => hours,
+    _ => DEFAULT_TTL_HOURS,
+  };
+  let conn = conn.clone();
+  let report = tokio::task::spawn_blocking(move || purge_expired(&conn, hours)).await??;
+  if report.uploads > 0 || report.orphans > 0 {
+    log::info!(
+      "Upload cleanup: removed {} uploads and {} orphaned files, reclaimed {} bytes",
+      report.uploads,
+      report.orphans,
+      report.bytes
+    );
+  }
+  Ok(report)
+}
+
+pub fn spawn_upload_cleaner(conn: DBConnection) {
+  if ttl_hours() <= 0 {
+    return;
+  }
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(CLEAN_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = cleanup(&conn).await {
+        log::warn!("Failed to clean up expired uploads: {err}");
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backend::utils::backend::{LocalBackend, once};
+
+  #[tokio::test]
+  async fn test_resolve_target() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-upload-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    std::fs::create_dir_all(root.join("dir")).unwrap();
+    let backend = LocalBackend::new(root.clone());
+    backend.write("a.txt", once("a".into())).await.unwrap();
+
+    assert_eq!(
+      resolve_target(&backend, "b.txt", OnConflict::Fail)
+        .await
+        .ok()
+        .as_deref(),
+      Some("b.txt")
+    );
+    let err = resolve_target(&backend, "a.txt", OnConflict::Fail)
+      .await
+      .unwrap_err();
+    assert_eq!(err.code(), Some(FILE_EXISTS));
+    assert_eq!(
+      resolve_target(&backend, "a.txt", OnConflict::Overwrite)
+        .await
+        .ok()
+        .as_deref(),
+      Some("a.txt")
+    );
+    assert_eq!(
+      resolve_target(&backend, "a.txt", OnConflict::Rename)
+        .await
+        .ok()
+        .as_deref(),
+      Some("a (1).txt")
+    );
+    assert!(
+      resolve_target(&backend, "dir", OnConflict::Overwrite)
+        .await
+        .is_err()
+    );
+    assert_eq!("rename".parse::<OnConflict>().unwrap(), OnConflict::Rename);
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
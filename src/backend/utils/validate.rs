@@ -1,3 +1,5 @@
+use crate::backend::utils::file::is_system_file;
+
 /// 常见系统保留名（可以按需补充）
 const RESERVED_NAMES: &[&str] = &[
   ".",
@@ -93,9 +95,9 @@ pub fn validate_path(path: &str) -> bool {
     return false;
   }
 
-  // 分割并校验每一段
+  // 分割并校验每一段，`.storkitty` 等系统目录保存回收站、历史版本和上传暂存文件，不能通过路径访问
   for segment in path.split('/') {
-    if !segment.is_empty() && !validate_name(segment) {
+    if !segment.is_empty() && (!validate_name(segment) || is_system_file(segment)) {
       return false;
     }
   }
@@ -110,6 +112,7 @@ mod tests {
   #[test]
   fn test_validate_path() {
     assert!(validate_path("/data"));
+    assert!(!validate_path("data/.storkitty/trash/1"));
   }
 
   #[test]
@@ -0,0 +1,263 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use axum::http::StatusCode;
+use similar::TextDiff;
+
+use crate::backend::{
+  db::{
+    self, DBConnection,
+    storage::{StorageDatabase, StorageKind},
+  },
+  error::AppError,
+  utils::{backend::temp_file, search},
+};
+
+/// 清理过期历史版本的间隔
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn versions_dir(root: &Path) -> PathBuf {
+  root.join(".storkitty").join("versions")
+}
+
+pub fn version_path(root: &Path, id: i64) -> PathBuf {
+  versions_dir(root).join(id.to_string())
+}
+
+/// 覆盖文件前调用，把当前内容保存为历史版本，并删除超出存储设置数量的旧版本。
+/// 目标不是已有文件、远程存储或存储不保留版本时什么也不做
+///
+/// 版本总是复制而不是硬链接，原地修改文件的写入方式不会改动历史版本。
+/// Linux 上 `fs::copy` 使用 copy_file_range，支持的文件系统会直接共享数据块
+pub async fn preserve(
+  conn: &DBConnection,
+  storage_id: i64,
+  path: &str,
+  user_id: i64,
+) -> anyhow::Result<()> {
+  let (conn, path) = (conn.clone(), path.to_string());
+  tokio::task::spawn_blocking(move || preserve_blocking(&conn, storage_id, &path, user_id)).await?
+}
+
+/// 在阻塞线程中使用的 [`preserve`]，只在读写数据库时持有锁
+pub fn preserve_blocking(
+  conn: &DBConnection,
+  storage_id: i64,
+  path: &str,
+  user_id: i64,
+) -> anyhow::Result<()> {
+  let storage = db::storage::get_storage_by_id(&conn.blocking_lock(), storage_id)?;
+  if storage.kind != StorageKind::Local || storage.max_versions == 0 {
+    return Ok(());
+  }
+  let root = Path::new(&storage.local_path);
+  let target = root.join(path);
+  let Ok(metadata) = fs::metadata(&target) else {
+    return Ok(());
+  };
+  if !metadata.is_file() {
+    return Ok(());
+  }
+  let modified = metadata
+    .modified()
+    .ok()
+    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+    .map(|modified| modified.as_secs() as i64);
+
+  fs::create_dir_all(versions_dir(root))?;
+  let id = db::version::create_version(
+    &conn.blocking_lock(),
+    storage_id,
+    path,
+    metadata.len(),
+    modified,
+    user_id,
+  )?;
+  let saved = version_path(root, id);
+  if let Err(err) = fs::copy(&target, &saved) {
+    fs::remove_file(&saved).ok();
+    db::version::delete_version(&conn.blocking_lock(), id)?;
+    return Err(err).context("保存历史版本失败");
+  }
+  prune(conn, &storage, path)
+}
+
+/// 只保留最新的 `max_versions` 个版本
+fn prune(conn: &DBConnection, storage: &StorageDatabase, path: &str) -> anyhow::Result<()> {
+  let root = Path::new(&storage.local_path);
+  let versions = db::version::get_versions(&conn.blocking_lock(), storage.id, path)?;
+  for version in versions.iter().skip(storage.max_versions as usize) {
+    remove(conn, root, version.id)?;
+  }
+  Ok(())
+}
+
+/// 删除版本文件和记录，需要在阻塞线程中调用
+pub fn remove(conn: &DBConnection, root: &Path, id: i64) -> anyhow::Result<()> {
+  let path = version_path(root, id);
+  if path.exists() {
+    fs::remove_file(&path)?;
+  }
+  db::version::delete_version(&conn.blocking_lock(), id)?;
+  Ok(())
+}
+
+/// 用历史版本替换当前文件，当前文件先保存为新的版本，所以还原也可以撤销。
+/// 文件已被删除时重新创建，返回文件的本地路径
+pub async fn restore(
+  conn: &DBConnection,
+  storage_id: i64,
+  root: &Path,
+  id: i64,
+  user_id: i64,
+) -> Result<PathBuf, AppError> {
+  let version = db::version::get_version(&*conn.lock().await, storage_id, id)
+    .map_err(|_| AppError::with_status(StatusCode::NOT_FOUND, "版本不存在"))?;
+  let target = root.join(&version.path);
+  if target.is_dir() {
+    return Err(AppError::new("目标是文件夹"));
+  }
+
+  // 先复制出来，保存当前文件时这个版本可能因为数量限制被删除
+  let temp = temp_file(root).await?;
+  let result = async {
+    tokio::fs::copy(version_path(root, id), &temp)
+      .await
+      .context("版本文件已丢失")?;
+    preserve(conn, storage_id, &version.path, user_id).await?;
+    if let Some(parent) = target.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(&temp, &target).await?;
+    Ok::<_, AppError>(())
+  }
+  .await;
+  if let Err(err) = result {
+    tokio::fs::remove_file(&temp).await.ok();
+    return Err(err);
+  }
+  search::refresh(storage_id, root, &target);
+  Ok(target)
+}
+
+/// 两段文本的统一格式差异
+pub fn diff_text(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+  TextDiff::from_lines(old, new)
+    .unified_diff()
+    .header(old_name, new_name)
+    .to_string()
+}
+
+/// 删除所有存储中超过保留天数的版本，单个版本失败时记录日志并继续。需要在阻塞线程中调用
+pub fn purge_expired(conn: &DBConnection) -> anyhow::Result<()> {
+  let storages = db::storage::get_all_storage(&conn.blocking_lock())?;
+  for storage in storages {
+    if storage.version_days == 0 {
+      continue;
+    }
+    let root = Path::new(&storage.local_path);
+    let versions =
+      db::version::get_expired_versions(&conn.blocking_lock(), storage.id, storage.version_days)?;
+    for version in versions {
+      log::info!("Purge expired version: {} ({})", version.path, storage.path);
+      if let Err(err) = remove(conn, root, version.id) {
+        log::warn!("Failed to purge expired version {}: {err}", version.id);
+      }
+    }
+  }
+  Ok(())
+}
+
+pub fn spawn_version_cleaner(conn: DBConnection) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(CLEAN_INTERVAL);
+    loop {
+      interval.tick().await;
+      let conn = conn.clone();
+      let result = tokio::task::spawn_blocking(move || purge_expired(&conn)).await;
+      match result {
+        Ok(Err(err)) => log::warn!("Failed to purge expired versions: {err}"),
+        Err(err) => log::warn!("Failed to purge expired versions: {err}"),
+        Ok(Ok(())) => {}
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backend::db::storage::CreateStorageDto;
+
+  #[test]
+  fn test_preserve_and_prune() {
+    let root = std::env::temp_dir().join(format!(
+      "storkitty-version-{}",
+      hex::encode(rand::random::<[u8; 8]>())
+    ));
+    fs::create_dir_all(&root).unwrap();
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    db::user::create_user_database(&conn).unwrap();
+    db::storage::create_storage_database(&conn).unwrap();
+    db::version::create_version_database(&conn).unwrap();
+    db::storage::create_storage(
+      &conn,
+      CreateStorageDto {
+        name: "a".to_string(),
+        path: "a".to_string(),
+        local_path: root.to_string_lossy().to_string(),
+        kind: StorageKind::Local,
+        config: Default::default(),
+        max_file_size: 0,
+        allow_extensions: String::new(),
+        block_extensions: String::new(),
+        max_versions: 2,
+        version_days: 30,
+        sort_index: 0,
+      },
+    )
+    .unwrap();
+    let storage_id = db::storage::get_storage_by_path(&conn, "a").unwrap().id;
+    let conn: DBConnection = std::sync::Arc::new(tokio::sync::Mutex::new(conn));
+
+    // 不存在的文件没有可以保存的版本
+    preserve_blocking(&conn, storage_id, "a.txt", 1).unwrap();
+    for content in ["1", "2", "3", "4"] {
+      preserve_blocking(&conn, storage_id, "a.txt", 1).unwrap();
+      let temp = root.join("a.txt.tmp");
+      fs::write(&temp, content).unwrap();
+      fs::rename(&temp, root.join("a.txt")).unwrap();
+    }
+
+    let versions = db::version::get_versions(&conn.blocking_lock(), storage_id, "a.txt").unwrap();
+    assert_eq!(versions.len(), 2);
+    let contents = versions
+      .iter()
+      .map(|version| fs::read_to_string(version_path(&root, version.id)).unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(contents, vec!["3", "2"]);
+
+    // 原地修改当前文件不会影响已保存的版本
+    preserve_blocking(&conn, storage_id, "a.txt", 1).unwrap();
+    fs::OpenOptions::new()
+      .append(true)
+      .open(root.join("a.txt"))
+      .and_then(|mut file| std::io::Write::write_all(&mut file, b"5"))
+      .unwrap();
+    let latest = db::version::get_versions(&conn.blocking_lock(), storage_id, "a.txt").unwrap();
+    let saved = fs::read_to_string(version_path(&root, latest[0].id)).unwrap();
+    assert_eq!(saved, "4");
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn test_diff_text() {
+    let diff = diff_text("a\nb\nc\n", "a\nB\nc\n", "old", "new");
+    assert!(diff.starts_with("--- old\n+++ new\n"));
+    assert!(diff.contains("-b\n+B\n"));
+  }
+}
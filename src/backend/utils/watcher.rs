@@ -0,0 +1,264 @@
+use std::{
+  collections::{HashMap, HashSet},
+  path::{Path, PathBuf},
+  sync::LazyLock,
+  time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+  sync::{Notify, broadcast, mpsc},
+  time::{Instant, interval, interval_at, sleep_until},
+};
+
+use crate::backend::{
+  db::{self, DBConnection, storage::StorageKind},
+  utils::file::is_system_file,
+};
+
+/// 事件停止这么久之后才发布，合并 rsync 之类工具产生的大量事件
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// 事件持续不断时最多延迟这么久发布
+const MAX_DELAY: Duration = Duration::from_secs(10);
+/// 同步存储列表（新增、禁用、删除、修改路径）的间隔
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 文件系统变更，已经过防抖和合并
+#[derive(Debug, Clone)]
+pub struct FsChange {
+  pub storage_id: i64,
+  pub root: PathBuf,
+  /// 发生变化的路径，可能已经不存在；全量扫描时只有存储根目录
+  pub paths: Vec<PathBuf>,
+}
+
+static CHANGES: LazyLock<broadcast::Sender<FsChange>> =
+  LazyLock::new(|| broadcast::channel(1024).0);
+
+static RELOAD: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// 存储新增、修改、禁用或删除后调用，立即同步监听列表
+pub fn reload() {
+  RELOAD.notify_one();
+}
+
+/// 订阅文件系统变更，索引、缩略图、容量统计等都从这里获取变更
+///
+/// 需要在 spawn_watcher 之前订阅，否则会错过启动时的全量扫描
+pub fn subscribe() -> broadcast::Receiver<FsChange> {
+  CHANGES.subscribe()
+}
+
+/// 通过接口修改文件后调用，立即发布变更，不经过防抖
+pub fn notify(storage_id: i64, root: &Path, paths: Vec<PathBuf>) {
+  publish(storage_id, root, paths);
+}
+
+/// 是否实时监听文件变化，WATCH_FS=false 时只做定期全量扫描
+fn watch_enabled() -> bool {
+  std::env::var("WATCH_FS")
+    .map(|value| value != "false" && value != "0")
+    .unwrap_or(true)
+}
+
+/// 定期全量扫描的间隔，弥补丢失的事件，WATCH_RESCAN_INTERVAL 单位为秒，默认 1 小时
+fn rescan_interval() -> Duration {
+  std::env::var("WATCH_RESCAN_INTERVAL")
+    .ok()
+    .and_then(|value| value.parse::<u64>().ok())
+    .filter(|secs| *secs > 0)
+    .map(Duration::from_secs)
+    .unwrap_or(Duration::from_secs(60 * 60))
+}
+
+struct WatchedStorage {
+  root: PathBuf,
+  /// drop 时停止监听
+  _watcher: Option<RecommendedWatcher>,
+}
+
+pub fn spawn_watcher(conn: DBConnection) {
+  tokio::spawn(async move {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(i64, Vec<PathBuf>)>();
+    let mut storages: HashMap<i64, WatchedStorage> = HashMap::new();
+    let mut pending: HashMap<i64, HashSet<PathBuf>> = HashMap::new();
+    let mut first_pending = Instant::now();
+    let mut last_event = Instant::now();
+    let mut reconcile = interval(RECONCILE_INTERVAL);
+    // 启动时由 reconcile 触发首次全量扫描
+    let mut rescan = interval_at(Instant::now() + rescan_interval(), rescan_interval());
+
+    loop {
+      let deadline = (last_event + DEBOUNCE).min(first_pending + MAX_DELAY);
+      tokio::select! {
+        biased;
+        _ = reconcile.tick() => reconcile_storages(&conn, &mut storages, &tx).await,
+        _ = RELOAD.notified() => reconcile_storages(&conn, &mut storages, &tx).await,
+        _ = rescan.tick() => {
+          for (storage_id, storage) in &storages {
+            publish(*storage_id, &storage.root, vec![storage.root.clone()]);
+          }
+        }
+        Some((storage_id, paths)) = rx.recv() => {
+          if pending.is_empty() {
+            first_pending = Instant::now();
+          }
+          last_event = Instant::now();
+          pending.entry(storage_id).or_default().extend(paths);
+        }
+        _ = sleep_until(deadline), if !pending.is_empty() => {
+          for (storage_id, paths) in pending.drain() {
+            if let Some(storage) = storages.get(&storage_id) {
+              publish(storage_id, &storage.root, collapse_paths(paths));
+            }
+          }
+        }
+      }
+    }
+  });
+}
+
+fn publish(storage_id: i64, root: &Path, paths: Vec<PathBuf>) {
+  // 没有订阅者时发送失败，可以忽略
+  let _ = CHANGES.send(FsChange {
+    storage_id,
+    root: root.to_path_buf(),
+    paths,
+  });
+}
+
+/// 按数据库中的已启用存储增删监听
+async fn reconcile_storages(
+  conn: &DBConnection,
+  storages: &mut HashMap<i64, WatchedStorage>,
+  tx: &mpsc::UnboundedSender<(i64, Vec<PathBuf>)>,
+) {
+  let enabled = {
+    let conn = conn.lock().await;
+    match db::storage::get_all_enabled_storage(&conn) {
+      Ok(enabled) => enabled,
+      Err(err) => {
+        log::warn!("Failed to load storages for watcher: {err}");
+        return;
+      }
+    }
+  };
+
+  // 远程存储的文件不在本地，不需要监听
+  let roots = enabled
+    .into_iter()
+    .filter(|storage| storage.kind == StorageKind::Local)
+    .map(|storage| (storage.id, PathBuf::from(storage.local_path)))
+    .collect::<HashMap<_, _>>();
+  storages.retain(|id, storage| roots.get(id) == Some(&storage.root));
+
+  for (storage_id, root) in roots {
+    if storages.contains_key(&storage_id) {
+      continue;
+    }
+    let watcher = watch_enabled()
+      .then(|| watch(storage_id, &root, tx.clone()))
+      .flatten();
+    // 新加入的存储先做一次全量扫描
+    publish(storage_id, &root, vec![root.clone()]);
+    storages.insert(
+      storage_id,
+      WatchedStorage {
+        root,
+        _watcher: watcher,
+      },
+    );
+  }
+}
+
+fn watch(
+  storage_id: i64,
+  root: &Path,
+  tx: mpsc::UnboundedSender<(i64, Vec<PathBuf>)>,
+) -> Option<RecommendedWatcher> {
+  let watch_root = root.to_path_buf();
+  let handler = move |result: notify::Result<notify::Event>| match result {
+    Ok(event) => {
+      if matches!(event.kind, EventKind::Access(_)) {
+        return;
+      }
+      let paths = event
+        .paths
+        .into_iter()
+        .filter(|path| !is_ignored(&watch_root, path))
+        .collect::<Vec<_>>();
+      if !paths.is_empty() {
+        let _ = tx.send((storage_id, paths));
+      }
+    }
+    Err(err) => log::warn!("Watcher error: {err}"),
+  };
+
+  let result = notify::recommended_watcher(handler).and_then(|mut watcher| {
+    watcher
+      .watch(root, RecursiveMode::Recursive)
+      .map(|_| watcher)
+  });
+  match result {
+    Ok(watcher) => {
+      log::info!("Watching storage {}", root.display());
+      Some(watcher)
+    }
+    Err(err) => {
+      log::warn!("Failed to watch {}: {err}", root.display());
+      None
+    }
+  }
+}
+
+/// 忽略存储外的路径以及 `.storkitty` 等系统文件
+fn is_ignored(root: &Path, path: &Path) -> bool {
+  match path.strip_prefix(root) {
+    Ok(relative_path) => relative_path
+      .components()
+      .any(|component| is_system_file(&component.as_os_str().to_string_lossy())),
+    Err(_) => true,
+  }
+}
+
+/// 去掉已被祖先路径覆盖的子路径，减少重复扫描
+fn collapse_paths(paths: HashSet<PathBuf>) -> Vec<PathBuf> {
+  let mut paths = paths.into_iter().collect::<Vec<_>>();
+  paths.sort();
+  let mut result: Vec<PathBuf> = Vec::new();
+  for path in paths {
+    if result.last().is_some_and(|last| path.starts_with(last)) {
+      continue;
+    }
+    result.push(path);
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_collapse_paths() {
+    let paths = ["/data/a", "/data/a/b.txt", "/data/ab", "/data/c/d"]
+      .into_iter()
+      .map(PathBuf::from)
+      .collect::<HashSet<_>>();
+    assert_eq!(
+      collapse_paths(paths),
+      ["/data/a", "/data/ab", "/data/c/d"]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn test_is_ignored() {
+    let root = Path::new("/data");
+    assert!(is_ignored(root, Path::new("/data/.storkitty/chunks/a")));
+    assert!(is_ignored(root, Path::new("/other/a.txt")));
+    assert!(!is_ignored(root, Path::new("/data/docs/a.txt")));
+  }
+}
@@ -0,0 +1,30 @@
+/// WebDAV / S3 接口手写 XML，只需要处理转义
+pub fn escape_xml(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+pub fn unescape_xml(value: &str) -> String {
+  value
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_escape_xml() {
+    let value = r#"a&b <"c'd">"#;
+    assert_eq!(escape_xml(value), "a&amp;b &lt;&quot;c&apos;d&quot;&gt;");
+    assert_eq!(unescape_xml(&escape_xml(value)), value);
+  }
+}
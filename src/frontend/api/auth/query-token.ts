@@ -0,0 +1,6 @@
+import { http } from "@/api/http";
+
+/** 生成放在 `?token=` 中的短期 token，登录 token 不能放在链接里 */
+export function createQueryToken() {
+  return http.post("token").json<{ token: string; expires: number }>();
+}
@@ -13,6 +13,7 @@ const fileListSchema = z.object({
       fileType: z.nativeEnum(FileType),
       size: z.nullable(z.number()),
       items: z.nullable(z.number()),
+      thumbnail: z.boolean().optional(),
       modified: z.string(),
     }),
   ),
@@ -0,0 +1,9 @@
+import { http } from "@/api/http";
+
+export function signDownloadUrl(path: string, expiresIn?: number) {
+  return http
+    .get(`file/sign/${path}`, {
+      searchParams: expiresIn ? { expiresIn } : undefined,
+    })
+    .json<{ url: string; expires: number }>();
+}
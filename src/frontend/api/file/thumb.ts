@@ -0,0 +1,16 @@
+export type ThumbSize = "small" | "medium" | "large";
+
+/** 缩略图地址，`<img>` 不能携带请求头，短期 token 放在查询参数中 */
+export function thumbUrl(
+  path: string,
+  token: string,
+  size: ThumbSize = "small",
+) {
+  const encodedPath = path
+    .split("/")
+    .filter(Boolean)
+    .map(encodeURIComponent)
+    .join("/");
+  const params = new URLSearchParams({ size, token });
+  return `/api/thumb/${encodedPath}?${params}`;
+}
@@ -3,9 +3,17 @@ import type { Progress } from "@/hooks/use-file-upload";
 
 const DEFAULT_CHUNK_SIZE = 5 * 1024 * 1024; // 5MB
 
+type UploadSession = {
+  id: string;
+  chunkSize: number;
+  totalChunks: number;
+  missingChunks: number[];
+};
+
 type FileTask = {
   file: File;
   path: string;
+  sessionId?: string;
   chunks: ChunkTask[];
   progress: {
     onProgress: (p: Progress) => void;
@@ -19,7 +27,6 @@ type ChunkTask = {
   chunkIndex: number;
   start: number;
   end: number;
-  totalChunks: number;
   controller: AbortController;
 };
 
@@ -51,33 +58,17 @@ export class FileUploader {
     onProgress: (p: Progress) => void,
     onError: (error: unknown) => void,
   ) {
-    const totalChunks = Math.ceil(file.size / this.chunkSize);
     const fileTask: FileTask = {
       file,
       path,
       progress: {
         onProgress,
-        transferredBytes: Array(totalChunks).fill(0),
+        transferredBytes: [],
         onError,
       },
       chunks: [],
     };
 
-    for (let i = 0; i < totalChunks; i++) {
-      const start = i * this.chunkSize;
-      const end = Math.min(start + this.chunkSize, file.size);
-      const controller = new AbortController();
-
-      fileTask.chunks.push({
-        fileTask,
-        chunkIndex: i,
-        start,
-        end,
-        totalChunks,
-        controller,
-      });
-    }
-
     this.pendingFiles.push(fileTask);
     this.schedule();
   }
@@ -120,11 +111,10 @@ export class FileUploader {
       this.pendingChunks = this.pendingChunks.filter(
         (t) => t.fileTask.file.name !== file.name,
       );
-      http.post(`file/abort/${fileTask.path}`, {
-        json: {
-          file: file.name,
-        },
-      });
+      if (fileTask.sessionId) {
+        http.delete(`upload/${fileTask.sessionId}`);
+        localStorage.removeItem(sessionKey(fileTask));
+      }
       this.checkFileDone(fileTask);
       this.schedule(); // 下一个调度
     }
@@ -145,13 +135,70 @@ export class FileUploader {
       const fileTask = this.pendingFiles.shift();
       if (fileTask) {
         this.activeFiles.add(fileTask);
+        this.startFile(fileTask);
+      }
+    }
+  }
 
-        // 将该文件的 chunk 放入全局 chunk 队列
-        for (const chunk of fileTask.chunks) {
-          this.pendingChunks.push(chunk);
+  /** 创建或恢复上传会话，只把缺失的分片放入队列 */
+  private async startFile(fileTask: FileTask) {
+    const { file, progress } = fileTask;
+    try {
+      const session = await this.openSession(fileTask);
+      if (!this.activeFiles.has(fileTask)) {
+        // 创建会话期间已被取消
+        http.delete(`upload/${session.id}`);
+        return;
+      }
+      fileTask.sessionId = session.id;
+      localStorage.setItem(sessionKey(fileTask), session.id);
+
+      const missing = new Set(session.missingChunks);
+      for (let i = 0; i < session.totalChunks; i++) {
+        const start = i * session.chunkSize;
+        const end = Math.min(start + session.chunkSize, file.size);
+        progress.transferredBytes[i] = missing.has(i) ? 0 : end - start;
+        if (missing.has(i)) {
+          fileTask.chunks.push({
+            fileTask,
+            chunkIndex: i,
+            start,
+            end,
+            controller: new AbortController(),
+          });
         }
       }
+      // 将该文件缺失的 chunk 放入全局 chunk 队列
+      this.pendingChunks.push(...fileTask.chunks);
+      this.checkFileDone(fileTask);
+    } catch (error) {
+      this.activeFiles.delete(fileTask);
+      progress.onError(error);
     }
+    this.schedule();
+  }
+
+  private async openSession(fileTask: FileTask): Promise<UploadSession> {
+    const savedId = localStorage.getItem(sessionKey(fileTask));
+    if (savedId) {
+      try {
+        return await http.get(`upload/${savedId}`).json<UploadSession>();
+      } catch {
+        localStorage.removeItem(sessionKey(fileTask));
+      }
+    }
+    return http
+      .post("upload", {
+        json: {
+          path: fileTask.path,
+          filename: fileTask.file.name,
+          size: fileTask.file.size,
+          chunkSize: this.chunkSize,
+          // 同名文件已存在时自动重命名，不覆盖
+          conflict: "rename",
+        },
+      })
+      .json<UploadSession>();
   }
 
   /** 限制全局 chunk 并发 */
@@ -168,7 +215,7 @@ export class FileUploader {
   /** 上传单片 */
   private async uploadChunk(task: ChunkTask) {
     const { fileTask, chunkIndex, start, end } = task;
-    const { file, path, progress } = fileTask;
+    const { file, sessionId, progress } = fileTask;
 
     this.activeChunks.add(task);
 
@@ -176,20 +223,17 @@ export class FileUploader {
     const formData = new FormData();
 
     formData.append("file", blob);
-    formData.append("chunk", chunkIndex.toString());
-    formData.append("total", task.totalChunks.toString());
-    formData.append("size", file.size.toString());
-    formData.append("filename", encodeURIComponent(file.name));
 
     try {
-      await http.post(`file/upload/${path}`, {
+      await http.put(`upload/${sessionId}/${chunkIndex}`, {
         body: formData,
         signal: task.controller.signal,
       });
       progress.transferredBytes[chunkIndex] = blob.size;
       const uploaded = progress.transferredBytes.reduce((s, v) => s + v, 0);
+      // 服务端合并完成后才算 100%
       progress.onProgress({
-        percent: Math.min(uploaded / file.size, 1),
+        percent: Math.min(uploaded / file.size, 0.99),
         transferredBytes: uploaded,
       });
     } catch (error) {
@@ -201,15 +245,29 @@ export class FileUploader {
     }
   }
 
-  /** 检查文件是否已经全部完成 */
+  /** 检查文件是否已经全部完成，全部分片上传后通知服务端合并 */
   private checkFileDone(fileTask: FileTask) {
-    const finishedChunks = fileTask.progress.transferredBytes.filter(
-      (x) => x > 0,
-    ).length;
-
-    if (finishedChunks === fileTask.chunks.length) {
-      this.activeFiles.delete(fileTask);
-      // TODO: 在这里可以触发 onFileComplete(fileTask)
+    const { file, sessionId, progress } = fileTask;
+    const pending = fileTask.chunks.some(
+      (chunk) => progress.transferredBytes[chunk.chunkIndex] === 0,
+    );
+    if (pending || !this.activeFiles.has(fileTask)) {
+      return;
     }
+
+    this.activeFiles.delete(fileTask);
+    if (!sessionId) return;
+    http
+      .post(`upload/${sessionId}/complete`)
+      .then(() => {
+        localStorage.removeItem(sessionKey(fileTask));
+        progress.onProgress({ percent: 1, transferredBytes: file.size });
+      })
+      .catch(progress.onError);
   }
 }
+
+/** 本地保存的会话 ID，刷新页面后重新选择同一文件可以续传 */
+function sessionKey({ file, path }: FileTask) {
+  return `upload:${path}/${file.name}:${file.size}:${file.lastModified}`;
+}
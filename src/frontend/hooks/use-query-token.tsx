@@ -0,0 +1,16 @@
+import { createQueryToken } from "@/api/auth/query-token";
+import { useQuery } from "@tanstack/react-query";
+
+/** token 有效期 10 分钟，提前刷新 */
+const REFRESH_INTERVAL = 5 * 60 * 1000;
+
+/** 缩略图等 `<img>` 地址使用的短期 token，获取前返回 undefined */
+export function useQueryToken() {
+  const { data } = useQuery({
+    queryKey: ["query-token"],
+    queryFn: createQueryToken,
+    staleTime: REFRESH_INTERVAL,
+    refetchInterval: REFRESH_INTERVAL,
+  });
+  return data?.token;
+}
@@ -48,7 +48,11 @@ import { FileListEmpty } from "./components/file-list-empty";
 import { ListContextMenu } from "./components/list-context-menu";
 import { ListDialog, type ListDialogProps } from "./components/list-dialog";
 import { FileListSidebar } from "./components/sidebar";
-import { createDownloadUrl, downloadFile } from "./utils/downloadFile";
+import {
+  createDownloadUrl,
+  downloadArchive,
+  downloadFile,
+} from "./utils/downloadFile";
 import { writeTextIntoClipboard } from "./utils/writeTextIntoClipboard";
 
 export const Route = createFileRoute("/list/$space/$")({
@@ -382,7 +386,17 @@ function FileListItem({
   ];
 
   const items: MenuListProps["items"] = isFolder
-    ? baseItems
+    ? [
+        {
+          label: "打包下载",
+          icon: <CloudDownload className="mr-2 h-4 w-4" />,
+          onClick: () => downloadArchive(path, [file.name]),
+        },
+        {
+          type: "separator",
+        },
+        ...baseItems,
+      ]
     : [
         {
           label: "编辑",
@@ -405,10 +419,11 @@ function FileListItem({
           label: "复制链接",
           icon: <Link className="mr-2 h-4 w-4" />,
           onClick: () => {
-            const url = createDownloadUrl(path, file.name);
-            writeTextIntoClipboard(url).then(() => {
-              toast.success("链接已复制到剪贴板");
-            });
+            createDownloadUrl(path, file.name)
+              .then(writeTextIntoClipboard)
+              .then(() => {
+                toast.success("链接已复制到剪贴板");
+              });
           },
         },
         {
@@ -428,7 +443,7 @@ function FileListItem({
       <div className="flex items-center justify-between p-3 hover:bg-muted/50 data-[state=open]:bg-muted/50 has-data-[[state=open]]:bg-muted/50 cursor-pointer group mt-0">
         <div className="flex items-center space-x-3 flex-1 min-w-0">
           <div className="w-8 h-8 flex items-center justify-center rounded bg-muted group-hover:bg-muted/80">
-            <FileIcon fileInfo={file} />
+            <FileIcon fileInfo={file} path={path} />
           </div>
           <div className="flex-1 min-w-0">
             <p className="text-sm font-medium min-w-0 truncate flex-1">
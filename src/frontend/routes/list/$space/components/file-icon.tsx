@@ -1,4 +1,6 @@
 import type { FileInfo } from "@/api/file/list";
+import { thumbUrl } from "@/api/file/thumb";
+import { useQueryToken } from "@/hooks/use-query-token";
 import { cn } from "@/lib/utils";
 import {
   File,
@@ -11,16 +13,39 @@ import {
   FileVideo,
   FolderOpen,
 } from "lucide-react";
+import { useState } from "react";
 
 export function FileIcon({
   fileInfo,
+  path,
 }: {
   fileInfo: {
     fileType: FileInfo["fileType"];
     name: FileInfo["name"];
+    thumbnail?: FileInfo["thumbnail"];
   };
+  /** 文件所在的文件夹，提供时图片显示缩略图 */
+  path?: string;
 }) {
+  const [thumbFailed, setThumbFailed] = useState(false);
+  const queryToken = useQueryToken();
   const baseClass = "h-4 w-4";
+  if (
+    fileInfo.thumbnail &&
+    path !== undefined &&
+    queryToken &&
+    !thumbFailed
+  ) {
+    return (
+      <img
+        src={thumbUrl(`${path}/${fileInfo.name}`, queryToken)}
+        alt={fileInfo.name}
+        loading="lazy"
+        className="h-8 w-8 rounded object-cover"
+        onError={() => setThumbFailed(true)}
+      />
+    );
+  }
   if (fileInfo.fileType === "folder") {
     return <FolderOpen className={cn(baseClass, "text-primary")} />;
   }
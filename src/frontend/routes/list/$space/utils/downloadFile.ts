@@ -1,8 +1,8 @@
-export function downloadFile(path: string, fileName: string) {
-  console.log("path", path);
-  console.log("fileName", fileName);
-  const url = createDownloadUrl(path, fileName);
-  console.log("url", url);
+import { createQueryToken } from "@/api/auth/query-token";
+import { signDownloadUrl } from "@/api/file/sign";
+
+export async function downloadFile(path: string, fileName: string) {
+  const url = await createDownloadUrl(path, fileName);
   // 创建一个临时的a标签来触发下载
   const link = document.createElement("a");
   link.href = url;
@@ -18,8 +18,32 @@ export function downloadFile(path: string, fileName: string) {
   document.body.removeChild(link);
 }
 
-export function createDownloadUrl(path: string, fileName: string) {
+/** 生成带签名的下载链接，无需携带 token 即可下载 */
+export async function createDownloadUrl(path: string, fileName: string) {
   const baseUrl = window.location.origin;
-  const url = `${baseUrl}/download/${path}${fileName}`;
-  return url;
+  const { url } = await signDownloadUrl(`${path}${fileName}`);
+  return `${baseUrl}${url}`;
+}
+
+/** 把文件夹下选中的条目打包下载，服务端边打包边发送 */
+export async function downloadArchive(
+  folder: string,
+  targets: string[],
+  format: "zip" | "tarGz" = "zip",
+) {
+  const encodedPath = folder
+    .split("/")
+    .filter(Boolean)
+    .map(encodeURIComponent)
+    .join("/");
+  const { token } = await createQueryToken();
+  const params = new URLSearchParams({ format, token });
+  for (const target of targets) {
+    params.append("targets", target);
+  }
+  const link = document.createElement("a");
+  link.href = `/api/archive/${encodedPath}?${params}`;
+  document.body.appendChild(link);
+  link.click();
+  document.body.removeChild(link);
 }